@@ -0,0 +1,164 @@
+// Measure the cost terms of the functions of the contract and generate src/measuredcosts.rs
+//
+// Usage: cargo bench --bench costs
+//
+// The bets of a typical market, with base58 addresses and request ids, are stored in both layouts and settled with the functions
+// of this crate. The bytes of the bets read and written are measured exactly, the compute time as the fastest of several native runs.
+// The terms are fitted from markets of SMALLMARKET and LARGEMARKET bets and written to src/measuredcosts.rs, see costs.rs.
+
+use prediction_market_core::settlement::*;
+use prediction_market_core::storage::*;
+use std::collections::HashMap;
+use std::time::Instant;
+
+// sizes of the markets the terms linear in the number of bets are fitted from
+const SMALLMARKET: i64 = 100;
+const LARGEMARKET: i64 = 1000;
+// runs of every measurement, of which the fastest is taken
+const RUNS: usize = 20;
+
+// base58 like the addresses (33 bytes) and request ids (34 bytes) of the chain
+fn base58(seed: i64, length: usize) -> String {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let mut state: u64 = (seed as u64).wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    (0..length).map(|_i| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ALPHABET[(state >> 33) as usize % ALPHABET.len()] as char
+    }).collect()
+}
+
+fn address(seed: i64) -> String {
+    base58(seed, 45)
+}
+
+fn bet(seed: i64) -> Bet {
+    let betamount: i64 = 100 + seed % 900;
+    Bet {
+        betamount: betamount as i32,
+        betisforvalue: if seed % 2 == 0 { "yes".to_string() } else { "no".to_string() },
+        placedat: 1622563200 + seed,
+        attachedamount: betamount,
+        addressencoding: "base58".to_string(),
+        requestid: base58(-seed - 1, 46),
+        clientid: "".to_string(),
+        campaign: "".to_string(),
+        history: vec![BetEntry { placedat: 1622563200 + seed, betamount, campaign: "".to_string() }],
+    }
+}
+
+fn market(bets: i64) -> ContainerOfBets {
+    ContainerOfBets { map: (0..bets).map(|seed| (address(seed), bet(seed))).collect::<HashMap<String, Bet>>() }
+}
+
+// fastest native time of the measured function in nanoseconds
+fn nanos(mut measured: impl FnMut()) -> i64 {
+    (0..RUNS).map(|_run| {
+        let start = Instant::now();
+        measured();
+        start.elapsed().as_nanos() as i64
+    }).min().unwrap()
+}
+
+// fixed and per unit term of a cost measured for the small and the large market
+fn fitted(small: i64, large: i64) -> (i64, i64) {
+    let perunit: i64 = ((large - small) as f64 / (LARGEMARKET - SMALLMARKET) as f64).round() as i64;
+    ((small - perunit * SMALLMARKET).max(0), perunit)
+}
+
+// bytes of the bet of an account in the map, its key and its binary layout
+fn mapbytes(address: &str, bet: &Bet) -> i64 {
+    (address.len() + encodebet(bet).len()) as i64
+}
+
+fn jsonbytes(containerofbets: &ContainerOfBets) -> i64 {
+    serde_json::to_string(containerofbets).unwrap().len() as i64
+}
+
+struct Terms {
+    function: &'static str,
+    layout: &'static str,
+    readbytes: (i64, i64),
+    writtenbytes: (i64, i64),
+    transfers: (i64, i64),
+    computenanos: (i64, i64),
+}
+
+fn main() {
+    let small: ContainerOfBets = market(SMALLMARKET);
+    let large: ContainerOfBets = market(LARGEMARKET);
+    let newaddress: String = address(LARGEMARKET);
+    let newbet: Bet = bet(LARGEMARKET);
+    let mut terms: Vec<Terms> = Vec::new();
+
+    // bet in the map: the bet of the account is read, written again and a new account appended to betteraddresses
+    let betbytes: i64 = mapbytes(&newaddress, &newbet);
+    let encoded: Vec<u8> = encodebet(&newbet);
+    terms.push(Terms {
+        function: "bet", layout: STORAGE_MAP,
+        readbytes: (betbytes, 0),
+        writtenbytes: (betbytes + newaddress.len() as i64, 0),
+        transfers: (0, 0),
+        computenanos: (nanos(|| { decodebet(&encoded).unwrap(); encodebet(&newbet); }), 0),
+    });
+
+    // bet in the json: all bets are read and written again
+    let betnanos = |containerofbets: &ContainerOfBets| {
+        let json: String = serde_json::to_string(containerofbets).unwrap();
+        nanos(|| {
+            let mut parsed: ContainerOfBets = parsecontainerofbets(&json);
+            parsed.map.insert(newaddress.clone(), bet(LARGEMARKET));
+            serde_json::to_string(&parsed).unwrap();
+        })
+    };
+    terms.push(Terms {
+        function: "bet", layout: STORAGE_JSON,
+        readbytes: fitted(jsonbytes(&small), jsonbytes(&large)),
+        writtenbytes: fitted(jsonbytes(&small) + betbytes, jsonbytes(&large) + betbytes),
+        transfers: (0, 0),
+        computenanos: fitted(betnanos(&small), betnanos(&large)),
+    });
+
+    // closemarket reads and settles all bets, the transfers follow with payout or claim
+    let storedbytes = |containerofbets: &ContainerOfBets| containerofbets.map.iter().map(|(address, bet)| mapbytes(address, bet) + address.len() as i64).sum::<i64>();
+    let closenanosmap = |containerofbets: &ContainerOfBets| {
+        let encoded: Vec<(String, Vec<u8>)> = containerofbets.map.iter().map(|(address, bet)| (address.clone(), encodebet(bet))).collect();
+        nanos(|| {
+            let decoded = ContainerOfBets { map: encoded.iter().map(|(address, bytes)| (address.clone(), decodebet(bytes).unwrap())).collect() };
+            settle(&decoded, "yes");
+        })
+    };
+    terms.push(Terms {
+        function: "closemarket", layout: STORAGE_MAP,
+        readbytes: fitted(storedbytes(&small), storedbytes(&large)),
+        writtenbytes: (0, 0),
+        transfers: (0, 0),
+        computenanos: fitted(closenanosmap(&small), closenanosmap(&large)),
+    });
+    let closenanosjson = |containerofbets: &ContainerOfBets| {
+        let json: String = serde_json::to_string(containerofbets).unwrap();
+        nanos(|| { settle(&parsecontainerofbets(&json), "yes"); })
+    };
+    terms.push(Terms {
+        function: "closemarket", layout: STORAGE_JSON,
+        readbytes: fitted(jsonbytes(&small), jsonbytes(&large)),
+        writtenbytes: (0, 0),
+        transfers: (0, 0),
+        computenanos: fitted(closenanosjson(&small), closenanosjson(&large)),
+    });
+
+    // payout transfers to every winner, in either layout
+    for layout in [STORAGE_MAP, STORAGE_JSON].iter() {
+        terms.push(Terms { function: "payout", layout, readbytes: (0, 0), writtenbytes: (0, 0), transfers: (0, 1), computenanos: (0, 0) });
+    }
+
+    let mut generated: String = "// Cost terms measured by `cargo bench --bench costs`, which generates this file, see benches/costs.rs. Do not edit.\n\n".to_string();
+    generated += &("pub const MEASUREDCOSTS: [CostTerms; ".to_string() + &terms.len().to_string() + "] = [\n");
+    for term in terms.iter() {
+        generated += &format!("    CostTerms {{ function: \"{}\", layout: \"{}\", readbytes: {:?}, writtenbytes: {:?}, transfers: {:?}, computenanos: {:?} }},\n",
+            term.function, term.layout, term.readbytes, term.writtenbytes, term.transfers, term.computenanos);
+    }
+    generated += "];\n";
+    let path: String = env!("CARGO_MANIFEST_DIR").to_string() + "/src/measuredcosts.rs";
+    std::fs::write(&path, &generated).expect("failed to write the measured costs");
+    print!("{}", generated);
+}
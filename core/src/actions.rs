@@ -0,0 +1,135 @@
+// Time-locked owner actions, without access to the contract's context
+//
+// In a market initialized with a TIMELOCK, parameter changes of the contract owner are announced by proposeaction instead of being
+// applied directly. The proposed AdminAction takes effect TIMELOCK seconds later, when anybody can execute it by executeaction,
+// and the owner can cancel it by cancelaction before it is executed. An action is executed or cancelled once, and only for the market
+// it was proposed for. The contract stores the actions as json in the adminactions array, indexed by their ACTIONID.
+
+use serde::{Deserialize, Serialize};
+
+use crate::roles::{ROLE_MARKETADMIN, ROLE_TREASURER};
+
+// kinds of owner actions that are delayed by the time lock, with the parameters of their direct function as payload
+pub const ACTION_SETALERTS: &str = "setalerts";
+pub const ACTION_GRANTEXCEPTION: &str = "grantexception";
+pub const ACTION_SETSWEEPDEST: &str = "setsweepdest";
+
+pub const ACTIONSTATUS_PENDING: &str = "pending";
+pub const ACTIONSTATUS_EXECUTED: &str = "executed";
+pub const ACTIONSTATUS_CANCELLED: &str = "cancelled";
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AdminAction {
+    // kind of the action, e.g. "setalerts"
+    pub kind: String,
+    // parameters of the action, e.g. the alert rules for "setalerts" or "address|amount|expires" for "grantexception"
+    pub payload: String,
+    // UNIX timestamp from which on the action can be executed
+    pub effectiveat: i64,
+    // "pending", "executed" or "cancelled"
+    pub status: String,
+    // MARKETID of the market the action applies to, "" for the market without MARKETID
+    #[serde(default)]
+    pub marketid: String,
+}
+
+// the role besides the contract owner that may propose and cancel actions of a kind
+pub fn actionrole(kind: &str) -> &'static str {
+    if kind == ACTION_SETSWEEPDEST { ROLE_TREASURER } else { ROLE_MARKETADMIN }
+}
+
+// the action of kind with payload proposed at currtime for the market marketid, which takes effect after timelock seconds
+pub fn proposedaction(kind: &str, payload: &str, currtime: i64, timelock: i64, marketid: &str) -> AdminAction {
+    AdminAction {
+        kind: kind.to_string(),
+        payload: payload.to_string(),
+        effectiveat: currtime.saturating_add(timelock),
+        status: ACTIONSTATUS_PENDING.to_string(),
+        marketid: marketid.to_string(),
+    }
+}
+
+// Mark the action as executed at currtime by a call for the market marketid, or Err with the reason it cannot be executed.
+pub fn executeadminaction(action: &mut AdminAction, currtime: i64, marketid: &str) -> Result<(), String> {
+    if action.status != ACTIONSTATUS_PENDING {
+        return Err("the action is not pending".to_string());
+    }
+    if currtime < action.effectiveat {
+        return Err("the action cannot be executed before its time lock has passed".to_string());
+    }
+    if marketid != action.marketid {
+        return Err("the action applies to the market with MARKETID ".to_string() + &action.marketid);
+    }
+    action.status = ACTIONSTATUS_EXECUTED.to_string();
+    Ok(())
+}
+
+// Mark the action as cancelled, or Err if it was already executed or cancelled.
+pub fn canceladminaction(action: &mut AdminAction) -> Result<(), String> {
+    if action.status != ACTIONSTATUS_PENDING {
+        return Err("the action is not pending".to_string());
+    }
+    action.status = ACTIONSTATUS_CANCELLED.to_string();
+    Ok(())
+}
+
+// "address|amount|expires" of a grantexception action
+pub fn parseexceptionpayload(payload: &str) -> Result<(String, i64, i64), String> {
+    let parts: Vec<&str> = payload.split('|').collect();
+    if parts.len() != 3 {
+        return Err("the payload of grantexception must be address|amount|expires".to_string());
+    }
+    let amount: i64 = parts[1].trim().parse::<i64>().map_err(|_error| "the amount of grantexception must be an integer".to_string())?;
+    let expires: i64 = parts[2].trim().parse::<i64>().map_err(|_error| "the expiry of grantexception must be an integer".to_string())?;
+    Ok((parts[0].trim().to_string(), amount, expires))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1622563200;
+    const TIMELOCK: i64 = 86400;
+
+    // the stored action after a round trip through its json, like between the calls of the contract
+    fn stored(action: &AdminAction) -> AdminAction {
+        serde_json::from_str(&serde_json::to_string(action).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn a_proposed_action_is_executed_once_after_its_time_lock() {
+        let mut action: AdminAction = stored(&proposedaction(ACTION_SETALERTS, "yes>8000", NOW, TIMELOCK, "worldcup"));
+        assert_eq!(action.effectiveat, NOW + TIMELOCK);
+        assert_eq!(action.status, ACTIONSTATUS_PENDING);
+
+        // premature execution is rejected and leaves the action pending
+        let error: String = executeadminaction(&mut action, NOW + TIMELOCK - 1, "worldcup").unwrap_err();
+        assert!(error.contains("time lock"));
+        assert_eq!(action.status, ACTIONSTATUS_PENDING);
+        // so is an execution for another market
+        assert!(executeadminaction(&mut action, NOW + TIMELOCK, "").unwrap_err().contains("MARKETID worldcup"));
+
+        assert_eq!(executeadminaction(&mut action, NOW + TIMELOCK, "worldcup"), Ok(()));
+        let mut action: AdminAction = stored(&action);
+        assert_eq!(action.status, ACTIONSTATUS_EXECUTED);
+        // neither executed again nor cancelled afterwards
+        assert!(executeadminaction(&mut action, NOW + 2 * TIMELOCK, "worldcup").unwrap_err().contains("not pending"));
+        assert!(canceladminaction(&mut action).unwrap_err().contains("not pending"));
+    }
+
+    #[test]
+    fn a_cancelled_action_cannot_be_executed() {
+        let mut action: AdminAction = proposedaction(ACTION_SETSWEEPDEST, "treasury", NOW, TIMELOCK, "");
+        assert_eq!(actionrole(&action.kind), ROLE_TREASURER);
+        assert_eq!(canceladminaction(&mut action), Ok(()));
+        assert_eq!(stored(&action).status, ACTIONSTATUS_CANCELLED);
+        assert!(executeadminaction(&mut action, NOW + TIMELOCK, "").unwrap_err().contains("not pending"));
+        assert!(canceladminaction(&mut action).is_err());
+        // without a time lock, an action takes effect at once
+        let mut immediate: AdminAction = proposedaction(ACTION_GRANTEXCEPTION, "alice|500|1622649600", NOW, 0, "");
+        assert_eq!(actionrole(&immediate.kind), ROLE_MARKETADMIN);
+        assert_eq!(executeadminaction(&mut immediate, NOW, ""), Ok(()));
+        assert_eq!(parseexceptionpayload(&immediate.payload), Ok(("alice".to_string(), 500, 1622649600)));
+        assert!(parseexceptionpayload("alice|500").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+// Encodings of wallet addresses stored in the contract's state, without access to the contract's context
+//
+// Addresses are stored as strings, e.g. as keys of the bets and in entitlements, and decoded again for transfers. Every stored entry
+// records the flag of the encoding its address was stored in, so that the state of a market holding entries of both encodings, e.g.
+// bets stored before and after the chain changed its address format, settles and pays every entry to the right address.
+// Entries without a flag were stored in base58, the encoding used before flags were recorded.
+// * base58, the encoding of ScAddress::to_string and the one entries are stored in now,
+// * hex, lowercase, the secondary encoding, e.g. of entries imported from a chain that encodes addresses this way.
+// Base58 is passed in, the contract uses the base58 of its ScUtility.
+
+// number of bytes of a decoded address: the address type followed by its 32 bytes hash
+pub const ADDRESS_LENGTH: usize = 33;
+
+pub const ENCODING_BASE58: &str = "base58";
+pub const ENCODING_HEX: &str = "hex";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddressEncoding {
+    Base58,
+    Hex,
+}
+
+impl AddressEncoding {
+    // encoding of addresses stored from now on
+    pub const CURRENT: AddressEncoding = AddressEncoding::Base58;
+
+    // encoding for the flag stored with an entry, "" for entries stored before flags were recorded
+    pub fn from_flag(flag: &str) -> Result<AddressEncoding, String> {
+        match flag {
+            "" | ENCODING_BASE58 => Ok(AddressEncoding::Base58),
+            ENCODING_HEX => Ok(AddressEncoding::Hex),
+            _ => Err("unknown address encoding: ".to_string() + flag),
+        }
+    }
+
+    pub fn flag(&self) -> &'static str {
+        match self {
+            AddressEncoding::Base58 => ENCODING_BASE58,
+            AddressEncoding::Hex => ENCODING_HEX,
+        }
+    }
+
+    pub fn encode(&self, bytes: &[u8], base58: &impl Fn(&[u8]) -> String) -> String {
+        match self {
+            AddressEncoding::Base58 => base58(bytes),
+            AddressEncoding::Hex => bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        }
+    }
+
+    // decode an address for a transfer, rejecting strings that do not decode to the length of an address
+    pub fn decode(&self, encoded: &str, base58: &impl Fn(&str) -> Vec<u8>) -> Result<Vec<u8>, String> {
+        let bytes: Vec<u8> = match self {
+            AddressEncoding::Base58 => base58(encoded),
+            AddressEncoding::Hex => decodehex(encoded)?,
+        };
+        if bytes.len() != ADDRESS_LENGTH {
+            return Err("address ".to_string() + encoded + " does not decode to " + &ADDRESS_LENGTH.to_string() + " bytes");
+        }
+        Ok(bytes)
+    }
+}
+
+fn decodehex(encoded: &str) -> Result<Vec<u8>, String> {
+    if encoded.len() & 1 != 0 || !encoded.is_ascii() {
+        return Err("address ".to_string() + encoded + " is not hex encoded");
+    }
+    (0..encoded.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&encoded[i..i + 2], 16).map_err(|_error| "address ".to_string() + encoded + " is not hex encoded"))
+        .collect()
+}
+
+// The address an entry stored with the address encoded and the flag of its encoding is paid to.
+pub fn recipient(encoded: &str, flag: &str, base58: &impl Fn(&str) -> Vec<u8>) -> Result<Vec<u8>, String> {
+    AddressEncoding::from_flag(flag).and_then(|encoding| encoding.decode(encoded, base58))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::{parsecontainerofbets, settle, ContainerOfBets, SettlementResult};
+    use std::collections::BTreeMap;
+
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    // base58 with the bitcoin alphabet, like ScUtility
+    fn base58encode(bytes: &[u8]) -> String {
+        let mut digits: Vec<u8> = Vec::new();
+        for byte in bytes {
+            let mut carry: u32 = *byte as u32;
+            for digit in digits.iter_mut() {
+                carry += (*digit as u32) << 8;
+                *digit = (carry % 58) as u8;
+                carry /= 58;
+            }
+            while carry > 0 {
+                digits.push((carry % 58) as u8);
+                carry /= 58;
+            }
+        }
+        let zeros: usize = bytes.iter().take_while(|byte| **byte == 0).count();
+        "1".repeat(zeros) + &digits.iter().rev().map(|digit| ALPHABET[*digit as usize] as char).collect::<String>()
+    }
+
+    fn base58decode(encoded: &str) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        for character in encoded.bytes() {
+            let mut carry: u32 = match ALPHABET.iter().position(|letter| *letter == character) {
+                Some(position) => position as u32,
+                None => return Vec::new(),
+            };
+            for byte in bytes.iter_mut() {
+                carry += *byte as u32 * 58;
+                *byte = (carry & 0xff) as u8;
+                carry >>= 8;
+            }
+            while carry > 0 {
+                bytes.push((carry & 0xff) as u8);
+                carry >>= 8;
+            }
+        }
+        let zeros: usize = encoded.bytes().take_while(|character| *character == b'1').count();
+        let mut decoded: Vec<u8> = vec![0; zeros];
+        decoded.extend(bytes.iter().rev());
+        decoded
+    }
+
+    // an address of the given type, its hash filled with seed
+    fn address(addresstype: u8, seed: u8) -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![addresstype];
+        bytes.extend((0..32).map(|i| seed.wrapping_mul(31).wrapping_add(i)));
+        bytes
+    }
+
+    #[test]
+    fn both_encodings_round_trip_and_validate_the_length() {
+        let bytes: Vec<u8> = address(0, 7);
+        for encoding in [AddressEncoding::Base58, AddressEncoding::Hex] {
+            let encoded: String = encoding.encode(&bytes, &base58encode);
+            assert_eq!(encoding.decode(&encoded, &base58decode).unwrap(), bytes);
+            assert_eq!(AddressEncoding::from_flag(encoding.flag()).unwrap(), encoding);
+        }
+        assert_eq!(AddressEncoding::Hex.encode(&bytes, &base58encode).len(), 2 * ADDRESS_LENGTH);
+        // too short, too long, not hex and unknown flags are refused
+        assert!(AddressEncoding::Hex.decode("00ff", &base58decode).is_err());
+        assert!(AddressEncoding::Hex.decode(&(AddressEncoding::Hex.encode(&bytes, &base58encode) + "00"), &base58decode).is_err());
+        assert!(AddressEncoding::Hex.decode(&"zz".repeat(ADDRESS_LENGTH), &base58decode).is_err());
+        assert!(AddressEncoding::Base58.decode("3yZe7d", &base58decode).is_err());
+        assert!(AddressEncoding::from_flag("bech32").is_err());
+        // entries stored before flags were recorded are base58
+        assert_eq!(AddressEncoding::from_flag("").unwrap(), AddressEncoding::Base58);
+    }
+
+    #[test]
+    fn entries_stored_in_both_encodings_pay_out_to_their_addresses() {
+        // the fixtures were re-encoded on purpose: carol's bet was stored in hex by an import, alice's before flags were recorded
+        let alice: Vec<u8> = address(0, 1);
+        let bob: Vec<u8> = address(0, 2);
+        let carol: Vec<u8> = address(2, 3);
+        let keys: [(String, &str); 3] = [
+            (base58encode(&alice), ""),
+            (AddressEncoding::Base58.encode(&bob, &base58encode), ENCODING_BASE58),
+            (AddressEncoding::Hex.encode(&carol, &base58encode), ENCODING_HEX),
+        ];
+        let betsjson: String = "{\"map\":{".to_string()
+            + &format!("\"{}\":{{\"betamount\":100,\"betisforvalue\":\"yes\",\"addressencoding\":\"{}\"}},", keys[0].0, keys[0].1)
+            + &format!("\"{}\":{{\"betamount\":300,\"betisforvalue\":\"no\",\"addressencoding\":\"{}\"}},", keys[1].0, keys[1].1)
+            + &format!("\"{}\":{{\"betamount\":300,\"betisforvalue\":\"yes\",\"addressencoding\":\"{}\"}}", keys[2].0, keys[2].1)
+            + "}}";
+        let containerofbets: ContainerOfBets = parsecontainerofbets(&betsjson);
+        assert_eq!(containerofbets.map.len(), 3);
+
+        // yes wins, and every entitlement is paid to the address its key decodes to with the flag stored with the bet
+        let settlement: SettlementResult = settle(&containerofbets, "yes");
+        let mut paid: BTreeMap<Vec<u8>, i64> = BTreeMap::new();
+        for (betteraddress, winamount) in settlement.winamounts.iter() {
+            let flag: &str = &containerofbets.map[betteraddress].addressencoding;
+            *paid.entry(recipient(betteraddress, flag, &base58decode).unwrap()).or_insert(0) += winamount;
+        }
+        assert_eq!(paid[&alice], 175);
+        assert_eq!(paid[&carol], 525);
+        assert!(!paid.contains_key(&bob));
+        assert_eq!(paid.values().sum::<i64>(), 700);
+
+        // the hex key decoded as base58 is no address, so an entry never pays out under the wrong flag
+        assert!(recipient(&keys[2].0, ENCODING_BASE58, &base58decode).is_err());
+        assert!(recipient(&keys[0].0, ENCODING_HEX, &base58decode).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+// Alert rules on the implied probabilities of a market, without access to the contract's context
+//
+// The contract owner registers rules like "yes>8000,no<1000" by setalerts. After each accepted bet, the contract evaluates the rules on
+// the value of the bet against its implied probability, the share of the pool bet on it in basis points. A rule fires when it becomes met,
+// and only fires again after the probability moved back beyond its threshold by ALERTHYSTERESISBPS, so that a probability hovering
+// around the threshold does not fire on every bet. The state of the rules is kept as one flag per rule, "1" while the rule is met.
+
+use serde::{Deserialize, Serialize};
+
+pub const MAXALERTRULES: usize = 8;
+pub const ALERTHYSTERESISBPS: i64 = 100;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AlertRule {
+    // value whose implied probability is watched
+    pub betvalue: String,
+    // true if the rule is met above the threshold, false if below
+    pub above: bool,
+    // threshold of the implied probability in basis points
+    pub thresholdbps: i64,
+}
+
+// a rule that became met by a bet, with the implied probability after the bet
+#[derive(Debug, PartialEq)]
+pub struct Alert {
+    pub betvalue: String,
+    pub above: bool,
+    pub thresholdbps: i64,
+    pub probabilitybps: i64,
+}
+
+// Parse alert rules like "yes>8000,no<1000", of which there can be at most MAXALERTRULES.
+pub fn parsealertrules(alert: &str) -> Result<Vec<AlertRule>, String> {
+    let mut alertrules: Vec<AlertRule> = Vec::new();
+    for rule in alert.split(',').map(|rule| rule.trim()).filter(|rule| !rule.is_empty()) {
+        let formaterror = || "alert rule must be formatted as VALUE>BPS or VALUE<BPS: ".to_string() + rule;
+        let position: usize = rule.find(['>', '<']).ok_or_else(formaterror)?;
+        let thresholdbps: i64 = rule[position + 1..].trim().parse::<i64>().map_err(|_error| formaterror())?;
+        if position == 0 {
+            return Err(formaterror());
+        }
+        if !(0..=10000).contains(&thresholdbps) {
+            return Err("alert threshold must be between 0 and 10000: ".to_string() + rule);
+        }
+        alertrules.push(AlertRule {
+            betvalue: rule[..position].trim().to_string(),
+            above: &rule[position..position + 1] == ">",
+            thresholdbps,
+        });
+    }
+    if alertrules.len() > MAXALERTRULES {
+        return Err("at most ".to_string() + &MAXALERTRULES.to_string() + " alert rules are allowed");
+    }
+    Ok(alertrules)
+}
+
+// the flags of rules none of which is met
+pub fn initialalertflags(alertrules: &[AlertRule]) -> String {
+    "0".repeat(alertrules.len())
+}
+
+// Evaluate the rules on the value a bet was placed on, given the pool and the amount bet on the value after the bet, and the flags
+// stored before the bet. Returns the flags to store and the alerts of the rules that became met. Rules on other values are left as they are.
+pub fn evaluatealerts(alertrules: &[AlertRule], alertflags: &str, betvalue: &str, totalbetamount: i64, totalbetamountforvalue: i64) -> (String, Vec<Alert>) {
+    let mut flags: Vec<u8> = alertflags.as_bytes().to_vec();
+    flags.resize(alertrules.len(), b'0');
+    let mut alerts: Vec<Alert> = Vec::new();
+    if totalbetamount > 0 {
+        let probabilitybps: i64 = totalbetamountforvalue * 10000 / totalbetamount;
+        for (i, rule) in alertrules.iter().enumerate().filter(|(_i, rule)| rule.betvalue == betvalue) {
+            let met: bool = if rule.above { probabilitybps > rule.thresholdbps } else { probabilitybps < rule.thresholdbps };
+            let rearmed: bool = if rule.above { probabilitybps < rule.thresholdbps - ALERTHYSTERESISBPS } else { probabilitybps > rule.thresholdbps + ALERTHYSTERESISBPS };
+            if flags[i] == b'0' && met {
+                flags[i] = b'1';
+                alerts.push(Alert { betvalue: betvalue.to_string(), above: rule.above, thresholdbps: rule.thresholdbps, probabilitybps });
+            } else if flags[i] == b'1' && rearmed {
+                flags[i] = b'0';
+            }
+        }
+    }
+    (flags.iter().map(|flag| if *flag == b'1' { '1' } else { '0' }).collect(), alerts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn rules_are_parsed_and_malformed_ones_rejected() {
+        let alertrules: Vec<AlertRule> = parsealertrules(" yes>8000, no<1000 ,").unwrap();
+        assert_eq!(alertrules, vec![
+            AlertRule { betvalue: "yes".to_string(), above: true, thresholdbps: 8000 },
+            AlertRule { betvalue: "no".to_string(), above: false, thresholdbps: 1000 },
+        ]);
+        assert_eq!(initialalertflags(&alertrules), "00");
+        assert!(parsealertrules("").unwrap().is_empty());
+        for alert in ["yes=8000", ">8000", "yes>", "yes>eighty", "yes>10001", "no<-1"].iter() {
+            assert!(parsealertrules(alert).is_err(), "{}", alert);
+        }
+        let tenrules: String = (0..10).map(|i| "v".to_string() + &i.to_string() + ">5000").collect::<Vec<String>>().join(",");
+        assert!(parsealertrules(&tenrules).unwrap_err().contains("at most 8"));
+    }
+
+    #[test]
+    fn crossing_a_threshold_and_back_fires_exactly_twice() {
+        let alertrules: Vec<AlertRule> = parsealertrules("yes>8000").unwrap();
+        let mut alertflags: String = initialalertflags(&alertrules);
+        let mut totals: BTreeMap<&str, i64> = BTreeMap::new();
+        let mut alerts: Vec<Alert> = Vec::new();
+        // the probability of yes after each bet on yes: 5000, 8333 fires, 8076 hovers above the threshold, 7019 rearms, 8339 fires again.
+        // The bets on no move it as well, but are not evaluated against the rule on yes.
+        let bets: [(&str, i64); 8] = [("no", 100), ("yes", 100), ("yes", 400), ("no", 25), ("yes", 25), ("no", 100), ("yes", 5), ("yes", 600)];
+        for (betvalue, betamount) in bets.iter() {
+            *totals.entry(betvalue).or_insert(0) += betamount;
+            let totalbetamount: i64 = totals.values().sum();
+            let (flags, fired) = evaluatealerts(&alertrules, &alertflags, betvalue, totalbetamount, totals[betvalue]);
+            alertflags = flags;
+            alerts.extend(fired);
+        }
+        assert_eq!(alerts, vec![
+            Alert { betvalue: "yes".to_string(), above: true, thresholdbps: 8000, probabilitybps: 8333 },
+            Alert { betvalue: "yes".to_string(), above: true, thresholdbps: 8000, probabilitybps: 8339 },
+        ]);
+        assert_eq!(alertflags, "1");
+    }
+}
@@ -0,0 +1,167 @@
+// Tombstones of archived markets, without access to the contract's context
+//
+// archivemarket deletes the state of a settled and paid out market and keeps a tombstone with a few summary fields and a digest
+// of everything that was deleted. The state is emitted as archive.chunk events before, as the json of an ArchiveExport split into
+// chunks like a migration, so that anybody holding the events can rebuild the export and derive the digest again:
+// * the bets root is the hash of canonicalbets of the bets, like the bets root of exportmarket,
+// * the payout root is the Merkle root, see merkle, over canonicalpayout of the entitlements in the order they were recorded,
+// * the receipt and the configuration are hashed as canonicalentries of their json alone, under TAG_RECEIPT and TAG_CONFIG,
+// * the admin log and the transitions of the market are hashed as canonicalentries of their entries, under TAG_ADMINLOG and TAG_TRANSITIONS,
+// * the digest is the hash of the TAG_TOMBSTONE encoding of the market id and these six hashes in this order.
+// TOMBSTONERECIPE names this recipe and is stored with every tombstone. The hash function is passed in, the contract uses blake2b-256.
+
+use serde::{Serialize, Deserialize};
+use serde_with::serde_as;
+
+use crate::canonical::{canonicalbets, canonicalentries, canonicalpayout, CanonicalEncoder};
+use crate::canonical::{TAG_ADMINLOG, TAG_CONFIG, TAG_RECEIPT, TAG_TOMBSTONE, TAG_TRANSITIONS};
+use crate::merkle::merkleroot;
+use crate::settlement::ExportedBet;
+
+
+pub const TOMBSTONERECIPE: &str = TAG_TOMBSTONE;
+
+// an entitlement of an archived market
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+pub struct ArchivedPayout {
+    pub address: String,
+    pub amount: i64,
+    pub paidamount: i64,
+    pub reason: String,
+    pub paid: bool,
+}
+
+// everything of a market that its digest covers, emitted by archivemarket before the state is deleted
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+pub struct ArchiveExport {
+    pub marketid: String,
+    // the bets sorted by address, see exportedbets
+    pub bets: Vec<ExportedBet>,
+    pub payouts: Vec<ArchivedPayout>,
+    pub receiptjson: String,
+    // the admin log entries of the calls with the MARKETID of the market
+    pub adminlog: Vec<String>,
+    // the transitions of the market like "1622563200|settled", in the order they happened
+    pub transitions: Vec<String>,
+    pub configjson: String,
+}
+
+// the hashes the digest of a tombstone is taken over, and the digest
+#[derive(Debug, PartialEq)]
+pub struct TombstoneDigest {
+    pub betsroot: Vec<u8>,
+    pub payoutroot: Vec<u8>,
+    pub receipthash: Vec<u8>,
+    pub adminloghash: Vec<u8>,
+    pub transitionloghash: Vec<u8>,
+    pub confighash: Vec<u8>,
+    pub digest: Vec<u8>,
+}
+
+pub fn tombstonedigest(export: &ArchiveExport, hash: &impl Fn(&[u8]) -> Vec<u8>) -> TombstoneDigest {
+    let payouts: Vec<Vec<u8>> = export.payouts.iter()
+        .map(|payout| canonicalpayout(&payout.address, payout.amount, payout.paidamount, &payout.reason, payout.paid))
+        .collect();
+    let betsroot: Vec<u8> = hash(&canonicalbets(&export.bets));
+    let payoutroot: Vec<u8> = merkleroot(&payouts, hash);
+    let receipthash: Vec<u8> = hash(&canonicalentries(TAG_RECEIPT, std::slice::from_ref(&export.receiptjson)));
+    let adminloghash: Vec<u8> = hash(&canonicalentries(TAG_ADMINLOG, &export.adminlog));
+    let transitionloghash: Vec<u8> = hash(&canonicalentries(TAG_TRANSITIONS, &export.transitions));
+    let confighash: Vec<u8> = hash(&canonicalentries(TAG_CONFIG, std::slice::from_ref(&export.configjson)));
+    let digest: Vec<u8> = hash(&CanonicalEncoder::new(TAG_TOMBSTONE).string(&export.marketid)
+        .hash(&betsroot).hash(&payoutroot).hash(&receipthash).hash(&adminloghash).hash(&transitionloghash).hash(&confighash).bytes());
+    TombstoneDigest { betsroot, payoutroot, receipthash, adminloghash, transitionloghash, confighash, digest }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::{joinmigrationchunks, migrationchunks};
+    use crate::settlement::{exportedbets, parsecontainerofbets, settle, ContainerOfBets, SettlementResult};
+
+    // FNV-1a, not a cryptographic hash, but deterministic and sensitive to every byte, which is all the recipe needs here
+    fn hash(bytes: &[u8]) -> Vec<u8> {
+        let mut value: u64 = 0xcbf29ce484222325;
+        for byte in bytes {
+            value ^= *byte as u64;
+            value = value.wrapping_mul(0x100000001b3);
+        }
+        value.to_be_bytes().to_vec()
+    }
+
+    // a settled market with every winner paid, as archivemarket exports it
+    fn settledexport() -> ArchiveExport {
+        let containerofbets: ContainerOfBets = parsecontainerofbets(r#"{"map":{
+            "alice":{"betamount":300,"betisforvalue":"yes","placedat":10,"attachedamount":300},
+            "bob":{"betamount":100,"betisforvalue":"yes","placedat":11,"attachedamount":150},
+            "carol":{"betamount":600,"betisforvalue":"no","placedat":12,"attachedamount":600}}}"#);
+        let settlement: SettlementResult = settle(&containerofbets, "yes");
+        let mut winners: Vec<(&String, &i64)> = settlement.winamounts.iter().collect();
+        winners.sort();
+        let payouts: Vec<ArchivedPayout> = winners.iter()
+            .map(|(address, amount)| ArchivedPayout { address: address.to_string(), amount: **amount, paidamount: **amount, reason: "win".to_string(), paid: true })
+            .collect();
+        ArchiveExport {
+            marketid: "weekly-1".to_string(),
+            bets: exportedbets(&containerofbets, None),
+            payouts,
+            receiptjson: r#"{"closedat":20,"betvaluewinning":"yes","totalbetamount":1000}"#.to_string(),
+            adminlog: vec!["20|closemarket|yes|pending:".to_string()],
+            transitions: vec!["1|initialized".to_string(), "20|settled".to_string()],
+            configjson: r#"{"marketid":{"value":"weekly-1","provenance":"builtin"}}"#.to_string(),
+        }
+    }
+
+    #[test]
+    fn the_digest_is_derived_again_from_the_archive_events() {
+        let export: ArchiveExport = settledexport();
+        let tombstone: TombstoneDigest = tombstonedigest(&export, &hash);
+
+        // off chain: join the chunks of the archive.chunk events and follow the recipe step by step
+        let payload: String = serde_json::to_string(&export).unwrap();
+        let chunks: Vec<Vec<u8>> = migrationchunks(payload.as_bytes()).iter().map(|chunk| chunk.to_vec()).collect();
+        let joined: Vec<u8> = joinmigrationchunks(chunks.len() as i64, |index| chunks.get(index as usize).cloned()).unwrap();
+        let received: ArchiveExport = serde_json::from_slice(&joined).unwrap();
+        let entries = |tag: &str, entries: &[String]| {
+            let mut encoder = CanonicalEncoder::new(tag);
+            encoder.length(entries.len());
+            entries.iter().for_each(|entry| { encoder.string(entry); });
+            hash(&encoder.bytes())
+        };
+        let leaves: Vec<Vec<u8>> = received.payouts.iter()
+            .map(|payout| CanonicalEncoder::new("payout.v1").string(&payout.address).int(payout.amount).int(payout.paidamount).string(&payout.reason).flag(payout.paid).bytes())
+            .collect();
+        let digest: Vec<u8> = hash(&CanonicalEncoder::new("tombstone.v1").string(&received.marketid)
+            .hash(&hash(&canonicalbets(&received.bets)))
+            .hash(&merkleroot(&leaves, &hash))
+            .hash(&entries("receipt.v1", std::slice::from_ref(&received.receiptjson)))
+            .hash(&entries("adminlog.v1", &received.adminlog))
+            .hash(&entries("transitions.v1", &received.transitions))
+            .hash(&entries("config.v1", std::slice::from_ref(&received.configjson)))
+            .bytes());
+        assert_eq!(digest, tombstone.digest);
+        assert_eq!(received.payouts.len(), 2);
+        assert_eq!(TOMBSTONERECIPE, "tombstone.v1");
+    }
+
+    #[test]
+    fn every_part_of_the_export_changes_the_digest() {
+        let digest: Vec<u8> = tombstonedigest(&settledexport(), &hash).digest;
+        let changes: Vec<fn(&mut ArchiveExport)> = vec![
+            |export| export.marketid = "weekly-2".to_string(),
+            |export| export.bets[0].betamount += 1,
+            |export| export.payouts[0].paid = false,
+            |export| export.receiptjson.push(' '),
+            |export| export.adminlog.clear(),
+            |export| export.transitions.reverse(),
+            |export| export.configjson.push(' '),
+        ];
+        for change in changes {
+            let mut export: ArchiveExport = settledexport();
+            change(&mut export);
+            assert_ne!(tombstonedigest(&export, &hash).digest, digest);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+// Rejections and accepted amounts of a bet, without access to the contract's context
+//
+// A rejected bet never fails the transaction in the default mode: the contract refunds what came with it, so that no IOTA of
+// a late bet or of a bet without BETVALUE stay in the contract. Only a soft rejection fails the transaction in strict mode.
+
+pub const ERR_BETS_FROZEN: &str = "ERR_BETS_FROZEN";
+pub const ERR_BET_TOO_LATE: &str = "ERR_BET_TOO_LATE";
+pub const ERR_MISSING_BETVALUE: &str = "ERR_MISSING_BETVALUE";
+pub const ERR_NO_IOTA: &str = "ERR_NO_IOTA";
+pub const ERR_INTENT_EXPIRED: &str = "ERR_INTENT_EXPIRED";
+
+// The reason a bet is rejected, with the error code returned in the result ERROR and the logged message.
+// A soft rejection, of a bet while bets are frozen or after the end time, fails the transaction in strict mode instead.
+#[derive(Debug, PartialEq)]
+pub struct BetRejection {
+    pub error: &'static str,
+    pub soft: bool,
+    pub message: &'static str,
+}
+
+// Check a bet against the rules that need neither its outcome nor the stored bets, in this order:
+// * the bets of the market are frozen because the outcome was declared early
+// * the bet comes after the end time for bets, see betontime
+// * the parameter BETVALUE is missing or empty
+// * no IOTA, or tokens of the market's color, were sent with the bet
+// A late bet is rejected whatever came with it, so a late call without IOTA reports ERR_BET_TOO_LATE.
+pub fn betrejection(betsfrozen: bool, betenddatetime: i64, currtime: i64, betvalue: &str, incoming: i64) -> Option<BetRejection> {
+    if betsfrozen {
+        return Some(BetRejection { error: ERR_BETS_FROZEN, soft: true, message: "bet was not accepted, bets are frozen because the outcome was already declared" });
+    }
+    if !crate::time::betontime(betenddatetime, currtime) {
+        return Some(BetRejection { error: ERR_BET_TOO_LATE, soft: true, message: "bet was not provided on time" });
+    }
+    if betvalue.is_empty() {
+        return Some(BetRejection { error: ERR_MISSING_BETVALUE, soft: false, message: "bet was not accepted, the parameter BETVALUE was not found" });
+    }
+    if incoming == 0 {
+        return Some(BetRejection { error: ERR_NO_IOTA, soft: false, message: "bet was not accepted, no IOTA were sent with it" });
+    }
+    None
+}
+
+// Check a bet executed at currtime against its optional NOTAFTER, the UNIX timestamp after which it must not be executed anymore,
+// e.g. when a congested chain delays the request. A bet in the very second of NOTAFTER is executed, a NOTAFTER of 0 never expires.
+pub fn intentexpiry(notafter: i64, currtime: i64) -> Option<BetRejection> {
+    if notafter > 0 && currtime > notafter {
+        return Some(BetRejection { error: ERR_INTENT_EXPIRED, soft: false, message: "bet was not accepted, it was executed after its NOTAFTER time" });
+    }
+    None
+}
+
+// Determine how much of the incoming IOTA is accepted for a bet, if no betting account may hold more than maxsharebps basis points of the pool after its bet.
+// The share is computed against the pool after the bet, where the bet adds to the previousamount of the same account, which is part of totalbetamount.
+// While the pool of the other bets is not larger than maxshareexemptpool, the share is not limited, which allows the very first bets.
+// A maxsharebps of 0 (or 10000 and more) does not limit the share. Later bets of other accounts may dilute the share below the maximum again.
+pub fn cappedbetamount(totalbetamount: i64, previousamount: i64, incoming: i64, maxsharebps: i64, maxshareexemptpool: i64) -> i64 {
+    if maxsharebps <= 0 || maxsharebps >= 10000 {
+        return incoming;
+    }
+    let otherbetamount: i64 = totalbetamount - previousamount;
+    if otherbetamount <= maxshareexemptpool {
+        return incoming;
+    }
+    // betamount / (otherbetamount + betamount) <= maxsharebps / 10000
+    let maxbetamount: i64 = (maxsharebps as i128 * otherbetamount as i128 / (10000 - maxsharebps) as i128) as i64;
+    incoming.min((maxbetamount - previousamount).max(0))
+}
+
+// Round a bet amount down to a multiple of the granularity, e.g. 10 IOTA; a granularity of 0 or 1 keeps the amount.
+pub fn flooredbetamount(amount: i64, betgranularity: i64) -> i64 {
+    if betgranularity <= 1 {
+        return amount;
+    }
+    amount / betgranularity * betgranularity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const END: i64 = 1622563200;
+
+    fn error(betsfrozen: bool, currtime: i64, betvalue: &str, incoming: i64) -> Option<&'static str> {
+        betrejection(betsfrozen, END, currtime, betvalue, incoming).map(|rejection| rejection.error)
+    }
+
+    #[test]
+    fn a_bet_on_time_with_value_and_iota_is_not_rejected() {
+        assert_eq!(error(false, END, "yes", 100), None);
+        assert!(error(false, END + 86400, "yes", 100).is_some());
+        // a market without an end time accepts bets at any time
+        assert_eq!(betrejection(false, 0, END + 86400, "yes", 100), None);
+    }
+
+    #[test]
+    fn a_late_bet_is_rejected_softly() {
+        let rejection: BetRejection = betrejection(false, END, END + 1, "yes", 100).unwrap();
+        assert_eq!(rejection.error, ERR_BET_TOO_LATE);
+        assert!(rejection.soft);
+        // whatever else is wrong with it
+        assert_eq!(error(false, END + 1, "", 0), Some(ERR_BET_TOO_LATE));
+    }
+
+    #[test]
+    fn frozen_bets_are_rejected_before_the_end_time_is_checked() {
+        assert_eq!(error(true, END, "yes", 100), Some(ERR_BETS_FROZEN));
+        assert_eq!(error(true, END + 1, "yes", 100), Some(ERR_BETS_FROZEN));
+        assert!(betrejection(true, END, END, "yes", 100).unwrap().soft);
+    }
+
+    #[test]
+    fn a_bet_without_betvalue_is_refunded_without_failing() {
+        let rejection: BetRejection = betrejection(false, END, END, "", 100).unwrap();
+        assert_eq!(rejection.error, ERR_MISSING_BETVALUE);
+        assert!(!rejection.soft);
+        assert_eq!(error(false, END, "", 0), Some(ERR_MISSING_BETVALUE));
+    }
+
+    #[test]
+    fn a_bet_without_iota_is_rejected() {
+        let rejection: BetRejection = betrejection(false, END, END, "yes", 0).unwrap();
+        assert_eq!(rejection.error, ERR_NO_IOTA);
+        assert!(!rejection.soft);
+        assert_eq!(error(false, END, "yes", 1), None);
+    }
+
+    #[test]
+    fn a_bet_is_executed_until_its_notafter_time() {
+        assert_eq!(intentexpiry(END, END - 60), None);
+        assert_eq!(intentexpiry(END, END), None);
+        let rejection: BetRejection = intentexpiry(END, END + 1).unwrap();
+        assert_eq!(rejection.error, ERR_INTENT_EXPIRED);
+        // the bet is refunded in strict mode as well
+        assert!(!rejection.soft);
+        // without NOTAFTER, a bet does not expire
+        assert_eq!(intentexpiry(0, END + 86400), None);
+    }
+
+    #[test]
+    fn the_share_of_the_pool_is_capped_after_the_exempt_pool() {
+        // a third of the pool at most: 500 of others allow a bet of 250
+        assert_eq!(cappedbetamount(500, 0, 1000, 3333, 0), 249);
+        assert_eq!(cappedbetamount(600, 100, 1000, 3333, 0), 149);
+        assert_eq!(cappedbetamount(500, 0, 1000, 3333, 500), 1000);
+        assert_eq!(cappedbetamount(500, 0, 1000, 0, 0), 1000);
+        // an account beyond its share already cannot add to its bet
+        assert_eq!(cappedbetamount(1000, 800, 100, 3333, 0), 0);
+    }
+
+    #[test]
+    fn a_bet_amount_is_floored_to_the_granularity() {
+        assert_eq!(flooredbetamount(129, 10), 120);
+        assert_eq!(flooredbetamount(9, 10), 0);
+        assert_eq!(flooredbetamount(129, 1), 129);
+        assert_eq!(flooredbetamount(129, 0), 129);
+    }
+}
@@ -0,0 +1,190 @@
+// Addresses blocked contract-wide by the contract owner, without access to the contract's context
+//
+// A blocked address cannot bet, sponsor, take over bets or claim. Blocking never confiscates: the entitlements of a blocked account stay
+// recorded but frozen. A payout skipping such an entitlement queues it as "marketid|index" in the frozen entitlements of the account,
+// across markets, and unblockaddress releases the queue in batches of MAXFROZENRELEASE, continuing at the head left by the previous call.
+// A released entitlement is queued for a retry by the payouts of its market, unless it is still queued there or is paid by a claim anyway.
+
+pub const ERR_ADDRESS_BLOCKED: &str = "ERR_ADDRESS_BLOCKED";
+
+// number of frozen entitlements released by a call to unblockaddress
+pub const MAXFROZENRELEASE: i64 = 100;
+
+// what a payout does with an unpaid entitlement of an account, blocked or not, which may have been frozen before
+#[derive(Debug, PartialEq)]
+pub enum FrozenPayout {
+    // the account is not blocked, the entitlement is paid
+    Pay,
+    // the entitlement is frozen and queued in the frozen entitlements of the account
+    Freeze,
+    // the entitlement was frozen and queued before, the payout skips it
+    Skip,
+}
+
+pub fn frozenpayout(blocked: bool, frozen: bool) -> FrozenPayout {
+    match (blocked, frozen) {
+        (false, _) => FrozenPayout::Pay,
+        (true, false) => FrozenPayout::Freeze,
+        (true, true) => FrozenPayout::Skip,
+    }
+}
+
+// the entry of the entitlement at index of the market marketid in the frozen entitlements of its account
+pub fn frozenentry(marketid: &str, index: i32) -> String {
+    marketid.to_string() + "|" + &index.to_string()
+}
+
+// the market id and the index of a frozen entry, the market id may contain "|" itself
+pub fn parsefrozenentry(entry: &str) -> Option<(&str, i32)> {
+    let separator: usize = entry.rfind('|')?;
+    Some((&entry[..separator], entry[separator + 1..].parse::<i32>().ok()?))
+}
+
+// Positions of the frozen entries released by the next call to unblockaddress, of the frozenqueued entries of the account
+// that were queued ever, from the head left by the previous call.
+pub fn releaserange(head: i64, frozenqueued: i64) -> std::ops::Range<i64> {
+    head..frozenqueued.min(head.saturating_add(MAXFROZENRELEASE))
+}
+
+// Whether a released entitlement is queued for a retry by the payouts of its market: not if it was paid, if it is flagged for manual
+// intervention, which only a claim pays, or if it failed before, in which case it is still queued in the payout retries.
+pub fn retryonrelease(paid: bool, manual: bool, attempts: i64) -> bool {
+    !paid && !manual && attempts == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    // an entitlement of a market, as the part of the contract keeping frozen entitlements sees it
+    #[derive(Clone, Debug, Default)]
+    struct Entitlement {
+        address: String,
+        amount: i64,
+        paid: bool,
+        manual: bool,
+        attempts: i64,
+        frozen: bool,
+    }
+
+    #[derive(Default)]
+    struct Contract {
+        blocked: BTreeSet<String>,
+        markets: BTreeMap<String, Vec<Entitlement>>,
+        frozen: BTreeMap<String, Vec<String>>,
+        frozenhead: BTreeMap<String, i64>,
+        retries: BTreeMap<String, Vec<i32>>,
+        wallets: BTreeMap<String, i64>,
+        refunded: BTreeMap<String, i64>,
+    }
+
+    impl Contract {
+        // a bet of a blocked account is refunded, otherwise it is accepted
+        fn bet(&mut self, address: &str, incoming: i64) -> Result<(), &'static str> {
+            if self.blocked.contains(address) {
+                *self.refunded.entry(address.to_string()).or_insert(0) += incoming;
+                return Err(ERR_ADDRESS_BLOCKED);
+            }
+            Ok(())
+        }
+
+        fn pay(&mut self, marketid: &str, index: i32) {
+            let blocked: bool = self.blocked.contains(&self.markets[marketid][index as usize].address);
+            let entitlement: &mut Entitlement = &mut self.markets.get_mut(marketid).unwrap()[index as usize];
+            if entitlement.paid {
+                return;
+            }
+            match frozenpayout(blocked, entitlement.frozen) {
+                FrozenPayout::Pay => {
+                    entitlement.paid = true;
+                    *self.wallets.entry(entitlement.address.clone()).or_insert(0) += entitlement.amount;
+                }
+                FrozenPayout::Freeze => {
+                    entitlement.frozen = true;
+                    self.frozen.entry(entitlement.address.clone()).or_default().push(frozenentry(marketid, index));
+                }
+                FrozenPayout::Skip => {}
+            }
+        }
+
+        // unblockaddress, returning the number released and still frozen
+        fn unblock(&mut self, address: &str) -> (i64, i64) {
+            self.blocked.remove(address);
+            let queued: Vec<String> = self.frozen.get(address).cloned().unwrap_or_default();
+            let head: i64 = *self.frozenhead.get(address).unwrap_or(&0);
+            let range: std::ops::Range<i64> = releaserange(head, queued.len() as i64);
+            for position in range.clone() {
+                let (marketid, index) = parsefrozenentry(&queued[position as usize]).unwrap();
+                let entitlement: &mut Entitlement = &mut self.markets.get_mut(marketid).unwrap()[index as usize];
+                entitlement.frozen = false;
+                if retryonrelease(entitlement.paid, entitlement.manual, entitlement.attempts) {
+                    self.retries.entry(marketid.to_string()).or_default().push(index);
+                }
+            }
+            self.frozenhead.insert(address.to_string(), range.end);
+            (range.end - range.start, queued.len() as i64 - range.end)
+        }
+    }
+
+    #[test]
+    fn a_blocked_account_cannot_bet_and_is_refunded() {
+        let mut contract: Contract = Contract::default();
+        contract.blocked.insert("mallory".to_string());
+        assert_eq!(contract.bet("mallory", 100), Err(ERR_ADDRESS_BLOCKED));
+        assert_eq!(contract.refunded["mallory"], 100);
+        assert_eq!(contract.bet("alice", 100), Ok(()));
+        contract.unblock("mallory");
+        assert_eq!(contract.bet("mallory", 100), Ok(()));
+    }
+
+    #[test]
+    fn frozen_entitlements_are_released_in_batches_after_unblocking() {
+        let mut contract: Contract = Contract::default();
+        // 150 entitlements of mallory in each of two markets, whose ids contain the separator, among those of alice
+        for marketid in ["cup|final", "cup|semi"].iter() {
+            let mut entitlements: Vec<Entitlement> = Vec::new();
+            for i in 0..300 {
+                let address: &str = if i % 2 == 0 { "mallory" } else { "alice" };
+                entitlements.push(Entitlement { address: address.to_string(), amount: 10, ..Entitlement::default() });
+            }
+            // one of mallory's failed before and one was flagged for manual intervention
+            entitlements[0].attempts = 1;
+            entitlements[2].manual = true;
+            contract.markets.insert(marketid.to_string(), entitlements);
+        }
+        contract.blocked.insert("mallory".to_string());
+        for marketid in ["cup|final", "cup|semi"].iter() {
+            for index in 0..300 {
+                contract.pay(marketid, index);
+            }
+            // paying again skips the frozen entitlements instead of queueing them twice
+            for index in 0..300 {
+                contract.pay(marketid, index);
+            }
+        }
+        // the claims of mallory are frozen, the entitlements stay attributed to mallory
+        assert_eq!(contract.wallets["alice"], 3000);
+        assert!(!contract.wallets.contains_key("mallory"));
+        assert_eq!(contract.frozen["mallory"].len(), 300);
+        assert!(contract.markets.values().flatten().filter(|entitlement| entitlement.address == "mallory").all(|entitlement| entitlement.frozen && !entitlement.paid));
+
+        // unblocking releases 100 per call
+        assert_eq!(contract.unblock("mallory"), (100, 200));
+        assert_eq!(contract.unblock("mallory"), (100, 100));
+        assert_eq!(contract.unblock("mallory"), (100, 0));
+        assert_eq!(contract.unblock("mallory"), (0, 0));
+        assert!(contract.markets.values().flatten().all(|entitlement| !entitlement.frozen));
+        // all but the failed and the manual one of each market are queued for retry, and paid by it
+        assert_eq!(contract.retries.values().map(|retries| retries.len()).sum::<usize>(), 296);
+        let retries: BTreeMap<String, Vec<i32>> = contract.retries.clone();
+        for (marketid, indices) in retries.iter() {
+            for index in indices {
+                contract.pay(marketid, *index);
+            }
+        }
+        assert_eq!(contract.wallets["mallory"], 2960);
+        assert_eq!(parsefrozenentry("cup|final|12"), Some(("cup|final", 12)));
+        assert_eq!(parsefrozenentry("12"), None);
+    }
+}
@@ -0,0 +1,226 @@
+// The fixed-odds book of a market, without access to the contract's context
+//
+// A market initialized with BOOK=true is not parimutuel: the contract owner quotes decimal odds per outcome in basis points,
+// e.g. 19000 for 1.90, and a bet is locked at the odds quoted when it is accepted, so that it wins its amount times these odds.
+// The implied probability of odds is 10000 * 10000 / odds in basis points, rounded to the nearest, and the implied probabilities
+// of all outcomes sum to the overround of the book. Every book has to keep its overround within OVERROUNDMINBPS and OVERROUNDMAXBPS:
+// below 10000 the book offers an arbitrage, a sure win for whoever backs every outcome, far above it the odds are absurd.
+// The owner funds the book with a bankroll: a bet is only accepted as far as the bankroll and the stakes cover the locked payouts
+// of its outcome, the liability of the outcome.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::settlement::{bettotals, winningvalues, BetTotals, ContainerOfBets, SettlementResult};
+
+// a bet on a value without odds in the book, e.g. before the first odds were set
+pub const ERR_NO_ODDS: &str = "ERR_NO_ODDS";
+// a bet beyond what the book can cover
+pub const ERR_BOOK_LIABILITY: &str = "ERR_BOOK_LIABILITY";
+
+// band of the overround unless OVERROUNDMINBPS and OVERROUNDMAXBPS are given
+pub const DEFAULTOVERROUNDMINBPS: i64 = 10000;
+pub const DEFAULTOVERROUNDMAXBPS: i64 = 11500;
+// odds above 1.00 and up to 1000.00
+pub const MAXODDSBPS: i64 = 10_000_000;
+
+// The odds of every outcome, given as ODDS like "yes:19000,no:19000": every outcome of the market exactly once, and no other value.
+pub fn parsebook(odds: &str, outcomes: &[String]) -> Result<BTreeMap<String, i64>, String> {
+    let mut book: BTreeMap<String, i64> = BTreeMap::new();
+    for entry in odds.split(',').filter(|entry| !entry.trim().is_empty()) {
+        let (betvalue, oddsbps) = match entry.split_once(':') {
+            Some((betvalue, oddsbps)) => (betvalue.trim(), oddsbps.trim()),
+            None => return Err("an entry of ODDS must be OUTCOME:ODDSBPS, not ".to_string() + entry),
+        };
+        if !outcomes.iter().any(|outcome| outcome == betvalue) {
+            return Err("ODDS gives odds for ".to_string() + betvalue + ", which is not one of the OUTCOMES of the market");
+        }
+        let oddsbps: i64 = match oddsbps.parse::<i64>() {
+            Ok(oddsbps) if oddsbps > 10000 && oddsbps <= MAXODDSBPS => oddsbps,
+            _ => return Err("the odds of ".to_string() + betvalue + " must be basis points above 10000 and up to " + &MAXODDSBPS.to_string()),
+        };
+        if book.insert(betvalue.to_string(), oddsbps).is_some() {
+            return Err("ODDS gives more than one odds for ".to_string() + betvalue);
+        }
+    }
+    if let Some(missing) = outcomes.iter().find(|outcome| !book.contains_key(*outcome)) {
+        return Err("ODDS gives no odds for the outcome ".to_string() + missing);
+    }
+    Ok(book)
+}
+
+// the implied probability of odds in basis points
+pub fn impliedbps(oddsbps: i64) -> i64 {
+    (100_000_000 + oddsbps / 2) / oddsbps
+}
+
+// the sum of the implied probabilities of the odds of a book in basis points
+pub fn overroundbps(book: &BTreeMap<String, i64>) -> i64 {
+    book.values().map(|oddsbps| impliedbps(*oddsbps)).sum()
+}
+
+// The overround of a book, or Err with the reason if it is outside of the band from overroundminbps to overroundmaxbps.
+pub fn validatebook(book: &BTreeMap<String, i64>, overroundminbps: i64, overroundmaxbps: i64) -> Result<i64, String> {
+    let overround: i64 = overroundbps(book);
+    if overround < overroundminbps {
+        let arbitrage: &str = if overround < 10000 { ", which offers an arbitrage" } else { "" };
+        return Err("the implied probabilities of the odds sum to ".to_string() + &overround.to_string() + " basis points, below the minimum of "
+            + &overroundminbps.to_string() + arbitrage);
+    }
+    if overround > overroundmaxbps {
+        return Err("the implied probabilities of the odds sum to ".to_string() + &overround.to_string() + " basis points, above the maximum of "
+            + &overroundmaxbps.to_string());
+    }
+    Ok(overround)
+}
+
+// what a bet of amount IOTA locked at oddsbps wins, rounded down
+pub fn lockedpayout(amount: i64, oddsbps: i64) -> i64 {
+    amount.saturating_mul(oddsbps) / 10000
+}
+
+// The amount of a bet at oddsbps the book can accept on an outcome with the locked payouts liability, funded by the bankroll and
+// the stakes of all bets: the bet adds its amount to the funds and its payout to the liability, which must not exceed the funds.
+pub fn bookcappedamount(amount: i64, oddsbps: i64, liability: i64, funded: i64) -> i64 {
+    let headroom: i64 = (funded - liability).max(0);
+    amount.min(headroom.saturating_mul(10000) / (oddsbps - 10000))
+}
+
+// what the book has to pay if an outcome wins, and what it could still pay on top of that
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Liability {
+    pub payout: i64,
+    pub remaining: i64,
+}
+
+// The liability of every outcome of a book with the locked payouts per outcome, funded by the bankroll and the stakes of all bets.
+pub fn bookliabilities(book: &BTreeMap<String, i64>, payouts: &BTreeMap<String, i64>, funded: i64) -> BTreeMap<String, Liability> {
+    book.keys().map(|betvalue| {
+        let payout: i64 = *payouts.get(betvalue).unwrap_or(&0);
+        (betvalue.to_string(), Liability { payout, remaining: funded - payout })
+    }).collect()
+}
+
+// Settle a book for the winning value: every bet on it wins the payouts locked by the account, payouts per address, the rest of
+// the bankroll and the stakes stays in the pool as residual funds. A book takes no fee, its margin is the overround.
+pub fn booksettlement(containerofbets: &ContainerOfBets, payouts: &HashMap<String, i64>, betvaluewinning: &str) -> SettlementResult {
+    let BetTotals { totalbetamount, betvalue_totalbetamount } = bettotals(containerofbets);
+    let winning: Vec<&str> = winningvalues(betvaluewinning);
+    let totalbetamountforwinningvalue: i32 = winning.iter().map(|betvalue| *betvalue_totalbetamount.get(*betvalue).unwrap_or(&0)).sum();
+    let winamounts: HashMap<String, i64> = containerofbets.map.iter()
+        .filter(|(_, bet)| winning.contains(&bet.betisforvalue.as_str()))
+        .map(|(betteraddress, _)| (betteraddress.to_string(), *payouts.get(betteraddress).unwrap_or(&0)))
+        .collect();
+    let totalwinamount: i64 = winamounts.values().sum();
+    let realizedoddsbps: i64 = if totalbetamountforwinningvalue > 0 { totalwinamount * 10000 / totalbetamountforwinningvalue as i64 } else { 0 };
+    SettlementResult {
+        totalbetamount,
+        betvalue_totalbetamount,
+        totalbetamountforwinningvalue,
+        winamounts,
+        realizedoddsbps,
+        operatortake: 0,
+        returnper100: realizedoddsbps / 100,
+        unanimous: false,
+        remainder: 0,
+        remainderrecipient: "".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::parsecontainerofbets;
+
+    fn outcomes() -> Vec<String> {
+        vec!["home".to_string(), "draw".to_string(), "away".to_string()]
+    }
+
+    #[test]
+    fn the_odds_cover_every_outcome_once() {
+        let book: BTreeMap<String, i64> = parsebook("home:25000, draw:32000,away:30000", &outcomes()).unwrap();
+        assert_eq!(book["draw"], 32000);
+        assert!(parsebook("home:25000,draw:32000", &outcomes()).unwrap_err().ends_with("away"));
+        assert!(parsebook("home:25000,draw:32000,away:30000,other:20000", &outcomes()).is_err());
+        assert!(parsebook("home:25000,draw:32000,away:30000,home:26000", &outcomes()).is_err());
+        assert!(parsebook("home:10000,draw:32000,away:30000", &outcomes()).is_err());
+        assert!(parsebook("home:2.5,draw:32000,away:30000", &outcomes()).is_err());
+    }
+
+    #[test]
+    fn an_arbitrage_book_is_rejected() {
+        // 1/2.10 + 1/2.10 = 95.24%: backing both outcomes wins whatever happens
+        let outcomes: Vec<String> = vec!["yes".to_string(), "no".to_string()];
+        let book: BTreeMap<String, i64> = parsebook("yes:21000,no:21000", &outcomes).unwrap();
+        assert_eq!(overroundbps(&book), 9524);
+        let error: String = validatebook(&book, DEFAULTOVERROUNDMINBPS, DEFAULTOVERROUNDMAXBPS).unwrap_err();
+        assert!(error.contains("9524") && error.contains("arbitrage"));
+        // and so is an absurd margin of 1/1.50 + 1/1.50 = 133.33%
+        let absurd: BTreeMap<String, i64> = parsebook("yes:15000,no:15000", &outcomes).unwrap();
+        assert!(validatebook(&absurd, DEFAULTOVERROUNDMINBPS, DEFAULTOVERROUNDMAXBPS).unwrap_err().contains("above the maximum of 11500"));
+    }
+
+    #[test]
+    fn a_normal_book_is_accepted() {
+        // 40% + 31.25% + 33.33% = 104.58%
+        let book: BTreeMap<String, i64> = parsebook("home:25000,draw:32000,away:30000", &outcomes()).unwrap();
+        assert_eq!((impliedbps(25000), impliedbps(32000), impliedbps(30000)), (4000, 3125, 3333));
+        assert_eq!(validatebook(&book, DEFAULTOVERROUNDMINBPS, DEFAULTOVERROUNDMAXBPS), Ok(10458));
+        assert!(validatebook(&book, 10500, DEFAULTOVERROUNDMAXBPS).is_err());
+    }
+
+    #[test]
+    fn a_bet_is_accepted_as_far_as_the_book_can_cover_it() {
+        // a bankroll of 1000 IOTA covers a bet of 1000 IOTA at 2.00, which adds its amount to the funds and 2000 IOTA to the liability
+        assert_eq!(bookcappedamount(1500, 20000, 0, 1000), 1000);
+        assert_eq!(bookcappedamount(500, 20000, 0, 1000), 500);
+        // without bankroll nothing is accepted
+        assert_eq!(bookcappedamount(500, 20000, 0, 0), 0);
+        // the stakes on the other outcomes fund the book as well
+        assert_eq!(bookcappedamount(5000, 30000, 2000, 2000 + 1000), 500);
+    }
+
+    #[test]
+    fn locked_bets_keep_their_odds_when_the_book_changes() {
+        let outcomes: Vec<String> = vec!["yes".to_string(), "no".to_string()];
+        let mut book: BTreeMap<String, i64> = parsebook("yes:19000,no:19000", &outcomes).unwrap();
+        let bankroll: i64 = 5000;
+        // alice backs yes at 1.90 and carol no at 1.90, then the owner shortens yes to 1.50 and bob backs yes at that price
+        let mut payouts: HashMap<String, i64> = HashMap::new();
+        payouts.insert("alice".to_string(), lockedpayout(1000, book["yes"]));
+        payouts.insert("carol".to_string(), lockedpayout(400, book["no"]));
+        let repriced: BTreeMap<String, i64> = parsebook("yes:15000,no:26000", &outcomes).unwrap();
+        assert!(validatebook(&repriced, DEFAULTOVERROUNDMINBPS, DEFAULTOVERROUNDMAXBPS).is_ok());
+        book = repriced;
+        // a further bet of alice is locked at the new odds, her first one keeps its payout
+        *payouts.get_mut("alice").unwrap() += lockedpayout(100, book["yes"]);
+        payouts.insert("bob".to_string(), lockedpayout(1000, book["yes"]));
+        assert_eq!(payouts["alice"], 1900 + 150);
+        assert_eq!(payouts["bob"], 1500);
+        assert_eq!(payouts["carol"], 760);
+
+        let mut peroutcome: BTreeMap<String, i64> = BTreeMap::new();
+        peroutcome.insert("yes".to_string(), payouts["alice"] + payouts["bob"]);
+        peroutcome.insert("no".to_string(), payouts["carol"]);
+        let stakes: i64 = 1100 + 1000 + 400;
+        let liabilities: BTreeMap<String, Liability> = bookliabilities(&book, &peroutcome, bankroll + stakes);
+        assert_eq!(liabilities["yes"], Liability { payout: 3550, remaining: 3950 });
+        assert_eq!(liabilities["no"], Liability { payout: 760, remaining: 6740 });
+
+        // the settlement pays the locked payouts, not the odds quoted at the end
+        let containerofbets: ContainerOfBets = parsecontainerofbets(r#"{"map":{
+            "alice":{"betamount":1100,"betisforvalue":"yes","placedat":10,"attachedamount":1100},
+            "bob":{"betamount":1000,"betisforvalue":"yes","placedat":11,"attachedamount":1000},
+            "carol":{"betamount":400,"betisforvalue":"no","placedat":12,"attachedamount":400}}}"#);
+        let settlement: SettlementResult = booksettlement(&containerofbets, &payouts, "yes");
+        assert_eq!(settlement.winamounts.len(), 2);
+        assert_eq!((settlement.winamounts["alice"], settlement.winamounts["bob"]), (2050, 1500));
+        assert_eq!(settlement.realizedoddsbps, 3550 * 10000 / 2100);
+        assert_eq!(settlement.operatortake, 0);
+        // what the winners do not get of the bankroll and the stakes stays in the pool
+        assert_eq!(bankroll + stakes - settlement.winamounts.values().sum::<i64>(), 3950);
+        let nowinner: SettlementResult = booksettlement(&containerofbets, &payouts, "no");
+        assert_eq!(nowinner.winamounts["carol"], 760);
+    }
+}
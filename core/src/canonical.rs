@@ -0,0 +1,324 @@
+// Canonical byte encodings of everything the contract hashes or has signed
+//
+// A hash over json depends on the field order of the structs and on how serde serializes them, so every hashed or signed
+// structure is encoded here explicitly instead:
+// * an encoding starts with its tag, which names the structure and its version, e.g. "bets.v1",
+// * a string is its length as 4 bytes followed by its utf-8 bytes, an integer is 8 bytes, a flag is 1 byte, all big endian,
+// * a hash is its length as 4 bytes followed by its bytes, like a string,
+// * a list is its length as 4 bytes followed by its items, a map is encoded as the list of its entries sorted by key,
+// * a nested structure is its own encoding including its tag,
+// * the fields follow in the order written down here, which does not depend on the order of the fields of the structs.
+// Changing an encoding changes the hashes of all markets, so it requires a new tag.
+
+use std::collections::BTreeMap;
+
+use crate::settlement::{Bet, ContainerOfBets, ExportedBet};
+
+
+pub const TAG_ADMINLOG: &str = "adminlog.v1";
+pub const TAG_BETFOR: &str = "betfor2";
+pub const TAG_BETS: &str = "bets.v1";
+pub const TAG_COMMITMENT: &str = "commitment.v1";
+pub const TAG_CONFIG: &str = "config.v1";
+pub const TAG_CONTAINEROFBETS: &str = "containerofbets.v1";
+pub const TAG_DEFAULTS: &str = "defaults.v1";
+pub const TAG_LEAF: &str = "leaf.v1";
+pub const TAG_MIGRATION: &str = "migration.v1";
+pub const TAG_PAYOUT: &str = "payout.v1";
+pub const TAG_RECEIPT: &str = "receipt.v1";
+pub const TAG_SETTINGS: &str = "settings.v1";
+pub const TAG_TIEBREAK: &str = "tiebreak.v1";
+pub const TAG_TOMBSTONE: &str = "tombstone.v1";
+pub const TAG_TRANSITIONS: &str = "transitions.v1";
+
+pub struct CanonicalEncoder {
+    bytes: Vec<u8>,
+}
+
+impl CanonicalEncoder {
+    // an encoding starting with the tag, or without one for a tag that is encoded like a string field, e.g. a version of a message
+    pub fn new(tag: &str) -> CanonicalEncoder {
+        let mut encoder = CanonicalEncoder { bytes: Vec::new() };
+        if !tag.is_empty() {
+            encoder.string(tag);
+        }
+        encoder
+    }
+
+    pub fn string(&mut self, value: &str) -> &mut CanonicalEncoder {
+        self.length(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+        self
+    }
+
+    pub fn int(&mut self, value: i64) -> &mut CanonicalEncoder {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn flag(&mut self, value: bool) -> &mut CanonicalEncoder {
+        self.bytes.push(value as u8);
+        self
+    }
+
+    pub fn hash(&mut self, value: &[u8]) -> &mut CanonicalEncoder {
+        self.length(value.len());
+        self.bytes.extend_from_slice(value);
+        self
+    }
+
+    // number of items of a list or entries of a map
+    pub fn length(&mut self, length: usize) -> &mut CanonicalEncoder {
+        self.bytes.extend_from_slice(&(length as u32).to_be_bytes());
+        self
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+}
+
+// The exported bets in their order, which exportedbets sorts by address. Their hash is the bets root.
+pub fn canonicalbets(bets: &[ExportedBet]) -> Vec<u8> {
+    let mut encoder = CanonicalEncoder::new(TAG_BETS);
+    encoder.length(bets.len());
+    for bet in bets {
+        encoder.string(&bet.address)
+            .int(bet.betamount as i64)
+            .string(&bet.betisforvalue)
+            .int(bet.placedat)
+            .int(bet.attachedamount)
+            .string(&bet.requestid);
+    }
+    encoder.bytes()
+}
+
+// All stored fields of the bets sorted by address, e.g. for carrying a market to another contract.
+pub fn canonicalcontainerofbets(containerofbets: &ContainerOfBets) -> Vec<u8> {
+    let sorted: BTreeMap<&String, &Bet> = containerofbets.map.iter().collect();
+    let mut encoder = CanonicalEncoder::new(TAG_CONTAINEROFBETS);
+    encoder.length(sorted.len());
+    for (address, bet) in sorted {
+        encoder.string(address)
+            .int(bet.betamount as i64)
+            .string(&bet.betisforvalue)
+            .int(bet.placedat)
+            .int(bet.attachedamount)
+            .string(&bet.addressencoding)
+            .string(&bet.requestid)
+            .string(&bet.clientid)
+            .string(&bet.campaign);
+    }
+    encoder.bytes()
+}
+
+// leaf of the Merkle tree for the bet of an address
+pub fn canonicalleaf(address: &str, betisforvalue: &str, betamount: i64) -> Vec<u8> {
+    CanonicalEncoder::new(TAG_LEAF).string(address).string(betisforvalue).int(betamount).bytes()
+}
+
+// leaf of the Merkle tree of the payouts of a market for an entitlement: the address, the amount it is entitled to,
+// the IOTA transferred to it, the reason and whether it is paid
+pub fn canonicalpayout(address: &str, amount: i64, paidamount: i64, reason: &str, paid: bool) -> Vec<u8> {
+    CanonicalEncoder::new(TAG_PAYOUT).string(address).int(amount).int(paidamount).string(reason).flag(paid).bytes()
+}
+
+// A list of entries in their order, like the admin log of a market, under the tag naming the list.
+pub fn canonicalentries(tag: &str, entries: &[String]) -> Vec<u8> {
+    let mut encoder = CanonicalEncoder::new(tag);
+    encoder.length(entries.len());
+    for entry in entries {
+        encoder.string(entry);
+    }
+    encoder.bytes()
+}
+
+// settings given as strings and as integers, each sorted by key
+pub fn canonicalsettings(settings: &BTreeMap<String, String>, intsettings: &BTreeMap<String, i64>) -> Vec<u8> {
+    let mut encoder = CanonicalEncoder::new(TAG_SETTINGS);
+    encoder.length(settings.len());
+    for (key, value) in settings {
+        encoder.string(key).string(value);
+    }
+    encoder.length(intsettings.len());
+    for (key, value) in intsettings {
+        encoder.string(key).int(*value);
+    }
+    encoder.bytes()
+}
+
+// The parameters of a bet relayed by betfor that affect the bet, all of which the betting account signs, so that the relayer can change none of them.
+pub struct BetIntent {
+    pub betvalue: String,
+    pub amount: i64,
+    pub notafter: i64,
+    // hash of the terms the betting account acknowledges, "" for a market without terms
+    pub ackterms: String,
+    pub clientid: String,
+    pub campaign: String,
+    // the encrypted memo, "" if none
+    pub memocipher: String,
+}
+
+// Bytes a betting account signs to authorize a bet relayed by betfor, used both for verifying the signature and by the view getnonce,
+// so that wallets cannot diverge from the verification: the market id, the bet value, the amount, the nonce, the NOTAFTER timestamp,
+// the acknowledged terms hash ACKTERMS, the CLIENTID, the CAMPAIGN and the MEMOCIPHER. Parameters not given are signed as "" or 0.
+pub fn canonicalbetfor(marketid: &str, intent: &BetIntent, nonce: i64) -> Vec<u8> {
+    CanonicalEncoder::new(TAG_BETFOR).string(marketid).string(&intent.betvalue).int(intent.amount).int(nonce).int(intent.notafter)
+        .string(&intent.ackterms).string(&intent.clientid).string(&intent.campaign).string(&intent.memocipher).bytes()
+}
+
+// Bytes hashed to the commitment of a sealed bet of a commit-reveal market: the market id, the address of the betting account,
+// the bet value and the salt chosen by the account, so that a commitment cannot be revealed by another account or in another market.
+pub fn canonicalcommitment(marketid: &str, address: &str, betvalue: &str, salt: &str) -> Vec<u8> {
+    CanonicalEncoder::new(TAG_COMMITMENT).string(marketid).string(address).string(betvalue).string(salt).bytes()
+}
+
+// State of an open market carried to another contract: its id, all its bets, its pool and its settings.
+pub fn canonicalmigration(marketid: &str, containerofbets: &ContainerOfBets, pool: i64, settings: &BTreeMap<String, String>, intsettings: &BTreeMap<String, i64>) -> Vec<u8> {
+    let mut bytes: Vec<u8> = CanonicalEncoder::new(TAG_MIGRATION).string(marketid).int(pool).bytes();
+    bytes.extend(canonicalcontainerofbets(containerofbets));
+    bytes.extend(canonicalsettings(settings, intsettings));
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::containerofexportedbets;
+
+    fn bet(address: &str, betamount: i32) -> ExportedBet {
+        ExportedBet {
+            address: address.to_string(),
+            betamount,
+            betisforvalue: "yes".to_string(),
+            placedat: 2,
+            attachedamount: 3,
+            requestid: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn fields_are_encoded_length_prefixed_and_big_endian() {
+        let bytes: Vec<u8> = CanonicalEncoder::new("t.v1").string("ab").int(258).flag(true).length(2).bytes();
+        assert_eq!(bytes, vec![
+            0, 0, 0, 4, b't', b'.', b'v', b'1',
+            0, 0, 0, 2, b'a', b'b',
+            0, 0, 0, 0, 0, 0, 1, 2,
+            1,
+            0, 0, 0, 2,
+        ]);
+        assert_eq!(CanonicalEncoder::new("").int(-1).bytes(), vec![255; 8]);
+        assert_eq!(CanonicalEncoder::new("").hash(&[7, 8]).bytes(), vec![0, 0, 0, 2, 7, 8]);
+    }
+
+    #[test]
+    fn leaf_encoding_is_fixed() {
+        let mut expected: Vec<u8> = vec![0, 0, 0, 7];
+        expected.extend_from_slice(b"leaf.v1");
+        expected.extend_from_slice(&[0, 0, 0, 1, b'a', 0, 0, 0, 3, b'y', b'e', b's', 0, 0, 0, 0, 0, 0, 0, 100]);
+        assert_eq!(canonicalleaf("a", "yes", 100), expected);
+    }
+
+    #[test]
+    fn a_commitment_is_bound_to_its_market_and_address() {
+        let mut expected: Vec<u8> = vec![0, 0, 0, 13];
+        expected.extend_from_slice(b"commitment.v1");
+        expected.extend_from_slice(&[0, 0, 0, 1, b'm', 0, 0, 0, 1, b'a', 0, 0, 0, 3, b'y', b'e', b's', 0, 0, 0, 1, b's']);
+        assert_eq!(canonicalcommitment("m", "a", "yes", "s"), expected);
+        assert_ne!(canonicalcommitment("m", "a", "yes", "s"), canonicalcommitment("m", "b", "yes", "s"));
+        assert_ne!(canonicalcommitment("m", "a", "yes", "s"), canonicalcommitment("n", "a", "yes", "s"));
+    }
+
+    #[test]
+    fn bets_encoding_is_fixed() {
+        let mut expected: Vec<u8> = vec![0, 0, 0, 7];
+        expected.extend_from_slice(b"bets.v1");
+        expected.extend_from_slice(&[0, 0, 0, 1]);
+        expected.extend_from_slice(&[0, 0, 0, 1, b'a']);
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 100]);
+        expected.extend_from_slice(&[0, 0, 0, 3, b'y', b'e', b's']);
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2]);
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 3]);
+        expected.extend_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(canonicalbets(&[bet("a", 100)]), expected);
+    }
+
+    #[test]
+    fn container_of_bets_is_encoded_in_address_order() {
+        let first: ContainerOfBets = containerofexportedbets(vec![bet("c", 1), bet("a", 2), bet("b", 3)]);
+        let second: ContainerOfBets = containerofexportedbets(vec![bet("b", 3), bet("c", 1), bet("a", 2)]);
+        assert_eq!(canonicalcontainerofbets(&first), canonicalcontainerofbets(&second));
+        assert_ne!(canonicalbets(&[bet("a", 1)]), canonicalbets(&[bet("a", 2)]));
+    }
+
+    #[test]
+    fn settings_encoding_is_fixed() {
+        let mut settings: BTreeMap<String, String> = BTreeMap::new();
+        settings.insert("b".to_string(), "x".to_string());
+        settings.insert("a".to_string(), "".to_string());
+        let mut intsettings: BTreeMap<String, i64> = BTreeMap::new();
+        intsettings.insert("n".to_string(), 5);
+        let mut expected: Vec<u8> = vec![0, 0, 0, 11];
+        expected.extend_from_slice(b"settings.v1");
+        expected.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 1, b'a', 0, 0, 0, 0, 0, 0, 0, 1, b'b', 0, 0, 0, 1, b'x']);
+        expected.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, b'n', 0, 0, 0, 0, 0, 0, 0, 5]);
+        assert_eq!(canonicalsettings(&settings, &intsettings), expected);
+    }
+
+    fn intent() -> BetIntent {
+        BetIntent {
+            betvalue: "yes".to_string(),
+            amount: 100,
+            notafter: 5,
+            ackterms: "t".to_string(),
+            clientid: "c".to_string(),
+            campaign: "".to_string(),
+            memocipher: "m".to_string(),
+        }
+    }
+
+    #[test]
+    fn betfor_encoding_is_fixed() {
+        let mut expected: Vec<u8> = vec![0, 0, 0, 7];
+        expected.extend_from_slice(b"betfor2");
+        expected.extend_from_slice(&[0, 0, 0, 1, b'k', 0, 0, 0, 3, b'y', b'e', b's']);
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 100, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 5]);
+        expected.extend_from_slice(&[0, 0, 0, 1, b't', 0, 0, 0, 1, b'c', 0, 0, 0, 0, 0, 0, 0, 1, b'm']);
+        assert_eq!(canonicalbetfor("k", &intent(), 2), expected);
+    }
+
+    // a relayer changing any parameter of the bet invalidates the signature
+    #[test]
+    fn every_parameter_of_a_relayed_bet_is_signed() {
+        let signed: Vec<u8> = canonicalbetfor("k", &intent(), 2);
+        let changes: [fn(&mut BetIntent); 7] = [
+            |intent| intent.betvalue = "no".to_string(),
+            |intent| intent.amount = 101,
+            |intent| intent.notafter = 6,
+            |intent| intent.ackterms = "u".to_string(),
+            |intent| intent.clientid = "d".to_string(),
+            |intent| intent.campaign = "x".to_string(),
+            |intent| intent.memocipher = "".to_string(),
+        ];
+        for change in changes.iter() {
+            let mut changed: BetIntent = intent();
+            change(&mut changed);
+            assert_ne!(canonicalbetfor("k", &changed, 2), signed);
+        }
+        assert_ne!(canonicalbetfor("l", &intent(), 2), signed);
+        assert_ne!(canonicalbetfor("k", &intent(), 3), signed);
+    }
+
+    #[test]
+    fn migration_starts_with_market_and_pool() {
+        let containerofbets: ContainerOfBets = containerofexportedbets(Vec::new());
+        let bytes: Vec<u8> = canonicalmigration("m", &containerofbets, 7, &BTreeMap::new(), &BTreeMap::new());
+        let mut expected: Vec<u8> = vec![0, 0, 0, 12];
+        expected.extend_from_slice(b"migration.v1");
+        expected.extend_from_slice(&[0, 0, 0, 1, b'm', 0, 0, 0, 0, 0, 0, 0, 7]);
+        expected.extend(canonicalcontainerofbets(&containerofbets));
+        expected.extend(canonicalsettings(&BTreeMap::new(), &BTreeMap::new()));
+        assert_eq!(bytes, expected);
+    }
+}
@@ -0,0 +1,100 @@
+// Sealed bets of a commit-reveal market, without access to the contract's context
+//
+// In a market initialized with COMMITREVEAL, a bet is committed as the hash of its value and a salt, with its IOTA held in escrow,
+// and revealed later. The number of unrevealed commitments of an address and the IOTA they hold are limited, so that nobody can
+// flood the market with commitments never meant to be revealed. An unrevealed commitment expires at the reveal deadline.
+
+pub const ERR_COMMIT_LIMIT: &str = "ERR_COMMIT_LIMIT";
+// a bet placed by bet or betfor in a commit-reveal market
+pub const ERR_BETS_SEALED: &str = "ERR_BETS_SEALED";
+
+// number of unrevealed commitments an address may have unless MAXCOMMITS is given, and the seconds after the end time for bets
+// within which commitments can be revealed unless REVEALWINDOW is given
+pub const DEFAULTMAXCOMMITS: i64 = 3;
+pub const DEFAULTREVEALWINDOW: i64 = 86400;
+
+// the escrow of a commitment not revealed by the reveal deadline is returned to the committing address,
+// unless the market forfeits it to the contract owner with FORFEIT_OWNER, see disputes
+pub const COMMITFORFEIT_REFUND: &str = "REFUND";
+
+// unrevealed commitments of an address and the IOTA they hold in escrow
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CommitUsage {
+    pub commits: i64,
+    pub escrow: i64,
+}
+
+impl CommitUsage {
+    // The usage after a further commitment of amount IOTA, or Err with the reason if it would exceed maxcommits commitments
+    // or maxcommitescrow IOTA, 0 for no limit of the escrow.
+    pub fn admit(&self, amount: i64, maxcommits: i64, maxcommitescrow: i64) -> Result<CommitUsage, String> {
+        if self.commits >= maxcommits {
+            return Err("an address can have up to ".to_string() + &maxcommits.to_string() + " unrevealed commitments");
+        }
+        if maxcommitescrow > 0 && self.escrow.saturating_add(amount) > maxcommitescrow {
+            return Err("the unrevealed commitments of an address can hold up to ".to_string() + &maxcommitescrow.to_string() + " IOTA");
+        }
+        Ok(CommitUsage { commits: self.commits + 1, escrow: self.escrow.saturating_add(amount) })
+    }
+
+    // the usage after a commitment of amount IOTA was revealed or expired
+    pub fn release(&self, amount: i64) -> CommitUsage {
+        CommitUsage { commits: (self.commits - 1).max(0), escrow: (self.escrow - amount).max(0) }
+    }
+}
+
+// Time until which the commitments can be revealed, revealwindow seconds after the end time for bets.
+pub fn revealdeadline(betenddatetime: i64, revealwindow: i64) -> i64 {
+    betenddatetime.saturating_add(revealwindow)
+}
+
+// Whether a commitment can be revealed at currtime, up to and including the second of the reveal deadline.
+pub fn revealontime(revealdeadline: i64, currtime: i64) -> bool {
+    currtime <= revealdeadline
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_number_of_unrevealed_commitments_is_limited() {
+        let mut usage: CommitUsage = CommitUsage::default();
+        for _commitment in 0..DEFAULTMAXCOMMITS {
+            usage = usage.admit(100, DEFAULTMAXCOMMITS, 0).unwrap();
+        }
+        assert_eq!(usage, CommitUsage { commits: 3, escrow: 300 });
+        assert!(usage.admit(1, DEFAULTMAXCOMMITS, 0).unwrap_err().contains("up to 3 unrevealed"));
+    }
+
+    #[test]
+    fn the_escrow_of_unrevealed_commitments_is_limited() {
+        let usage: CommitUsage = CommitUsage::default().admit(600, 3, 1000).unwrap();
+        assert_eq!(usage.admit(400, 3, 1000), Ok(CommitUsage { commits: 2, escrow: 1000 }));
+        assert!(usage.admit(401, 3, 1000).unwrap_err().contains("1000 IOTA"));
+        // without MAXCOMMITESCROW only the number is limited
+        assert!(usage.admit(i64::MAX, 3, 0).is_ok());
+    }
+
+    #[test]
+    fn a_revealed_commitment_makes_room_for_another() {
+        let full: CommitUsage = CommitUsage { commits: 3, escrow: 900 };
+        assert!(full.admit(100, 3, 1000).is_err());
+        let revealed: CommitUsage = full.release(300);
+        assert_eq!(revealed, CommitUsage { commits: 2, escrow: 600 });
+        assert_eq!(revealed.admit(400, 3, 1000), Ok(CommitUsage { commits: 3, escrow: 1000 }));
+        // releasing more than was committed never goes below nothing
+        assert_eq!(CommitUsage::default().release(100), CommitUsage::default());
+    }
+
+    #[test]
+    fn commitments_expire_after_the_reveal_deadline() {
+        let deadline: i64 = revealdeadline(1622563200, DEFAULTREVEALWINDOW);
+        assert_eq!(deadline, 1622649600);
+        assert!(revealontime(deadline, deadline));
+        assert!(!revealontime(deadline, deadline + 1));
+        // an expired commitment, refunded or kept as fee, frees its place like a revealed one
+        let usage: CommitUsage = CommitUsage { commits: 3, escrow: 300 }.release(100);
+        assert!(usage.admit(100, 3, 300).is_ok());
+    }
+}
@@ -0,0 +1,139 @@
+// Consistency rules of the configuration of a market, without access to the contract's context
+//
+// Every setting is checked on its own by initmarket. The rules here catch combinations of settings that are legal individually
+// but not together, either contradictions rejecting the market or merely suspicious combinations returned as warnings.
+
+use serde_with::serde_as;
+use serde::{Serialize, Deserialize};
+
+// shortest time in seconds bets must be accepted after the ANNOUNCEDELAY of a market with an end time
+pub const MINOPENWINDOW: i64 = 3600;
+
+// An inconsistent combination of settings found by configurationissues: fatal issues are contradictions that reject initmarket,
+// the others are merely suspicious and returned as warnings.
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+pub struct ConfigurationIssue {
+    pub rule: String,
+    pub fatal: bool,
+    pub message: String,
+}
+
+// Check the effective configuration, whose integer settings are given by their key, for combinations of settings that are legal
+// individually but not together:
+// * heartbeatwithoutend (fatal): a HEARTBEATWINDOW without BETENDUTC, the fallback resolution would never start
+// * gracewithoutend (fatal): a RESOLUTIONGRACE without BETENDUTC, the forced refund would never become possible
+// * extensionwithoutend (fatal): an EXTENSIONWINDOW without BETENDUTC, there is no end time to extend
+// * betendpassed (fatal): a BETENDUTC before the time of initmarket, no bet could be placed
+// * endbeforeopen (fatal): a BETENDUTC less than MINOPENWINDOW after the end of the ANNOUNCEDELAY, bets could hardly be placed
+// * exemptwithoutshare: a MAXSHAREEXEMPTPOOL without MAXSHAREBPS has no effect
+// * sharewithoutlimit: a MAXSHAREBPS of 10000 or more does not limit the share
+// * minbetoffgrid: a MINBET that is not a multiple of BETGRANULARITY, the actual minimum is the next multiple
+// * timelockafterend: a TIMELOCK not shorter than the time until BETENDUTC, time-locked changes cannot apply while bets are placed
+// * extensionbeyondresolution: the end time for bets can be extended, by EXTENSIONWINDOW up to MAXEXTENSIONS times or MAXTOTALEXTENSION,
+//   by more than the gap to the resolution, the shorter of RESOLUTIONGRACE and HEARTBEATWINDOW, so bets may be placed once the outcome is known
+pub fn configurationissues(setting: impl Fn(&str) -> i64, currtime: i64) -> Vec<ConfigurationIssue> {
+    let betenddatetime: i64 = setting("betenddatetime");
+    let heartbeatwindow: i64 = setting("heartbeatwindow");
+    let resolutiongrace: i64 = setting("resolutiongrace");
+    let maxsharebps: i64 = setting("maxsharebps");
+    let maxshareexemptpool: i64 = setting("maxshareexemptpool");
+    let minbet: i64 = setting("minbet");
+    let betgranularity: i64 = setting("betgranularity");
+    let timelock: i64 = setting("timelock");
+    let announcedelay: i64 = setting("announcedelay");
+    let extensionwindow: i64 = setting("extensionwindow");
+
+    let mut maxextension: i64 = extensionwindow.saturating_mul(setting("maxextensions"));
+    if setting("maxtotalextension") > 0 {
+        maxextension = maxextension.min(setting("maxtotalextension"));
+    }
+    let resolutiongap: i64 = [resolutiongrace, heartbeatwindow].iter().copied().filter(|gap| *gap > 0).min().unwrap_or(0);
+
+    let rules: [(&str, bool, bool, &str); 10] = [
+        ("heartbeatwithoutend", true, heartbeatwindow > 0 && betenddatetime == 0,
+            "HEARTBEATWINDOW needs BETENDUTC, the window starts at the end time for bets"),
+        ("gracewithoutend", true, resolutiongrace > 0 && betenddatetime == 0,
+            "RESOLUTIONGRACE needs BETENDUTC, the grace period starts at the end time for bets"),
+        ("extensionwithoutend", true, extensionwindow > 0 && betenddatetime == 0,
+            "EXTENSIONWINDOW needs BETENDUTC, only an end time for bets can be extended"),
+        ("betendpassed", true, betenddatetime != 0 && betenddatetime < currtime,
+            "BETENDUTC has already passed, no bet could be placed"),
+        ("endbeforeopen", true, announcedelay > 0 && betenddatetime != 0 && betenddatetime < currtime.saturating_add(announcedelay).saturating_add(MINOPENWINDOW),
+            "BETENDUTC must be at least an hour after the end of the ANNOUNCEDELAY, so that bets can be placed"),
+        ("exemptwithoutshare", false, maxshareexemptpool > 0 && maxsharebps == 0,
+            "MAXSHAREEXEMPTPOOL has no effect without MAXSHAREBPS"),
+        ("sharewithoutlimit", false, maxsharebps >= 10000,
+            "MAXSHAREBPS of 10000 or more does not limit the share of the pool"),
+        ("minbetoffgrid", false, betgranularity > 1 && minbet > 1 && minbet % betgranularity != 0,
+            "MINBET is not a multiple of BETGRANULARITY, the smallest accepted bet is the next multiple"),
+        ("timelockafterend", false, timelock > 0 && betenddatetime != 0 && currtime.saturating_add(timelock) >= betenddatetime,
+            "TIMELOCK is not shorter than the time until BETENDUTC, time-locked changes cannot take effect while bets are placed"),
+        ("extensionbeyondresolution", false, extensionwindow > 0 && resolutiongap > 0 && maxextension > resolutiongap,
+            "the end time for bets can be extended by more than RESOLUTIONGRACE or HEARTBEATWINDOW, bets may be placed once the outcome is known"),
+    ];
+    rules.iter()
+        .filter(|(_rule, _fatal, applies, _message)| *applies)
+        .map(|(rule, fatal, _applies, message)| ConfigurationIssue { rule: rule.to_string(), fatal: *fatal, message: message.to_string() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    // time of initmarket in the tests, and an end time for bets a day later
+    const NOW: i64 = 1622563200;
+    const END: i64 = NOW + 86400;
+
+    type Settings<'a> = &'a [(&'a str, i64)];
+
+    fn rules(settings: Settings) -> Vec<(String, bool)> {
+        let settings: HashMap<&str, i64> = settings.iter().copied().collect();
+        configurationissues(|key| *settings.get(key).unwrap_or(&0), NOW).into_iter().map(|issue| (issue.rule, issue.fatal)).collect()
+    }
+
+    #[test]
+    fn every_rule_applies_to_its_combination_only() {
+        let cases: [(&str, bool, Settings, Settings); 10] = [
+            ("heartbeatwithoutend", true, &[("heartbeatwindow", 600)], &[("heartbeatwindow", 600), ("betenddatetime", END)]),
+            ("gracewithoutend", true, &[("resolutiongrace", 600)], &[("resolutiongrace", 600), ("betenddatetime", END)]),
+            ("extensionwithoutend", true, &[("extensionwindow", 60)], &[("extensionwindow", 60), ("betenddatetime", END)]),
+            ("betendpassed", true, &[("betenddatetime", NOW - 1)], &[("betenddatetime", NOW)]),
+            ("endbeforeopen", true, &[("announcedelay", 86400 - MINOPENWINDOW + 1), ("betenddatetime", END)],
+                &[("announcedelay", 86400 - MINOPENWINDOW), ("betenddatetime", END)]),
+            ("exemptwithoutshare", false, &[("maxshareexemptpool", 1000)], &[("maxshareexemptpool", 1000), ("maxsharebps", 2500)]),
+            ("sharewithoutlimit", false, &[("maxsharebps", 10000)], &[("maxsharebps", 9999)]),
+            ("minbetoffgrid", false, &[("minbet", 25), ("betgranularity", 10)], &[("minbet", 30), ("betgranularity", 10)]),
+            ("timelockafterend", false, &[("timelock", 86400), ("betenddatetime", END)], &[("timelock", 86399), ("betenddatetime", END)]),
+            ("extensionbeyondresolution", false,
+                &[("betenddatetime", END), ("extensionwindow", 300), ("maxextensions", 5), ("resolutiongrace", 1499)],
+                &[("betenddatetime", END), ("extensionwindow", 300), ("maxextensions", 5), ("resolutiongrace", 1500)]),
+        ];
+        for (rule, fatal, applying, passing) in cases.iter() {
+            assert_eq!(rules(applying), vec![(rule.to_string(), *fatal)], "{} applies", rule);
+            assert!(rules(passing).is_empty(), "{} does not apply", rule);
+        }
+    }
+
+    #[test]
+    fn a_consistent_configuration_has_no_issues() {
+        assert!(rules(&[]).is_empty());
+        assert!(rules(&[("betenddatetime", END), ("minbet", 20), ("betgranularity", 10), ("maxsharebps", 2500), ("timelock", 3600)]).is_empty());
+    }
+
+    #[test]
+    fn the_extension_is_limited_by_the_total_and_compared_with_the_shorter_gap() {
+        let extension: [(&str, i64); 4] = [("betenddatetime", END), ("extensionwindow", 300), ("maxextensions", 5), ("maxtotalextension", 600)];
+        assert!(rules(&[extension[0], extension[1], extension[2], extension[3], ("resolutiongrace", 600)]).is_empty());
+        assert_eq!(rules(&[extension[0], extension[1], extension[2], extension[3], ("resolutiongrace", 3600), ("heartbeatwindow", 599)]),
+            vec![("extensionbeyondresolution".to_string(), false)]);
+    }
+
+    #[test]
+    fn several_issues_are_reported_in_the_order_of_the_rules() {
+        let issues = rules(&[("heartbeatwindow", 600), ("extensionwindow", 60), ("maxsharebps", 10000)]);
+        assert_eq!(issues, vec![("heartbeatwithoutend".to_string(), true), ("extensionwithoutend".to_string(), true), ("sharewithoutlimit".to_string(), false)]);
+    }
+}
@@ -0,0 +1,75 @@
+// Cost estimates of the functions of the contract, without access to the contract's context
+//
+// Every estimate is linear in a count N: the number of bets stored in the market for bet and closemarket, the number of winners
+// to pay for payout. Its terms are measured by the bench costs of this crate on the bets of a typical market and checked in as
+// measuredcosts.rs, which `cargo bench --bench costs` generates again after a change of the layouts or the settlement.
+// The estimates are rough: the bytes of the bets read from and written to the state are exact, while other state, e.g. the
+// entitlements or the running totals, is not counted, and the compute time is a native time, only meant to compare functions and layouts.
+
+use serde::Serialize;
+
+// terms of the cost of a function for a layout of the bets, each as its fixed part and its part per unit of N
+pub struct CostTerms {
+    pub function: &'static str,
+    pub layout: &'static str,
+    pub readbytes: (i64, i64),
+    pub writtenbytes: (i64, i64),
+    pub transfers: (i64, i64),
+    pub computenanos: (i64, i64),
+}
+
+include!("measuredcosts.rs");
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CostEstimate {
+    // layout of the bets, see storage.rs
+    pub layout: String,
+    // bytes of the bets read from and written to the state
+    pub readbytes: i64,
+    pub writtenbytes: i64,
+    // number of transfers
+    pub transfers: i64,
+    // native compute time in nanoseconds
+    pub computenanos: i64,
+}
+
+// Estimate the cost of the function for N, for every layout of the bets measured, or none for a function without measurements.
+pub fn costestimates(function: &str, n: i64) -> Vec<CostEstimate> {
+    let linear = |(fixed, perunit): (i64, i64)| fixed.saturating_add(perunit.saturating_mul(n.max(0)));
+    MEASUREDCOSTS.iter().filter(|terms| terms.function == function).map(|terms| CostEstimate {
+        layout: terms.layout.to_string(),
+        readbytes: linear(terms.readbytes),
+        writtenbytes: linear(terms.writtenbytes),
+        transfers: linear(terms.transfers),
+        computenanos: linear(terms.computenanos),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{STORAGE_JSON, STORAGE_MAP};
+
+    #[test]
+    fn every_function_is_estimated_for_both_layouts() {
+        for function in ["bet", "closemarket", "payout"].iter() {
+            let layouts: Vec<String> = costestimates(function, 10).into_iter().map(|estimate| estimate.layout).collect();
+            assert_eq!(layouts, vec![STORAGE_MAP.to_string(), STORAGE_JSON.to_string()], "{}", function);
+        }
+        assert!(costestimates("unknown", 10).is_empty());
+    }
+
+    #[test]
+    fn a_bet_in_the_map_does_not_scale_with_the_stored_bets() {
+        let estimate = |n: i64, layout: &str| costestimates("bet", n).into_iter().find(|estimate| estimate.layout == layout).unwrap();
+        assert_eq!(estimate(1, STORAGE_MAP), estimate(500, STORAGE_MAP));
+        assert!(estimate(500, STORAGE_JSON).readbytes > 100 * estimate(500, STORAGE_MAP).readbytes);
+    }
+
+    #[test]
+    fn estimates_are_linear_in_n() {
+        let transfers: Vec<i64> = [0, 1, 250].iter().map(|n| costestimates("payout", *n)[0].transfers).collect();
+        assert_eq!(transfers, vec![0, 1, 250]);
+        assert_eq!(costestimates("payout", -5)[0].transfers, 0);
+    }
+}
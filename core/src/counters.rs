@@ -0,0 +1,215 @@
+// Counters derived from the bets of a market, recomputed by rebuildcounters, without access to the contract's context
+//
+// The contract keeps counters at bet time, so that no call has to add up all bets: the running totals per value and overall, the
+// number of betting accounts, and the number of bets and their volume per campaign. rebuildcounters recomputes them from the stored
+// bets in batches, accumulating RebuiltCounters across its calls, and compares them with the stored counters by the names of countervalues:
+// * totalbetamount and numbettors,
+// * total_<value> for every value bet on,
+// * campaignbets|<campaign> and campaignvolume|<campaign> for every campaign counted.
+// A campaign counts every entry of the history of a bet that was counted for it. Bets stored before the entries recorded their campaign
+// count all their entries for the campaign of the bet, or a single one if they have no history either.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::settlement::Bet;
+
+pub const COUNTER_TOTALBETAMOUNT: &str = "totalbetamount";
+pub const COUNTER_NUMBETTORS: &str = "numbettors";
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct RebuiltCounters {
+    pub totalbetamount: i64,
+    pub betvalue_totalbetamount: BTreeMap<String, i64>,
+    pub numbettors: i64,
+    // number of bets and IOTA per campaign
+    pub campaignbets: BTreeMap<String, i64>,
+    pub campaignvolume: BTreeMap<String, i64>,
+}
+
+// a counter whose stored value differs from the value recomputed from the bets
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CounterDiff {
+    pub counter: String,
+    pub stored: i64,
+    pub rebuilt: i64,
+}
+
+pub fn totalcounter(betvalue: &str) -> String {
+    "total_".to_string() + betvalue
+}
+
+pub fn campaignbetscounter(campaign: &str) -> String {
+    "campaignbets|".to_string() + campaign
+}
+
+pub fn campaignvolumecounter(campaign: &str) -> String {
+    "campaignvolume|".to_string() + campaign
+}
+
+// Add the bet of a betting account to the counters rebuilt so far.
+pub fn addrebuiltbet(rebuilt: &mut RebuiltCounters, bet: &Bet) {
+    rebuilt.totalbetamount += bet.betamount as i64;
+    *rebuilt.betvalue_totalbetamount.entry(bet.betisforvalue.clone()).or_insert(0) += bet.betamount as i64;
+    rebuilt.numbettors += 1;
+    let mut campaigns: Vec<(&str, i64)> = bet.history.iter()
+        .filter(|entry| !entry.campaign.is_empty())
+        .map(|entry| (entry.campaign.as_str(), entry.betamount))
+        .collect();
+    if campaigns.is_empty() && !bet.campaign.is_empty() {
+        campaigns = if bet.history.is_empty() {
+            vec![(bet.campaign.as_str(), bet.betamount as i64)]
+        } else {
+            bet.history.iter().map(|entry| (bet.campaign.as_str(), entry.betamount)).collect()
+        };
+    }
+    for (campaign, betamount) in campaigns {
+        *rebuilt.campaignbets.entry(campaign.to_string()).or_insert(0) += 1;
+        *rebuilt.campaignvolume.entry(campaign.to_string()).or_insert(0) += betamount;
+    }
+}
+
+// the rebuilt counters by their names
+pub fn countervalues(rebuilt: &RebuiltCounters) -> BTreeMap<String, i64> {
+    let mut values: BTreeMap<String, i64> = BTreeMap::new();
+    values.insert(COUNTER_TOTALBETAMOUNT.to_string(), rebuilt.totalbetamount);
+    values.insert(COUNTER_NUMBETTORS.to_string(), rebuilt.numbettors);
+    for (betvalue, total) in &rebuilt.betvalue_totalbetamount {
+        values.insert(totalcounter(betvalue), *total);
+    }
+    for (campaign, bets) in &rebuilt.campaignbets {
+        values.insert(campaignbetscounter(campaign), *bets);
+        values.insert(campaignvolumecounter(campaign), rebuilt.campaignvolume[campaign]);
+    }
+    values
+}
+
+// The differences of the stored counters from the rebuilt ones in the order of their names, where a counter missing on one side is 0 there.
+pub fn counterdiffs(stored: &BTreeMap<String, i64>, rebuilt: &BTreeMap<String, i64>) -> Vec<CounterDiff> {
+    let mut counters: Vec<&String> = stored.keys().chain(rebuilt.keys()).collect();
+    counters.sort();
+    counters.dedup();
+    counters.into_iter()
+        .map(|counter| CounterDiff { counter: counter.clone(), stored: *stored.get(counter).unwrap_or(&0), rebuilt: *rebuilt.get(counter).unwrap_or(&0) })
+        .filter(|diff| diff.stored != diff.rebuilt)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::{bethistory, BetEntry};
+
+    // a bet of betamount on betvalue, placed in parts counted for the campaigns, "" for a part without campaign
+    fn bet(betvalue: &str, parts: &[(i64, &str)]) -> Bet {
+        let mut bet: Bet = Bet {
+            betamount: 0,
+            betisforvalue: betvalue.to_string(),
+            placedat: 0,
+            attachedamount: 0,
+            addressencoding: "".to_string(),
+            requestid: "".to_string(),
+            clientid: "".to_string(),
+            campaign: "".to_string(),
+            history: Vec::new(),
+        };
+        for (position, (betamount, campaign)) in parts.iter().enumerate() {
+            let history: Vec<BetEntry> = bethistory(if position == 0 { None } else { Some(&bet) }, position as i64, *betamount, campaign);
+            bet.history = history;
+            bet.betamount += *betamount as i32;
+            if !campaign.is_empty() {
+                bet.campaign = campaign.to_string();
+            }
+        }
+        bet
+    }
+
+    fn bets() -> Vec<Bet> {
+        (0..30).map(|i| {
+            let betvalue: &str = ["yes", "no", "draw"][i % 3];
+            match i % 4 {
+                0 => bet(betvalue, &[(10 + i as i64, "spring")]),
+                1 => bet(betvalue, &[(20, ""), (5 + i as i64, "summer")]),
+                2 => bet(betvalue, &[(30, "spring"), (7, "summer"), (1, "")]),
+                _ => bet(betvalue, &[(40 + i as i64, "")]),
+            }
+        }).collect()
+    }
+
+    // the counters kept at bet time: every part adds to the totals and to its campaign
+    fn counted(bets: &[Bet]) -> BTreeMap<String, i64> {
+        let mut counters: BTreeMap<String, i64> = BTreeMap::new();
+        for bet in bets {
+            *counters.entry(COUNTER_NUMBETTORS.to_string()).or_insert(0) += 1;
+            for entry in &bet.history {
+                *counters.entry(COUNTER_TOTALBETAMOUNT.to_string()).or_insert(0) += entry.betamount;
+                *counters.entry(totalcounter(&bet.betisforvalue)).or_insert(0) += entry.betamount;
+                if !entry.campaign.is_empty() {
+                    *counters.entry(campaignbetscounter(&entry.campaign)).or_insert(0) += 1;
+                    *counters.entry(campaignvolumecounter(&entry.campaign)).or_insert(0) += entry.betamount;
+                }
+            }
+        }
+        counters
+    }
+
+    #[test]
+    fn corrupted_counters_are_found_and_restored_over_several_batches() {
+        let bets: Vec<Bet> = bets();
+        let mut stored: BTreeMap<String, i64> = counted(&bets);
+        // two counters drifted, e.g. after a bug
+        *stored.get_mut("total_no").unwrap() += 50;
+        *stored.get_mut("campaignbets|summer").unwrap() -= 1;
+
+        // batches of 7 bets, the progress stored as json between the calls like the contract does
+        let mut progress: String = "".to_string();
+        let mut cursor: usize = 0;
+        let mut calls: i64 = 0;
+        while cursor < bets.len() {
+            let mut rebuilt: RebuiltCounters = if progress.is_empty() { RebuiltCounters::default() } else { serde_json::from_str(&progress).unwrap() };
+            let end: usize = bets.len().min(cursor + 7);
+            for bet in &bets[cursor..end] {
+                addrebuiltbet(&mut rebuilt, bet);
+            }
+            cursor = end;
+            progress = serde_json::to_string(&rebuilt).unwrap();
+            calls += 1;
+        }
+        assert_eq!(calls, 5);
+
+        // the call completing the scan reports exactly the two drifted counters, which are then overwritten
+        let rebuilt: BTreeMap<String, i64> = countervalues(&serde_json::from_str(&progress).unwrap());
+        let diffs: Vec<CounterDiff> = counterdiffs(&stored, &rebuilt);
+        assert_eq!(diffs, vec![
+            CounterDiff { counter: "campaignbets|summer".to_string(), stored: 14, rebuilt: 15 },
+            CounterDiff { counter: "total_no".to_string(), stored: rebuilt["total_no"] + 50, rebuilt: rebuilt["total_no"] },
+        ]);
+        assert_eq!(rebuilt, counted(&bets));
+        for diff in &diffs {
+            stored.insert(diff.counter.clone(), diff.rebuilt);
+        }
+        assert!(counterdiffs(&stored, &rebuilt).is_empty());
+        assert_eq!(rebuilt[COUNTER_NUMBETTORS], 30);
+    }
+
+    #[test]
+    fn bets_stored_before_their_entries_recorded_a_campaign_count_for_the_campaign_of_the_bet() {
+        let mut legacy: Bet = bet("yes", &[(10, ""), (15, "")]);
+        legacy.campaign = "spring".to_string();
+        let mut unhistoried: Bet = bet("no", &[]);
+        unhistoried.betamount = 40;
+        unhistoried.campaign = "summer".to_string();
+        let mut rebuilt: RebuiltCounters = RebuiltCounters::default();
+        addrebuiltbet(&mut rebuilt, &legacy);
+        addrebuiltbet(&mut rebuilt, &unhistoried);
+        let values: BTreeMap<String, i64> = countervalues(&rebuilt);
+        assert_eq!(values["campaignbets|spring"], 2);
+        assert_eq!(values["campaignvolume|spring"], 25);
+        assert_eq!(values["campaignbets|summer"], 1);
+        assert_eq!(values["campaignvolume|summer"], 40);
+        // a counter the rebuild does not find, e.g. of a value nobody bet on, differs if it is not 0
+        let stored: BTreeMap<String, i64> = vec![("total_draw".to_string(), 3), ("total_other".to_string(), 0)].into_iter().collect();
+        let diffs: Vec<CounterDiff> = counterdiffs(&stored, &BTreeMap::new());
+        assert_eq!(diffs, vec![CounterDiff { counter: "total_draw".to_string(), stored: 3, rebuilt: 0 }]);
+    }
+}
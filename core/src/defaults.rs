@@ -0,0 +1,213 @@
+// The platform defaults, which setdefaults changes for the markets initialized afterwards, and their history, without access to the contract's context
+//
+// A market takes a snapshot of the defaults when it is first initialized, so changing them never changes the rules of a market
+// already created, and is stamped with the version of the defaults it took. Every setdefaults bumps the version, starting from
+// version 0 for the built-in defaults, and appends the new version to the history, which keeps the latest MAXDEFAULTSHISTORY versions.
+// The versions form a hash chain: the head after a version is the hash of the TAG_DEFAULTS encoding of the head before it, the version,
+// its time and its defaults, starting from no bytes before version 1. A version dropped from the history stays folded into the chain,
+// whose head before the first retained version is kept, so an off-chain copy of any version can be verified against the current head.
+// The hash function is passed in, the contract uses blake2b-256.
+
+use serde::{Serialize, Deserialize};
+use serde_with::serde_as;
+
+use crate::canonical::{CanonicalEncoder, TAG_DEFAULTS};
+use crate::fees::MAXCREATORSHAREBPS;
+
+// number of versions of the defaults the history retains
+pub const MAXDEFAULTSHISTORY: usize = 16;
+
+// built-in ceiling of the bytes of all metadata of a market, see metadata
+pub const DEFAULTMAXCONFIGBYTES: i64 = 8192;
+
+#[serde_as]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PlatformDefaults {
+    // share of the fee of a market credited to the creator of the market, in basis points, see fees
+    #[serde(default)]
+    pub creatorsharebps: i64,
+    // ceiling of the bytes of all metadata of a market, 0 for DEFAULTMAXCONFIGBYTES, see metadata
+    #[serde(default)]
+    pub maxconfigbytes: i64,
+}
+
+// a version of the defaults, with the head of the chain before and after it as hex
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DefaultsVersion {
+    pub version: i64,
+    pub setat: i64,
+    pub defaults: PlatformDefaults,
+    pub previous: String,
+    pub head: String,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct DefaultsHistory {
+    // the current version, 0 before the first setdefaults, and the head of the chain after it
+    pub version: i64,
+    pub head: String,
+    // the head of the chain before the first retained version, "" as long as no version was dropped
+    pub foldedhead: String,
+    pub entries: Vec<DefaultsVersion>,
+}
+
+impl DefaultsHistory {
+    // The current defaults, the built-in ones before the first setdefaults.
+    pub fn current(&self) -> PlatformDefaults {
+        self.entries.last().map_or(PlatformDefaults::default(), |entry| entry.defaults.clone())
+    }
+}
+
+// The history stored as json, an empty history before setdefaults was called.
+pub fn parsehistory(historyjson: &str) -> DefaultsHistory {
+    serde_json::from_str(historyjson).unwrap_or_default()
+}
+
+pub fn validatedefaults(defaults: &PlatformDefaults) -> Result<(), String> {
+    if defaults.creatorsharebps < 0 || defaults.creatorsharebps > MAXCREATORSHAREBPS {
+        return Err("CREATORSHAREBPS must be between 0 and ".to_string() + &MAXCREATORSHAREBPS.to_string());
+    }
+    if defaults.maxconfigbytes < 0 {
+        return Err("MAXCONFIGBYTES must not be negative".to_string());
+    }
+    Ok(())
+}
+
+// the ceiling of the bytes of the metadata of a market given the maxconfigbytes of the platform defaults it took
+pub fn configceiling(maxconfigbytes: i64) -> i64 {
+    if maxconfigbytes > 0 { maxconfigbytes } else { DEFAULTMAXCONFIGBYTES }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(hex: &str) -> Vec<u8> {
+    (0..hex.len() / 2).filter_map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()).collect()
+}
+
+// The bytes hashed into the head after a version, given the head before it as hex. A maxconfigbytes of 0 is not encoded,
+// so that the heads of the versions set before it was recorded stay the same.
+pub fn defaultslink(previous: &str, version: i64, setat: i64, defaults: &PlatformDefaults) -> Vec<u8> {
+    let mut encoder: CanonicalEncoder = CanonicalEncoder::new(TAG_DEFAULTS);
+    encoder.hash(&unhex(previous)).int(version).int(setat).int(defaults.creatorsharebps);
+    if defaults.maxconfigbytes != 0 {
+        encoder.int(defaults.maxconfigbytes);
+    }
+    encoder.bytes()
+}
+
+// Append the defaults set at setat as the next version, folding the oldest retained version into the chain beyond MAXDEFAULTSHISTORY.
+pub fn appenddefaults(history: &mut DefaultsHistory, defaults: &PlatformDefaults, setat: i64, hash: &impl Fn(&[u8]) -> Vec<u8>) -> DefaultsVersion {
+    let version: i64 = history.version + 1;
+    let head: String = hex(&hash(&defaultslink(&history.head, version, setat, defaults)));
+    let entry: DefaultsVersion = DefaultsVersion { version, setat, defaults: defaults.clone(), previous: history.head.clone(), head: head.clone() };
+    history.entries.push(entry.clone());
+    while history.entries.len() > MAXDEFAULTSHISTORY {
+        let folded: DefaultsVersion = history.entries.remove(0);
+        history.foldedhead = folded.head;
+    }
+    history.version = version;
+    history.head = head;
+    entry
+}
+
+// Whether the retained versions link the folded head to the current head, each version following its predecessor.
+pub fn verifyhistory(history: &DefaultsHistory, hash: &impl Fn(&[u8]) -> Vec<u8>) -> bool {
+    let mut head: String = history.foldedhead.clone();
+    let mut version: i64 = history.version - history.entries.len() as i64;
+    for entry in history.entries.iter() {
+        version += 1;
+        if entry.version != version || entry.previous != head || hex(&hash(&defaultslink(&head, entry.version, entry.setat, &entry.defaults))) != entry.head {
+            return false;
+        }
+        head = entry.head.clone();
+    }
+    head == history.head
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // FNV-1a, not a cryptographic hash, but deterministic and sensitive to every byte, which is all the chain needs here
+    fn hash(bytes: &[u8]) -> Vec<u8> {
+        let mut value: u64 = 0xcbf29ce484222325;
+        for byte in bytes {
+            value ^= *byte as u64;
+            value = value.wrapping_mul(0x100000001b3);
+        }
+        value.to_be_bytes().to_vec()
+    }
+
+    #[test]
+    fn the_defaults_are_validated() {
+        assert!(validatedefaults(&PlatformDefaults { creatorsharebps: 2500, ..PlatformDefaults::default() }).is_ok());
+        assert!(validatedefaults(&PlatformDefaults { creatorsharebps: MAXCREATORSHAREBPS, ..PlatformDefaults::default() }).is_ok());
+        assert!(validatedefaults(&PlatformDefaults { creatorsharebps: MAXCREATORSHAREBPS + 1, ..PlatformDefaults::default() }).is_err());
+        assert!(validatedefaults(&PlatformDefaults { creatorsharebps: -1, ..PlatformDefaults::default() }).is_err());
+        // before the first setdefaults, and for json without the field, the creator gets no share
+        assert_eq!(parsehistory("").current(), PlatformDefaults::default());
+        assert_eq!(serde_json::from_str::<PlatformDefaults>("{}").unwrap().creatorsharebps, 0);
+    }
+
+    #[test]
+    fn markets_are_stamped_with_the_version_they_were_created_under() {
+        // a market created under every version, given as its stamp and the creator share it took
+        let mut history: DefaultsHistory = parsehistory("");
+        let mut markets: Vec<(i64, i64)> = vec![(history.version, history.current().creatorsharebps)];
+        let first: DefaultsVersion = appenddefaults(&mut history, &PlatformDefaults { creatorsharebps: 2500, ..PlatformDefaults::default() }, 1000, &hash);
+        markets.push((history.version, history.current().creatorsharebps));
+        let second: DefaultsVersion = appenddefaults(&mut history, &PlatformDefaults { creatorsharebps: 4000, ..PlatformDefaults::default() }, 2000, &hash);
+        markets.push((history.version, history.current().creatorsharebps));
+        assert_eq!(markets, vec![(0, 0), (1, 2500), (2, 4000)]);
+
+        // the chain links the versions, and the history survives being stored as json
+        assert_eq!(first.previous, "");
+        assert_eq!(second.previous, first.head);
+        assert_eq!(history.head, second.head);
+        let stored: DefaultsHistory = parsehistory(&serde_json::to_string(&history).unwrap());
+        assert_eq!(stored, history);
+        assert!(verifyhistory(&stored, &hash));
+        // an off-chain copy of the first version reproduces its head
+        assert_eq!(hex(&hash(&defaultslink("", 1, 1000, &PlatformDefaults { creatorsharebps: 2500, ..PlatformDefaults::default() }))), stored.entries[0].head);
+
+        // a rewritten version breaks the chain
+        let mut tampered: DefaultsHistory = stored.clone();
+        tampered.entries[0].defaults.creatorsharebps = 9000;
+        assert!(!verifyhistory(&tampered, &hash));
+    }
+
+    #[test]
+    fn the_ceiling_of_the_metadata_is_a_default_that_keeps_earlier_heads() {
+        let legacy: PlatformDefaults = serde_json::from_str("{\"creatorsharebps\":2500}").unwrap();
+        assert_eq!(configceiling(legacy.maxconfigbytes), DEFAULTMAXCONFIGBYTES);
+        // without a ceiling the encoding is the one of the versions set before it was recorded
+        let encoded: Vec<u8> = CanonicalEncoder::new(TAG_DEFAULTS).hash(&[]).int(1).int(1000).int(2500).bytes();
+        assert_eq!(defaultslink("", 1, 1000, &legacy), encoded);
+        let raised: PlatformDefaults = PlatformDefaults { maxconfigbytes: 16384, ..legacy.clone() };
+        assert_ne!(defaultslink("", 1, 1000, &raised), encoded);
+        assert_eq!(configceiling(raised.maxconfigbytes), 16384);
+        assert!(validatedefaults(&raised).is_ok());
+        assert!(validatedefaults(&PlatformDefaults { maxconfigbytes: -1, ..legacy }).is_err());
+    }
+
+    #[test]
+    fn old_versions_are_folded_into_the_chain() {
+        let mut history: DefaultsHistory = DefaultsHistory::default();
+        let mut heads: Vec<String> = Vec::new();
+        for version in 1..=(MAXDEFAULTSHISTORY as i64 + 3) {
+            heads.push(appenddefaults(&mut history, &PlatformDefaults { creatorsharebps: version * 100, ..PlatformDefaults::default() }, version * 10, &hash).head);
+        }
+        assert_eq!(history.entries.len(), MAXDEFAULTSHISTORY);
+        assert_eq!(history.entries[0].version, 4);
+        // the head before the first retained version is the one after the last folded version
+        assert_eq!(history.foldedhead, heads[2]);
+        assert_eq!(history.entries[0].previous, heads[2]);
+        assert_eq!(history.head, heads[heads.len() - 1]);
+        assert!(verifyhistory(&history, &hash));
+        assert_eq!(history.current().creatorsharebps, (MAXDEFAULTSHISTORY as i64 + 3) * 100);
+    }
+}
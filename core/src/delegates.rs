@@ -0,0 +1,147 @@
+// Delegates claiming the entitlements of betting accounts, without access to the contract's context
+//
+// A betting account can register an agent by delegateclaim, e.g. a hot wallet for an account on a hardware wallet, which then claims
+// on behalf of the account by claim with the account's wallet address as FOR. The delegate only triggers the claim: the IOTA are always
+// transferred to the account. The contract keeps the delegate's agent id per account in the claimdelegates map of the market, "" once
+// revoked by revokedelegate. The account itself can still claim after delegating, and a later delegateclaim replaces the delegate.
+
+// Check delegateclaim of the account with the agent id caller, which is a betting account of the market if better, for the delegate.
+pub fn validatedelegate(delegate: &str, caller: &str, better: bool) -> Result<(), String> {
+    if delegate.is_empty() {
+        return Err("delegate parameter not found".to_string());
+    }
+    if delegate == caller {
+        return Err("an account cannot delegate its claim to itself".to_string());
+    }
+    if !better {
+        return Err("Not authorised to delegate a claim - only a betting account of the market is allowed to do this.".to_string());
+    }
+    Ok(())
+}
+
+// Check revokedelegate of an account given its registered delegate, "" for none.
+pub fn validaterevocation(delegate: &str) -> Result<(), String> {
+    if delegate.is_empty() {
+        return Err("no delegate registered".to_string());
+    }
+    Ok(())
+}
+
+// The wallet address whose entitlements a claim pays, of the caller with the agent id caller and the wallet address calleraddress,
+// with FOR claimfor if given and the delegate registered for it, "" for none. Err if the caller is neither the account nor its delegate.
+pub fn claimedaddress(calleraddress: &str, caller: &str, claimfor: Option<&str>, delegate: &str) -> Result<String, String> {
+    match claimfor {
+        Some(claimfor) if claimfor != calleraddress => {
+            if delegate.is_empty() || delegate != caller {
+                return Err("Not authorised to claim for ".to_string() + claimfor + " - only the account or its delegate is allowed to do this.");
+            }
+            Ok(claimfor.to_string())
+        }
+        _ => Ok(calleraddress.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // the part of the contract claiming entitlements: the delegates and outstanding entitlements of the accounts and the wallets paid,
+    // an account with the wallet address "alice" calls with the agent id "agent:alice"
+    #[derive(Default)]
+    struct Contract {
+        claimdelegates: BTreeMap<String, String>,
+        entitlements: BTreeMap<String, i64>,
+        wallets: BTreeMap<String, i64>,
+    }
+
+    fn agent(address: &str) -> String {
+        "agent:".to_string() + address
+    }
+
+    impl Contract {
+        fn delegate(&self, address: &str) -> String {
+            self.claimdelegates.get(address).cloned().unwrap_or_default()
+        }
+
+        fn delegateclaim(&mut self, address: &str, delegate: &str) -> Result<(), String> {
+            validatedelegate(delegate, &agent(address), self.entitlements.contains_key(address))?;
+            self.claimdelegates.insert(address.to_string(), delegate.to_string());
+            Ok(())
+        }
+
+        fn revokedelegate(&mut self, address: &str) -> Result<(), String> {
+            validaterevocation(&self.delegate(address))?;
+            self.claimdelegates.insert(address.to_string(), "".to_string());
+            Ok(())
+        }
+
+        // claim by the account with the wallet address, returning CLAIMED
+        fn claim(&mut self, address: &str, claimfor: Option<&str>) -> Result<i64, String> {
+            let delegate: String = claimfor.map(|claimfor| self.delegate(claimfor)).unwrap_or_default();
+            let entitledaddress: String = claimedaddress(address, &agent(address), claimfor, &delegate)?;
+            let claimed: i64 = self.entitlements.insert(entitledaddress.clone(), 0).unwrap_or(0);
+            *self.wallets.entry(entitledaddress).or_insert(0) += claimed;
+            Ok(claimed)
+        }
+    }
+
+    fn contract() -> Contract {
+        let mut contract: Contract = Contract::default();
+        contract.entitlements.insert("alice".to_string(), 300);
+        contract.entitlements.insert("bob".to_string(), 200);
+        contract
+    }
+
+    #[test]
+    fn the_delegate_claims_for_the_account_and_the_account_receives_the_iota() {
+        let mut contract: Contract = contract();
+        contract.delegateclaim("alice", &agent("hotwallet")).unwrap();
+        assert_eq!(contract.claim("hotwallet", Some("alice")), Ok(300));
+        assert_eq!(contract.wallets["alice"], 300);
+        assert!(!contract.wallets.contains_key("hotwallet"));
+        // nothing is left for a second claim
+        assert_eq!(contract.claim("hotwallet", Some("alice")), Ok(0));
+    }
+
+    #[test]
+    fn the_account_still_claims_itself_after_delegating() {
+        let mut contract: Contract = contract();
+        contract.delegateclaim("alice", &agent("hotwallet")).unwrap();
+        assert_eq!(contract.claim("alice", None), Ok(300));
+        // FOR with the own address is the own claim
+        assert_eq!(contract.claim("alice", Some("alice")), Ok(0));
+        assert_eq!(contract.claim("hotwallet", Some("alice")), Ok(0));
+        assert_eq!(contract.wallets["alice"], 300);
+    }
+
+    #[test]
+    fn a_revoked_delegate_cannot_claim_anymore() {
+        let mut contract: Contract = contract();
+        contract.delegateclaim("alice", &agent("hotwallet")).unwrap();
+        contract.revokedelegate("alice").unwrap();
+        assert!(contract.claim("hotwallet", Some("alice")).unwrap_err().contains("Not authorised to claim for alice"));
+        assert_eq!(contract.entitlements["alice"], 300);
+        assert_eq!(contract.revokedelegate("alice"), Err("no delegate registered".to_string()));
+        // a later delegateclaim registers a delegate again, replacing any previous one
+        contract.delegateclaim("alice", &agent("hotwallet")).unwrap();
+        contract.delegateclaim("alice", &agent("laptop")).unwrap();
+        assert!(contract.claim("hotwallet", Some("alice")).is_err());
+        assert_eq!(contract.claim("laptop", Some("alice")), Ok(300));
+    }
+
+    #[test]
+    fn a_third_party_can_neither_claim_nor_delegate() {
+        let mut contract: Contract = contract();
+        contract.delegateclaim("alice", &agent("hotwallet")).unwrap();
+        // neither without a delegate, nor as the delegate of another account
+        assert!(contract.claim("mallory", Some("bob")).unwrap_err().contains("only the account or its delegate"));
+        assert!(contract.claim("mallory", Some("alice")).is_err());
+        assert!(contract.claim("hotwallet", Some("bob")).is_err());
+        assert_eq!(contract.entitlements.values().sum::<i64>(), 500);
+        // an account without bets cannot delegate, and no account can delegate to itself
+        assert!(contract.delegateclaim("mallory", &agent("hotwallet")).unwrap_err().contains("only a betting account"));
+        assert_eq!(contract.delegateclaim("bob", &agent("bob")), Err("an account cannot delegate its claim to itself".to_string()));
+        assert_eq!(contract.delegateclaim("bob", ""), Err("delegate parameter not found".to_string()));
+    }
+}
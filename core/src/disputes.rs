@@ -0,0 +1,86 @@
+// Settlement of the bonds of disputes, without access to the contract's context
+//
+// A dispute is filed with a bond against the outcome declared for a market. When the market is closed, the bond is returned if the
+// disputed outcome was overturned and forfeited to the recipient chosen by the market's policy otherwise.
+
+use std::collections::HashMap;
+
+
+// recipients of the bonds of disputes against an outcome that stands: the winners of the market, the contract owner,
+// or the destination of residual funds, e.g. a charity
+pub const FORFEIT_POOL: &str = "POOL";
+pub const FORFEIT_OWNER: &str = "OWNER";
+pub const FORFEIT_CHARITY: &str = "CHARITY";
+
+// Whether the disputed outcome was overturned by closing the market with the winning value, or by voiding it without one.
+pub fn overturned(betvaluewinning: Option<&str>, disputedoutcome: &str) -> bool {
+    betvaluewinning != Some(disputedoutcome)
+}
+
+// Whether the bond of a dispute is returned: if the disputed outcome was overturned, or if the bond is below the DISPUTEBOND of the market.
+pub fn bondreturned(betvaluewinning: Option<&str>, disputedoutcome: &str, bond: i64, disputebond: i64) -> bool {
+    overturned(betvaluewinning, disputedoutcome) || bond < disputebond
+}
+
+// The recipient of forfeited bonds by the policy of the market: a policy that cannot be applied, POOL without winners or CHARITY
+// without destination of residual funds, falls back to OWNER.
+pub fn forfeitrecipient(policy: &str, totalwinamount: i64, hassweepdest: bool) -> &str {
+    if (policy == FORFEIT_POOL && totalwinamount == 0) || (policy == FORFEIT_CHARITY && !hassweepdest) {
+        return FORFEIT_OWNER;
+    }
+    policy
+}
+
+// The shares of the forfeited bonds of the winners in proportion to their win amounts, rounded down and sorted by address,
+// without the winners whose share rounds down to 0. The rounding residue is not shared.
+pub fn bondshares(forfeited: i64, winamounts: &HashMap<String, i64>) -> Vec<(String, i64)> {
+    let totalwinamount: i64 = winamounts.values().sum();
+    if totalwinamount == 0 {
+        return Vec::new();
+    }
+    let mut betteraddresses: Vec<&String> = winamounts.keys().collect();
+    betteraddresses.sort();
+    betteraddresses.into_iter()
+        .map(|betteraddress| (betteraddress.to_string(), (forfeited as i128 * winamounts[betteraddress] as i128 / totalwinamount as i128) as i64))
+        .filter(|(_betteraddress, share)| *share != 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_bond_is_returned_if_the_outcome_is_overturned_or_voided() {
+        assert!(!bondreturned(Some("yes"), "yes", 100, 100));
+        assert!(bondreturned(Some("no"), "yes", 100, 100));
+        assert!(bondreturned(None, "yes", 100, 100));
+        assert!(overturned(None, "yes"));
+        assert!(!overturned(Some("yes"), "yes"));
+    }
+
+    #[test]
+    fn a_bond_below_the_dispute_bond_is_always_returned() {
+        assert!(bondreturned(Some("yes"), "yes", 99, 100));
+        assert!(!bondreturned(Some("yes"), "yes", 101, 100));
+    }
+
+    #[test]
+    fn a_policy_that_cannot_be_applied_falls_back_to_the_owner() {
+        assert_eq!(forfeitrecipient(FORFEIT_POOL, 500, false), FORFEIT_POOL);
+        assert_eq!(forfeitrecipient(FORFEIT_POOL, 0, true), FORFEIT_OWNER);
+        assert_eq!(forfeitrecipient(FORFEIT_CHARITY, 0, true), FORFEIT_CHARITY);
+        assert_eq!(forfeitrecipient(FORFEIT_CHARITY, 500, false), FORFEIT_OWNER);
+        assert_eq!(forfeitrecipient(FORFEIT_OWNER, 500, true), FORFEIT_OWNER);
+    }
+
+    #[test]
+    fn forfeited_bonds_are_shared_in_proportion_to_the_win_amounts() {
+        let winamounts: HashMap<String, i64> = [("b", 300), ("a", 100), ("c", 1)].iter().map(|(address, amount)| (address.to_string(), *amount)).collect();
+        // 100 * 100 / 401 = 24, 100 * 300 / 401 = 74, and c's share rounds down to 0
+        assert_eq!(bondshares(100, &winamounts), vec![("a".to_string(), 24), ("b".to_string(), 74)]);
+        assert!(bondshares(100, &HashMap::new()).is_empty());
+        let total: i64 = bondshares(1000, &winamounts).iter().map(|(_address, share)| share).sum();
+        assert!(total <= 1000 && total > 1000 - 3);
+    }
+}
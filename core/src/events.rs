@@ -0,0 +1,329 @@
+// Events emitted by the contract, without access to the contract's context
+//
+// Every event payload is a "|" separated string, prefixed by the version of its schema and its name,
+// e.g. "v1|market.alert|yes|>|8000|8125". Indexers use the version to know which layout they are parsing.
+// Each event kind has one builder function taking its fields in order, so fields cannot be emitted in the wrong order.
+// Whenever the fields of an event change, its version has to be bumped.
+
+use serde::Serialize;
+
+
+// name, version and ordered field names of an event kind
+#[derive(Serialize)]
+pub struct EventSchema {
+    pub name: &'static str,
+    pub version: u32,
+    pub fields: &'static [&'static str],
+}
+
+pub const MARKET_INITIALIZED: EventSchema = EventSchema { name: "market.initialized", version: 1, fields: &["marketid", "betenddatetime", "externalref"] };
+pub const MARKET_BETTINGCLOSED: EventSchema = EventSchema { name: "market.bettingclosed", version: 2, fields: &["marketid", "outcomes", "merkleroot"] };
+pub const MARKET_CLOSED: EventSchema = EventSchema { name: "market.closed", version: 2, fields: &["marketid", "betvaluewinning", "realizedoddsbps", "operatortake", "returnper100", "totalbetamount", "winners"] };
+pub const MARKET_UNANIMOUS: EventSchema = EventSchema { name: "market.unanimous", version: 1, fields: &["marketid", "betvaluewinning", "refunded"] };
+pub const MARKET_CHECKPOINT: EventSchema = EventSchema { name: "market.checkpoint", version: 1, fields: &["index", "timestamp", "betsroot"] };
+pub const MARKET_ALERT: EventSchema = EventSchema { name: "market.alert", version: 1, fields: &["betvalue", "comparison", "thresholdbps", "probabilitybps"] };
+pub const PAYOUT_PROGRESS: EventSchema = EventSchema { name: "payout.progress", version: 1, fields: &["processed", "entitlements"] };
+pub const SETTLEMENT_SHORTFALL: EventSchema = EventSchema { name: "settlement.shortfall", version: 1, fields: &["policy", "shortfall", "fractionbps"] };
+pub const FUNDS_SWEPT: EventSchema = EventSchema { name: "funds.swept", version: 1, fields: &["bucket", "destination", "amount"] };
+pub const ACTION_PROPOSED: EventSchema = EventSchema { name: "action.proposed", version: 1, fields: &["actionid", "kind", "payload", "effectiveat"] };
+pub const ACTION_EXECUTED: EventSchema = EventSchema { name: "action.executed", version: 1, fields: &["actionid"] };
+pub const ACTION_CANCELLED: EventSchema = EventSchema { name: "action.cancelled", version: 1, fields: &["actionid"] };
+pub const CHALLENGE_CREATED: EventSchema = EventSchema { name: "challenge.created", version: 1, fields: &["challengeid", "challenger", "opponent", "stake"] };
+pub const CHALLENGE_ACCEPTED: EventSchema = EventSchema { name: "challenge.accepted", version: 1, fields: &["challengeid"] };
+pub const CHALLENGE_RECLAIMED: EventSchema = EventSchema { name: "challenge.reclaimed", version: 1, fields: &["challengeid"] };
+pub const CHALLENGE_SETTLED: EventSchema = EventSchema { name: "challenge.settled", version: 1, fields: &["challengeid", "betvaluewinning"] };
+pub const MIRROR_REGISTERED: EventSchema = EventSchema { name: "mirror.registered", version: 1, fields: &["mirrorid", "url", "registrant"] };
+pub const MIRROR_REMOVED: EventSchema = EventSchema { name: "mirror.removed", version: 1, fields: &["mirrorid", "slashed"] };
+pub const DISPUTE_FILED: EventSchema = EventSchema { name: "dispute.filed", version: 1, fields: &["disputer", "bond", "disputedoutcome"] };
+pub const DISPUTE_SETTLED: EventSchema = EventSchema { name: "dispute.settled", version: 1, fields: &["disputer", "bond", "overturned"] };
+pub const BET_REFUNDED: EventSchema = EventSchema { name: "bet.refunded", version: 1, fields: &["error", "address", "amount"] };
+pub const MARKET_ODDSREVEALED: EventSchema = EventSchema { name: "market.oddsrevealed", version: 1, fields: &["marketid", "pool"] };
+pub const MIGRATION_CHUNK: EventSchema = EventSchema { name: "migration.chunk", version: 1, fields: &["hash", "index", "count", "chunk"] };
+pub const BET_PLACED: EventSchema = EventSchema { name: "bet.placed", version: 1, fields: &["marketid", "address", "betvalue", "amount", "timestamp"] };
+pub const PAYOUT_TRANSFERRED: EventSchema = EventSchema { name: "payout.transferred", version: 1, fields: &["address", "amount", "reason"] };
+pub const PAYOUT_FAILED: EventSchema = EventSchema { name: "payout.failed", version: 1, fields: &["address", "amount", "attempts", "nextattemptat"] };
+pub const PAYOUT_MANUAL: EventSchema = EventSchema { name: "payout.manual", version: 1, fields: &["address", "amount", "attempts"] };
+pub const COUNTER_DIFF: EventSchema = EventSchema { name: "counter.diff", version: 1, fields: &["marketid", "counter", "stored", "rebuilt"] };
+pub const MARKET_COUNTERSREBUILT: EventSchema = EventSchema { name: "market.countersrebuilt", version: 1, fields: &["marketid", "bets", "differences"] };
+pub const MARKET_TIMELINECHANGED: EventSchema = EventSchema { name: "market.timelinechanged", version: 1, fields: &["marketid", "betscloseat", "resolveby"] };
+pub const BETEND_EXTENDED: EventSchema = EventSchema { name: "betend.extended", version: 1, fields: &["marketid", "previousbetend", "betend", "betsequence"] };
+pub const ARCHIVE_CHUNK: EventSchema = EventSchema { name: "archive.chunk", version: 1, fields: &["marketid", "digest", "index", "count", "chunk"] };
+pub const MARKET_SCHEDULED: EventSchema = EventSchema { name: "market.scheduled", version: 1, fields: &["scheduleid", "marketid", "betenddatetime", "skipped", "remaining"] };
+
+// all event kinds, as returned by the geteventschema view
+pub const SCHEMAS: [&EventSchema; 33] = [
+    &MARKET_INITIALIZED,
+    &MARKET_BETTINGCLOSED,
+    &MARKET_CLOSED,
+    &MARKET_UNANIMOUS,
+    &MARKET_CHECKPOINT,
+    &MARKET_ALERT,
+    &PAYOUT_PROGRESS,
+    &SETTLEMENT_SHORTFALL,
+    &FUNDS_SWEPT,
+    &ACTION_PROPOSED,
+    &ACTION_EXECUTED,
+    &ACTION_CANCELLED,
+    &CHALLENGE_CREATED,
+    &CHALLENGE_ACCEPTED,
+    &CHALLENGE_RECLAIMED,
+    &CHALLENGE_SETTLED,
+    &MIRROR_REGISTERED,
+    &MIRROR_REMOVED,
+    &DISPUTE_FILED,
+    &DISPUTE_SETTLED,
+    &BET_REFUNDED,
+    &MIGRATION_CHUNK,
+    &MARKET_ODDSREVEALED,
+    &BET_PLACED,
+    &PAYOUT_TRANSFERRED,
+    &PAYOUT_FAILED,
+    &PAYOUT_MANUAL,
+    &COUNTER_DIFF,
+    &MARKET_COUNTERSREBUILT,
+    &MARKET_TIMELINECHANGED,
+    &BETEND_EXTENDED,
+    &MARKET_SCHEDULED,
+    &ARCHIVE_CHUNK,
+];
+
+// build the payload of an event from its field values, which have to be given in the order of the schema
+fn payload(schema: &EventSchema, values: &[String]) -> String {
+    assert_eq!(values.len(), schema.fields.len(), "wrong number of fields for event {}", schema.name);
+    let mut payload: String = "v".to_string() + &schema.version.to_string() + "|" + schema.name;
+    for value in values {
+        payload = payload + "|" + value;
+    }
+    payload
+}
+
+pub fn marketinitialized(marketid: &str, betenddatetime: i64, externalref: &str) -> String {
+    payload(&MARKET_INITIALIZED, &[marketid.to_string(), betenddatetime.to_string(), externalref.to_string()])
+}
+
+pub fn marketbettingclosed(marketid: &str, outcomes: i64, merkleroot: &str) -> String {
+    payload(&MARKET_BETTINGCLOSED, &[marketid.to_string(), outcomes.to_string(), merkleroot.to_string()])
+}
+
+pub fn marketclosed(marketid: &str, betvaluewinning: &str, realizedoddsbps: i64, operatortake: i64, returnper100: i64, totalbetamount: i64, winners: usize) -> String {
+    payload(&MARKET_CLOSED, &[marketid.to_string(), betvaluewinning.to_string(), realizedoddsbps.to_string(), operatortake.to_string(), returnper100.to_string(),
+        totalbetamount.to_string(), winners.to_string()])
+}
+
+pub fn marketunanimous(marketid: &str, betvaluewinning: &str, refunded: i64) -> String {
+    payload(&MARKET_UNANIMOUS, &[marketid.to_string(), betvaluewinning.to_string(), refunded.to_string()])
+}
+
+pub fn marketcheckpoint(index: usize, timestamp: i64, betsroot: &str) -> String {
+    payload(&MARKET_CHECKPOINT, &[index.to_string(), timestamp.to_string(), betsroot.to_string()])
+}
+
+pub fn marketalert(betvalue: &str, above: bool, thresholdbps: i64, probabilitybps: i64) -> String {
+    let comparison: &str = if above { ">" } else { "<" };
+    payload(&MARKET_ALERT, &[betvalue.to_string(), comparison.to_string(), thresholdbps.to_string(), probabilitybps.to_string()])
+}
+
+pub fn payoutprogress(processed: i64, entitlements: i64) -> String {
+    payload(&PAYOUT_PROGRESS, &[processed.to_string(), entitlements.to_string()])
+}
+
+pub fn settlementshortfall(policy: &str, shortfall: i64, fractionbps: i64) -> String {
+    payload(&SETTLEMENT_SHORTFALL, &[policy.to_string(), shortfall.to_string(), fractionbps.to_string()])
+}
+
+pub fn fundsswept(bucket: &str, destination: &str, amount: i64) -> String {
+    payload(&FUNDS_SWEPT, &[bucket.to_string(), destination.to_string(), amount.to_string()])
+}
+
+pub fn actionproposed(actionid: i64, kind: &str, actionpayload: &str, effectiveat: i64) -> String {
+    payload(&ACTION_PROPOSED, &[actionid.to_string(), kind.to_string(), actionpayload.to_string(), effectiveat.to_string()])
+}
+
+pub fn actionexecuted(actionid: i64) -> String {
+    payload(&ACTION_EXECUTED, &[actionid.to_string()])
+}
+
+pub fn actioncancelled(actionid: i64) -> String {
+    payload(&ACTION_CANCELLED, &[actionid.to_string()])
+}
+
+pub fn challengecreated(challengeid: i64, challenger: &str, opponent: &str, stake: i64) -> String {
+    payload(&CHALLENGE_CREATED, &[challengeid.to_string(), challenger.to_string(), opponent.to_string(), stake.to_string()])
+}
+
+pub fn challengeaccepted(challengeid: i64) -> String {
+    payload(&CHALLENGE_ACCEPTED, &[challengeid.to_string()])
+}
+
+pub fn challengereclaimed(challengeid: i64) -> String {
+    payload(&CHALLENGE_RECLAIMED, &[challengeid.to_string()])
+}
+
+pub fn challengesettled(challengeid: i64, betvaluewinning: &str) -> String {
+    payload(&CHALLENGE_SETTLED, &[challengeid.to_string(), betvaluewinning.to_string()])
+}
+
+pub fn mirrorregistered(mirrorid: i64, url: &str, registrant: &str) -> String {
+    payload(&MIRROR_REGISTERED, &[mirrorid.to_string(), url.to_string(), registrant.to_string()])
+}
+
+pub fn mirrorremoved(mirrorid: i64, slashed: bool) -> String {
+    payload(&MIRROR_REMOVED, &[mirrorid.to_string(), slashed.to_string()])
+}
+
+pub fn disputefiled(disputer: &str, bond: i64, disputedoutcome: &str) -> String {
+    payload(&DISPUTE_FILED, &[disputer.to_string(), bond.to_string(), disputedoutcome.to_string()])
+}
+
+pub fn disputesettled(disputer: &str, bond: i64, overturned: bool) -> String {
+    payload(&DISPUTE_SETTLED, &[disputer.to_string(), bond.to_string(), overturned.to_string()])
+}
+
+pub fn betrefunded(error: &str, address: &str, amount: i64) -> String {
+    payload(&BET_REFUNDED, &[error.to_string(), address.to_string(), amount.to_string()])
+}
+
+pub fn marketoddsrevealed(marketid: &str, pool: i64) -> String {
+    payload(&MARKET_ODDSREVEALED, &[marketid.to_string(), pool.to_string()])
+}
+
+pub fn migrationchunk(hash: &str, index: i64, count: i64, chunk: &str) -> String {
+    payload(&MIGRATION_CHUNK, &[hash.to_string(), index.to_string(), count.to_string(), chunk.to_string()])
+}
+
+pub fn betplaced(marketid: &str, address: &str, betvalue: &str, amount: i64, timestamp: i64) -> String {
+    payload(&BET_PLACED, &[marketid.to_string(), address.to_string(), betvalue.to_string(), amount.to_string(), timestamp.to_string()])
+}
+
+pub fn payouttransferred(address: &str, amount: i64, reason: &str) -> String {
+    payload(&PAYOUT_TRANSFERRED, &[address.to_string(), amount.to_string(), reason.to_string()])
+}
+
+pub fn payoutfailed(address: &str, amount: i64, attempts: i64, nextattemptat: i64) -> String {
+    payload(&PAYOUT_FAILED, &[address.to_string(), amount.to_string(), attempts.to_string(), nextattemptat.to_string()])
+}
+
+pub fn payoutmanual(address: &str, amount: i64, attempts: i64) -> String {
+    payload(&PAYOUT_MANUAL, &[address.to_string(), amount.to_string(), attempts.to_string()])
+}
+
+pub fn counterdiff(marketid: &str, counter: &str, stored: i64, rebuilt: i64) -> String {
+    payload(&COUNTER_DIFF, &[marketid.to_string(), counter.to_string(), stored.to_string(), rebuilt.to_string()])
+}
+
+pub fn countersrebuilt(marketid: &str, bets: i64, differences: i64) -> String {
+    payload(&MARKET_COUNTERSREBUILT, &[marketid.to_string(), bets.to_string(), differences.to_string()])
+}
+
+pub fn markettimelinechanged(marketid: &str, betscloseat: i64, resolveby: i64) -> String {
+    payload(&MARKET_TIMELINECHANGED, &[marketid.to_string(), betscloseat.to_string(), resolveby.to_string()])
+}
+
+pub fn betendextended(marketid: &str, previousbetend: i64, betend: i64, betsequence: i64) -> String {
+    payload(&BETEND_EXTENDED, &[marketid.to_string(), previousbetend.to_string(), betend.to_string(), betsequence.to_string()])
+}
+
+pub fn marketscheduled(scheduleid: &str, marketid: &str, betenddatetime: i64, skipped: i64, remaining: i64) -> String {
+    payload(&MARKET_SCHEDULED, &[scheduleid.to_string(), marketid.to_string(), betenddatetime.to_string(), skipped.to_string(), remaining.to_string()])
+}
+
+pub fn archivechunk(marketid: &str, digest: &str, index: i64, count: i64, chunk: &str) -> String {
+    payload(&ARCHIVE_CHUNK, &[marketid.to_string(), digest.to_string(), index.to_string(), count.to_string(), chunk.to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a payload of every event kind, built with the same fields across the versions, so that a change of a layout shows in the diff
+    fn snapshots() -> Vec<(&'static str, String)> {
+        vec![
+            (MARKET_INITIALIZED.name, marketinitialized("worldcup", 1622570400, "fifa:final")),
+            (MARKET_BETTINGCLOSED.name, marketbettingclosed("worldcup", 3, "7Hq2")),
+            (MARKET_CLOSED.name, marketclosed("worldcup", "yes", 15000, 20, 150, 1000, 2)),
+            (MARKET_UNANIMOUS.name, marketunanimous("worldcup", "yes", 500)),
+            (MARKET_CHECKPOINT.name, marketcheckpoint(4, 1622570400, "9kPz")),
+            (MARKET_ALERT.name, marketalert("yes", true, 8000, 8125)),
+            (PAYOUT_PROGRESS.name, payoutprogress(50, 120)),
+            (SETTLEMENT_SHORTFALL.name, settlementshortfall("haircut", 30, 9700)),
+            (FUNDS_SWEPT.name, fundsswept("pools", "treasury", 12)),
+            (ACTION_PROPOSED.name, actionproposed(7, "setalerts", "yes>8000", 1622574000)),
+            (ACTION_EXECUTED.name, actionexecuted(7)),
+            (ACTION_CANCELLED.name, actioncancelled(8)),
+            (CHALLENGE_CREATED.name, challengecreated(3, "alice", "bob", 100)),
+            (CHALLENGE_ACCEPTED.name, challengeaccepted(3)),
+            (CHALLENGE_RECLAIMED.name, challengereclaimed(4)),
+            (CHALLENGE_SETTLED.name, challengesettled(3, "no")),
+            (MIRROR_REGISTERED.name, mirrorregistered(2, "https://mirror.example", "carol")),
+            (MIRROR_REMOVED.name, mirrorremoved(2, true)),
+            (DISPUTE_FILED.name, disputefiled("dave", 50, "no")),
+            (DISPUTE_SETTLED.name, disputesettled("dave", 50, false)),
+            (BET_REFUNDED.name, betrefunded("ERR_BET_TOO_LATE", "alice", 100)),
+            (MIGRATION_CHUNK.name, migrationchunk("5fGh", 0, 2, "{\"map\":{}}")),
+            (MARKET_ODDSREVEALED.name, marketoddsrevealed("worldcup", 1000)),
+            (BET_PLACED.name, betplaced("worldcup", "alice", "yes", 100, 1622566800)),
+            (PAYOUT_TRANSFERRED.name, payouttransferred("alice", 150, "win")),
+            (PAYOUT_FAILED.name, payoutfailed("bob", 150, 2, 1622574000)),
+            (PAYOUT_MANUAL.name, payoutmanual("bob", 150, 5)),
+            (COUNTER_DIFF.name, counterdiff("worldcup", "total_no", 350, 300)),
+            (MARKET_COUNTERSREBUILT.name, countersrebuilt("worldcup", 30, 1)),
+            (MARKET_TIMELINECHANGED.name, markettimelinechanged("worldcup", 1622570400, 1622656800)),
+            (BETEND_EXTENDED.name, betendextended("worldcup", 1622570400, 1622570700, 12)),
+            (MARKET_SCHEDULED.name, marketscheduled("weekly", "weekly-3", 1623175200, 1, 9)),
+            (ARCHIVE_CHUNK.name, archivechunk("worldcup", "3mNb", 1, 4, "eyJiZXRz")),
+        ]
+    }
+
+    #[test]
+    fn every_event_kind_has_its_snapshot() {
+        let names: Vec<&str> = snapshots().iter().map(|(name, _payload)| *name).collect();
+        assert_eq!(names, SCHEMAS.iter().map(|schema| schema.name).collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn payloads_match_their_snapshots() {
+        let expected: [&str; 33] = [
+            "v1|market.initialized|worldcup|1622570400|fifa:final",
+            "v2|market.bettingclosed|worldcup|3|7Hq2",
+            "v2|market.closed|worldcup|yes|15000|20|150|1000|2",
+            "v1|market.unanimous|worldcup|yes|500",
+            "v1|market.checkpoint|4|1622570400|9kPz",
+            "v1|market.alert|yes|>|8000|8125",
+            "v1|payout.progress|50|120",
+            "v1|settlement.shortfall|haircut|30|9700",
+            "v1|funds.swept|pools|treasury|12",
+            "v1|action.proposed|7|setalerts|yes>8000|1622574000",
+            "v1|action.executed|7",
+            "v1|action.cancelled|8",
+            "v1|challenge.created|3|alice|bob|100",
+            "v1|challenge.accepted|3",
+            "v1|challenge.reclaimed|4",
+            "v1|challenge.settled|3|no",
+            "v1|mirror.registered|2|https://mirror.example|carol",
+            "v1|mirror.removed|2|true",
+            "v1|dispute.filed|dave|50|no",
+            "v1|dispute.settled|dave|50|false",
+            "v1|bet.refunded|ERR_BET_TOO_LATE|alice|100",
+            "v1|migration.chunk|5fGh|0|2|{\"map\":{}}",
+            "v1|market.oddsrevealed|worldcup|1000",
+            "v1|bet.placed|worldcup|alice|yes|100|1622566800",
+            "v1|payout.transferred|alice|150|win",
+            "v1|payout.failed|bob|150|2|1622574000",
+            "v1|payout.manual|bob|150|5",
+            "v1|counter.diff|worldcup|total_no|350|300",
+            "v1|market.countersrebuilt|worldcup|30|1",
+            "v1|market.timelinechanged|worldcup|1622570400|1622656800",
+            "v1|betend.extended|worldcup|1622570400|1622570700|12",
+            "v1|market.scheduled|weekly|weekly-3|1623175200|1|9",
+            "v1|archive.chunk|worldcup|3mNb|1|4|eyJiZXRz",
+        ];
+        for ((name, payload), expected) in snapshots().iter().zip(expected.iter()) {
+            assert_eq!(payload, expected, "{}", name);
+        }
+        // the fields of every payload are the fields of its schema, in their order
+        for ((_name, payload), schema) in snapshots().iter().zip(SCHEMAS.iter()) {
+            assert_eq!(payload.split('|').count(), schema.fields.len() + 2, "{}", schema.name);
+        }
+        assert_eq!(marketalert("no", false, 1000, 950), "v1|market.alert|no|<|1000|950");
+    }
+}
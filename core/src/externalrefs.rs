@@ -0,0 +1,109 @@
+// References of markets to events outside of the chain, without access to the contract's context
+//
+// A market can be initialized with an EXTERNALREF, e.g. an event id of a sports feed, by which wallets find it with findmarket.
+// The contract keeps a reverse index in the externalrefs map, from the hash of the reference to the market id, so that a reference is
+// used by a single market only. Initializing the market again keeps its own reference or frees it when it changes, and cancelling,
+// voiding or archiving the market frees it for another market. The contract computes the hash, the index entry is "" while free.
+
+pub const MAXEXTERNALREF: usize = 128;
+
+// Check the EXTERNALREF of initmarket against the market id the reference is indexed for, "" if it is free, for the market marketid.
+pub fn validateexternalref(externalref: &str, indexedmarketid: &str, marketid: &str) -> Result<(), String> {
+    if externalref.len() > MAXEXTERNALREF {
+        return Err("EXTERNALREF must not be longer than ".to_string() + &MAXEXTERNALREF.to_string() + " bytes");
+    }
+    if !externalref.is_empty() && !indexedmarketid.is_empty() && indexedmarketid != marketid {
+        return Err("EXTERNALREF is already used by another market".to_string());
+    }
+    Ok(())
+}
+
+// the reference a market initialized again with externalref frees, if it had another one before
+pub fn releasedexternalref<'a>(previousexternalref: &'a str, externalref: &str) -> Option<&'a str> {
+    if previousexternalref.is_empty() || previousexternalref == externalref { None } else { Some(previousexternalref) }
+}
+
+// the market findmarket returns for the index entry of a reference, None if the reference is free
+pub fn foundmarket(indexedmarketid: &str) -> Option<&str> {
+    if indexedmarketid.is_empty() { None } else { Some(indexedmarketid) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // the part of the contract keeping external references: the reverse index and the reference of each market
+    #[derive(Default)]
+    struct Contract {
+        externalrefs: BTreeMap<String, String>,
+        markets: BTreeMap<String, String>,
+    }
+
+    impl Contract {
+        fn index(&self, externalref: &str) -> String {
+            self.externalrefs.get(externalref).cloned().unwrap_or_default()
+        }
+
+        // initmarket with EXTERNALREF for the market marketid, all or nothing like a call of the contract
+        fn initmarket(&mut self, marketid: &str, externalref: &str) -> Result<(), String> {
+            validateexternalref(externalref, &self.index(externalref), marketid)?;
+            let previousexternalref: String = self.markets.get(marketid).cloned().unwrap_or_default();
+            if let Some(released) = releasedexternalref(&previousexternalref, externalref) {
+                self.externalrefs.insert(released.to_string(), "".to_string());
+            }
+            if !externalref.is_empty() {
+                self.externalrefs.insert(externalref.to_string(), marketid.to_string());
+            }
+            self.markets.insert(marketid.to_string(), externalref.to_string());
+            Ok(())
+        }
+
+        // cancelmarket, a tie voiding the market or archivemarket, which clears the state of the market
+        fn release(&mut self, marketid: &str) {
+            let externalref: String = self.markets.remove(marketid).unwrap_or_default();
+            if !externalref.is_empty() {
+                self.externalrefs.insert(externalref, "".to_string());
+            }
+        }
+
+        fn findmarket(&self, externalref: &str) -> Option<String> {
+            foundmarket(&self.index(externalref)).map(|marketid| marketid.to_string())
+        }
+    }
+
+    #[test]
+    fn a_reference_is_used_by_a_single_market() {
+        let mut contract: Contract = Contract::default();
+        contract.initmarket("final", "sportsfeed:12345").unwrap();
+        assert_eq!(contract.findmarket("sportsfeed:12345"), Some("final".to_string()));
+        // a duplicate reference is refused and leaves the index unchanged
+        assert_eq!(contract.initmarket("semifinal", "sportsfeed:12345"), Err("EXTERNALREF is already used by another market".to_string()));
+        assert_eq!(contract.findmarket("sportsfeed:12345"), Some("final".to_string()));
+        assert!(!contract.markets.contains_key("semifinal"));
+        // initializing the market again keeps its reference
+        contract.initmarket("final", "sportsfeed:12345").unwrap();
+        assert_eq!(contract.findmarket("sportsfeed:12345"), Some("final".to_string()));
+        // changing it frees the old one for another market
+        contract.initmarket("final", "sportsfeed:12346").unwrap();
+        assert_eq!(contract.findmarket("sportsfeed:12345"), None);
+        contract.initmarket("semifinal", "sportsfeed:12345").unwrap();
+        assert_eq!(contract.findmarket("sportsfeed:12345"), Some("semifinal".to_string()));
+        assert_eq!(contract.findmarket("sportsfeed:99999"), None);
+        assert!(validateexternalref(&"a".repeat(MAXEXTERNALREF + 1), "", "final").unwrap_err().contains("128 bytes"));
+    }
+
+    #[test]
+    fn an_archived_market_is_not_found_and_frees_its_reference() {
+        let mut contract: Contract = Contract::default();
+        contract.initmarket("final", "sportsfeed:12345").unwrap();
+        contract.initmarket("semifinal", "sportsfeed:12344").unwrap();
+        contract.release("final");
+        assert_eq!(contract.findmarket("sportsfeed:12345"), None);
+        assert_eq!(contract.findmarket("sportsfeed:12344"), Some("semifinal".to_string()));
+        // a new market, even one with the id of the archived market, can take up the reference
+        contract.initmarket("replay", "sportsfeed:12345").unwrap();
+        assert_eq!(contract.findmarket("sportsfeed:12345"), Some("replay".to_string()));
+        assert_eq!(contract.initmarket("final", "sportsfeed:12345"), Err("EXTERNALREF is already used by another market".to_string()));
+    }
+}
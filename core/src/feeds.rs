@@ -0,0 +1,251 @@
+// Resolution of price markets from the median of several oracle feeds, without access to the contract's context
+//
+// A market initialized with FEEDS names up to MAXFEEDS feeds, each the hname of a contract on the chain and a key the contract reports
+// a value for, and with BOUNDARIES the values at which its OUTCOMES change: a value below the first boundary is the first outcome,
+// a value from the first to below the second boundary the second outcome, and so on, so there is one outcome more than boundaries.
+// resolvefromoracle reads every feed and settles the market on the outcome of the integer median of the values reported, if
+// at least FEEDQUORUM feeds reported one. A feed deviating from the median by more than FEEDDEVIATIONBPS of the median puts the
+// market into the dispute state instead, to be closed by a resolver. Every feed and the value it reported go into the receipt.
+
+use serde::{Serialize, Deserialize};
+use serde_with::serde_as;
+
+// the number of feeds a market reads at most, and the maximum length in bytes of the key of a feed
+pub const MAXFEEDS: usize = 5;
+pub const MAXFEEDKEY: usize = 64;
+
+// less feeds than the quorum reported a value, the market stays unresolved
+pub const ERR_NO_QUORUM: &str = "ERR_NO_QUORUM";
+
+// the outcome of reading the feeds: not enough values, values too far apart to settle on, or the market can be settled
+pub const FEEDS_NOQUORUM: &str = "noquorum";
+pub const FEEDS_DIVERGED: &str = "diverged";
+pub const FEEDS_RESOLVED: &str = "resolved";
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OracleFeed {
+    // hname of the contract reporting the value, as 8 hex digits like wasp-cli shows it
+    pub hname: String,
+    pub key: String,
+}
+
+// a feed and the value it reported, none if it had no value for its key
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FeedValue {
+    pub hname: String,
+    pub key: String,
+    pub value: Option<i64>,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FeedReport {
+    pub status: String,
+    pub feeds: Vec<FeedValue>,
+    pub quorum: i64,
+    pub responses: i64,
+    // the median of the values reported and its outcome, none without quorum
+    pub median: Option<i64>,
+    pub outcome: String,
+    // the largest deviation of a value from the median in basis points of the median, and the threshold it is compared with
+    pub deviationbps: i64,
+    pub maxdeviationbps: i64,
+}
+
+// The FEEDS of a market, given as a comma separated list of HNAME:KEY, e.g. "3e5d2a18:iota-usd,a1b2c3d4:iota-usd":
+// from 1 to MAXFEEDS feeds, none given twice.
+pub fn parsefeeds(feeds: &str) -> Result<Vec<OracleFeed>, String> {
+    let mut parsed: Vec<OracleFeed> = Vec::new();
+    for entry in feeds.split(',').map(|entry| entry.trim()) {
+        let (hname, key) = match entry.split_once(':') {
+            Some((hname, key)) => (hname.trim().to_lowercase(), key.trim().to_string()),
+            None => return Err("a feed of FEEDS must be HNAME:KEY, not ".to_string() + entry),
+        };
+        if hname.len() != 8 || u32::from_str_radix(&hname, 16).is_err() {
+            return Err("the HNAME of a feed must be 8 hex digits, not ".to_string() + &hname);
+        }
+        if key.is_empty() || key.len() > MAXFEEDKEY {
+            return Err("the KEY of a feed must have from 1 to ".to_string() + &MAXFEEDKEY.to_string() + " bytes");
+        }
+        let feed: OracleFeed = OracleFeed { hname, key };
+        if parsed.contains(&feed) {
+            return Err("FEEDS must not list a feed twice".to_string());
+        }
+        parsed.push(feed);
+    }
+    if parsed.len() > MAXFEEDS {
+        return Err("a market reads up to ".to_string() + &MAXFEEDS.to_string() + " feeds");
+    }
+    Ok(parsed)
+}
+
+// The bytes of the hname of a feed, a u32 in little endian like the hnames of wasp.
+pub fn hnamebytes(hname: &str) -> [u8; 4] {
+    u32::from_str_radix(hname, 16).unwrap_or(0).to_le_bytes()
+}
+
+// The BOUNDARIES of a market with the outcomes given, as a comma separated list of strictly ascending integers, one less than outcomes.
+pub fn parseboundaries(boundaries: &str, outcomes: &[String]) -> Result<Vec<i64>, String> {
+    let mut parsed: Vec<i64> = Vec::new();
+    for boundary in boundaries.split(',').map(|boundary| boundary.trim()) {
+        let value: i64 = match boundary.parse::<i64>() {
+            Ok(value) => value,
+            Err(_) => return Err("a boundary of BOUNDARIES must be an integer, not ".to_string() + boundary),
+        };
+        if matches!(parsed.last(), Some(last) if value <= *last) {
+            return Err("BOUNDARIES must be strictly ascending".to_string());
+        }
+        parsed.push(value);
+    }
+    if outcomes.len() != parsed.len() + 1 {
+        return Err("OUTCOMES must list one outcome more than BOUNDARIES, ".to_string() + &(parsed.len() + 1).to_string());
+    }
+    Ok(parsed)
+}
+
+// The outcome of a value: the outcome after the last boundary not above the value, the first outcome below the first boundary.
+pub fn bucketoutcome(value: i64, boundaries: &[i64], outcomes: &[String]) -> String {
+    outcomes[boundaries.iter().filter(|boundary| value >= **boundary).count()].clone()
+}
+
+// The integer median of values, rounded down between the two middle values of an even number of values, none without values.
+pub fn median(values: &[i64]) -> Option<i64> {
+    let mut sorted: Vec<i64> = values.to_vec();
+    sorted.sort_unstable();
+    let middle: usize = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        length if length % 2 == 1 => Some(sorted[middle]),
+        _ => Some(((sorted[middle - 1] as i128 + sorted[middle] as i128).div_euclid(2)) as i64),
+    }
+}
+
+// The deviation of value from median in basis points of the median, i64::MAX for a value other than a median of 0.
+pub fn deviationbps(value: i64, median: i64) -> i64 {
+    let difference: i128 = (value as i128 - median as i128).abs();
+    if difference == 0 {
+        return 0;
+    }
+    if median == 0 {
+        return i64::MAX;
+    }
+    (difference * 10000 / (median as i128).abs()).min(i64::MAX as i128) as i64
+}
+
+// The report of reading the feeds, given the value every feed reported: without at least quorum values the market stays unresolved,
+// with a value deviating more than maxdeviationbps from the median the feeds diverged, unless maxdeviationbps is 0, and otherwise
+// the market settles on the outcome of the median.
+pub fn resolvefeeds(feeds: &[OracleFeed], values: &[Option<i64>], quorum: i64, maxdeviationbps: i64, boundaries: &[i64], outcomes: &[String]) -> FeedReport {
+    let reported: Vec<i64> = values.iter().flatten().copied().collect();
+    let mut report: FeedReport = FeedReport {
+        status: FEEDS_NOQUORUM.to_string(),
+        feeds: feeds.iter().zip(values.iter()).map(|(feed, value)| FeedValue { hname: feed.hname.clone(), key: feed.key.clone(), value: *value }).collect(),
+        quorum,
+        responses: reported.len() as i64,
+        median: None,
+        outcome: "".to_string(),
+        deviationbps: 0,
+        maxdeviationbps,
+    };
+    if report.responses < quorum.max(1) {
+        return report;
+    }
+    let middle: i64 = median(&reported).unwrap_or(0);
+    report.median = Some(middle);
+    report.deviationbps = reported.iter().map(|value| deviationbps(*value, middle)).max().unwrap_or(0);
+    if maxdeviationbps > 0 && report.deviationbps > maxdeviationbps {
+        report.status = FEEDS_DIVERGED.to_string();
+        return report;
+    }
+    report.status = FEEDS_RESOLVED.to_string();
+    report.outcome = bucketoutcome(middle, boundaries, outcomes);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcomes() -> Vec<String> {
+        vec!["below".to_string(), "between".to_string(), "above".to_string()]
+    }
+
+    fn feeds() -> Vec<OracleFeed> {
+        parsefeeds("0000000a:iota-usd,0000000b:iota-usd,0000000c:iota-usd,0000000d:iota-usd,0000000e:iota-usd").unwrap()
+    }
+
+    #[test]
+    fn feeds_and_boundaries_are_validated() {
+        assert_eq!(feeds().len(), MAXFEEDS);
+        assert_eq!(parsefeeds("3E5D2A18:price").unwrap()[0].hname, "3e5d2a18");
+        assert_eq!(hnamebytes("3e5d2a18"), [0x18, 0x2a, 0x5d, 0x3e]);
+        assert!(parsefeeds("3e5d2a18").is_err());
+        assert!(parsefeeds("3e5d2a1:price").is_err());
+        assert!(parsefeeds("3e5d2a18:").is_err());
+        assert!(parsefeeds("3e5d2a18:price,3e5d2a18:price").is_err());
+        assert!(parsefeeds("01:a,02:a,03:a,04:a,05:a,06:a").is_err());
+        assert!(parsefeeds("00000001:a,00000002:a,00000003:a,00000004:a,00000005:a,00000006:a").is_err());
+
+        assert_eq!(parseboundaries("1000, 2000", &outcomes()), Ok(vec![1000, 2000]));
+        assert!(parseboundaries("2000,1000", &outcomes()).is_err());
+        assert!(parseboundaries("1000,1000", &outcomes()).is_err());
+        assert!(parseboundaries("1000", &outcomes()).is_err());
+        assert!(parseboundaries("1000,x", &outcomes()).is_err());
+        // a boundary belongs to the outcome above it
+        let boundaries: Vec<i64> = vec![1000, 2000];
+        assert_eq!(bucketoutcome(999, &boundaries, &outcomes()), "below");
+        assert_eq!(bucketoutcome(1000, &boundaries, &outcomes()), "between");
+        assert_eq!(bucketoutcome(2000, &boundaries, &outcomes()), "above");
+        assert_eq!(median(&[5, 1, 4, 2]), Some(3));
+        assert_eq!(median(&[-3, -2]), Some(-3));
+        assert_eq!(median(&[i64::MAX, i64::MAX]), Some(i64::MAX));
+        assert_eq!(median(&[]), None);
+    }
+
+    #[test]
+    fn feeds_in_full_agreement_settle_on_the_outcome_of_the_median() {
+        let values: Vec<Option<i64>> = vec![Some(1510), Some(1490), Some(1500), Some(1505), Some(1495)];
+        let report: FeedReport = resolvefeeds(&feeds(), &values, 3, 500, &[1000, 2000], &outcomes());
+        assert_eq!(report.status, FEEDS_RESOLVED);
+        assert_eq!(report.median, Some(1500));
+        assert_eq!(report.outcome, "between");
+        assert_eq!(report.responses, 5);
+        // 10 of 1500 are 66 basis points
+        assert_eq!(report.deviationbps, 66);
+        // every feed is reported with its value, for the receipt
+        assert_eq!(report.feeds.iter().map(|feed| feed.value).collect::<Vec<Option<i64>>>(), values);
+        assert_eq!(report.feeds[2].hname, "0000000c");
+    }
+
+    #[test]
+    fn a_missing_feed_counts_against_the_quorum_only() {
+        let values: Vec<Option<i64>> = vec![Some(2100), None, Some(2300), Some(2200), Some(2000)];
+        let report: FeedReport = resolvefeeds(&feeds(), &values, 3, 0, &[1000, 2000], &outcomes());
+        assert_eq!((report.status.as_str(), report.median, report.outcome.as_str()), (FEEDS_RESOLVED, Some(2150), "above"));
+        assert_eq!(report.feeds[1].value, None);
+        // below the quorum, nothing is resolved
+        let values: Vec<Option<i64>> = vec![Some(2100), None, None, Some(2200), None];
+        let report: FeedReport = resolvefeeds(&feeds(), &values, 3, 0, &[1000, 2000], &outcomes());
+        assert_eq!((report.status.as_str(), report.responses, report.median), (FEEDS_NOQUORUM, 2, None));
+        assert_eq!(report.outcome, "");
+        assert_eq!(resolvefeeds(&feeds(), &[None, None, None, None, None], 0, 0, &[1000, 2000], &outcomes()).status, FEEDS_NOQUORUM);
+    }
+
+    #[test]
+    fn divergent_feeds_trigger_the_dispute_instead_of_settling() {
+        // one feed reports a price 20% above the others, beyond a threshold of 10%
+        let values: Vec<Option<i64>> = vec![Some(1000), Some(1000), Some(1200), Some(990), Some(1010)];
+        let report: FeedReport = resolvefeeds(&feeds(), &values, 3, 1000, &[1000, 2000], &outcomes());
+        assert_eq!(report.status, FEEDS_DIVERGED);
+        assert_eq!((report.median, report.deviationbps), (Some(1000), 2000));
+        assert_eq!(report.outcome, "");
+        // a wider threshold settles on the median anyway, and without a threshold the deviation is only reported
+        assert_eq!(resolvefeeds(&feeds(), &values, 3, 2000, &[1000, 2000], &outcomes()).status, FEEDS_RESOLVED);
+        assert_eq!(resolvefeeds(&feeds(), &values, 3, 0, &[1000, 2000], &outcomes()).outcome, "between");
+        // values around a median of 0 cannot be measured in basis points of it
+        assert_eq!(deviationbps(1, 0), i64::MAX);
+        assert_eq!(deviationbps(0, 0), 0);
+    }
+}
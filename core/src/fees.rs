@@ -0,0 +1,69 @@
+// The split of the fee of a market between the treasury of the contract and the creator of the market, without access to the contract's context
+//
+// The fee a market takes at settlement, see settlewithfee, is divided by the CREATORSHAREBPS of the platform defaults the market was
+// created under: the creator's share, rounded down, is credited to a balance the creator claims, the rest goes to the treasury of the
+// contract, which the treasurer withdraws. Both add up to the fee, so the split never creates or loses an IOTA.
+
+pub const MAXCREATORSHAREBPS: i64 = 10000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeSplit {
+    pub creator: i64,
+    pub treasury: i64,
+}
+
+// the shares of a fee for a creator share of creatorsharebps, the treasury keeping what the rounding down leaves
+pub fn feesplit(fee: i64, creatorsharebps: i64) -> FeeSplit {
+    let creator: i64 = fee.max(0) * creatorsharebps.clamp(0, MAXCREATORSHAREBPS) / 10000;
+    FeeSplit { creator, treasury: fee.max(0) - creator }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::{parsecontainerofbets, settlewithfee, ContainerOfBets, SettlementResult};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn the_shares_add_up_to_the_fee() {
+        assert_eq!(feesplit(1000, 2500), FeeSplit { creator: 250, treasury: 750 });
+        // the treasury keeps the rounding
+        assert_eq!(feesplit(999, 3333), FeeSplit { creator: 332, treasury: 667 });
+        assert_eq!(feesplit(1000, 0), FeeSplit { creator: 0, treasury: 1000 });
+        assert_eq!(feesplit(1000, MAXCREATORSHAREBPS), FeeSplit { creator: 1000, treasury: 0 });
+        assert_eq!(feesplit(0, 5000), FeeSplit::default());
+    }
+
+    #[test]
+    fn every_creator_is_credited_the_shares_of_its_markets() {
+        // three markets of two creators, settled with fees of 5, 10 and 3 percent, under creator shares of 25% and then 40%
+        let markets: [(&str, i64, i64, &str); 3] = [
+            ("alice", 5, 2500, r#"{"map":{"a":{"betamount":3000,"betisforvalue":"yes","placedat":1,"attachedamount":3000},
+                "b":{"betamount":1333,"betisforvalue":"no","placedat":2,"attachedamount":1333}}}"#),
+            ("bob", 10, 2500, r#"{"map":{"c":{"betamount":777,"betisforvalue":"yes","placedat":1,"attachedamount":777},
+                "d":{"betamount":1001,"betisforvalue":"no","placedat":2,"attachedamount":1001},
+                "e":{"betamount":250,"betisforvalue":"yes","placedat":3,"attachedamount":250}}}"#),
+            ("alice", 3, 4000, r#"{"map":{"f":{"betamount":9999,"betisforvalue":"no","placedat":1,"attachedamount":9999},
+                "g":{"betamount":1,"betisforvalue":"yes","placedat":2,"attachedamount":1}}}"#),
+        ];
+        let mut claimable: BTreeMap<&str, i64> = BTreeMap::new();
+        let mut treasury: i64 = 0;
+        let mut fees: i64 = 0;
+        let mut expected: BTreeMap<&str, i64> = BTreeMap::new();
+        for (creator, feepercent, creatorsharebps, betsjson) in markets.iter() {
+            let containerofbets: ContainerOfBets = parsecontainerofbets(betsjson);
+            let settlement: SettlementResult = settlewithfee(&containerofbets, "yes", *feepercent);
+            let split: FeeSplit = feesplit(settlement.operatortake, *creatorsharebps);
+            // nothing of the pool is lost by the fee and its split
+            assert_eq!(settlement.winamounts.values().sum::<i64>() + split.creator + split.treasury, settlement.totalbetamount as i64);
+            *claimable.entry(creator).or_insert(0) += split.creator;
+            *expected.entry(creator).or_insert(0) += settlement.operatortake * creatorsharebps / 10000;
+            treasury += split.treasury;
+            fees += settlement.operatortake;
+        }
+        assert_eq!(claimable, expected);
+        assert_eq!(claimable["alice"], 216 / 4 + 300 * 4 / 10);
+        assert_eq!(claimable["bob"], 202 / 4);
+        assert_eq!(claimable.values().sum::<i64>() + treasury, fees);
+    }
+}
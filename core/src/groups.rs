@@ -0,0 +1,120 @@
+// Groups of linked markets, whose outcomes are declared together, without access to the contract's context
+//
+// Mutually exclusive markets, e.g. "Team A wins", "Team B wins" and "Draw" as three binary markets, are linked into a group by
+// linkmarkets, and closegroup declares the outcome of every member in one call, or of none of them if one outcome is invalid.
+// A group can cap what an address stakes across all its members, besides the caps of every member.
+
+use std::collections::BTreeMap;
+
+// number of markets a group links at most, and the maximum length in bytes of a GROUPID
+pub const MAXGROUPMEMBERS: usize = 16;
+pub const MAXGROUPID: usize = 64;
+
+// The MARKETIDS of a group, given as a comma separated list, e.g. "teama,teamb,draw": at least two markets,
+// each listed once and none without MARKETID, up to MAXGROUPMEMBERS, in the order given.
+pub fn groupmembers(marketids: &str) -> Result<Vec<String>, String> {
+    let members: Vec<String> = marketids.split(',').map(|marketid| marketid.trim().to_string()).collect();
+    if members.iter().any(|marketid| marketid.is_empty()) {
+        return Err("MARKETIDS must not contain an empty market id".to_string());
+    }
+    if members.len() < 2 || members.len() > MAXGROUPMEMBERS {
+        return Err("a group links from 2 to ".to_string() + &MAXGROUPMEMBERS.to_string() + " markets");
+    }
+    let mut unique: Vec<&String> = members.iter().collect();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != members.len() {
+        return Err("MARKETIDS must not list a market twice".to_string());
+    }
+    Ok(members)
+}
+
+// The outcome of every member of a group, given as OUTCOMEMAP like "teama:no,teamb:yes,draw:no": every member has to be given
+// exactly one outcome, and no other market any, so that a group is declared completely or not at all.
+pub fn groupoutcomes(members: &[String], outcomemap: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut outcomes: BTreeMap<String, String> = BTreeMap::new();
+    for entry in outcomemap.split(',').filter(|entry| !entry.trim().is_empty()) {
+        let (marketid, outcome) = match entry.split_once(':') {
+            Some((marketid, outcome)) => (marketid.trim(), outcome.trim()),
+            None => return Err("an entry of OUTCOMEMAP must be MARKETID:OUTCOME, not ".to_string() + entry),
+        };
+        if !members.iter().any(|member| member == marketid) {
+            return Err("OUTCOMEMAP gives an outcome for ".to_string() + marketid + ", which is not a member of the group");
+        }
+        if outcome.is_empty() {
+            return Err("OUTCOMEMAP gives an empty outcome for ".to_string() + marketid);
+        }
+        if outcomes.insert(marketid.to_string(), outcome.to_string()).is_some() {
+            return Err("OUTCOMEMAP gives more than one outcome for ".to_string() + marketid);
+        }
+    }
+    if let Some(missing) = members.iter().find(|member| !outcomes.contains_key(*member)) {
+        return Err("OUTCOMEMAP gives no outcome for the member ".to_string() + missing);
+    }
+    Ok(outcomes)
+}
+
+// What an address may still stake in the markets of a group, having staked staked in them, or None without GROUPMAXSTAKE.
+pub fn groupallowance(staked: i64, groupmaxstake: i64) -> Option<i64> {
+    if groupmaxstake <= 0 {
+        return None;
+    }
+    Some((groupmaxstake - staked).max(0))
+}
+
+// The amount of a bet accepted within the allowance of the group, the rest is refunded like the excess over the caps of a market.
+pub fn groupcappedamount(amount: i64, staked: i64, groupmaxstake: i64) -> i64 {
+    groupallowance(staked, groupmaxstake).map_or(amount, |allowance| amount.min(allowance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members() -> Vec<String> {
+        vec!["teama".to_string(), "teamb".to_string(), "draw".to_string()]
+    }
+
+    #[test]
+    fn a_group_links_distinct_markets_in_the_order_given() {
+        assert_eq!(groupmembers("teama, teamb,draw"), Ok(members()));
+        assert!(groupmembers("teama").is_err());
+        assert!(groupmembers("teama,teamb,teama").is_err());
+        assert!(groupmembers("teama,,draw").is_err());
+        let many: Vec<String> = (0..=MAXGROUPMEMBERS).map(|i| i.to_string()).collect();
+        assert!(groupmembers(&many.join(",")).is_err());
+        assert!(groupmembers(&many[1..].join(",")).is_ok());
+    }
+
+    #[test]
+    fn every_member_needs_exactly_one_outcome() {
+        let outcomes: BTreeMap<String, String> = groupoutcomes(&members(), "teama:no,teamb:yes,draw:no").unwrap();
+        assert_eq!(outcomes["teamb"], "yes");
+        assert_eq!(outcomes.len(), 3);
+        assert!(groupoutcomes(&members(), "teama:no,teamb:yes").unwrap_err().ends_with("draw"));
+        assert!(groupoutcomes(&members(), "teama:no,teamb:yes,draw:no,other:no").is_err());
+        assert!(groupoutcomes(&members(), "teama:no,teamb:yes,draw:no,draw:yes").is_err());
+        assert!(groupoutcomes(&members(), "teama:no,teamb:,draw:no").is_err());
+        assert!(groupoutcomes(&members(), "teama:no,teamb,draw:no").is_err());
+    }
+
+    #[test]
+    fn the_stake_of_an_address_is_capped_across_the_group() {
+        // bets of 400 IOTA on each of three linked markets with a GROUPMAXSTAKE of 1000
+        let mut staked: i64 = 0;
+        let mut accepted: Vec<i64> = Vec::new();
+        for _market in members() {
+            let amount: i64 = groupcappedamount(400, staked, 1000);
+            staked += amount;
+            accepted.push(amount);
+        }
+        assert_eq!(accepted, vec![400, 400, 200]);
+        assert_eq!(groupallowance(staked, 1000), Some(0));
+        assert_eq!(groupcappedamount(100, staked, 1000), 0);
+        // without GROUPMAXSTAKE only the caps of every market apply
+        assert_eq!(groupallowance(staked, 0), None);
+        assert_eq!(groupcappedamount(400, staked, 0), 400);
+        // an address that staked beyond the cap before its markets were linked has no allowance, not a negative one
+        assert_eq!(groupallowance(1200, 1000), Some(0));
+    }
+}
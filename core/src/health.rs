@@ -0,0 +1,129 @@
+// Checks of healthcheck, without access to the contract's context
+//
+// Every check only reads counters and flags the contract maintains, never the bets, so that monitoring can call it every few minutes.
+// The contract reads these values from its state into HealthCounters, and the checks decide on them alone.
+
+use std::collections::BTreeMap;
+
+use crate::ledger::ledgerinvariantholds;
+use crate::storage::{storageformat, storageformatconsistent};
+
+
+// time in seconds after which healthcheck reports a declared outcome or the payouts of a settled market as stuck
+pub const HEALTHSTALLSECONDS: i64 = 86400;
+
+// values of the state of the contract and of a market the checks read
+#[derive(Clone, Default)]
+pub struct HealthCounters {
+    // IOTA held by the contract, the sum of its ledger buckets and its number of transfers
+    pub balance: i64,
+    pub bucketstotal: i64,
+    pub transferscount: i64,
+    pub transfersblocked: bool,
+    // layout of the bets recorded for the market, its number of chunks and its json stored without chunks
+    pub storageformat: String,
+    pub chunks: i64,
+    pub unchunked: String,
+    pub marketclosed: bool,
+    // whether an outcome was declared and not closed yet, and the end time for bets, 0 without
+    pub pendingoutcome: bool,
+    pub betenddatetime: i64,
+    // number of entitlements, the position of the payouts among them, when the market was settled, 0 if not, and the payouts failed for good
+    pub entitlements: i64,
+    pub payoutcursor: i64,
+    pub closedat: i64,
+    pub payoutsmanual: i64,
+}
+
+// The result of every check at currtime:
+// * ledger: the balance matches the ledger buckets, within the fees of the transfers
+// * transfers: transfers are not blocked by a violated invariant
+// * storageformat: the keys of the bets match the recorded layout
+// * pendingoutcome: a declared outcome was not left without closing the market for HEALTHSTALLSECONDS after the end time for bets
+// * payoutcursor: the payouts did not run past the recorded entitlements
+// * payouts: the payouts of a settled market did not stall for HEALTHSTALLSECONDS after settlement
+// * payoutsmanual: no failed payout is flagged for manual intervention
+pub fn healthchecks(counters: &HealthCounters, currtime: i64) -> BTreeMap<String, bool> {
+    let mut checks: BTreeMap<String, bool> = BTreeMap::new();
+    checks.insert("ledger".to_string(), ledgerinvariantholds(counters.balance, counters.bucketstotal, counters.transferscount));
+    checks.insert("transfers".to_string(), !counters.transfersblocked);
+    checks.insert("storageformat".to_string(), storageformatconsistent(&counters.storageformat, storageformat(&counters.storageformat),
+        counters.chunks, &counters.unchunked));
+    let pendingoutcomestuck: bool = !counters.marketclosed && counters.pendingoutcome
+        && counters.betenddatetime != 0 && currtime > counters.betenddatetime.saturating_add(HEALTHSTALLSECONDS);
+    checks.insert("pendingoutcome".to_string(), !pendingoutcomestuck);
+    checks.insert("payoutcursor".to_string(), counters.payoutcursor <= counters.entitlements);
+    let payoutsstalled: bool = counters.marketclosed && counters.payoutcursor < counters.entitlements && counters.closedat != 0
+        && currtime > counters.closedat.saturating_add(HEALTHSTALLSECONDS);
+    checks.insert("payouts".to_string(), !payoutsstalled);
+    checks.insert("payoutsmanual".to_string(), counters.payoutsmanual == 0);
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1622563200;
+
+    type Corruption = fn(&mut HealthCounters);
+
+    // a settled market whose payouts are done, two days after its end time
+    fn healthy() -> HealthCounters {
+        HealthCounters {
+            balance: 1000,
+            bucketstotal: 1000,
+            transferscount: 3,
+            storageformat: "map".to_string(),
+            marketclosed: true,
+            betenddatetime: NOW - 2 * 86400,
+            entitlements: 3,
+            payoutcursor: 3,
+            closedat: NOW - 86400 - 1,
+            ..HealthCounters::default()
+        }
+    }
+
+    fn failed(counters: &HealthCounters) -> Vec<String> {
+        healthchecks(counters, NOW).into_iter().filter(|(_check, passed)| !passed).map(|(check, _passed)| check).collect()
+    }
+
+    #[test]
+    fn a_healthy_contract_passes_every_check() {
+        assert!(failed(&healthy()).is_empty());
+        assert!(failed(&HealthCounters::default()).is_empty());
+        assert_eq!(healthchecks(&healthy(), NOW).len(), 7);
+    }
+
+    #[test]
+    fn corrupting_one_counter_fails_exactly_its_check() {
+        let corruptions: [(&str, Corruption); 7] = [
+            ("ledger", |counters| counters.bucketstotal = 996),
+            ("transfers", |counters| counters.transfersblocked = true),
+            ("storageformat", |counters| counters.chunks = 2),
+            ("pendingoutcome", |counters| { counters.marketclosed = false; counters.pendingoutcome = true; counters.payoutcursor = 0; counters.entitlements = 0; }),
+            ("payoutcursor", |counters| counters.payoutcursor = 4),
+            ("payouts", |counters| counters.payoutcursor = 2),
+            ("payoutsmanual", |counters| counters.payoutsmanual = 1),
+        ];
+        for (check, corrupt) in corruptions.iter() {
+            let mut counters: HealthCounters = healthy();
+            corrupt(&mut counters);
+            assert_eq!(failed(&counters), vec![check.to_string()], "{}", check);
+        }
+    }
+
+    #[test]
+    fn stalls_are_only_reported_after_the_stall_time() {
+        let mut counters: HealthCounters = healthy();
+        counters.payoutcursor = 2;
+        counters.closedat = NOW - HEALTHSTALLSECONDS;
+        assert!(failed(&counters).is_empty());
+        counters.marketclosed = false;
+        counters.pendingoutcome = true;
+        counters.betenddatetime = NOW - HEALTHSTALLSECONDS;
+        assert!(failed(&counters).is_empty());
+        counters.betenddatetime = NOW - HEALTHSTALLSECONDS - 1;
+        assert_eq!(failed(&counters), vec!["pendingoutcome".to_string()]);
+    }
+}
@@ -0,0 +1,92 @@
+// Eviction of idempotency keys, without access to the contract's context
+//
+// The keys of a market are queued in insertion order as "timestamp|key" entries. Once more keys are queued than the market keeps,
+// every insert evicts up to MAXEVICTIONSPERINSERT of the oldest keys, so that an insert takes constant time and the state stays bounded,
+// but never a key younger than the minimum age, so that retries in flight stay protected.
+
+// value of an evicted idempotency key, which is still known as evicted until it drops out of the second queue
+pub const IDEMPOTENCY_EVICTED: &str = "evicted";
+
+// most keys evicted by one insert, more than one so that the queue shrinks back to its limit after keys were kept for their age
+pub const MAXEVICTIONSPERINSERT: usize = 2;
+
+// entry of the queue for a key inserted at queuedat
+pub fn queuedkey(queuedat: i64, key: &str) -> String {
+    queuedat.to_string() + "|" + key
+}
+
+// The key of the oldest entry of a queue of queuelength entries if it is evicted at currtime, i.e. if more than maxkeys are queued
+// and the oldest is at least minage seconds old.
+pub fn evictedkey(queuelength: i64, maxkeys: i64, oldest: &str, currtime: i64, minage: i64) -> Option<&str> {
+    if queuelength <= maxkeys {
+        return None;
+    }
+    let (queuedat, key) = oldest.split_once('|')?;
+    if currtime - queuedat.parse::<i64>().ok()? < minage {
+        return None;
+    }
+    Some(key)
+}
+
+// STATUS of a key as returned by getidempotencykey, given the request id stored for it: "executed", "evicted" or "not_found"
+pub fn idempotencystatus(originalrequestid: &str) -> &'static str {
+    if originalrequestid == IDEMPOTENCY_EVICTED {
+        "evicted"
+    } else if !originalrequestid.is_empty() {
+        "executed"
+    } else {
+        "not_found"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // insert a key at currtime like rememberidempotencykey, returning the evicted keys
+    fn insert(queue: &mut VecDeque<String>, key: &str, currtime: i64, maxkeys: i64, minage: i64) -> Vec<String> {
+        queue.push_back(queuedkey(currtime, key));
+        let mut evicted: Vec<String> = Vec::new();
+        for _eviction in 0..MAXEVICTIONSPERINSERT {
+            match evictedkey(queue.len() as i64, maxkeys, &queue[0], currtime, minage) {
+                Some(key) => evicted.push(key.to_string()),
+                None => break,
+            }
+            queue.pop_front();
+        }
+        evicted
+    }
+
+    #[test]
+    fn keys_beyond_the_cap_are_evicted_oldest_first() {
+        let mut queue: VecDeque<String> = VecDeque::new();
+        let mut evicted: Vec<String> = Vec::new();
+        for i in 0..6 {
+            evicted.extend(insert(&mut queue, &("key".to_string() + &i.to_string()), 1000 + i, 3, 0));
+        }
+        assert_eq!(evicted, vec!["key0", "key1", "key2"]);
+        assert_eq!(queue, vec!["1003|key3", "1004|key4", "1005|key5"]);
+    }
+
+    #[test]
+    fn keys_younger_than_the_minimum_age_are_kept_beyond_the_cap() {
+        let mut queue: VecDeque<String> = VecDeque::new();
+        for i in 0..5 {
+            assert!(insert(&mut queue, &("key".to_string() + &i.to_string()), 1000 + i, 2, 60).is_empty());
+        }
+        assert_eq!(queue.len(), 5);
+        // once old enough, the queue shrinks back by up to two keys per insert
+        assert_eq!(insert(&mut queue, "key5", 1061, 2, 60), vec!["key0", "key1"]);
+        assert_eq!(insert(&mut queue, "key6", 1062, 2, 60), vec!["key2"]);
+        assert_eq!(insert(&mut queue, "key7", 1064, 2, 60), vec!["key3", "key4"]);
+        assert_eq!(queue.len(), 3);
+    }
+
+    #[test]
+    fn an_evicted_key_has_its_own_status() {
+        assert_eq!(idempotencystatus(IDEMPOTENCY_EVICTED), "evicted");
+        assert_eq!(idempotencystatus("requestid"), "executed");
+        assert_eq!(idempotencystatus(""), "not_found");
+    }
+}
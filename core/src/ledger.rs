@@ -0,0 +1,106 @@
+// Ledger buckets the IOTA held by a contract are accounted in
+//
+// Every IOTA held by the contract belongs to exactly one bucket, so that the sum of the buckets can be checked against the balance.
+
+// stakes of bets, collected fees of the treasury, the shares of the fees claimable by the creators of markets, escrowed funds like bonds
+// and the stakes of challenges, deposits of registered mirrors, IOTA sent to the contract without being accepted for a purpose,
+// and IOTA of rejected requests waiting to be returned
+pub const BUCKET_POOLS: &str = "pools";
+pub const BUCKET_FEES: &str = "fees";
+pub const BUCKET_CREATORFEES: &str = "creatorfees";
+pub const BUCKET_ESCROWS: &str = "escrows";
+pub const BUCKET_DEPOSITS: &str = "deposits";
+pub const BUCKET_STRAYS: &str = "strays";
+pub const BUCKET_RETURNS: &str = "returns";
+pub const BUCKETS: [&str; 7] = [BUCKET_POOLS, BUCKET_FEES, BUCKET_CREATORFEES, BUCKET_ESCROWS, BUCKET_DEPOSITS, BUCKET_STRAYS, BUCKET_RETURNS];
+
+// key of the balance of a bucket in the state
+pub fn bucketkey(bucket: &str) -> String {
+    "bucket_".to_string() + bucket
+}
+
+// The IOTA held by the contract must equal the sum of the ledger buckets, except for the transaction fee of up to 1 IOTA per transfer.
+pub fn ledgerinvariantholds(balance: i64, bucketstotal: i64, transferscount: i64) -> bool {
+    (balance - bucketstotal).abs() <= transferscount
+}
+
+// IOTA missing to back a bet of amount IOTA that is recorded without being placed, e.g. one repaired from legacy records:
+// the part of the pool that backs none of the bets, totalling totalbetamount, backs it first, the rest has to be sent with the call.
+pub fn missingbacking(pool: i64, totalbetamount: i64, amount: i64) -> i64 {
+    let unbacked: i64 = (pool - totalbetamount).max(0);
+    (amount - unbacked).max(0)
+}
+
+// invariant violations of a transfer, recorded by the contract instead of transferring what it must not
+pub const VIOLATION_NEGATIVE: &str = "negative";
+pub const VIOLATION_EXCEEDSBALANCE: &str = "exceedsbalance";
+pub const VIOLATION_EXCEEDSBUCKET: &str = "exceedsbucket";
+
+// The amount that may be transferred of the requested amount, and the invariant violations it was found with. A negative amount
+// transfers nothing, and any amount is capped at the balance of the contract and then at the balance of the bucket it is paid from,
+// None for tokens that are not accounted in buckets.
+pub fn guardtransfer(amount: i64, balance: i64, bucketbalance: Option<i64>) -> (i64, Vec<&'static str>) {
+    if amount < 0 {
+        return (0, vec![VIOLATION_NEGATIVE]);
+    }
+    let mut violations: Vec<&'static str> = Vec::new();
+    let mut transferamount: i64 = amount;
+    if transferamount > balance {
+        violations.push(VIOLATION_EXCEEDSBALANCE);
+        transferamount = balance.max(0);
+    }
+    if let Some(bucketbalance) = bucketbalance {
+        if transferamount > bucketbalance {
+            violations.push(VIOLATION_EXCEEDSBUCKET);
+            transferamount = bucketbalance.max(0);
+        }
+    }
+    (transferamount, violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_amount_within_the_balances_is_transferred_in_full() {
+        assert_eq!(guardtransfer(100, 1000, Some(500)), (100, vec![]));
+        assert_eq!(guardtransfer(500, 500, Some(500)), (500, vec![]));
+        assert_eq!(guardtransfer(0, 0, Some(0)), (0, vec![]));
+    }
+
+    #[test]
+    fn a_negative_amount_transfers_nothing() {
+        assert_eq!(guardtransfer(-1, 1000, Some(1000)), (0, vec![VIOLATION_NEGATIVE]));
+        assert_eq!(guardtransfer(i64::MIN, 1000, None), (0, vec![VIOLATION_NEGATIVE]));
+    }
+
+    #[test]
+    fn an_absurd_amount_is_capped_at_the_balance_and_the_bucket() {
+        assert_eq!(guardtransfer(i64::MAX, 1000, None), (1000, vec![VIOLATION_EXCEEDSBALANCE]));
+        assert_eq!(guardtransfer(i64::MAX, 1000, Some(400)), (400, vec![VIOLATION_EXCEEDSBALANCE, VIOLATION_EXCEEDSBUCKET]));
+        assert_eq!(guardtransfer(600, 1000, Some(400)), (400, vec![VIOLATION_EXCEEDSBUCKET]));
+        // a bucket driven negative by a violation before never makes the amount negative
+        assert_eq!(guardtransfer(100, 1000, Some(-5)), (0, vec![VIOLATION_EXCEEDSBUCKET]));
+    }
+
+    #[test]
+    fn a_recorded_bet_is_backed_by_the_unbacked_pool_first() {
+        // the pool backs the bets and 50 IOTA more
+        assert_eq!(missingbacking(350, 300, 40), 0);
+        assert_eq!(missingbacking(350, 300, 50), 0);
+        assert_eq!(missingbacking(350, 300, 51), 1);
+        // a pool backing exactly the bets backs nothing of it
+        assert_eq!(missingbacking(300, 300, 100), 100);
+        // a pool short of the bets does not make it any more expensive
+        assert_eq!(missingbacking(200, 300, 100), 100);
+    }
+
+    #[test]
+    fn the_ledger_tolerates_one_iota_per_transfer() {
+        assert!(ledgerinvariantholds(1000, 1000, 0));
+        assert!(ledgerinvariantholds(998, 1000, 2));
+        assert!(!ledgerinvariantholds(997, 1000, 2));
+        assert!(!ledgerinvariantholds(1001, 1000, 0));
+    }
+}
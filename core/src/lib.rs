@@ -0,0 +1,58 @@
+// Core of the prediction market, shared by the contracts built on it
+//
+// Everything here only depends on its arguments, not on wasmlib or the state of a chain, so that it compiles for wasm32 as well as
+// for native targets, e.g. for the replay example and for other contracts settling parimutuel bets, like a tournament bracket.
+// The contract crate wires these functions to ScFuncContext and ScViewContext.
+//
+// Semver: the public items of this crate follow semantic versioning. Within a major version,
+// * the results of settle and haircut for the same bets stay the same, so that settled markets can be replayed,
+// * the json of Bet, ContainerOfBets and ExportedBet stays readable, new fields are only added with a default,
+// * the names of the ledger buckets and the keys and layouts of the stored bets do not change,
+// * the construction of the Merkle tree over the bets stays the same, so that issued inclusion proofs stay valid,
+// * the canonical encodings only change together with their tag, so that committed hashes stay verifiable.
+
+pub mod actions;
+pub mod addresses;
+pub mod alerts;
+pub mod archive;
+pub mod betting;
+pub mod blocklist;
+pub mod book;
+pub mod canonical;
+pub mod commits;
+pub mod configuration;
+pub mod costs;
+pub mod counters;
+pub mod defaults;
+pub mod delegates;
+pub mod disputes;
+pub mod events;
+pub mod externalrefs;
+pub mod feeds;
+pub mod fees;
+pub mod groups;
+pub mod health;
+pub mod idempotency;
+pub mod ledger;
+pub mod memos;
+pub mod merkle;
+pub mod metadata;
+pub mod migration;
+pub mod mirrors;
+pub mod odds;
+pub mod paging;
+pub mod payouts;
+pub mod provenance;
+pub mod recording;
+pub mod refusals;
+pub mod returns;
+pub mod roles;
+pub mod schedules;
+pub mod seeding;
+pub mod settlement;
+pub mod softfailures;
+pub mod storage;
+pub mod tally;
+pub mod terms;
+pub mod time;
+pub mod writes;
@@ -0,0 +1,140 @@
+// Encrypted memos of bets, without access to the contract's context
+//
+// A market initialized with a MEMOPUBKEY, the public key of the contract owner, takes a MEMOCIPHER with a bet, a memo encrypted off-chain
+// to that key, e.g. a support contact. The contract never decrypts it. It keeps the memo apart from the bet, in the memociphers map of the
+// market, so that no view or export of the bets includes it, and only the contract owner or a marketadmin read it by getbetprivate.
+// The state of the contract is public, so only the encryption keeps the memo private.
+
+use crate::betting::BetRejection;
+
+pub const ERR_INVALID_MEMO: &str = "ERR_INVALID_MEMO";
+pub const ERR_MEMO_TOO_LARGE: &str = "ERR_MEMO_TOO_LARGE";
+
+// maximum size in bytes of the encrypted memo of a bet, and size of the public key memos are encrypted to
+pub const MAXMEMOCIPHER: usize = 512;
+pub const MEMOPUBKEY_LENGTH: usize = 32;
+
+// Check the MEMOPUBKEY of initmarket given in base58 as memopubkey, decoded to the bytes decoded.
+pub fn validatememopubkey(memopubkey: &str, decoded: &[u8]) -> Result<(), String> {
+    if !memopubkey.is_empty() && decoded.len() != MEMOPUBKEY_LENGTH {
+        return Err("MEMOPUBKEY must be a base58 encoded public key of ".to_string() + &MEMOPUBKEY_LENGTH.to_string() + " bytes");
+    }
+    Ok(())
+}
+
+// Check the MEMOCIPHER of a bet given in base58 as memoparam, decoded to the bytes memocipher, in a market with the MEMOPUBKEY memopubkey.
+// Like a bet without BETVALUE, a bet with an invalid memo is refunded in strict mode as well.
+pub fn memorejection(memoparam: &str, memocipher: &[u8], memopubkey: &str) -> Option<BetRejection> {
+    if !memoparam.is_empty() && (memocipher.is_empty() || memopubkey.is_empty()) {
+        return Some(BetRejection { error: ERR_INVALID_MEMO, soft: false, message: "bet was not accepted, MEMOCIPHER must be base58 encoded and the market must have a MEMOPUBKEY" });
+    }
+    if memocipher.len() > MAXMEMOCIPHER {
+        return Some(BetRejection { error: ERR_MEMO_TOO_LARGE, soft: false, message: "bet was not accepted, MEMOCIPHER must have up to 512 bytes" });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roles::{authorised, ROLE_MARKETADMIN, ROLE_RESOLVER, ROLE_TREASURER};
+    use crate::settlement::{exportedbets, parsecontainerofbets, Bet, ContainerOfBets};
+    use std::collections::BTreeMap;
+
+    const MEMO: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+    // the part of the contract keeping memos: the bets and the memos apart from them
+    struct Contract {
+        memopubkey: String,
+        bets: ContainerOfBets,
+        memociphers: BTreeMap<String, Vec<u8>>,
+    }
+
+    impl Contract {
+        // a bet with MEMOCIPHER, already decoded from base58 as memocipher
+        fn bet(&mut self, address: &str, memoparam: &str, memocipher: &[u8]) -> Result<(), &'static str> {
+            if let Some(rejection) = memorejection(memoparam, memocipher, &self.memopubkey) {
+                return Err(rejection.error);
+            }
+            let bet: Bet = Bet {
+                betamount: 100,
+                betisforvalue: "yes".to_string(),
+                placedat: 1622563200,
+                attachedamount: 100,
+                addressencoding: "".to_string(),
+                requestid: "request1".to_string(),
+                clientid: "".to_string(),
+                campaign: "".to_string(),
+                history: Vec::new(),
+            };
+            self.bets.map.insert(address.to_string(), bet);
+            if !memocipher.is_empty() {
+                self.memociphers.insert(address.to_string(), memocipher.to_vec());
+            }
+            Ok(())
+        }
+
+        // getbetprivate of a caller that is the contract owner if iscreator, holding the roles held
+        fn getbetprivate(&self, address: &str, iscreator: bool, held: &[&str]) -> Result<Option<Vec<u8>>, String> {
+            if !authorised("getbetprivate", iscreator, held) {
+                return Err("Not authorised to read memos - only contract creator or a marketadmin is allowed to do this.".to_string());
+            }
+            Ok(self.memociphers.get(address).cloned())
+        }
+    }
+
+    fn contract() -> Contract {
+        Contract { memopubkey: "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi".to_string(), bets: parsecontainerofbets(""), memociphers: BTreeMap::new() }
+    }
+
+    #[test]
+    fn the_memo_is_stored_apart_from_the_bet_for_the_owner_only() {
+        let mut contract: Contract = contract();
+        contract.bet("alice", "6h8cQN", &MEMO).unwrap();
+        contract.bet("bob", "", &[]).unwrap();
+        assert_eq!(contract.getbetprivate("alice", true, &[]), Ok(Some(MEMO.to_vec())));
+        assert_eq!(contract.getbetprivate("alice", false, &[ROLE_MARKETADMIN]), Ok(Some(MEMO.to_vec())));
+        // a bet without memo is found without one
+        assert_eq!(contract.getbetprivate("bob", true, &[]), Ok(None));
+        // neither the betting account itself nor the other roles read it
+        assert!(contract.getbetprivate("alice", false, &[]).unwrap_err().contains("Not authorised to read memos"));
+        assert!(contract.getbetprivate("alice", false, &[ROLE_TREASURER, ROLE_RESOLVER]).is_err());
+    }
+
+    #[test]
+    fn an_invalid_or_too_large_memo_is_refunded() {
+        let mut contract: Contract = contract();
+        assert!(contract.bet("alice", "6h8cQN", &[0u8; MAXMEMOCIPHER]).is_ok());
+        assert_eq!(contract.bet("bob", "6h8cQN", &[0u8; MAXMEMOCIPHER + 1]), Err(ERR_MEMO_TOO_LARGE));
+        assert!(memorejection("6h8cQN", &[0u8; MAXMEMOCIPHER + 1], "key").unwrap().message.contains(&MAXMEMOCIPHER.to_string()));
+        // a memo that is not base58 decodes to nothing
+        assert_eq!(contract.bet("bob", "0OIl", &[]), Err(ERR_INVALID_MEMO));
+        assert!(!contract.bets.map.contains_key("bob"));
+        assert!(!contract.memociphers.contains_key("bob"));
+        // a market without MEMOPUBKEY takes no memos
+        let mut keyless: Contract = Contract { memopubkey: "".to_string(), ..self::contract() };
+        assert_eq!(keyless.bet("alice", "6h8cQN", &MEMO), Err(ERR_INVALID_MEMO));
+        assert!(keyless.bet("alice", "", &[]).is_ok());
+    }
+
+    #[test]
+    fn the_memo_is_not_part_of_the_bets_or_their_export() {
+        let mut contract: Contract = contract();
+        contract.bet("alice", "6h8cQN", &MEMO).unwrap();
+        let betjson: String = serde_json::to_string(&contract.bets.map["alice"]).unwrap();
+        let exportjson: String = serde_json::to_string(&exportedbets(&contract.bets, None)).unwrap();
+        for json in [betjson, exportjson].iter() {
+            assert!(!json.contains("memo"), "{}", json);
+            assert!(!json.contains("6h8cQN"), "{}", json);
+            assert!(!json.contains(&serde_json::to_string(&MEMO.to_vec()).unwrap()), "{}", json);
+        }
+    }
+
+    #[test]
+    fn the_memo_public_key_has_32_bytes() {
+        assert!(validatememopubkey("", &[]).is_ok());
+        assert!(validatememopubkey("key", &[7u8; MEMOPUBKEY_LENGTH]).is_ok());
+        assert!(validatememopubkey("key", &[7u8; MEMOPUBKEY_LENGTH - 1]).unwrap_err().contains("32 bytes"));
+        assert!(validatememopubkey("0OIl", &[]).is_err());
+    }
+}
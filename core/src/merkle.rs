@@ -0,0 +1,174 @@
+// Merkle tree over the bets, for proofs that a bet is included in a market without revealing the other bets
+//
+// The tree is built deterministically:
+// * every bet is a leaf encoded by canonicalleaf of the address, value and amount, the leaves are sorted by address, which is unique per market,
+// * a leaf is hashed as hash(0x00 ++ leaf) and an inner node as hash(0x01 ++ left ++ right), so a leaf cannot pose as a node,
+// * on every level, the nodes are paired from the left, and a last node without a sibling is carried up to the next level unchanged,
+// * the root of a tree without leaves is empty.
+// The hash function is passed in, the contract uses blake2b-256 of wasmlib, and a verifier has to use the same.
+
+use serde::{Serialize, Deserialize};
+use serde_with::serde_as;
+
+use crate::canonical::canonicalleaf;
+use crate::settlement::ContainerOfBets;
+
+
+// sibling of a node on the path from a leaf to the root, and whether it is the left one of the pair
+#[serde_as]
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ProofStep {
+    pub sibling: Vec<u8>,
+    pub left: bool,
+}
+
+// leaves of the bets, sorted by address
+pub fn merkleleaves(containerofbets: &ContainerOfBets) -> Vec<Vec<u8>> {
+    let mut addresses: Vec<&String> = containerofbets.map.keys().collect();
+    addresses.sort();
+    addresses.iter()
+        .map(|address| {
+            let bet = &containerofbets.map[*address];
+            canonicalleaf(address, &bet.betisforvalue, bet.betamount as i64)
+        })
+        .collect()
+}
+
+pub fn merkleleafhash(leaf: &[u8], hash: &impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![0];
+    bytes.extend_from_slice(leaf);
+    hash(&bytes)
+}
+
+fn merklenodehash(left: &[u8], right: &[u8], hash: &impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![1];
+    bytes.extend_from_slice(left);
+    bytes.extend_from_slice(right);
+    hash(&bytes)
+}
+
+// root of the tree over the leaves and the path of the leaf at index, if any
+fn merkletree(leaves: &[Vec<u8>], index: Option<usize>, hash: &impl Fn(&[u8]) -> Vec<u8>) -> (Vec<u8>, Vec<ProofStep>) {
+    let mut level: Vec<Vec<u8>> = leaves.iter().map(|leaf| merkleleafhash(leaf, hash)).collect();
+    let mut path: Vec<ProofStep> = Vec::new();
+    let mut position: Option<usize> = index;
+    if level.is_empty() {
+        return (Vec::new(), path);
+    }
+    while level.len() > 1 {
+        if let Some(current) = position {
+            let sibling: usize = current ^ 1;
+            if sibling < level.len() {
+                path.push(ProofStep { sibling: level[sibling].clone(), left: sibling < current });
+            }
+            position = Some(current / 2);
+        }
+        level = level.chunks(2)
+            .map(|pair| if pair.len() == 2 { merklenodehash(&pair[0], &pair[1], hash) } else { pair[0].clone() })
+            .collect();
+    }
+    (level.remove(0), path)
+}
+
+pub fn merkleroot(leaves: &[Vec<u8>], hash: &impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    merkletree(leaves, None, hash).0
+}
+
+// path of the leaf at index, from the leaf up to the root
+pub fn merkleproof(leaves: &[Vec<u8>], index: usize, hash: &impl Fn(&[u8]) -> Vec<u8>) -> Vec<ProofStep> {
+    merkletree(leaves, Some(index), hash).1
+}
+
+// Whether the leaf is included in the tree with the root, given the path returned by merkleproof.
+pub fn verifyinclusion(leaf: &[u8], path: &[ProofStep], root: &[u8], hash: &impl Fn(&[u8]) -> Vec<u8>) -> bool {
+    let mut node: Vec<u8> = merkleleafhash(leaf, hash);
+    for step in path {
+        node = if step.left { merklenodehash(&step.sibling, &node, hash) } else { merklenodehash(&node, &step.sibling, hash) };
+    }
+    node == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::containerofexportedbets;
+    use crate::settlement::ExportedBet;
+
+    // FNV-1a, not a cryptographic hash, but deterministic and sensitive to every byte, which is all the tree needs here
+    fn hash(bytes: &[u8]) -> Vec<u8> {
+        let mut value: u64 = 0xcbf29ce484222325;
+        for byte in bytes {
+            value ^= *byte as u64;
+            value = value.wrapping_mul(0x100000001b3);
+        }
+        value.to_be_bytes().to_vec()
+    }
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|index| canonicalleaf(&index.to_string(), "yes", index as i64 + 1)).collect()
+    }
+
+    #[test]
+    fn tree_without_leaves_has_an_empty_root() {
+        assert!(merkleroot(&[], &hash).is_empty());
+    }
+
+    #[test]
+    fn root_of_a_single_leaf_is_its_leaf_hash() {
+        let leaves: Vec<Vec<u8>> = leaves(1);
+        assert_eq!(merkleroot(&leaves, &hash), merkleleafhash(&leaves[0], &hash));
+        assert!(merkleproof(&leaves, 0, &hash).is_empty());
+    }
+
+    #[test]
+    fn last_node_without_sibling_is_carried_up() {
+        let leaves: Vec<Vec<u8>> = leaves(3);
+        let hashes: Vec<Vec<u8>> = leaves.iter().map(|leaf| merkleleafhash(leaf, &hash)).collect();
+        let expected: Vec<u8> = merklenodehash(&merklenodehash(&hashes[0], &hashes[1], &hash), &hashes[2], &hash);
+        assert_eq!(merkleroot(&leaves, &hash), expected);
+        assert_eq!(merkleproof(&leaves, 2, &hash).len(), 1);
+    }
+
+    #[test]
+    fn proof_of_every_leaf_verifies() {
+        for count in 1..10 {
+            let leaves: Vec<Vec<u8>> = leaves(count);
+            let root: Vec<u8> = merkleroot(&leaves, &hash);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let path: Vec<ProofStep> = merkleproof(&leaves, index, &hash);
+                assert!(verifyinclusion(leaf, &path, &root, &hash), "leaf {} of {}", index, count);
+            }
+        }
+    }
+
+    #[test]
+    fn proof_of_a_tampered_amount_is_rejected() {
+        let bets: Vec<ExportedBet> = ["a", "b", "c", "d", "e"].iter()
+            .map(|address| ExportedBet {
+                address: address.to_string(),
+                betamount: 100,
+                betisforvalue: "yes".to_string(),
+                placedat: 0,
+                attachedamount: 100,
+                requestid: "".to_string(),
+            })
+            .collect();
+        let leaves: Vec<Vec<u8>> = merkleleaves(&containerofexportedbets(bets));
+        let root: Vec<u8> = merkleroot(&leaves, &hash);
+        let path: Vec<ProofStep> = merkleproof(&leaves, 1, &hash);
+        assert_eq!(leaves[1], canonicalleaf("b", "yes", 100));
+        assert!(verifyinclusion(&canonicalleaf("b", "yes", 100), &path, &root, &hash));
+        assert!(!verifyinclusion(&canonicalleaf("b", "yes", 1000), &path, &root, &hash));
+        assert!(!verifyinclusion(&canonicalleaf("b", "no", 100), &path, &root, &hash));
+        assert!(!verifyinclusion(&canonicalleaf("c", "yes", 100), &path, &root, &hash));
+    }
+
+    #[test]
+    fn leaf_cannot_pose_as_a_node() {
+        let leaves: Vec<Vec<u8>> = leaves(2);
+        let root: Vec<u8> = merkleroot(&leaves, &hash);
+        let mut forged: Vec<u8> = merkleleafhash(&leaves[0], &hash);
+        forged.extend(merkleleafhash(&leaves[1], &hash));
+        assert!(!verifyinclusion(&forged, &[], &root, &hash));
+    }
+}
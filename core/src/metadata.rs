@@ -0,0 +1,136 @@
+// Bytes of the metadata of a market, without access to the contract's context
+//
+// Every metadata value of a market has its own limit, but together they could still bloat the configuration every call reads.
+// The contract counts the bytes of all metadata values of a market in configbytes, updated by every write of a value, and fails a write
+// that would exceed the maxconfigbytes of the market with ERR_CONFIG_TOO_LARGE. A market takes at most the ceiling of the platform
+// defaults, which only the contract creator raises by setdefaults, and may lower its own limit by MAXCONFIGBYTES. A clone counts the
+// values it takes from its template, and archiving a market deletes its values together with the counter.
+
+pub const ERR_CONFIG_TOO_LARGE: &str = "ERR_CONFIG_TOO_LARGE";
+
+// the keys of the metadata values of a market, and those a clone takes from its template
+pub const CONFIGVALUES: [&str; 6] = ["alertrulesjson", "externalref", "memopubkey", "outcomes", "refusalnotice", "termshash"];
+pub const CLONEDCONFIGVALUES: [&str; 5] = ["alertrulesjson", "memopubkey", "outcomes", "refusalnotice", "termshash"];
+
+// The bytes of the values of the keys, as read by value.
+pub fn configbytesof(keys: &[&str], value: impl Fn(&str) -> String) -> i64 {
+    keys.iter().map(|key| value(key).len() as i64).sum()
+}
+
+// The configbytes after replacing the value previous by value, or Err with ERR_CONFIG_TOO_LARGE if they would exceed maxconfigbytes,
+// where 0 is no limit, for markets initialized before it was recorded.
+pub fn configbytesafter(configbytes: i64, previous: &str, value: &str, maxconfigbytes: i64) -> Result<i64, String> {
+    let newconfigbytes: i64 = configbytes - previous.len() as i64 + value.len() as i64;
+    if maxconfigbytes > 0 && newconfigbytes > maxconfigbytes {
+        return Err(ERR_CONFIG_TOO_LARGE.to_string() + ": the metadata of the market would use " + &newconfigbytes.to_string() + " bytes, currently "
+            + &configbytes.to_string() + " of " + &maxconfigbytes.to_string() + " bytes are used");
+    }
+    Ok(newconfigbytes)
+}
+
+// The maxconfigbytes of a market initialized with MAXCONFIGBYTES requested, 0 for the ceiling of the platform defaults.
+pub fn marketmaxconfigbytes(requested: i64, ceiling: i64) -> Result<i64, String> {
+    if requested < 0 {
+        return Err("MAXCONFIGBYTES must not be negative".to_string());
+    }
+    if requested > ceiling {
+        return Err("MAXCONFIGBYTES must not exceed the ceiling of ".to_string() + &ceiling.to_string()
+            + " bytes of the platform defaults, which only the contract creator raises by setdefaults");
+    }
+    Ok(if requested == 0 { ceiling } else { requested })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defaults::{configceiling, PlatformDefaults, DEFAULTMAXCONFIGBYTES};
+    use std::collections::BTreeMap;
+
+    // the part of a market keeping metadata: its values, the counter and the limit
+    #[derive(Default)]
+    struct Market {
+        values: BTreeMap<String, String>,
+        configbytes: i64,
+        maxconfigbytes: i64,
+    }
+
+    impl Market {
+        fn value(&self, key: &str) -> String {
+            self.values.get(key).cloned().unwrap_or_default()
+        }
+
+        // setconfigvalue, all or nothing like a call of the contract
+        fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+            self.configbytes = configbytesafter(self.configbytes, &self.value(key), value, self.maxconfigbytes)?;
+            self.values.insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn recounted(&self) -> i64 {
+            configbytesof(&CONFIGVALUES, |key| self.value(key))
+        }
+
+        fn cloned(&self) -> Market {
+            let values: BTreeMap<String, String> = CLONEDCONFIGVALUES.iter().map(|key| (key.to_string(), self.value(key))).collect();
+            let configbytes: i64 = configbytesof(&CLONEDCONFIGVALUES, |key| self.value(key));
+            Market { values, configbytes, maxconfigbytes: self.maxconfigbytes }
+        }
+    }
+
+    #[test]
+    fn the_counter_stays_accurate_across_adds_amendments_clones_and_archiving() {
+        let mut market: Market = Market { maxconfigbytes: marketmaxconfigbytes(0, configceiling(0)).unwrap(), ..Market::default() };
+        assert_eq!(market.maxconfigbytes, DEFAULTMAXCONFIGBYTES);
+        market.set("externalref", "sportsfeed:12345").unwrap();
+        market.set("termshash", "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi").unwrap();
+        market.set("outcomes", "yes,no").unwrap();
+        assert_eq!(market.configbytes, 16 + 43 + 6);
+        assert_eq!(market.configbytes, market.recounted());
+
+        // amendments replace the bytes of the previous value, growing or shrinking
+        market.set("refusalnotice", &"a".repeat(280)).unwrap();
+        market.set("refusalnotice", "abandoned").unwrap();
+        market.set("outcomes", "home,draw,away").unwrap();
+        market.set("externalref", "").unwrap();
+        assert_eq!(market.configbytes, 43 + 9 + 14);
+        assert_eq!(market.configbytes, market.recounted());
+
+        // a clone counts what it takes from its template, its external reference is its own
+        let mut clone: Market = market.cloned();
+        assert_eq!(clone.configbytes, clone.recounted());
+        clone.set("externalref", "sportsfeed:12346").unwrap();
+        assert_eq!(clone.configbytes, market.configbytes + 16);
+
+        // archiving deletes the values together with the counter, a tombstone keeps neither
+        market = Market::default();
+        assert_eq!(market.configbytes, 0);
+        assert_eq!(market.configbytes, market.recounted());
+    }
+
+    #[test]
+    fn a_value_exceeding_the_limit_is_refused_and_leaves_the_counter() {
+        let mut market: Market = Market { maxconfigbytes: marketmaxconfigbytes(100, DEFAULTMAXCONFIGBYTES).unwrap(), ..Market::default() };
+        market.set("termshash", &"h".repeat(60)).unwrap();
+        let error: String = market.set("refusalnotice", &"a".repeat(41)).unwrap_err();
+        assert_eq!(error, "ERR_CONFIG_TOO_LARGE: the metadata of the market would use 101 bytes, currently 60 of 100 bytes are used");
+        assert_eq!(market.configbytes, 60);
+        assert_eq!(market.value("refusalnotice"), "");
+        market.set("refusalnotice", &"a".repeat(40)).unwrap();
+        // an amendment shrinking a value fits even at the limit
+        market.set("termshash", &"h".repeat(43)).unwrap();
+        assert_eq!(market.configbytes, market.recounted());
+        // markets initialized before the limit was recorded are not limited
+        assert_eq!(configbytesafter(0, "", &"a".repeat(100000), 0), Ok(100000));
+    }
+
+    #[test]
+    fn only_setdefaults_raises_the_ceiling() {
+        let raised: PlatformDefaults = PlatformDefaults { maxconfigbytes: 16384, ..PlatformDefaults::default() };
+        assert_eq!(marketmaxconfigbytes(0, configceiling(raised.maxconfigbytes)), Ok(16384));
+        assert_eq!(marketmaxconfigbytes(12000, configceiling(raised.maxconfigbytes)), Ok(12000));
+        // a market may lower its limit, but not raise it beyond the ceiling, whoever initializes it
+        assert_eq!(marketmaxconfigbytes(4096, configceiling(0)), Ok(4096));
+        assert!(marketmaxconfigbytes(16384, configceiling(0)).unwrap_err().contains("ceiling of 8192 bytes"));
+        assert!(marketmaxconfigbytes(-1, configceiling(0)).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+// Rules of moving a market to a redeployed contract, without access to the contract's context
+//
+// exportformigration emits the json of the migration state in chunks, which importmarket collects in any order and over as many
+// calls as needed. The chunks only carry the bets: their IOTA move with the last call of importmarket and have to back every bet.
+
+// number of bytes of the migration payload per chunk
+pub const MIGRATIONCHUNK: usize = 1024;
+
+// Split the payload of an export into chunks of MIGRATIONCHUNK bytes, of which only the last one can be shorter.
+pub fn migrationchunks(payload: &[u8]) -> Vec<&[u8]> {
+    payload.chunks(MIGRATIONCHUNK).collect()
+}
+
+// Join the payload of an export from its count chunks, given by their index in the order they were received, or None as long as
+// one of them is missing.
+pub fn joinmigrationchunks(count: i64, chunk: impl Fn(i64) -> Option<Vec<u8>>) -> Option<Vec<u8>> {
+    let mut payload: Vec<u8> = Vec::new();
+    for index in 0..count {
+        payload.extend(chunk(index)?);
+    }
+    Some(payload)
+}
+
+// Check the call completing an import: the imported bets have to add up to the exported pool, and the IOTA sent with the call
+// have to cover it. Returns the IOTA to refund to the caller beyond the pool.
+pub fn importrefund(totalbetamount: i64, pool: i64, incoming: i64) -> Result<i64, String> {
+    if totalbetamount != pool {
+        return Err("the bets of the export do not add up to its pool".to_string());
+    }
+    if incoming < pool {
+        return Err("the IOTA of the exported pool must be sent with the last chunk: ".to_string() + &pool.to_string());
+    }
+    Ok(incoming - pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn the_chunks_of_an_export_join_to_its_payload_in_any_order() {
+        let payload: Vec<u8> = (0..2500).map(|i| (i % 251) as u8).collect();
+        let chunks: Vec<&[u8]> = migrationchunks(&payload);
+        assert_eq!(chunks.iter().map(|chunk| chunk.len()).collect::<Vec<usize>>(), vec![1024, 1024, 452]);
+
+        // received as the last chunk first
+        let mut received: HashMap<i64, Vec<u8>> = HashMap::new();
+        for index in [2, 0, 1].iter() {
+            assert_eq!(joinmigrationchunks(3, |i| received.get(&i).cloned()), None);
+            received.insert(*index, chunks[*index as usize].to_vec());
+        }
+        assert_eq!(joinmigrationchunks(3, |i| received.get(&i).cloned()), Some(payload));
+    }
+
+    #[test]
+    fn a_payload_of_a_chunk_exactly_has_one_chunk() {
+        assert_eq!(migrationchunks(&[7u8; MIGRATIONCHUNK]).len(), 1);
+        assert_eq!(migrationchunks(&[7u8; MIGRATIONCHUNK + 1]).len(), 2);
+    }
+
+    #[test]
+    fn the_pool_has_to_back_the_bets_and_be_sent_with_the_import() {
+        assert_eq!(importrefund(1000, 1000, 1000), Ok(0));
+        assert_eq!(importrefund(1000, 1000, 1250), Ok(250));
+        assert!(importrefund(1000, 1000, 999).unwrap_err().ends_with(": 1000"));
+        // a pool larger or smaller than the bets is rejected, whatever is sent
+        assert!(importrefund(1000, 1200, 1200).is_err());
+        assert!(importrefund(1200, 1000, 1200).is_err());
+    }
+}
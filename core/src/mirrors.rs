@@ -0,0 +1,177 @@
+// Mirrors of the market data registered by indexers, without access to the contract's context
+//
+// Anybody can register the URL of a mirror by registermirror with a deposit of MIRRORDEPOSIT IOTA against spam, which is kept in the
+// deposits bucket. A mirror takes the first free of the MAXMIRRORS slots of the mirrors array, which the contract stores as json.
+// removemirror of the contract owner empties the slot again and returns the deposit to the registrant, or moves it to the fees
+// if the mirror is slashed as spam, or if the address of the registrant does not decode, so that the deposit is not stuck.
+
+use serde::{Deserialize, Serialize};
+
+// deposit in IOTA for registering a mirror, the maximum number of mirrors and the maximum length in bytes of their URL
+pub const MIRRORDEPOSIT: i64 = 100;
+pub const MAXMIRRORS: i32 = 16;
+pub const MAXMIRRORURL: usize = 256;
+
+// A mirror of the market data registered by an indexer, in one of the MAXMIRRORS slots of the mirrors array, which is empty once removed.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Mirror {
+    pub url: String,
+    // encoded wallet address of the registrant, to return the deposit to
+    pub registrant: String,
+    pub deposit: i64,
+    pub registeredat: i64,
+    // flag of the codec the registrant was encoded with
+    pub addressencoding: String,
+}
+
+// what removemirror does with the deposit of the removed mirror
+#[derive(Debug, PartialEq)]
+pub enum MirrorDeposit {
+    // the deposit is transferred to the registrant
+    Return,
+    // the deposit moves from the deposits to the fees bucket
+    Slash,
+}
+
+// Check the URL of a mirror and the IOTA sent to register it by registermirror.
+pub fn validatemirror(url: &str, incoming: i64) -> Result<(), String> {
+    if url.is_empty() {
+        return Err("URL parameter not found".to_string());
+    }
+    if url.len() > MAXMIRRORURL {
+        return Err("URL must not be longer than ".to_string() + &MAXMIRRORURL.to_string() + " bytes");
+    }
+    if incoming < MIRRORDEPOSIT {
+        return Err("a deposit of ".to_string() + &MIRRORDEPOSIT.to_string() + " IOTA has to be sent to register a mirror");
+    }
+    Ok(())
+}
+
+// The MIRRORID of a new mirror given the stored slots of the mirrors array, the first empty one or the next,
+// or Err if all MAXMIRRORS slots are taken.
+pub fn mirrorslot(slots: &[String]) -> Result<i32, String> {
+    let mirrorid: i32 = slots.iter().position(|slot| slot.is_empty()).unwrap_or(slots.len()) as i32;
+    if mirrorid >= MAXMIRRORS {
+        return Err("no more than ".to_string() + &MAXMIRRORS.to_string() + " mirrors can be registered");
+    }
+    Ok(mirrorid)
+}
+
+// the registered mirror in the slot mirrorid of the stored slots, or Err if there is none
+pub fn registeredmirror(slots: &[String], mirrorid: i64) -> Result<Mirror, String> {
+    if mirrorid < 0 || mirrorid >= slots.len() as i64 || slots[mirrorid as usize].is_empty() {
+        return Err("mirror not found".to_string());
+    }
+    serde_json::from_str(&slots[mirrorid as usize]).map_err(|error| "failed to get mirror: ".to_string() + &error.to_string())
+}
+
+// What happens to the deposit of a removed mirror, slashed by SLASH "true", given whether the address of the registrant decodes.
+pub fn mirrordeposit(slash: bool, registrantdecodes: bool) -> MirrorDeposit {
+    if slash || !registrantdecodes { MirrorDeposit::Slash } else { MirrorDeposit::Return }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{BUCKET_DEPOSITS, BUCKET_FEES, BUCKET_STRAYS};
+    use std::collections::BTreeMap;
+
+    const NOW: i64 = 1622563200;
+
+    // the part of the contract keeping mirrors: the mirrors array, the buckets and the wallets paid
+    #[derive(Default)]
+    struct Contract {
+        mirrors: Vec<String>,
+        buckets: BTreeMap<&'static str, i64>,
+        wallets: BTreeMap<String, i64>,
+    }
+
+    impl Contract {
+        // registermirror, the IOTA sent arriving in the strays bucket, returning the MIRRORID
+        fn register(&mut self, registrant: &str, url: &str, incoming: i64) -> Result<i32, String> {
+            validatemirror(url, incoming)?;
+            let mirrorid: i32 = mirrorslot(&self.mirrors)?;
+            let mirror: Mirror = Mirror {
+                url: url.to_string(),
+                registrant: registrant.to_string(),
+                deposit: MIRRORDEPOSIT,
+                registeredat: NOW,
+                addressencoding: "".to_string(),
+            };
+            if mirrorid as usize == self.mirrors.len() {
+                self.mirrors.push("".to_string());
+            }
+            self.mirrors[mirrorid as usize] = serde_json::to_string(&mirror).unwrap();
+            *self.buckets.entry(BUCKET_DEPOSITS).or_insert(0) += MIRRORDEPOSIT;
+            // the excess is refunded
+            *self.wallets.entry(registrant.to_string()).or_insert(0) += incoming - MIRRORDEPOSIT;
+            Ok(mirrorid)
+        }
+
+        fn remove(&mut self, mirrorid: i64, slash: bool) -> Result<MirrorDeposit, String> {
+            let mirror: Mirror = registeredmirror(&self.mirrors, mirrorid)?;
+            self.mirrors[mirrorid as usize] = "".to_string();
+            let deposit: MirrorDeposit = mirrordeposit(slash, mirror.registrant.starts_with("account"));
+            *self.buckets.get_mut(BUCKET_DEPOSITS).unwrap() -= mirror.deposit;
+            match deposit {
+                MirrorDeposit::Return => *self.wallets.entry(mirror.registrant).or_insert(0) += mirror.deposit,
+                MirrorDeposit::Slash => *self.buckets.entry(BUCKET_FEES).or_insert(0) += mirror.deposit,
+            }
+            Ok(deposit)
+        }
+    }
+
+    #[test]
+    fn a_mirror_is_registered_and_removed_with_its_deposit_returned() {
+        let mut contract: Contract = Contract::default();
+        assert_eq!(contract.register("account1", "https://mirror1.example", 150), Ok(0));
+        assert_eq!(contract.buckets[BUCKET_DEPOSITS], MIRRORDEPOSIT);
+        assert_eq!(contract.wallets["account1"], 50);
+        assert_eq!(registeredmirror(&contract.mirrors, 0).unwrap().url, "https://mirror1.example");
+        assert_eq!(contract.register("account2", "https://mirror2.example", 100), Ok(1));
+
+        assert_eq!(contract.remove(0, false), Ok(MirrorDeposit::Return));
+        assert_eq!(contract.wallets["account1"], 150);
+        assert_eq!(contract.buckets[BUCKET_DEPOSITS], MIRRORDEPOSIT);
+        assert!(!contract.buckets.contains_key(BUCKET_FEES));
+        // the removed mirror is gone, and cannot be removed again
+        assert_eq!(contract.remove(0, false), Err("mirror not found".to_string()));
+        assert_eq!(contract.remove(7, false), Err("mirror not found".to_string()));
+        assert_eq!(contract.remove(-1, false), Err("mirror not found".to_string()));
+    }
+
+    #[test]
+    fn a_slashed_deposit_moves_to_the_fees() {
+        let mut contract: Contract = Contract::default();
+        contract.register("account1", "https://spam.example", 100).unwrap();
+        contract.register("garbled", "https://mirror.example", 100).unwrap();
+        assert_eq!(contract.remove(0, true), Ok(MirrorDeposit::Slash));
+        assert_eq!(contract.wallets["account1"], 0);
+        // the deposit of a registrant whose address does not decode is slashed instead of being stuck
+        assert_eq!(contract.remove(1, false), Ok(MirrorDeposit::Slash));
+        assert_eq!(contract.buckets[BUCKET_FEES], 2 * MIRRORDEPOSIT);
+        assert_eq!(contract.buckets[BUCKET_DEPOSITS], 0);
+        assert!(!contract.buckets.contains_key(BUCKET_STRAYS));
+    }
+
+    #[test]
+    fn no_more_than_16_mirrors_are_registered_and_a_freed_slot_is_reused() {
+        let mut contract: Contract = Contract::default();
+        for i in 0..MAXMIRRORS {
+            assert_eq!(contract.register("account1", &("https://mirror".to_string() + &i.to_string() + ".example"), 100), Ok(i));
+        }
+        assert!(contract.register("account2", "https://mirror16.example", 100).unwrap_err().contains("no more than 16 mirrors"));
+        assert!(!contract.wallets.contains_key("account2"));
+        contract.remove(5, false).unwrap();
+        assert_eq!(contract.register("account2", "https://mirror16.example", 100), Ok(5));
+        assert_eq!(contract.mirrors.len(), MAXMIRRORS as usize);
+    }
+
+    #[test]
+    fn a_mirror_needs_a_url_of_up_to_256_bytes_and_the_deposit() {
+        assert_eq!(validatemirror("", 100), Err("URL parameter not found".to_string()));
+        assert!(validatemirror(&"a".repeat(MAXMIRRORURL), 100).is_ok());
+        assert!(validatemirror(&"a".repeat(MAXMIRRORURL + 1), 100).unwrap_err().contains("256 bytes"));
+        assert!(validatemirror("https://mirror.example", MIRRORDEPOSIT - 1).unwrap_err().contains("deposit of 100 IOTA"));
+    }
+}
@@ -0,0 +1,64 @@
+// Visibility of the odds of a market, without access to the contract's context
+//
+// A market initialized with ODDSREVEALPOOL hides its odds until its pool reaches that amount, so that the first bets do not show
+// wildly swinging odds. The state keeps the amounts per value all the time, only the views withhold them.
+
+use std::collections::BTreeMap;
+
+// Whether a bet bringing the pool of the market to pool reveals its odds, given the flag oddsrevealed of the market:
+// "false" while the odds are hidden, "true" once revealed, and empty for markets initialized before ODDSREVEALPOOL existed,
+// whose odds are never hidden. The odds are revealed only once, by the first bet that reaches the threshold.
+pub fn revealsodds(oddsrevealed: &str, pool: i64, oddsrevealpool: i64) -> bool {
+    oddsrevealed == "false" && pool >= oddsrevealpool
+}
+
+// The pools per value returned by the views together with their "total", of which only the total is returned while the odds are hidden.
+// Without bets, there are no pools and no total.
+pub fn visiblepools(mut pools: BTreeMap<String, i64>, hidden: bool) -> BTreeMap<String, i64> {
+    if pools.is_empty() {
+        return pools;
+    }
+    let total: i64 = pools.values().sum();
+    if hidden {
+        pools.clear();
+    }
+    pools.insert("total".to_string(), total);
+    pools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pools(entries: &[(&str, i64)]) -> BTreeMap<String, i64> {
+        entries.iter().map(|(betvalue, amount)| (betvalue.to_string(), *amount)).collect()
+    }
+
+    #[test]
+    fn the_odds_are_revealed_once_by_the_bet_reaching_the_threshold() {
+        let mut oddsrevealed: String = "false".to_string();
+        let mut reveals: Vec<i64> = Vec::new();
+        for pool in [400, 999, 1000, 1500, 2000].iter() {
+            if revealsodds(&oddsrevealed, *pool, 1000) {
+                oddsrevealed = "true".to_string();
+                reveals.push(*pool);
+            }
+        }
+        assert_eq!(reveals, vec![1000]);
+    }
+
+    #[test]
+    fn the_odds_of_markets_without_the_flag_are_never_hidden_nor_revealed() {
+        assert!(!revealsodds("", 0, 0));
+        assert!(!revealsodds("", 5000, 1000));
+        assert!(!revealsodds("true", 5000, 1000));
+    }
+
+    #[test]
+    fn hidden_odds_only_show_the_total() {
+        let bets: BTreeMap<String, i64> = pools(&[("no", 700), ("yes", 300)]);
+        assert_eq!(visiblepools(bets.clone(), true), pools(&[("total", 1000)]));
+        assert_eq!(visiblepools(bets, false), pools(&[("no", 700), ("total", 1000), ("yes", 300)]));
+        assert!(visiblepools(BTreeMap::new(), true).is_empty());
+    }
+}
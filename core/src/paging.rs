@@ -0,0 +1,81 @@
+// Paging through the indexes the contract appends to, without access to the contract's context
+//
+// An index like the refund index grows with every entry, so a single call only scans a bounded number of its entries,
+// and its oldest entries are pruned into totals once too many are kept. Pruned entries are cleared, and the index starts at its head.
+
+// Scan the entries of an index of the given length from start, calling visit with the position of every scanned entry, which returns
+// whether the entry is part of the page. The scan stops once the page has limit entries or maxscan entries were scanned.
+// Returns the position to continue at, or -1 once all entries were scanned.
+pub fn scanpage(start: i64, length: i64, limit: i64, maxscan: i64, mut visit: impl FnMut(i64) -> bool) -> i64 {
+    let mut found: i64 = 0;
+    for position in start..length {
+        if found == limit || position - start == maxscan {
+            return position;
+        }
+        if visit(position) {
+            found += 1;
+        }
+    }
+    -1
+}
+
+// The position an index whose entries from head to length are kept has to be pruned until, so that at most maxkept entries are kept.
+pub fn keptfrom(head: i64, length: i64, maxkept: i64) -> i64 {
+    head.max(length - maxkept)
+}
+
+// The position a call pruning an index until cursor prunes it until, at most maxscan entries after its head.
+pub fn pruneuntil(head: i64, cursor: i64, maxscan: i64) -> i64 {
+    cursor.min(head.saturating_add(maxscan)).max(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the positions of the page of the even entries
+    fn evens(entries: &[i64], start: i64, limit: i64, maxscan: i64) -> (Vec<i64>, i64) {
+        let mut page: Vec<i64> = Vec::new();
+        let next: i64 = scanpage(start, entries.len() as i64, limit, maxscan, |position| {
+            let even: bool = entries[position as usize] % 2 == 0;
+            if even {
+                page.push(position);
+            }
+            even
+        });
+        (page, next)
+    }
+
+    #[test]
+    fn a_page_ends_at_its_limit_and_continues_after_it() {
+        let entries: [i64; 6] = [2, 4, 5, 6, 8, 10];
+        assert_eq!(evens(&entries, 0, 2, 100), (vec![0, 1], 2));
+        assert_eq!(evens(&entries, 2, 2, 100), (vec![3, 4], 5));
+        assert_eq!(evens(&entries, 5, 2, 100), (vec![5], -1));
+    }
+
+    #[test]
+    fn a_page_reaching_the_end_with_its_limit_is_the_last() {
+        assert_eq!(evens(&[2, 4], 0, 2, 100), (vec![0, 1], -1));
+        assert_eq!(evens(&[], 0, 2, 100), (vec![], -1));
+        assert_eq!(evens(&[2, 4], 2, 2, 100), (vec![], -1));
+    }
+
+    #[test]
+    fn a_scan_is_bounded_even_if_nothing_matches() {
+        let entries: [i64; 5] = [1, 3, 5, 7, 9];
+        assert_eq!(evens(&entries, 0, 2, 3), (vec![], 3));
+        assert_eq!(evens(&entries, 3, 2, 3), (vec![], -1));
+    }
+
+    #[test]
+    fn pruning_keeps_the_latest_entries_and_is_bounded_per_call() {
+        assert_eq!(keptfrom(0, 10, 10), 0);
+        assert_eq!(keptfrom(0, 11, 10), 1);
+        assert_eq!(keptfrom(5, 11, 10), 5);
+        assert_eq!(pruneuntil(0, 5000, 1000), 1000);
+        assert_eq!(pruneuntil(1000, 1500, 1000), 1500);
+        // a cursor before the head prunes nothing
+        assert_eq!(pruneuntil(1000, 10, 1000), 1000);
+    }
+}
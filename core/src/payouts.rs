@@ -0,0 +1,96 @@
+// Progress of the payouts of a settled market, without access to the contract's context
+//
+// The entitlements of a market are paid in the order they were recorded, continuing after the last processed one at the payout cursor,
+// in batches by the payout function and by the calls to the contract that piggyback payouts. Failed payouts due for a retry are
+// attempted before new entitlements, so that every entitlement is processed once, whoever drives the payouts.
+
+// number of attempts after which a failed payout is flagged for manual intervention, and the backoff in seconds before
+// the first retry, which doubles with every further attempt up to the maximum
+pub const MAXPAYOUTATTEMPTS: i64 = 5;
+pub const PAYOUTRETRYBACKOFF: i64 = 60;
+pub const PAYOUTRETRYMAXBACKOFF: i64 = 3600;
+
+// End of the next batch of at most maxcount entitlements, of entitlementscount, which starts at the payout cursor, after retried
+// failed payouts already took their part of maxcount.
+pub fn payoutbatchend(payoutcursor: i64, entitlementscount: i64, maxcount: i64, retried: i64) -> i64 {
+    entitlementscount.min(payoutcursor + maxcount - retried)
+}
+
+// Whether all entitlements of a market were processed and none of its failed payouts is still to be attempted again,
+// so that it leaves the queue of piggybacked payouts.
+pub fn payoutsdone(payoutcursor: i64, entitlementscount: i64, pendingretries: bool) -> bool {
+    payoutcursor >= entitlementscount && !pendingretries
+}
+
+// seconds to wait after the given number of failed attempts
+pub fn payoutbackoff(attempts: i64) -> i64 {
+    PAYOUTRETRYBACKOFF.saturating_mul(1i64 << (attempts - 1).clamp(0, 32)).min(PAYOUTRETRYMAXBACKOFF)
+}
+
+// Time from which a payout that failed for the given number of attempts at currtime is attempted again,
+// or None if it is flagged for manual intervention, after MAXPAYOUTATTEMPTS attempts or at once if the failure is permanent.
+pub fn nextpayoutattempt(attempts: i64, permanent: bool, currtime: i64) -> Option<i64> {
+    if permanent || attempts >= MAXPAYOUTATTEMPTS {
+        return None;
+    }
+    Some(currtime.saturating_add(payoutbackoff(attempts)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn piggybacked_batches_pay_every_entitlement_once() {
+        // 23 entitlements, of which unrelated calls pay up to 5 each
+        let mut paid: Vec<i64> = vec![0; 23];
+        let mut payoutcursor: i64 = 0;
+        let mut calls: i64 = 0;
+        while !payoutsdone(payoutcursor, 23, false) {
+            let end: i64 = payoutbatchend(payoutcursor, 23, 5, 0);
+            assert!(end - payoutcursor <= 5);
+            for index in payoutcursor..end {
+                paid[index as usize] += 1;
+            }
+            payoutcursor = end;
+            calls += 1;
+        }
+        assert_eq!(calls, 5);
+        assert!(paid.iter().all(|count| *count == 1));
+    }
+
+    #[test]
+    fn retried_payouts_take_their_part_of_the_batch() {
+        assert_eq!(payoutbatchend(10, 100, 5, 2), 13);
+        assert_eq!(payoutbatchend(10, 100, 5, 5), 10);
+        assert_eq!(payoutbatchend(98, 100, 5, 0), 100);
+    }
+
+    #[test]
+    fn the_backoff_doubles_up_to_its_maximum() {
+        let backoffs: Vec<i64> = (1..=8).map(payoutbackoff).collect();
+        assert_eq!(backoffs, vec![60, 120, 240, 480, 960, 1920, 3600, 3600]);
+        assert_eq!(payoutbackoff(1000), PAYOUTRETRYMAXBACKOFF);
+    }
+
+    #[test]
+    fn two_transient_failures_are_retried_after_their_backoff() {
+        assert_eq!(nextpayoutattempt(1, false, 1000), Some(1060));
+        assert_eq!(nextpayoutattempt(2, false, 1060), Some(1180));
+    }
+
+    #[test]
+    fn a_payout_is_flagged_for_manual_intervention_after_the_last_attempt_or_a_permanent_failure() {
+        assert_eq!(nextpayoutattempt(MAXPAYOUTATTEMPTS - 1, false, 1000), Some(1000 + 480));
+        assert_eq!(nextpayoutattempt(MAXPAYOUTATTEMPTS, false, 1000), None);
+        assert_eq!(nextpayoutattempt(1, true, 1000), None);
+    }
+
+    #[test]
+    fn a_market_with_pending_retries_is_not_done() {
+        assert!(payoutsdone(100, 100, false));
+        assert!(!payoutsdone(100, 100, true));
+        assert!(!payoutsdone(99, 100, false));
+        assert!(payoutsdone(0, 0, false));
+    }
+}
@@ -0,0 +1,158 @@
+// Provenance of the settings of the effective configuration of a market, without access to the contract's context
+//
+// initmarket returns the effective configuration of the market as CONFIG, the value of every setting and where it came from: "param"
+// if it was given as a parameter of initmarket, "default" if the parameter was omitted or empty, "builtin" for settings the contract fixes,
+// and "platform" for the settings taken from the platform defaults of setdefaults. INITMARKETSETTINGS lists the settings of the
+// parameters, by their key in the configuration. A market created by a schedule takes the configuration of its template, with its own
+// market id and the platform defaults at the time it is created.
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+pub const PROVENANCE_PARAM: &str = "param";
+pub const PROVENANCE_DEFAULT: &str = "default";
+pub const PROVENANCE_BUILTIN: &str = "builtin";
+pub const PROVENANCE_PLATFORM: &str = "platform";
+
+// the settings of the parameters of initmarket as key and parameter, in the order initmarket reads them
+pub const INITMARKETSETTINGS: [(&str, &str); 48] = [
+    ("strictmode", "STRICT"), ("maxsharebps", "MAXSHAREBPS"), ("maxshareexemptpool", "MAXSHAREEXEMPTPOOL"), ("maxconfigbytes", "MAXCONFIGBYTES"),
+    ("externalref", "EXTERNALREF"), ("betgranularity", "BETGRANULARITY"), ("minbet", "MINBET"), ("shortfallpolicy", "SHORTFALLPOLICY"),
+    ("timelock", "TIMELOCK"), ("heartbeatwindow", "HEARTBEATWINDOW"), ("resolutiongrace", "RESOLUTIONGRACE"), ("extensionwindow", "EXTENSIONWINDOW"),
+    ("maxextensions", "MAXEXTENSIONS"), ("maxtotalextension", "MAXTOTALEXTENSION"), ("announcedelay", "ANNOUNCEDELAY"), ("tokencolor", "TOKENCOLOR"),
+    ("sweepdest", "SWEEPDEST"), ("disputebond", "DISPUTEBOND"), ("disputeforfeit", "DISPUTEFORFEIT"), ("oddsrevealpool", "ODDSREVEALPOOL"),
+    ("oracle", "ORACLE"), ("oracleonly", "ORACLEONLY"), ("piggybackpayouts", "PIGGYBACKPAYOUTS"), ("feepercent", "FEEPERCENT"),
+    ("payoutmode", "PAYOUTMODE"), ("idempotencykeys", "IDEMPOTENCYKEYS"), ("idempotencyminage", "IDEMPOTENCYMINAGE"), ("activitywindow", "ACTIVITYWINDOW"),
+    ("activitymedium", "ACTIVITYMEDIUM"), ("activityhigh", "ACTIVITYHIGH"), ("termshash", "TERMSHASH"), ("memopubkey", "MEMOPUBKEY"),
+    ("outcomes", "OUTCOMES"), ("feeds", "FEEDS"), ("boundaries", "BOUNDARIES"), ("feedquorum", "FEEDQUORUM"),
+    ("feeddeviationbps", "FEEDDEVIATIONBPS"), ("betenddatetime", "BETENDUTC"), ("votewindow", "VOTEWINDOW"), ("tiebreak", "TIEBREAK"),
+    ("commitreveal", "COMMITREVEAL"), ("revealwindow", "REVEALWINDOW"), ("maxcommits", "MAXCOMMITS"), ("maxcommitescrow", "MAXCOMMITESCROW"),
+    ("commitforfeit", "COMMITFORFEIT"), ("book", "BOOK"), ("overroundminbps", "OVERROUNDMINBPS"), ("overroundmaxbps", "OVERROUNDMAXBPS"),
+];
+
+// the settings of a market taken from the platform defaults when it is created
+pub const PLATFORMSETTINGS: [&str; 3] = ["configceiling", "creatorsharebps", "defaultsversion"];
+
+// A setting of the effective configuration of a market and where its value came from.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EffectiveSetting {
+    pub value: String,
+    pub provenance: String,
+}
+
+// the parameter of initmarket giving the setting key, None for a setting no parameter gives
+pub fn settingparam(key: &str) -> Option<&'static str> {
+    INITMARKETSETTINGS.iter().find(|(setting, _param)| *setting == key).map(|(_setting, param)| *param)
+}
+
+// the setting of a parameter with the effective value, whether the parameter was given or the default was taken
+pub fn paramsetting(value: String, given: bool) -> EffectiveSetting {
+    EffectiveSetting { value, provenance: (if given { PROVENANCE_PARAM } else { PROVENANCE_DEFAULT }).to_string() }
+}
+
+// the setting of the market id, given by MARKETID or the id of the request initializing the market
+pub fn marketidsetting(marketid: &str, given: bool) -> EffectiveSetting {
+    EffectiveSetting { value: marketid.to_string(), provenance: (if given { PROVENANCE_PARAM } else { PROVENANCE_BUILTIN }).to_string() }
+}
+
+pub fn builtinsetting(value: String) -> EffectiveSetting {
+    EffectiveSetting { value, provenance: PROVENANCE_BUILTIN.to_string() }
+}
+
+pub fn platformsetting(value: i64) -> EffectiveSetting {
+    EffectiveSetting { value: value.to_string(), provenance: PROVENANCE_PLATFORM.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    // the effective configuration initmarket builds for the parameters params, "" for an omitted one, under the platform defaults
+    // with creatorsharebps, where every omitted parameter takes "default" as its value
+    fn initmarket(params: &BTreeMap<&str, &str>, creatorsharebps: i64) -> BTreeMap<String, EffectiveSetting> {
+        let param = |name: &str| params.get(name).copied().unwrap_or("");
+        let mut configuration: BTreeMap<String, EffectiveSetting> = BTreeMap::new();
+        for (key, name) in INITMARKETSETTINGS.iter() {
+            let given: bool = !param(name).is_empty();
+            configuration.insert(key.to_string(), paramsetting((if given { param(name) } else { "default" }).to_string(), given));
+        }
+        let marketid: &str = if param("MARKETID").is_empty() { "request1" } else { param("MARKETID") };
+        configuration.insert("marketid".to_string(), marketidsetting(marketid, !param("MARKETID").is_empty()));
+        configuration.insert("creator".to_string(), builtinsetting("alice".to_string()));
+        for (key, value) in PLATFORMSETTINGS.iter().zip([8192, creatorsharebps, 1].iter()) {
+            configuration.insert(key.to_string(), platformsetting(*value));
+        }
+        configuration.insert("payoutbatch".to_string(), builtinsetting("50".to_string()));
+        // the configuration is stored and returned as json
+        serde_json::from_str(&serde_json::to_string(&configuration).unwrap()).unwrap()
+    }
+
+    fn provenances(configuration: &BTreeMap<String, EffectiveSetting>, provenance: &str) -> BTreeSet<String> {
+        configuration.iter().filter(|(_key, setting)| setting.provenance == provenance).map(|(key, _setting)| key.clone()).collect()
+    }
+
+    fn keys(keys: &[&str]) -> BTreeSet<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn every_setting_has_its_provenance_for_different_parameters() {
+        let subsets: [&[(&str, &str)]; 4] = [
+            &[],
+            &[("MARKETID", "worldcup"), ("BETENDUTC", "2021-06-01 18:00 UTC"), ("FEEPERCENT", "5")],
+            &[("OUTCOMES", "home,draw,away"), ("TERMSHASH", "4vJ9JU1b"), ("MAXCONFIGBYTES", "4096"), ("STRICT", "true")],
+            // a parameter given as "" counts as omitted
+            &[("FEEPERCENT", ""), ("BOOK", "true")],
+        ];
+        for subset in subsets.iter() {
+            let params: BTreeMap<&str, &str> = subset.iter().copied().collect();
+            let configuration: BTreeMap<String, EffectiveSetting> = initmarket(&params, 2500);
+            let given: BTreeSet<String> = INITMARKETSETTINGS.iter().filter(|(_key, param)| !params.get(param).copied().unwrap_or("").is_empty())
+                .map(|(key, _param)| key.to_string()).collect();
+            let mut expected: BTreeSet<String> = given.clone();
+            if params.contains_key("MARKETID") {
+                expected.insert("marketid".to_string());
+            }
+            assert_eq!(provenances(&configuration, PROVENANCE_PARAM), expected, "{:?}", subset);
+            let omitted: BTreeSet<String> = INITMARKETSETTINGS.iter().map(|(key, _param)| key.to_string()).filter(|key| !given.contains(key)).collect();
+            assert_eq!(provenances(&configuration, PROVENANCE_DEFAULT), omitted, "{:?}", subset);
+            assert_eq!(provenances(&configuration, PROVENANCE_PLATFORM), keys(&PLATFORMSETTINGS));
+            let mut builtin: BTreeSet<String> = keys(&["creator", "payoutbatch"]);
+            if !params.contains_key("MARKETID") {
+                builtin.insert("marketid".to_string());
+            }
+            assert_eq!(provenances(&configuration, PROVENANCE_BUILTIN), builtin, "{:?}", subset);
+            // the value of a given parameter is the one of the setting
+            for (key, param) in INITMARKETSETTINGS.iter() {
+                if given.contains(*key) {
+                    assert_eq!(configuration[*key].value, params[param]);
+                }
+            }
+        }
+        let configuration: BTreeMap<String, EffectiveSetting> = initmarket(&subsets[1].iter().copied().collect(), 2500);
+        assert_eq!(provenances(&configuration, PROVENANCE_PARAM), keys(&["betenddatetime", "feepercent", "marketid"]));
+        let configuration: BTreeMap<String, EffectiveSetting> = initmarket(&subsets[3].iter().copied().collect(), 2500);
+        assert_eq!(provenances(&configuration, PROVENANCE_PARAM), keys(&["book"]));
+        assert_eq!(configuration["feepercent"].provenance, PROVENANCE_DEFAULT);
+        let configuration: BTreeMap<String, EffectiveSetting> = initmarket(&[("BETENDUTC", "2021-06-01 18:00 UTC")].iter().copied().collect(), 2500);
+        assert_eq!(configuration["betenddatetime"], EffectiveSetting { value: "2021-06-01 18:00 UTC".to_string(), provenance: PROVENANCE_PARAM.to_string() });
+        assert_eq!(configuration["creatorsharebps"], EffectiveSetting { value: "2500".to_string(), provenance: PROVENANCE_PLATFORM.to_string() });
+        assert_eq!(configuration["marketid"], EffectiveSetting { value: "request1".to_string(), provenance: PROVENANCE_BUILTIN.to_string() });
+    }
+
+    #[test]
+    fn every_parameter_gives_a_single_setting() {
+        let settings: BTreeSet<&str> = INITMARKETSETTINGS.iter().map(|(key, _param)| *key).collect();
+        let params: BTreeSet<&str> = INITMARKETSETTINGS.iter().map(|(_key, param)| *param).collect();
+        assert_eq!(settings.len(), INITMARKETSETTINGS.len());
+        assert_eq!(params.len(), INITMARKETSETTINGS.len());
+        assert_eq!(settingparam("betenddatetime"), Some("BETENDUTC"));
+        assert_eq!(settingparam("idempotencykeys"), Some("IDEMPOTENCYKEYS"));
+        // the settings of the platform and the contract have no parameter
+        for key in PLATFORMSETTINGS.iter().chain(["creator", "marketid", "payoutbatch"].iter()) {
+            assert_eq!(settingparam(key), None);
+        }
+    }
+}
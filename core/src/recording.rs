@@ -0,0 +1,252 @@
+// Recording of the entitlements of a closed market in bounded batches, without access to the contract's context
+//
+// Closing, cancelling or voiding a market records no entitlement itself: it stores the terms the entitlements follow from, and the
+// calls driving the payouts record them from the stored bets, in the order the accounts first bet, continuing at the recording cursor.
+// The entitlement of a bet only depends on the terms and on the bet, so that the batches record exactly the win amounts of
+// settlewithtotals, booksettlement and haircut, whichever call records them. The batches share their size with the payouts:
+// the bets still to record come first, and the entitlements recorded so far are paid with the rest of the batch.
+
+use serde::{Deserialize, Serialize};
+
+use crate::settlement::{winningvalues, Bet, SettlementResult};
+
+pub const REASON_REFUND: &str = "refund";
+pub const REASON_WIN: &str = "win";
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RecordingTerms {
+    // true for a voided market, whose every bet is refunded its bet amount
+    pub refundall: bool,
+    // why the entitlements are recorded, "refund" for a voided or unanimous market, otherwise "win"
+    pub reason: String,
+    // winning values of the settlement, separated by commas
+    pub betvaluewinning: String,
+    // true for a book, whose winning bets receive the payouts locked by their accounts
+    pub book: bool,
+    // true if every bet was placed on the winning values, so that every bet gets back its bet amount
+    pub unanimous: bool,
+    // IOTA shared by the winners after the fee, and the total bet on the winning values
+    pub distributable: i64,
+    pub winningtotal: i64,
+    // IOTA left over by rounding down the win amounts, and the winner receiving them, see settlewithtotals
+    pub remainder: i64,
+    pub remainderrecipient: String,
+    // IOTA the win amounts are scaled down to by a haircut and the total win amount before, both 0 without a haircut
+    pub haircutavailable: i64,
+    pub haircuttotal: i64,
+}
+
+// terms of a voided market
+pub fn refundterms() -> RecordingTerms {
+    RecordingTerms {
+        refundall: true,
+        reason: REASON_REFUND.to_string(),
+        betvaluewinning: "".to_string(),
+        book: false,
+        unanimous: false,
+        distributable: 0,
+        winningtotal: 0,
+        remainder: 0,
+        remainderrecipient: "".to_string(),
+        haircutavailable: 0,
+        haircuttotal: 0,
+    }
+}
+
+// Terms of a market settled on betvaluewinning, with the available IOTA and the total win amount of a haircut, if one was applied.
+pub fn settlementterms(settlement: &SettlementResult, betvaluewinning: &str, book: bool, haircut: Option<(i64, i64)>) -> RecordingTerms {
+    let (haircutavailable, haircuttotal) = haircut.unwrap_or((0, 0));
+    RecordingTerms {
+        refundall: false,
+        // a unanimous market pays back the bets through the refunds, nobody won anything
+        reason: if settlement.unanimous { REASON_REFUND } else { REASON_WIN }.to_string(),
+        betvaluewinning: betvaluewinning.to_string(),
+        book,
+        unanimous: settlement.unanimous,
+        distributable: settlement.totalbetamount as i64 - settlement.operatortake,
+        winningtotal: settlement.totalbetamountforwinningvalue as i64,
+        remainder: settlement.remainder,
+        remainderrecipient: settlement.remainderrecipient.clone(),
+        haircutavailable,
+        haircuttotal,
+    }
+}
+
+// The entitlement of the bet of betteraddress under the terms, 0 if it gets nothing. bookpayout is the payout locked by the account
+// in a book, ignored otherwise.
+pub fn recordedamount(terms: &RecordingTerms, betteraddress: &str, bet: &Bet, bookpayout: i64) -> i64 {
+    if terms.refundall {
+        return bet.betamount as i64;
+    }
+    if !winningvalues(&terms.betvaluewinning).contains(&bet.betisforvalue.as_str()) {
+        return 0;
+    }
+    let mut winamount: i64 = if terms.book {
+        bookpayout
+    } else if terms.unanimous {
+        bet.betamount as i64
+    } else {
+        (bet.betamount as i128 * terms.distributable as i128 / terms.winningtotal as i128) as i64
+    };
+    if betteraddress == terms.remainderrecipient {
+        winamount += terms.remainder;
+    }
+    if terms.haircuttotal > 0 {
+        winamount = (winamount as i128 * terms.haircutavailable as i128 / terms.haircuttotal as i128) as i64;
+    }
+    winamount
+}
+
+// End of the next batch of at most maxcount bets to record, of betscount, which starts at the recording cursor.
+pub fn recordingbatchend(recordingcursor: i64, betscount: i64, maxcount: i64) -> i64 {
+    betscount.min(recordingcursor + maxcount.max(0)).max(recordingcursor)
+}
+
+// Whether the entitlements of every bet are recorded, so that the recorded entitlements are all there are.
+pub fn recordingdone(recordingcursor: i64, betscount: i64) -> bool {
+    recordingcursor >= betscount
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::booksettlement;
+    use crate::payouts::{payoutbatchend, payoutsdone};
+    use crate::settlement::{bettotals, haircut, parsecontainerofbets, settlewithtotals, ContainerOfBets};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn container(bets: &[(String, i64, &str)]) -> ContainerOfBets {
+        let entries: Vec<String> = bets.iter()
+            .map(|(betteraddress, betamount, betvalue)| format!("\"{}\":{{\"betamount\":{},\"betisforvalue\":\"{}\"}}", betteraddress, betamount, betvalue))
+            .collect();
+        parsecontainerofbets(&("{\"map\":{".to_string() + &entries.join(",") + "}}"))
+    }
+
+    // the entitlements recorded under the terms, by address, leaving out the bets that get nothing
+    fn recorded(terms: &RecordingTerms, containerofbets: &ContainerOfBets, bookpayouts: &HashMap<String, i64>) -> HashMap<String, i64> {
+        containerofbets.map.iter()
+            .map(|(betteraddress, bet)| (betteraddress.clone(), recordedamount(terms, betteraddress, bet, *bookpayouts.get(betteraddress).unwrap_or(&0))))
+            .filter(|(_, amount)| *amount != 0)
+            .collect()
+    }
+
+    #[test]
+    fn recorded_amounts_match_the_settlement() {
+        let bets: Vec<(String, i64, &str)> = vec![
+            ("alice".to_string(), 333, "home"),
+            ("bob".to_string(), 333, "draw"),
+            ("carol".to_string(), 1000, "away"),
+            ("dave".to_string(), 71, "home"),
+            ("erin".to_string(), 2, "draw"),
+        ];
+        let containerofbets: ContainerOfBets = container(&bets);
+        for (betvaluewinning, feepercent) in [("home", 0), ("home", 7), ("home,draw", 3), ("away", 10)] {
+            let settlement: SettlementResult = settlewithtotals(&containerofbets, bettotals(&containerofbets), betvaluewinning, feepercent);
+            let terms: RecordingTerms = settlementterms(&settlement, betvaluewinning, false, None);
+            assert_eq!(terms.reason, REASON_WIN);
+            assert_eq!(recorded(&terms, &containerofbets, &HashMap::new()), settlement.winamounts);
+
+            // a haircut to two thirds of the winnings scales every recorded amount like haircut does
+            let totalwinamount: i64 = settlement.winamounts.values().sum();
+            let available: i64 = totalwinamount * 2 / 3;
+            let terms: RecordingTerms = settlementterms(&settlement, betvaluewinning, false, Some((available, totalwinamount)));
+            assert_eq!(recorded(&terms, &containerofbets, &HashMap::new()), haircut(&settlement.winamounts, available).winamounts);
+        }
+
+        // the remainder goes to the largest winner only
+        let settlement: SettlementResult = settlewithtotals(&containerofbets, bettotals(&containerofbets), "home,draw", 3);
+        assert!(settlement.remainder > 0);
+        assert_eq!(settlementterms(&settlement, "home,draw", false, None).remainderrecipient, "alice");
+
+        // a unanimous market refunds every bet
+        let unanimous: ContainerOfBets = container(&[("alice".to_string(), 5, "yes"), ("bob".to_string(), 8, "yes")]);
+        let settlement: SettlementResult = settlewithtotals(&unanimous, bettotals(&unanimous), "yes", 10);
+        let terms: RecordingTerms = settlementterms(&settlement, "yes", false, None);
+        assert_eq!(terms.reason, REASON_REFUND);
+        assert_eq!(recorded(&terms, &unanimous, &HashMap::new()), settlement.winamounts);
+
+        // a book pays the locked payouts of the bets on the winning value
+        let bookpayouts: HashMap<String, i64> = bets.iter().map(|(betteraddress, betamount, _)| (betteraddress.clone(), betamount * 2)).collect();
+        let settlement: SettlementResult = booksettlement(&containerofbets, &bookpayouts, "draw");
+        let terms: RecordingTerms = settlementterms(&settlement, "draw", true, None);
+        assert_eq!(recorded(&terms, &containerofbets, &bookpayouts), settlement.winamounts);
+        assert_eq!(recorded(&terms, &containerofbets, &bookpayouts)["bob"], 666);
+
+        // a voided market refunds every bet amount
+        let refunds: HashMap<String, i64> = recorded(&refundterms(), &containerofbets, &HashMap::new());
+        assert_eq!(refunds.len(), 5);
+        assert!(bets.iter().all(|(betteraddress, betamount, _)| refunds[betteraddress] == *betamount));
+    }
+
+    #[test]
+    fn recording_batches_stay_within_the_bets() {
+        assert_eq!(recordingbatchend(0, 120, 50), 50);
+        assert_eq!(recordingbatchend(100, 120, 50), 120);
+        assert_eq!(recordingbatchend(120, 120, 50), 120);
+        // a batch used up by the recording leaves nothing, and never moves the cursor back
+        assert_eq!(recordingbatchend(70, 120, 0), 70);
+        assert_eq!(recordingbatchend(70, 120, -5), 70);
+        assert!(!recordingdone(119, 120));
+        assert!(recordingdone(120, 120));
+        assert!(recordingdone(0, 0));
+    }
+
+    #[test]
+    fn a_void_of_200_bettors_is_recorded_and_refunded_in_bounded_batches() {
+        // 200 accounts, stored in the order of their first bets, which is not the order of their addresses
+        let betteraddresses: Vec<String> = (0..200).map(|i| format!("account{:03}", (i * 37) % 200)).collect();
+        let bets: Vec<(String, i64, &str)> = betteraddresses.iter().enumerate()
+            .map(|(i, betteraddress)| (betteraddress.clone(), 10 + (i as i64 * 7919) % 1991, if i % 3 == 0 { "yes" } else { "no" }))
+            .collect();
+        let containerofbets: ContainerOfBets = container(&bets);
+        let stakes: BTreeMap<String, i64> = bets.iter().map(|(betteraddress, betamount, _)| (betteraddress.clone(), *betamount)).collect();
+        let pool: i64 = stakes.values().sum();
+
+        let terms: RecordingTerms = refundterms();
+        let mut balance: i64 = pool;
+        let mut pools: i64 = pool;
+        let mut wallets: BTreeMap<String, i64> = BTreeMap::new();
+        let mut entitlements: Vec<(String, i64, bool)> = Vec::new();
+        let mut recordingcursor: i64 = 0;
+        let mut payoutcursor: i64 = 0;
+        // the running total of the unpaid entitlements, which sweep takes instead of adding them up
+        let mut unpaid: i64 = 0;
+        let mut calls: i64 = 0;
+        while !(recordingdone(recordingcursor, 200) && payoutsdone(payoutcursor, entitlements.len() as i64, false)) {
+            let maxcount: i64 = 50;
+            let end: i64 = recordingbatchend(recordingcursor, 200, maxcount);
+            for index in recordingcursor..end {
+                let betteraddress: &String = &betteraddresses[index as usize];
+                let amount: i64 = recordedamount(&terms, betteraddress, &containerofbets.map[betteraddress], 0);
+                entitlements.push((betteraddress.clone(), amount, false));
+                unpaid += amount;
+            }
+            let recorded: i64 = end - recordingcursor;
+            recordingcursor = end;
+            let payoutend: i64 = payoutbatchend(payoutcursor, entitlements.len() as i64, maxcount - recorded, 0);
+            for index in payoutcursor..payoutend {
+                let (betteraddress, amount, _) = entitlements[index as usize].clone();
+                balance -= amount;
+                pools -= amount;
+                unpaid -= amount;
+                *wallets.entry(betteraddress).or_insert(0) += amount;
+                entitlements[index as usize].2 = true;
+            }
+            assert!(recorded + payoutend - payoutcursor <= maxcount);
+            payoutcursor = payoutend;
+            let scanned: i64 = entitlements.iter().filter(|(_, _, paid)| !paid).map(|(_, amount, _)| amount).sum();
+            assert_eq!(unpaid, scanned);
+            calls += 1;
+        }
+
+        // four calls record 50 bets each, four more pay 50 refunds each
+        assert_eq!(calls, 8);
+        assert_eq!(entitlements.len(), 200);
+        assert_eq!(wallets, stakes);
+        assert_eq!(balance, 0);
+        assert_eq!(pools, 0);
+        assert_eq!(unpaid, 0);
+        // nothing is left for sweep
+        assert_eq!(pools - unpaid, 0);
+    }
+}
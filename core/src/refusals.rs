@@ -0,0 +1,62 @@
+// Results of refused calls, without access to the contract's context
+//
+// A refused call, e.g. a rejected bet, a soft failure or a claim of a blocked account, returns its stable error code in the result ERROR.
+// The contract owner can add an explanation for participants by setrefusalnotice, e.g. "the match was abandoned, the bets are refunded",
+// which every refused call of the market returns alongside the error code in the result NOTICE. The notice is limited to
+// MAXREFUSALNOTICE bytes, so that it fits into the results of every refusal.
+
+pub const MAXREFUSALNOTICE: usize = 280;
+
+// Check a notice given to setrefusalnotice, an empty notice removes it.
+pub fn validaterefusalnotice(notice: &str) -> Result<(), String> {
+    if notice.len() > MAXREFUSALNOTICE {
+        return Err("NOTICE must not be longer than ".to_string() + &MAXREFUSALNOTICE.to_string() + " bytes");
+    }
+    Ok(())
+}
+
+// the results of a call refused with the error code, in the market with the notice, "" for none
+pub fn refusalresults(error: &str, notice: &str) -> Vec<(&'static str, String)> {
+    let mut results: Vec<(&'static str, String)> = vec![("ERROR", error.to_string())];
+    if !notice.is_empty() {
+        results.push(("NOTICE", notice.to_string()));
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::betting::{betrejection, ERR_BET_TOO_LATE};
+    use crate::blocklist::ERR_ADDRESS_BLOCKED;
+    use crate::softfailures::{closefailure, ERR_MARKET_CLOSED};
+
+    const END: i64 = 1622563200;
+    const NOTICE: &str = "This market closed early because the match was abandoned - refunds available via reclaim";
+
+    #[test]
+    fn the_notice_is_returned_on_every_refusal_path() {
+        validaterefusalnotice(NOTICE).unwrap();
+        // a late bet, closing a closed market and a claim of a blocked account
+        let errors: [&str; 3] = [
+            betrejection(false, END, END + 1, "yes", 100).unwrap().error,
+            closefailure(true, false, END, END + 1).unwrap().error,
+            ERR_ADDRESS_BLOCKED,
+        ];
+        assert_eq!(errors, [ERR_BET_TOO_LATE, ERR_MARKET_CLOSED, ERR_ADDRESS_BLOCKED]);
+        for error in errors.iter() {
+            assert_eq!(refusalresults(error, NOTICE), vec![("ERROR", error.to_string()), ("NOTICE", NOTICE.to_string())]);
+        }
+        // without a notice only the error code is returned
+        assert_eq!(refusalresults(ERR_BET_TOO_LATE, ""), vec![("ERROR", ERR_BET_TOO_LATE.to_string())]);
+    }
+
+    #[test]
+    fn the_notice_is_limited_to_280_bytes() {
+        assert!(validaterefusalnotice(&"a".repeat(MAXREFUSALNOTICE)).is_ok());
+        assert!(validaterefusalnotice(&"a".repeat(MAXREFUSALNOTICE + 1)).unwrap_err().contains("280 bytes"));
+        // bytes are counted, not characters: 141 umlauts take 282 bytes
+        assert!(validaterefusalnotice(&"ä".repeat(141)).is_err());
+        assert!(validaterefusalnotice("").is_ok());
+    }
+}
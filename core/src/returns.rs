@@ -0,0 +1,164 @@
+// Pending returns of the IOTA sent with strict requests that were rejected, without access to the contract's context
+//
+// A failed request would lose track of the IOTA sent with it, so a strict request with IOTA attached ends rejected instead, see
+// softfailurehandling, and its IOTA move from the strays to the returns bucket and are queued as PendingReturn in the pendingreturns
+// array. processreturns and the piggyback of every call pay the returns in the order they were queued, from pendingreturnshead on,
+// until the queue is empty. The contract reads the next returns and decides on them with payreturnbatch.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PendingReturn {
+    // id of the rejected request
+    pub requestid: String,
+    // encoded wallet address of the sender, to receive the IOTA
+    pub address: String,
+    // flag of the codec the address was encoded with
+    pub addressencoding: String,
+    // amount of IOTA sent with the request
+    pub amount: i64,
+    // error code of the rejection
+    pub error: String,
+}
+
+// a batch of pending returns worked off from the head of the queue
+#[derive(Debug, Default, PartialEq)]
+pub struct ReturnsBatch {
+    // number of returns paid
+    pub paid: i64,
+    // number of returns taken off the queue, paid or dropped
+    pub advanced: i64,
+    // returns whose address does not decode, with the error, which cannot ever be paid and whose IOTA go to the strays
+    pub dropped: Vec<(PendingReturn, String)>,
+}
+
+// Work off up to maxcount pending returns, given from the head of the queue on. pay transfers a return and returns the IOTA transferred,
+// 0 if the transfer failed, e.g. while transfers are blocked, or Err if the address of the return does not decode. A return whose
+// transfer failed stays first in line, and ends the batch. Dropped returns do not count towards maxcount.
+pub fn payreturnbatch(pendingreturns: &[PendingReturn], maxcount: i64, pay: &mut dyn FnMut(&PendingReturn) -> Result<i64, String>) -> ReturnsBatch {
+    let mut batch: ReturnsBatch = ReturnsBatch::default();
+    for pendingreturn in pendingreturns {
+        if batch.paid >= maxcount {
+            break;
+        }
+        match pay(pendingreturn) {
+            Ok(0) => break,
+            Ok(_transferred) => batch.paid += 1,
+            Err(error) => batch.dropped.push((pendingreturn.clone(), error)),
+        }
+        batch.advanced += 1;
+    }
+    batch
+}
+
+// number of returns still queued, of length queued ever, with the queue's head at head
+pub fn pendingreturnscount(length: i64, head: i64) -> i64 {
+    length - head
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{guardtransfer, BUCKET_RETURNS, BUCKET_STRAYS};
+    use crate::softfailures::{softfailurehandling, SoftFailureHandling, ERR_MARKET_CLOSED};
+    use std::collections::BTreeMap;
+
+    // the part of the contract keeping pending returns: its balance, buckets, the queue and the wallets paid
+    #[derive(Default)]
+    struct Contract {
+        balance: i64,
+        buckets: BTreeMap<&'static str, i64>,
+        pendingreturns: Vec<PendingReturn>,
+        head: i64,
+        wallets: BTreeMap<String, i64>,
+        transfersblocked: bool,
+    }
+
+    impl Contract {
+        // a strict request with incoming IOTA that is rejected, which arrived in the strays bucket
+        fn reject(&mut self, requestid: &str, sender: &str, incoming: i64) {
+            self.balance += incoming;
+            *self.buckets.entry(BUCKET_STRAYS).or_insert(0) += incoming;
+            assert_eq!(softfailurehandling(true, incoming, false), SoftFailureHandling::PendingReturn);
+            self.pendingreturns.push(PendingReturn {
+                requestid: requestid.to_string(),
+                address: sender.to_string(),
+                addressencoding: "".to_string(),
+                amount: incoming,
+                error: ERR_MARKET_CLOSED.to_string(),
+            });
+            *self.buckets.get_mut(BUCKET_STRAYS).unwrap() -= incoming;
+            *self.buckets.entry(BUCKET_RETURNS).or_insert(0) += incoming;
+        }
+
+        fn processreturns(&mut self, maxcount: i64) -> ReturnsBatch {
+            let queued: Vec<PendingReturn> = self.pendingreturns[self.head as usize..].to_vec();
+            let (balance, buckets, wallets, transfersblocked) = (&mut self.balance, &mut self.buckets, &mut self.wallets, self.transfersblocked);
+            let batch: ReturnsBatch = payreturnbatch(&queued, maxcount, &mut |pendingreturn: &PendingReturn| {
+                if !pendingreturn.address.starts_with("account") {
+                    return Err("address ".to_string() + &pendingreturn.address + " does not decode");
+                }
+                if transfersblocked {
+                    return Ok(0);
+                }
+                let (transferred, _violations) = guardtransfer(pendingreturn.amount, *balance, Some(buckets[BUCKET_RETURNS]));
+                *balance -= transferred;
+                *buckets.get_mut(BUCKET_RETURNS).unwrap() -= transferred;
+                *wallets.entry(pendingreturn.address.clone()).or_insert(0) += transferred;
+                Ok(transferred)
+            });
+            for (pendingreturn, _error) in &batch.dropped {
+                *self.buckets.get_mut(BUCKET_RETURNS).unwrap() -= pendingreturn.amount;
+                *self.buckets.get_mut(BUCKET_STRAYS).unwrap() += pendingreturn.amount;
+            }
+            self.head += batch.advanced;
+            batch
+        }
+
+        fn pending(&self) -> i64 {
+            pendingreturnscount(self.pendingreturns.len() as i64, self.head)
+        }
+    }
+
+    #[test]
+    fn a_strict_rejection_is_returned_and_the_returns_bucket_empties() {
+        let mut contract: Contract = Contract::default();
+        contract.reject("request1", "account1", 150);
+        assert_eq!(contract.pending(), 1);
+        assert_eq!(contract.buckets[BUCKET_RETURNS], 150);
+        assert_eq!(contract.buckets[BUCKET_STRAYS], 0);
+
+        let batch: ReturnsBatch = contract.processreturns(50);
+        assert_eq!(batch, ReturnsBatch { paid: 1, advanced: 1, dropped: Vec::new() });
+        assert_eq!(contract.wallets["account1"], 150);
+        assert_eq!(contract.buckets[BUCKET_RETURNS], 0);
+        assert_eq!(contract.balance, 0);
+        assert_eq!(contract.pending(), 0);
+        // processing an empty queue pays nothing
+        assert_eq!(contract.processreturns(50), ReturnsBatch::default());
+    }
+
+    #[test]
+    fn a_failed_transfer_stays_first_in_line_and_an_undecodable_address_is_dropped() {
+        let mut contract: Contract = Contract::default();
+        contract.reject("request1", "account1", 100);
+        contract.reject("request2", "garbled", 40);
+        contract.reject("request3", "account3", 60);
+
+        contract.transfersblocked = true;
+        assert_eq!(contract.processreturns(50), ReturnsBatch::default());
+        assert_eq!(contract.pending(), 3);
+
+        contract.transfersblocked = false;
+        let batch: ReturnsBatch = contract.processreturns(1);
+        assert_eq!((batch.paid, batch.advanced), (1, 1));
+        let batch: ReturnsBatch = contract.processreturns(50);
+        assert_eq!((batch.paid, batch.advanced), (1, 2));
+        assert_eq!(batch.dropped[0].0.requestid, "request2");
+        assert_eq!(contract.pending(), 0);
+        assert_eq!(contract.buckets[BUCKET_RETURNS], 0);
+        assert_eq!(contract.buckets[BUCKET_STRAYS], 40);
+        assert_eq!(contract.balance, 40);
+        assert_eq!(contract.wallets.values().sum::<i64>(), 160);
+    }
+}
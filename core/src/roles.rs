@@ -0,0 +1,89 @@
+// Roles of the operators of a contract, without access to the contract's context
+//
+// The contract owner can assign roles to other agents, which may call the functions of the role: the treasurer the ones moving funds
+// of the contract, resolvers the ones resolving markets and challenges, and market admins the ones configuring markets.
+// The contract owner always holds all roles. Every privileged function requires the roles listed for it in FUNCTIONROLES,
+// a function not listed there can only be called by the contract owner.
+
+pub const ROLE_TREASURER: &str = "treasurer";
+pub const ROLE_RESOLVER: &str = "resolver";
+pub const ROLE_MARKETADMIN: &str = "marketadmin";
+pub const ROLES: [&str; 3] = [ROLE_TREASURER, ROLE_RESOLVER, ROLE_MARKETADMIN];
+
+// the privileged functions and the roles a caller other than the contract owner has to hold all of
+pub const FUNCTIONROLES: [(&str, &[&str]); 21] = [
+    ("archivemarket", &[ROLE_MARKETADMIN]),
+    ("cancelmarket", &[ROLE_RESOLVER]),
+    ("checkpoint", &[ROLE_MARKETADMIN]),
+    ("closegroup", &[ROLE_RESOLVER]),
+    ("closechallenge", &[ROLE_RESOLVER]),
+    ("exportformigration", &[ROLE_MARKETADMIN, ROLE_TREASURER]),
+    ("getbetprivate", &[ROLE_MARKETADMIN]),
+    ("grantexception", &[ROLE_MARKETADMIN]),
+    ("heartbeat", &[ROLE_RESOLVER]),
+    ("importmarket", &[ROLE_MARKETADMIN]),
+    ("initmarket", &[ROLE_MARKETADMIN]),
+    ("prunerefunds", &[ROLE_TREASURER]),
+    ("reconcile", &[ROLE_TREASURER]),
+    ("reconcilelegacy", &[ROLE_MARKETADMIN]),
+    ("removemirror", &[ROLE_MARKETADMIN]),
+    ("seedmarket", &[ROLE_MARKETADMIN]),
+    ("setalerts", &[ROLE_MARKETADMIN]),
+    ("setodds", &[ROLE_MARKETADMIN]),
+    ("setrefusalnotice", &[ROLE_MARKETADMIN]),
+    ("setsweepdest", &[ROLE_TREASURER]),
+    ("withdrawfees", &[ROLE_TREASURER]),
+];
+
+// the roles the function requires, none for a function only the contract owner can call
+pub fn functionroles(function: &str) -> &'static [&'static str] {
+    FUNCTIONROLES.iter().find(|(name, _roles)| *name == function).map_or(&[], |(_name, roles)| roles)
+}
+
+// Whether a caller holding the roles held, or the contract owner, may call the function.
+pub fn authorised(function: &str, iscreator: bool, held: &[&str]) -> bool {
+    let required: &[&str] = functionroles(function);
+    iscreator || (!required.is_empty() && required.iter().all(|role| held.contains(role)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_function_is_authorised_for_its_role_and_the_creator_only() {
+        for (function, required) in FUNCTIONROLES.iter() {
+            assert!(authorised(function, true, &[]), "creator calls {}", function);
+            assert!(authorised(function, false, required), "{:?} calls {}", required, function);
+            for role in ROLES.iter().filter(|role| !required.contains(role)) {
+                let others: Vec<&str> = ROLES.iter().copied().filter(|other| other != role && !required.contains(other)).collect();
+                assert!(!authorised(function, false, &[*role]), "{} calls {}", role, function);
+                assert!(!authorised(function, false, &others), "{:?} calls {}", others, function);
+            }
+        }
+    }
+
+    #[test]
+    fn a_function_requiring_two_roles_needs_both() {
+        assert!(!authorised("exportformigration", false, &[ROLE_MARKETADMIN]));
+        assert!(!authorised("exportformigration", false, &[ROLE_TREASURER]));
+        assert!(authorised("exportformigration", false, &[ROLE_TREASURER, ROLE_MARKETADMIN]));
+    }
+
+    #[test]
+    fn functions_not_listed_are_for_the_creator_only() {
+        for function in ["addrole", "removerole", "blockaddress", "rebuildcounters"].iter() {
+            assert!(functionroles(function).is_empty());
+            assert!(!authorised(function, false, &ROLES));
+            assert!(authorised(function, true, &[]));
+        }
+    }
+
+    #[test]
+    fn every_function_is_listed_once() {
+        for (i, (function, roles)) in FUNCTIONROLES.iter().enumerate() {
+            assert!(!roles.is_empty());
+            assert!(FUNCTIONROLES[i + 1..].iter().all(|(other, _roles)| other != function), "{}", function);
+        }
+    }
+}
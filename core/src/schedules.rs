@@ -0,0 +1,77 @@
+// Schedules of recurring markets, without access to the contract's context
+//
+// A schedule clones a template market into a series, e.g. a weekly market: once the current market of the schedule is
+// settled or voided, the next one is created with the end time for bets advanced by the interval, until no instance remains.
+// Intervals whose end time passed before the rollover, because nobody called the contract for a long time, are skipped,
+// so that a new instance never opens with its end time in the past.
+
+// number of schedules active at once, which every call to the contract checks, and the maximum length in bytes of a SCHEDULEID,
+// which leaves room for the number of the instance in the MARKETID of a market of the schedule
+pub const MAXSCHEDULES: i32 = 16;
+pub const MAXSCHEDULEID: usize = 48;
+
+// the next market of a schedule
+#[derive(Debug, PartialEq)]
+pub struct Rollover {
+    pub instance: i64,
+    pub marketid: String,
+    pub betenddatetime: i64,
+    pub skipped: i64,
+    pub remaining: i64,
+}
+
+// MARKETID of the instance-th market created by a schedule, e.g. "weekly-3"
+pub fn scheduledmarketid(scheduleid: &str, instance: i64) -> String {
+    scheduleid.to_string() + "-" + &instance.to_string()
+}
+
+// The market following the instance-th market of a schedule, whose end time for bets was betenddatetime, if an instance remains:
+// its end time is one interval later, or the first end time of the series after currtime, skipping the intervals in between.
+pub fn nextrollover(scheduleid: &str, instance: i64, betenddatetime: i64, interval: i64, remaining: i64, currtime: i64) -> Option<Rollover> {
+    if remaining <= 0 || interval <= 0 {
+        return None;
+    }
+    let mut next: i64 = betenddatetime.saturating_add(interval);
+    let mut skipped: i64 = 0;
+    if next <= currtime {
+        skipped = (currtime - next) / interval + 1;
+        next = next.saturating_add(skipped.saturating_mul(interval));
+    }
+    Some(Rollover {
+        instance: instance + 1,
+        marketid: scheduledmarketid(scheduleid, instance + 1),
+        betenddatetime: next,
+        skipped,
+        remaining: remaining - 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEEK: i64 = 7 * 24 * 3600;
+
+    #[test]
+    fn a_schedule_rolls_over_twice_and_skips_a_missed_interval() {
+        let templateend: i64 = 1622563200;
+        // the template is settled a day after its end time: the first instance ends a week after the template
+        let first: Rollover = nextrollover("weekly", 0, templateend, WEEK, 3, templateend + 86400).unwrap();
+        assert_eq!(first, Rollover { instance: 1, marketid: "weekly-1".to_string(), betenddatetime: templateend + WEEK, skipped: 0, remaining: 2 });
+        // nobody calls the contract for two weeks after the first instance settled, so its following week is skipped
+        let second: Rollover = nextrollover("weekly", first.instance, first.betenddatetime, WEEK, first.remaining, first.betenddatetime + 2 * WEEK - 3600).unwrap();
+        assert_eq!(second, Rollover { instance: 2, marketid: "weekly-2".to_string(), betenddatetime: templateend + 3 * WEEK, skipped: 1, remaining: 1 });
+        assert!(second.betenddatetime > first.betenddatetime + 2 * WEEK - 3600);
+        let third: Rollover = nextrollover("weekly", second.instance, second.betenddatetime, WEEK, second.remaining, second.betenddatetime).unwrap();
+        assert_eq!((third.betenddatetime, third.skipped, third.remaining), (templateend + 4 * WEEK, 0, 0));
+        assert_eq!(nextrollover("weekly", third.instance, third.betenddatetime, WEEK, third.remaining, third.betenddatetime), None);
+    }
+
+    #[test]
+    fn an_end_time_at_the_rollover_is_skipped() {
+        // the end time has to be in the future, so an end time equal to the time of the rollover counts as passed
+        let rollover: Rollover = nextrollover("daily", 4, 1000, 100, 10, 1100).unwrap();
+        assert_eq!((rollover.betenddatetime, rollover.skipped), (1200, 1));
+        assert_eq!(rollover.marketid, "daily-5");
+    }
+}
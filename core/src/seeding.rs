@@ -0,0 +1,269 @@
+// The split of a single seed amount over the outcomes of a market, without access to the contract's context
+//
+// seedmarket seeds every outcome of a market with one transfer: the contract owner or a market admin attaches the whole seed and gives SEEDSPLIT as
+// * "uniform", an equal share for every outcome,
+// * "weights:" followed by outcome=percent pairs separated by commas, e.g. "weights:yes=70,no=30", the percent with up to two
+//   decimals, i.e. in basis points. The weights have to name every outcome of the market exactly once and sum to 10000 basis points.
+// Every outcome gets its share rounded down, and the IOTA the rounding leaves are handed out one each by the largest remainder,
+// remainders that tie in the order of the outcomes of the market, so the seeds always add up to the attached amount.
+// A seed is a bet of the seeding account, stored under seedkey, so that the account can seed every outcome with a bet of its own.
+// Everything that counts per account, like the entitlements and the stakes across a group, counts a seed bet for its seedowner.
+// A market can be seeded as long as bets are on time, see betontime, so a market without end time can always be seeded.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use crate::settlement::{bethistory, Bet, ContainerOfBets};
+use crate::time::betontime;
+
+pub const SEEDSPLIT_UNIFORM: &str = "uniform";
+pub const SEEDSPLIT_WEIGHTS: &str = "weights:";
+// separates the address of the seeding account from the outcome in the key of a seed bet, base58 addresses never contain it
+pub const SEEDSEPARATOR: char = '#';
+
+// The weights of the outcomes given by seedsplit, in basis points, in the order of the outcomes of the market.
+pub fn parseseedsplit(seedsplit: &str, outcomes: &[String]) -> Result<Vec<(String, i64)>, String> {
+    if outcomes.is_empty() {
+        return Err("SEEDSPLIT needs a market with OUTCOMES".to_string());
+    }
+    if seedsplit == SEEDSPLIT_UNIFORM {
+        let share: i64 = 10000 / outcomes.len() as i64;
+        // the basis points the division leaves go to the first outcomes, the amounts are split by equal weights regardless
+        return Ok(outcomes.iter().enumerate().map(|(position, outcome)| (outcome.clone(), share + if (position as i64) < 10000 % outcomes.len() as i64 { 1 } else { 0 })).collect());
+    }
+    if !seedsplit.starts_with(SEEDSPLIT_WEIGHTS) {
+        return Err("SEEDSPLIT must be \"uniform\" or \"weights:\" followed by outcome=percent pairs".to_string());
+    }
+    let mut weights: Vec<(String, i64)> = Vec::new();
+    for pair in seedsplit[SEEDSPLIT_WEIGHTS.len()..].split(',') {
+        let mut parts = pair.splitn(2, '=');
+        let outcome: String = parts.next().unwrap_or("").trim().to_string();
+        let bps: Option<i64> = parts.next().and_then(|percent| percentbps(percent.trim()));
+        if bps.is_none() {
+            return Err("weight of \"".to_string() + &outcome + "\" must be a percent with up to two decimals");
+        }
+        if !outcomes.contains(&outcome) {
+            return Err("\"".to_string() + &outcome + "\" of SEEDSPLIT is not one of the OUTCOMES of the market");
+        }
+        if weights.iter().any(|(weighted, _)| *weighted == outcome) {
+            return Err("\"".to_string() + &outcome + "\" is weighted twice by SEEDSPLIT");
+        }
+        weights.push((outcome, bps.unwrap()));
+    }
+    if weights.len() != outcomes.len() {
+        return Err("SEEDSPLIT must weight every one of the OUTCOMES of the market".to_string());
+    }
+    if weights.iter().map(|(_, bps)| bps).sum::<i64>() != 10000 {
+        return Err("the weights of SEEDSPLIT must sum to 100 percent, i.e. 10000 basis points".to_string());
+    }
+    Ok(outcomes.iter().map(|outcome| (outcome.clone(), weights.iter().find(|(weighted, _)| weighted == outcome).unwrap().1)).collect())
+}
+
+// a percent with up to two decimals in basis points, e.g. "70" is 7000 and "33.34" is 3334
+fn percentbps(percent: &str) -> Option<i64> {
+    let mut parts = percent.splitn(2, '.');
+    let whole: &str = parts.next().unwrap_or("");
+    let decimals: &str = parts.next().unwrap_or("");
+    if whole.is_empty() || decimals.len() > 2 || !whole.chars().chain(decimals.chars()).all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+    let bps: i64 = whole.parse::<i64>().ok()?.checked_mul(100)?;
+    let fraction: i64 = if decimals.is_empty() { 0 } else { (decimals.to_string() + &"0".repeat(2 - decimals.len())).parse::<i64>().ok()? };
+    bps.checked_add(fraction)
+}
+
+// The seed of every outcome for the split of amount by weights, see above. A uniform split divides by equal weights,
+// not by the rounded basis points of parseseedsplit, so that no outcome gets more than one IOTA more than another.
+pub fn seedamounts(amount: i64, seedsplit: &str, weights: &[(String, i64)]) -> Vec<(String, i64)> {
+    let uniform: bool = seedsplit == SEEDSPLIT_UNIFORM;
+    let total: i128 = if uniform { weights.len() as i128 } else { weights.iter().map(|(_, bps)| *bps as i128).sum() };
+    if amount <= 0 || total <= 0 {
+        return weights.iter().map(|(outcome, _)| (outcome.clone(), 0)).collect();
+    }
+    let mut seeds: Vec<(String, i64)> = Vec::new();
+    let mut remainders: Vec<(i128, usize)> = Vec::new();
+    for (position, (outcome, bps)) in weights.iter().enumerate() {
+        let weighted: i128 = amount as i128 * if uniform { 1 } else { *bps as i128 };
+        seeds.push((outcome.clone(), (weighted / total) as i64));
+        remainders.push((weighted % total, position));
+    }
+    let leftover: i64 = amount - seeds.iter().map(|(_, seed)| seed).sum::<i64>();
+    // the largest remainders first, ties in the order of the outcomes
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for (_, position) in remainders.iter().take(leftover as usize) {
+        seeds[*position].1 += 1;
+    }
+    seeds
+}
+
+// the key of the seed bet of seeder on outcome in the bets of the market
+pub fn seedkey(seeder: &str, outcome: &str) -> String {
+    seeder.to_string() + &SEEDSEPARATOR.to_string() + outcome
+}
+
+// The account a bet stored under betteraddress belongs to: the seeding account for a seed bet, the betting account otherwise.
+pub fn seedowner(betteraddress: &str) -> &str {
+    betteraddress.split(SEEDSEPARATOR).next().unwrap_or(betteraddress)
+}
+
+// The seed bets of seeder for seeds at currtime, as the key and the bet to store, each adding to the previous seed bet of its outcome
+// given by previous. Outcomes without a seed get no bet. Refused once the bets of a market ending at betenddatetime are no longer on time.
+pub fn seedbets(previous: impl Fn(&str) -> Option<Bet>, seeder: &str, seeds: &[(String, i64)], betenddatetime: i64, currtime: i64,
+    addressencoding: &str, requestid: &str) -> Result<Vec<(String, Bet)>, String> {
+    if !betontime(betenddatetime, currtime) {
+        return Err("the bets of the market have ended".to_string());
+    }
+    let mut bets: Vec<(String, Bet)> = Vec::new();
+    for (outcome, seed) in seeds.iter().filter(|(_, seed)| *seed > 0) {
+        let key: String = seedkey(seeder, outcome);
+        let previousbet: Option<Bet> = previous(&key);
+        let previousamount: i64 = previousbet.as_ref().map_or(0, |bet| bet.betamount as i64);
+        let betamount: i32 = match i32::try_from(previousamount + seed) {
+            Ok(betamount) => betamount,
+            Err(_) => return Err("bets of an account are limited to ".to_string() + &i32::MAX.to_string() + " IOTA"),
+        };
+        let bet: Bet = Bet {
+            betamount,
+            betisforvalue: outcome.clone(),
+            placedat: currtime,
+            attachedamount: previousbet.as_ref().map_or(0, |bet| bet.attachedamount) + seed,
+            addressencoding: addressencoding.to_string(),
+            requestid: requestid.to_string(),
+            clientid: "".to_string(),
+            campaign: "".to_string(),
+            history: bethistory(previousbet.as_ref(), currtime, *seed, ""),
+        };
+        bets.push((key, bet));
+    }
+    Ok(bets)
+}
+
+// The amount every account stakes in the bets, its seed bets added to its own bet.
+pub fn accountstakes(containerofbets: &ContainerOfBets) -> BTreeMap<String, i64> {
+    let mut stakes: BTreeMap<String, i64> = BTreeMap::new();
+    for (betteraddress, bet) in containerofbets.map.iter() {
+        *stakes.entry(seedowner(betteraddress).to_string()).or_insert(0) += bet.betamount as i64;
+    }
+    stakes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::{parsecontainerofbets, settlewithfee, SettlementResult};
+
+    fn outcomes(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn split(amount: i64, seedsplit: &str, values: &[&str]) -> Vec<(String, i64)> {
+        seedamounts(amount, seedsplit, &parseseedsplit(seedsplit, &outcomes(values)).unwrap())
+    }
+
+    #[test]
+    fn the_split_is_validated() {
+        let yesno: Vec<String> = outcomes(&["yes", "no"]);
+        assert_eq!(parseseedsplit("weights:yes=70,no=30", &yesno).unwrap(), vec![("yes".to_string(), 7000), ("no".to_string(), 3000)]);
+        // the weights are returned in the order of the outcomes, and take two decimals
+        assert_eq!(parseseedsplit("weights:no=33.34,yes=66.66", &yesno).unwrap(), vec![("yes".to_string(), 6666), ("no".to_string(), 3334)]);
+        assert_eq!(parseseedsplit("uniform", &outcomes(&["a", "b", "c"])).unwrap().iter().map(|(_, bps)| bps).sum::<i64>(), 10000);
+        // weights that miss an outcome, name an unknown one or twice, or do not sum to 100 percent
+        assert!(parseseedsplit("weights:yes=100", &yesno).is_err());
+        assert!(parseseedsplit("weights:yes=70,maybe=30", &yesno).is_err());
+        assert!(parseseedsplit("weights:yes=70,yes=30", &yesno).is_err());
+        assert!(parseseedsplit("weights:yes=70,no=20", &yesno).is_err());
+        assert!(parseseedsplit("weights:yes=70.001,no=29.999", &yesno).is_err());
+        assert!(parseseedsplit("weights:yes=-70,no=170", &yesno).is_err());
+        assert!(parseseedsplit("weights:yes=70,no", &yesno).is_err());
+        assert!(parseseedsplit("even", &yesno).is_err());
+        assert!(parseseedsplit("uniform", &[]).is_err());
+    }
+
+    #[test]
+    fn a_uniform_split_hands_out_the_remainder_in_the_order_of_the_outcomes() {
+        assert_eq!(split(900, "uniform", &["a", "b", "c"]), vec![("a".to_string(), 300), ("b".to_string(), 300), ("c".to_string(), 300)]);
+        // 1000 over three outcomes leaves one IOTA, which goes to the first outcome
+        assert_eq!(split(1000, "uniform", &["a", "b", "c"]), vec![("a".to_string(), 334), ("b".to_string(), 333), ("c".to_string(), 333)]);
+        assert_eq!(split(1001, "uniform", &["c", "b", "a"]), vec![("c".to_string(), 334), ("b".to_string(), 334), ("a".to_string(), 333)]);
+        // fewer IOTA than outcomes leave the last outcomes without a seed
+        assert_eq!(split(2, "uniform", &["a", "b", "c"]), vec![("a".to_string(), 1), ("b".to_string(), 1), ("c".to_string(), 0)]);
+    }
+
+    #[test]
+    fn a_weighted_split_hands_out_the_remainder_by_the_largest_remainder() {
+        assert_eq!(split(1000, "weights:yes=70,no=30", &["yes", "no"]), vec![("yes".to_string(), 700), ("no".to_string(), 300)]);
+        // 101 * 70% is 70.7 and 101 * 30% is 30.3, so the IOTA left goes to yes
+        assert_eq!(split(101, "weights:yes=70,no=30", &["yes", "no"]), vec![("yes".to_string(), 71), ("no".to_string(), 30)]);
+        // 10 * 15% is 1.5, 10 * 25% is 2.5 and 10 * 60% is 6, the IOTA left goes to a, whose remainder ties with the one of b and comes first
+        assert_eq!(split(10, "weights:a=15,b=25,c=60", &["a", "b", "c"]), vec![("a".to_string(), 2), ("b".to_string(), 2), ("c".to_string(), 6)]);
+        assert_eq!(split(10, "weights:b=25,a=15,c=60", &["b", "a", "c"]), vec![("b".to_string(), 3), ("a".to_string(), 1), ("c".to_string(), 6)]);
+        // 7 * 45% is 3.15, 7 * 35% is 2.45 and 7 * 20% is 1.4, the IOTA left goes to the largest remainder of b, not to the largest seed
+        assert_eq!(split(7, "weights:a=45,b=35,c=20", &["a", "b", "c"]), vec![("a".to_string(), 3), ("b".to_string(), 3), ("c".to_string(), 1)]);
+        // the seeds always add up to the amount
+        for amount in [1, 2, 3, 99, 12345, i32::MAX as i64] {
+            assert_eq!(split(amount, "weights:a=33.33,b=33.33,c=33.34", &["a", "b", "c"]).iter().map(|(_, seed)| seed).sum::<i64>(), amount);
+        }
+    }
+
+    #[test]
+    fn a_seed_bet_belongs_to_the_seeding_account() {
+        let key: String = seedkey("1Aq3Dz", "yes");
+        assert_eq!(key, "1Aq3Dz#yes");
+        assert_eq!(seedowner(&key), "1Aq3Dz");
+        assert_eq!(seedowner("1Aq3Dz"), "1Aq3Dz");
+    }
+
+    // the bets of a market as json, as the contract stores them
+    fn stored(containerofbets: &ContainerOfBets) -> String {
+        serde_json::to_string(containerofbets).unwrap()
+    }
+
+    #[test]
+    fn a_market_is_seeded_while_its_bets_are_on_time() {
+        let seeds: Vec<(String, i64)> = split(1001, "weights:yes=70,no=30", &["yes", "no"]);
+        let none = |_: &str| -> Option<Bet> { None };
+        // a market without end time can always be seeded, one with an end time until the very second of it
+        let openended: Vec<(String, Bet)> = seedbets(none, "owner", &seeds, 0, 1_900_000_000, "b58", "request").unwrap();
+        assert_eq!(openended.iter().map(|(key, bet)| (key.as_str(), bet.betamount, bet.betisforvalue.as_str())).collect::<Vec<_>>(),
+            vec![("owner#yes", 701, "yes"), ("owner#no", 300, "no")]);
+        assert!(seedbets(none, "owner", &seeds, 1000, 1000, "b58", "request").is_ok());
+        assert!(seedbets(none, "owner", &seeds, 1000, 1001, "b58", "request").is_err());
+        // an outcome without a seed gets no bet
+        assert_eq!(seedbets(none, "owner", &split(1, "uniform", &["yes", "no"]), 0, 1000, "b58", "request").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_seeded_market_is_settled_like_every_other_market() {
+        let mut containerofbets: ContainerOfBets = parsecontainerofbets(r#"{"map":{
+            "alice":{"betamount":300,"betisforvalue":"yes","placedat":1,"attachedamount":300},
+            "bob":{"betamount":700,"betisforvalue":"no","placedat":2,"attachedamount":700}}}"#);
+        // the owner seeds twice, the second seed adds to the seed bets of the first
+        for (currtime, amount) in [(3, 1000), (4, 101)] {
+            let json: String = stored(&containerofbets);
+            let previous = |key: &str| parsecontainerofbets(&json).map.remove(key);
+            for (key, bet) in seedbets(previous, "owner", &split(amount, "weights:yes=70,no=30", &["yes", "no"]), 0, currtime, "b58", "request").unwrap() {
+                containerofbets.map.insert(key, bet);
+            }
+        }
+        assert_eq!(containerofbets.map["owner#yes"].betamount, 700 + 71);
+        assert_eq!(containerofbets.map["owner#no"].betamount, 300 + 30);
+        assert_eq!(containerofbets.map["owner#yes"].history.len(), 2);
+
+        // the stakes count the seed bets for the owner, not for accounts named after the seed keys
+        let stakes: BTreeMap<String, i64> = accountstakes(&containerofbets);
+        assert_eq!(stakes.keys().cloned().collect::<Vec<String>>(), vec!["alice".to_string(), "bob".to_string(), "owner".to_string()]);
+        assert_eq!(stakes["owner"], 1101);
+
+        // yes wins a pool of 2101 IOTA with 1071 IOTA on it: alice gets 300 * 2101 / 1071 rounded down, the seed bet on yes the rest
+        let settlement: SettlementResult = settlewithfee(&containerofbets, "yes", 0);
+        assert_eq!(settlement.totalbetamount, 2101);
+        assert_eq!(settlement.winamounts["alice"], 300 * 2101 / 1071);
+        let mut entitlements: BTreeMap<String, i64> = BTreeMap::new();
+        for (betteraddress, winamount) in settlement.winamounts.iter() {
+            *entitlements.entry(seedowner(betteraddress).to_string()).or_insert(0) += winamount;
+        }
+        assert_eq!(entitlements["owner"], 2101 - 300 * 2101 / 1071);
+        assert_eq!(entitlements.values().sum::<i64>(), 2101);
+        assert!(!entitlements.contains_key("owner#yes"));
+    }
+}
@@ -0,0 +1,519 @@
+// Settlement of the bets, without access to the contract's context
+//
+// The functions here only depend on the bets and the winning value. They are shared by the contract and by the replay example,
+// which settles a market exported by exportmarket locally and has to come to the same results.
+
+use serde_with::serde_as;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+pub struct Bet {
+    // bet size in IOTA
+    pub betamount: i32,
+    // value for which the bet is valid, e.g., "yes" or "no" regarding a question or an outcome of an event
+    pub betisforvalue: String,
+    // timestamp of the transaction placing the bet, 0 for bets stored before it was recorded
+    #[serde(default)]
+    pub placedat: i64,
+    // amount of IOTA sent with the bet, of which betamount was accepted, 0 for bets stored before it was recorded
+    #[serde(default)]
+    pub attachedamount: i64,
+    // flag of the codec the address of the betting account was encoded with, "" for bets stored before it was recorded
+    #[serde(default)]
+    pub addressencoding: String,
+    // id of the request placing the bet and sending its IOTA, "" for bets stored before it was recorded
+    #[serde(default)]
+    pub requestid: String,
+    // attribution of the bet to the client and the marketing campaign it came from, "" if none, which is omitted from the json;
+    // they play no part in the settlement and are not exported
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub clientid: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub campaign: String,
+    // every bet of the account that makes up betamount, in the order they were placed, empty for bets stored before it was recorded;
+    // it plays no part in the settlement and the canonical encodings
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<BetEntry>,
+}
+
+// a single bet added to the bet of an account
+#[serde_as]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct BetEntry {
+    // timestamp of the transaction placing the bet
+    pub placedat: i64,
+    // accepted bet amount in IOTA
+    pub betamount: i64,
+    // campaign the bet was counted for, "" if none or for entries stored before it was recorded, which is omitted from the json
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub campaign: String,
+}
+
+// The history of a bet after adding the accepted amount at timestamp, counted for campaign, to the previous bet of the account.
+// A previous bet stored before the history was recorded starts it with a single entry for its whole amount at the time of its last bet.
+pub fn bethistory(previousbet: Option<&Bet>, timestamp: i64, betamount: i64, campaign: &str) -> Vec<BetEntry> {
+    let mut history: Vec<BetEntry> = match previousbet {
+        Some(bet) if bet.history.is_empty() => vec![BetEntry { placedat: bet.placedat, betamount: bet.betamount as i64, campaign: "".to_string() }],
+        Some(bet) => bet.history.clone(),
+        None => Vec::new(),
+    };
+    history.push(BetEntry { placedat: timestamp, betamount, campaign: campaign.to_string() });
+    history
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+pub struct ContainerOfBets {
+    // map betting account's wallet address (string) to a Bet
+    pub map: HashMap<String,Bet>,
+}
+
+// de-serialize and re-create the container of bets from its json string, which is empty if no bets are stored yet
+pub fn parsecontainerofbets(containerofbetsjson: &str) -> ContainerOfBets {
+    if containerofbetsjson.is_empty() {
+        return ContainerOfBets {
+            map : HashMap::new()
+        };
+    }
+    serde_json::from_str(containerofbetsjson).expect("failed to get container of bets")
+}
+
+// overall amount of bets and the amount per value, which the contract also keeps as running totals at bet time
+pub struct BetTotals {
+    pub totalbetamount: i32,
+    pub betvalue_totalbetamount: HashMap<String, i32>,
+}
+
+pub fn bettotals(containerofbets: &ContainerOfBets) -> BetTotals {
+    let mut betvalue_totalbetamount: HashMap<String, i32> = HashMap::new();
+    let mut totalbetamount:i32 = 0;
+    for bet in containerofbets.map.values() {
+        totalbetamount += bet.betamount;
+        *betvalue_totalbetamount.entry(bet.betisforvalue.clone()).or_insert(0) += bet.betamount;
+    }
+    BetTotals { totalbetamount, betvalue_totalbetamount }
+}
+
+// result of settling all bets for a winning value, computed without access to the contract's context
+pub struct SettlementResult {
+    // overall amount in bets, regardless on which outcome value the bet was placed
+    pub totalbetamount: i32,
+    // total amount of bet amounts per value, e.g., 500 IOTA on "yes" and 2000 IOTA on "no"
+    pub betvalue_totalbetamount: HashMap<String, i32>,
+    // total amount of bets placed on the winning values, 0 if nobody bet on them
+    pub totalbetamountforwinningvalue: i32,
+    // map winning betting account's wallet address to the amount of IOTA it receives
+    pub winamounts: HashMap<String, i64>,
+    // realized odds of the winning value, the overall amount of bets per IOTA bet on it in basis points, 0 if nobody bet on it
+    pub realizedoddsbps: i64,
+    // IOTA kept by the operator as fee, FEEPERCENT of the overall amount of bets rounded down
+    pub operatortake: i64,
+    // IOTA received for a winning bet of 100 IOTA
+    pub returnper100: i64,
+    // true if all bets were placed on the winning value, so that every bet is effectively refunded
+    pub unanimous: bool,
+    // IOTA left over by rounding down the win amounts, which is added to the win amount of the largest winner
+    pub remainder: i64,
+    // wallet address of the winner receiving the remainder, "" if there is no remainder
+    pub remainderrecipient: String,
+}
+
+// The winning values of a settlement, given as a comma separated list, e.g. "home,draw" for a tie between two outcomes.
+// A single value is a list of one.
+pub fn winningvalues(betvaluewinning: &str) -> Vec<&str> {
+    betvaluewinning.split(',').collect()
+}
+
+// Settle the bets for the winning value: a winning bet receives its share of the total amount bet on the winning value,
+// applied to the overall amount of bets. This is shared by closemarket and the views, so they cannot diverge.
+// The win amounts are computed with integer math and rounded down, so they never exceed the pool. The remainder, less
+// than 1 IOTA per winner, goes to the winner with the largest win amount, the smallest address among equal ones,
+// so that no dust is left in the pools. With several winning values, see winningvalues, the bets on all of them win
+// and share the pool in proportion to their bet amounts, as if they were placed on a single value.
+pub fn settle(containerofbets: &ContainerOfBets, betvaluewinning: &str) -> SettlementResult {
+    settlewithfee(containerofbets, betvaluewinning, 0)
+}
+
+// Settle the bets like settle, after deducting feepercent of the overall amount of bets, rounded down, as the operator's take.
+// The winners share the distributable rest. A unanimous market refunds every bet, so no fee is taken from it.
+pub fn settlewithfee(containerofbets: &ContainerOfBets, betvaluewinning: &str, feepercent: i64) -> SettlementResult {
+    settlewithtotals(containerofbets, bettotals(containerofbets), betvaluewinning, feepercent)
+}
+
+// Settle the bets like settlewithfee with totals known beforehand, e.g. the running totals of the contract,
+// so that the bets are only looped over once for the win amounts. The totals have to match the bets.
+pub fn settlewithtotals(containerofbets: &ContainerOfBets, totals: BetTotals, betvaluewinning: &str, feepercent: i64) -> SettlementResult {
+    let BetTotals { totalbetamount, betvalue_totalbetamount } = totals;
+
+    let winning: Vec<&str> = winningvalues(betvaluewinning);
+    let totalbetamountforwinningvalue: i32 = winning.iter().map(|betvalue| *betvalue_totalbetamount.get(*betvalue).unwrap_or(&0)).sum();
+    // if everybody bet on the winning value, every winner gets back exactly the bet amount, not subject to rounding
+    let unanimous: bool = totalbetamountforwinningvalue > 0 && totalbetamountforwinningvalue == totalbetamount;
+    let operatortake: i64 = if unanimous || totalbetamountforwinningvalue == 0 { 0 } else { totalbetamount as i64 * feepercent / 100 };
+    let distributable: i64 = totalbetamount as i64 - operatortake;
+    let mut winamounts: HashMap<String, i64> = HashMap::new();
+    for (betteraddress, bet) in &containerofbets.map {
+        if winning.contains(&bet.betisforvalue.as_str()) {
+            let winamount:i64 = if unanimous {
+                bet.betamount as i64
+            } else {
+                (bet.betamount as i128 * distributable as i128 / totalbetamountforwinningvalue as i128) as i64
+            };
+            winamounts.insert(betteraddress.to_string(), winamount);
+        }
+    }
+
+    let mut realizedoddsbps: i64 = 0;
+    let mut returnper100: i64 = 0;
+    if totalbetamountforwinningvalue > 0 {
+        realizedoddsbps = distributable * 10000 / totalbetamountforwinningvalue as i64;
+        returnper100 = distributable * 100 / totalbetamountforwinningvalue as i64;
+    }
+
+    let remainder: i64 = if winamounts.is_empty() { 0 } else { distributable - winamounts.values().sum::<i64>() };
+    let mut remainderrecipient: String = "".to_string();
+    if remainder > 0 {
+        let (betteraddress, _winamount) = winamounts.iter()
+            .max_by(|(address, winamount), (otheraddress, otherwinamount)| winamount.cmp(otherwinamount).then(otheraddress.cmp(address)))
+            .unwrap();
+        remainderrecipient = betteraddress.to_string();
+        *winamounts.get_mut(&remainderrecipient).unwrap() += remainder;
+    }
+
+    SettlementResult {
+        totalbetamount,
+        betvalue_totalbetamount,
+        totalbetamountforwinningvalue,
+        winamounts,
+        realizedoddsbps,
+        operatortake,
+        returnper100,
+        unanimous,
+        remainder,
+        remainderrecipient,
+    }
+}
+
+// win amounts scaled down to the available IOTA
+pub struct Haircut {
+    pub winamounts: HashMap<String, i64>,
+    // applied fraction in basis points, rounded down
+    pub fractionbps: i64,
+    // IOTA left over by rounding down the scaled win amounts
+    pub residue: i64,
+}
+
+// Scale every win amount by available / total win amount with integer math, rounding down. The fraction is applied to each win amount
+// directly rather than rounded to basis points first, so that every winner is paid the same fraction up to less than 1 IOTA.
+pub fn haircut(winamounts: &HashMap<String, i64>, available: i64) -> Haircut {
+    let totalwinamount: i64 = winamounts.values().sum();
+    let mut scaled: HashMap<String, i64> = HashMap::new();
+    for (betteraddress, winamount) in winamounts {
+        scaled.insert(betteraddress.to_string(), (*winamount as i128 * available as i128 / totalwinamount as i128) as i64);
+    }
+    let residue: i64 = available - scaled.values().sum::<i64>();
+    Haircut {
+        winamounts: scaled,
+        fractionbps: (available as i128 * 10000 / totalwinamount as i128) as i64,
+        residue,
+    }
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+pub struct ExportedBet {
+    // betting account's wallet address
+    pub address: String,
+    // bet size in IOTA
+    pub betamount: i32,
+    // value for which the bet is valid
+    pub betisforvalue: String,
+    // timestamp of the transaction placing the bet
+    pub placedat: i64,
+    // amount of IOTA sent with the bet
+    pub attachedamount: i64,
+    // id of the request placing the bet, omitted for bets stored before it was recorded, so that their bets root is unchanged
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub requestid: String,
+}
+
+// List the bets sorted by address, optionally only those placed at or before the timestamp asof.
+// The stake of an account as of the timestamp is rebuilt from the history of its bet. If the account added to its bet later,
+// the request and the IOTA sent with the earlier bet are not kept: the request is then omitted and the accepted amount of the last bet
+// until the timestamp is used as attached amount.
+pub fn exportedbets(containerofbets: &ContainerOfBets, asof: Option<i64>) -> Vec<ExportedBet> {
+    let mut bets: Vec<ExportedBet> = containerofbets.map.iter()
+        .filter_map(|(address, bet)| match asof {
+            None => Some(exportedbet(address, bet)),
+            Some(timestamp) => exportedbetasof(address, bet, timestamp),
+        })
+        .collect();
+    bets.sort_by(|a, b| a.address.cmp(&b.address));
+    bets
+}
+
+fn exportedbet(address: &str, bet: &Bet) -> ExportedBet {
+    ExportedBet {
+        address: address.to_string(),
+        betamount: bet.betamount,
+        betisforvalue: bet.betisforvalue.clone(),
+        placedat: bet.placedat,
+        attachedamount: bet.attachedamount,
+        requestid: bet.requestid.clone(),
+    }
+}
+
+// the bet of an account as of the timestamp, None if it was placed later
+fn exportedbetasof(address: &str, bet: &Bet, timestamp: i64) -> Option<ExportedBet> {
+    // a bet stored before its history was recorded is known at the time of its last bet only
+    if bet.history.is_empty() || bet.placedat <= timestamp {
+        return if bet.placedat <= timestamp { Some(exportedbet(address, bet)) } else { None };
+    }
+    let entries: Vec<&BetEntry> = bet.history.iter().filter(|entry| entry.placedat <= timestamp).collect();
+    let last: &BetEntry = entries.last()?;
+    Some(ExportedBet {
+        address: address.to_string(),
+        betamount: entries.iter().map(|entry| entry.betamount).sum::<i64>() as i32,
+        betisforvalue: bet.betisforvalue.clone(),
+        placedat: last.placedat,
+        attachedamount: last.betamount,
+        requestid: "".to_string(),
+    })
+}
+
+// Re-create the container of bets from the bets exported by exportmarket.
+pub fn containerofexportedbets(bets: Vec<ExportedBet>) -> ContainerOfBets {
+    let mut containerofbets = ContainerOfBets {
+        map : HashMap::new()
+    };
+    for exportedbet in bets {
+        containerofbets.map.insert(exportedbet.address, Bet {
+            betamount: exportedbet.betamount,
+            betisforvalue: exportedbet.betisforvalue,
+            placedat: exportedbet.placedat,
+            attachedamount: exportedbet.attachedamount,
+            addressencoding: "".to_string(),
+            requestid: exportedbet.requestid,
+            clientid: "".to_string(),
+            campaign: "".to_string(),
+            history: Vec::new(),
+        });
+    }
+    containerofbets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bet(betamount: i32, betisforvalue: &str) -> Bet {
+        Bet {
+            betamount,
+            betisforvalue: betisforvalue.to_string(),
+            placedat: 0,
+            attachedamount: betamount as i64,
+            addressencoding: "".to_string(),
+            requestid: "".to_string(),
+            clientid: "".to_string(),
+            campaign: "".to_string(),
+            history: Vec::new(),
+        }
+    }
+
+    fn market(bets: &[(&str, i32, &str)]) -> ContainerOfBets {
+        let mut containerofbets = ContainerOfBets { map: HashMap::new() };
+        for (address, betamount, betisforvalue) in bets {
+            containerofbets.map.insert(address.to_string(), bet(*betamount, betisforvalue));
+        }
+        containerofbets
+    }
+
+    #[test]
+    fn winners_share_the_pool_in_proportion_to_their_bets() {
+        let containerofbets = market(&[("a", 100, "yes"), ("b", 300, "no"), ("c", 200, "yes")]);
+        let settlement = settle(&containerofbets, "yes");
+        assert_eq!(settlement.totalbetamount, 600);
+        assert_eq!(settlement.totalbetamountforwinningvalue, 300);
+        assert_eq!(settlement.winamounts.len(), 2);
+        assert_eq!(settlement.winamounts["a"], 200);
+        assert_eq!(settlement.winamounts["c"], 400);
+        assert_eq!(settlement.realizedoddsbps, 20000);
+        assert_eq!(settlement.returnper100, 200);
+        assert_eq!(settlement.remainder, 0);
+        assert_eq!(settlement.remainderrecipient, "");
+        assert!(!settlement.unanimous);
+    }
+
+    #[test]
+    fn remainder_goes_to_the_largest_winner() {
+        // 5 IOTA shared by bets of 2 and 1: 3 and 1 rounded down, the remaining 1 IOTA to the larger winner
+        let containerofbets = market(&[("b", 2, "yes"), ("a", 1, "yes"), ("c", 2, "no")]);
+        let settlement = settle(&containerofbets, "yes");
+        assert_eq!(settlement.remainder, 1);
+        assert_eq!(settlement.remainderrecipient, "b");
+        assert_eq!(settlement.winamounts["b"], 4);
+        assert_eq!(settlement.winamounts["a"], 1);
+    }
+
+    #[test]
+    fn remainder_goes_to_the_smallest_address_among_equal_winners() {
+        let containerofbets = market(&[("c", 1, "yes"), ("a", 1, "yes"), ("b", 1, "yes"), ("d", 1, "no")]);
+        let settlement = settle(&containerofbets, "yes");
+        assert_eq!(settlement.remainder, 1);
+        assert_eq!(settlement.remainderrecipient, "a");
+        assert_eq!(settlement.winamounts["a"], 2);
+        assert_eq!(settlement.winamounts["b"], 1);
+        assert_eq!(settlement.winamounts["c"], 1);
+    }
+
+    #[test]
+    fn payouts_add_up_to_the_pool_and_rounding_loses_less_than_one_iota_per_winner() {
+        // deterministic pseudo random markets
+        let mut seed: u64 = 42;
+        for _market in 0..200 {
+            let mut containerofbets = ContainerOfBets { map: HashMap::new() };
+            let bettors: u64 = 1 + seed % 17;
+            for bettor in 0..bettors {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                let value: &str = match (seed >> 33) % 3 { 0 => "no", _ => "yes" };
+                containerofbets.map.insert(bettor.to_string(), bet(1 + ((seed >> 40) % 100000) as i32, value));
+            }
+            let settlement = settlewithfee(&containerofbets, "yes", 3);
+            if settlement.winamounts.is_empty() {
+                continue;
+            }
+            let paid: i64 = settlement.winamounts.values().sum();
+            assert!(paid + settlement.operatortake <= settlement.totalbetamount as i64);
+            assert_eq!(paid + settlement.operatortake, settlement.totalbetamount as i64);
+            assert!(settlement.remainder >= 0);
+            assert!(settlement.remainder < settlement.winamounts.len() as i64);
+        }
+    }
+
+    #[test]
+    fn fee_is_taken_from_the_overall_amount_of_bets() {
+        let containerofbets = market(&[("a", 100, "yes"), ("b", 899, "no")]);
+        let settlement = settlewithfee(&containerofbets, "yes", 10);
+        // 10% of 999 rounded down
+        assert_eq!(settlement.operatortake, 99);
+        assert_eq!(settlement.winamounts["a"], 900);
+    }
+
+    // three bettors who all bet on the winning value get exactly their bets back, without a fee
+    #[test]
+    fn unanimous_market_refunds_every_bet() {
+        let containerofbets = market(&[("a", 100, "yes"), ("b", 250, "yes"), ("c", 7, "yes")]);
+        let settlement = settlewithfee(&containerofbets, "yes", 5);
+        assert!(settlement.unanimous);
+        assert_eq!(settlement.operatortake, 0);
+        assert_eq!(settlement.remainder, 0);
+        assert_eq!(settlement.winamounts["a"], 100);
+        assert_eq!(settlement.winamounts["b"], 250);
+        assert_eq!(settlement.winamounts["c"], 7);
+        assert_eq!(settlement.realizedoddsbps, 10000);
+        assert_eq!(settlement.returnper100, 100);
+    }
+
+    #[test]
+    fn nobody_wins_a_value_nobody_bet_on() {
+        let containerofbets = market(&[("a", 100, "yes"), ("b", 100, "no")]);
+        let settlement = settlewithfee(&containerofbets, "maybe", 5);
+        assert!(settlement.winamounts.is_empty());
+        assert_eq!(settlement.totalbetamountforwinningvalue, 0);
+        assert_eq!(settlement.operatortake, 0);
+        assert_eq!(settlement.remainder, 0);
+        assert_eq!(settlement.realizedoddsbps, 0);
+        assert!(!settlement.unanimous);
+    }
+
+    #[test]
+    fn several_winning_values_share_the_pool_like_a_single_value() {
+        let containerofbets = market(&[("a", 100, "home"), ("b", 300, "draw"), ("c", 400, "away")]);
+        let settlement = settle(&containerofbets, "home,draw");
+        assert_eq!(winningvalues("home,draw"), vec!["home", "draw"]);
+        assert_eq!(settlement.totalbetamountforwinningvalue, 400);
+        assert_eq!(settlement.winamounts["a"], 200);
+        assert_eq!(settlement.winamounts["b"], 600);
+        assert!(!settlement.winamounts.contains_key("c"));
+    }
+
+    #[test]
+    fn totals_sum_the_bets_per_value() {
+        let totals = bettotals(&market(&[("a", 100, "yes"), ("b", 300, "no"), ("c", 200, "yes")]));
+        assert_eq!(totals.totalbetamount, 600);
+        assert_eq!(totals.betvalue_totalbetamount["yes"], 300);
+        assert_eq!(totals.betvalue_totalbetamount["no"], 300);
+    }
+
+    #[test]
+    fn haircut_scales_every_win_amount_down_to_the_available_iota() {
+        let mut winamounts: HashMap<String, i64> = HashMap::new();
+        winamounts.insert("a".to_string(), 300);
+        winamounts.insert("b".to_string(), 700);
+        let haircut = haircut(&winamounts, 500);
+        assert_eq!(haircut.fractionbps, 5000);
+        assert_eq!(haircut.winamounts["a"], 150);
+        assert_eq!(haircut.winamounts["b"], 350);
+        assert_eq!(haircut.residue, 0);
+    }
+
+    #[test]
+    fn haircut_rounds_down_and_reports_the_residue() {
+        let mut winamounts: HashMap<String, i64> = HashMap::new();
+        winamounts.insert("a".to_string(), 1);
+        winamounts.insert("b".to_string(), 1);
+        winamounts.insert("c".to_string(), 1);
+        let haircut = haircut(&winamounts, 2);
+        assert_eq!(haircut.fractionbps, 6666);
+        assert_eq!(haircut.winamounts.values().sum::<i64>(), 0);
+        assert_eq!(haircut.residue, 2);
+    }
+
+    #[test]
+    fn exported_bets_are_sorted_by_address_and_recreate_the_container() {
+        let mut containerofbets = market(&[("c", 200, "yes"), ("a", 100, "yes"), ("b", 300, "no")]);
+        containerofbets.map.get_mut("b").unwrap().placedat = 20;
+        let exported = exportedbets(&containerofbets, None);
+        let addresses: Vec<&str> = exported.iter().map(|bet| bet.address.as_str()).collect();
+        assert_eq!(addresses, vec!["a", "b", "c"]);
+        let recreated = containerofexportedbets(exported);
+        assert_eq!(recreated.map.len(), 3);
+        assert_eq!(recreated.map["b"].betamount, 300);
+        assert_eq!(recreated.map["b"].betisforvalue, "no");
+        assert_eq!(recreated.map["b"].placedat, 20);
+    }
+
+    #[test]
+    fn exported_bets_as_of_a_timestamp_are_rebuilt_from_the_history() {
+        let mut containerofbets = market(&[("a", 150, "yes"), ("b", 100, "no"), ("c", 50, "yes")]);
+        let topped: &mut Bet = containerofbets.map.get_mut("a").unwrap();
+        topped.placedat = 30;
+        topped.attachedamount = 60;
+        topped.requestid = "r3".to_string();
+        topped.history = vec![BetEntry { placedat: 10, betamount: 40, campaign: "".to_string() }, BetEntry { placedat: 20, betamount: 60, campaign: "".to_string() }, BetEntry { placedat: 30, betamount: 50, campaign: "".to_string() }];
+        containerofbets.map.get_mut("b").unwrap().placedat = 15;
+        // stored before the history was recorded
+        containerofbets.map.get_mut("c").unwrap().placedat = 25;
+
+        let exported = exportedbets(&containerofbets, Some(20));
+        assert_eq!(exported.len(), 2);
+        assert_eq!(exported[0].address, "a");
+        assert_eq!(exported[0].betamount, 100);
+        assert_eq!(exported[0].placedat, 20);
+        assert_eq!(exported[0].attachedamount, 60);
+        assert_eq!(exported[0].requestid, "");
+        assert_eq!(exported[1].address, "b");
+        assert_eq!(exported[1].betamount, 100);
+
+        // at the time of the last bet, the bet is exported as stored
+        let exported = exportedbets(&containerofbets, Some(30));
+        assert_eq!(exported.len(), 3);
+        assert_eq!(exported[0].betamount, 150);
+        assert_eq!(exported[0].requestid, "r3");
+
+        assert!(exportedbets(&containerofbets, Some(9)).is_empty());
+    }
+}
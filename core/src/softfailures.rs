@@ -0,0 +1,106 @@
+// Soft failures of calls that cannot have any effect, without access to the contract's context
+//
+// A bet after the end time or closing a market that was already closed cannot have any effect. By default such a call succeeds: it logs
+// the reason and returns the error code in ERROR, and a rejected bet is refunded. In strict mode the call fails instead, so that integrations
+// can retry on failed transactions, unless IOTA were sent with it, which a failed request would lose track of: the request then ends
+// rejected and records the IOTA as pending return to its sender. The contract decides on the handling by softfailurehandling alone.
+
+pub const ERR_MARKET_CLOSED: &str = "ERR_MARKET_CLOSED";
+pub const ERR_BETTING_NOT_ENDED: &str = "ERR_BETTING_NOT_ENDED";
+pub const ERR_NO_BETS: &str = "ERR_NO_BETS";
+
+// a soft failure with the error code returned in the result ERROR and the logged message
+#[derive(Debug, PartialEq)]
+pub struct SoftFailure {
+    pub error: &'static str,
+    pub message: &'static str,
+}
+
+pub const SOFTFAIL_MARKET_CLOSED: SoftFailure = SoftFailure { error: ERR_MARKET_CLOSED, message: "the prediction market was already closed" };
+pub const SOFTFAIL_REVEAL_PENDING: SoftFailure = SoftFailure { error: ERR_BETTING_NOT_ENDED, message: "closing a market with sealed bets can be only done after their reveal deadline has passed" };
+pub const SOFTFAIL_BETTING_NOT_ENDED: SoftFailure = SoftFailure { error: ERR_BETTING_NOT_ENDED, message: "closing the market can be only done after the end time for placing bets has passed" };
+pub const SOFTFAIL_NO_BETS: SoftFailure = SoftFailure { error: ERR_NO_BETS, message: "no bets stored" };
+
+#[derive(Debug, PartialEq)]
+pub enum SoftFailureHandling {
+    // the transaction fails with the error code
+    Fail,
+    // the request ends rejected, and the IOTA sent with it are recorded as pending return to the sender
+    PendingReturn,
+    // the IOTA, or tokens, sent with the call are refunded right away, like those of every rejected bet
+    Refund,
+    // the message is only logged, and the IOTA sent with the call stay with the contract
+    Log,
+}
+
+// The handling of a soft failure of a call in strict mode or not, with incoming IOTA, where refunded tells whether the call refunds
+// what came with it in the default mode, which a rejected bet does.
+pub fn softfailurehandling(strict: bool, incoming: i64, refunded: bool) -> SoftFailureHandling {
+    match (strict, incoming > 0, refunded) {
+        (true, true, _) => SoftFailureHandling::PendingReturn,
+        (true, false, _) => SoftFailureHandling::Fail,
+        (false, _, true) => SoftFailureHandling::Refund,
+        (false, _, false) => SoftFailureHandling::Log,
+    }
+}
+
+// The soft failure of closing a market at currtime, in this order: the market was already closed, the sealed bets can still be revealed,
+// or the end time for bets, 0 for none, has not passed. A market without bets is closed nevertheless before it fails with SOFTFAIL_NO_BETS.
+pub fn closefailure(marketclosed: bool, revealpending: bool, betenddatetime: i64, currtime: i64) -> Option<SoftFailure> {
+    if marketclosed {
+        return Some(SOFTFAIL_MARKET_CLOSED);
+    }
+    if revealpending {
+        return Some(SOFTFAIL_REVEAL_PENDING);
+    }
+    if betenddatetime != 0 && currtime <= betenddatetime {
+        return Some(SOFTFAIL_BETTING_NOT_ENDED);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::betting::{betrejection, BetRejection, ERR_BETS_FROZEN, ERR_BET_TOO_LATE};
+
+    const END: i64 = 1622570400;
+
+    #[test]
+    fn closing_fails_softly_until_the_market_can_be_closed() {
+        assert_eq!(closefailure(true, false, END, END + 1), Some(SOFTFAIL_MARKET_CLOSED));
+        assert_eq!(closefailure(true, true, END, END), Some(SOFTFAIL_MARKET_CLOSED));
+        assert_eq!(closefailure(false, true, END, END + 1), Some(SOFTFAIL_REVEAL_PENDING));
+        assert_eq!(closefailure(false, false, END, END), Some(SOFTFAIL_BETTING_NOT_ENDED));
+        assert_eq!(closefailure(false, false, END, END + 1), None);
+        // a market without end time can be closed at any time
+        assert_eq!(closefailure(false, false, 0, END), None);
+    }
+
+    #[test]
+    fn every_soft_failure_fails_in_strict_mode_and_succeeds_otherwise() {
+        // every soft-failure branch: the soft rejections of a bet, which refund in the default mode, and those of closemarket and cancelmarket
+        let frozen: BetRejection = betrejection(true, END, END, "yes", 100).unwrap();
+        let late: BetRejection = betrejection(false, END, END + 1, "yes", 100).unwrap();
+        assert!(frozen.soft && late.soft);
+        let branches: [(&str, bool); 6] = [
+            (frozen.error, true),
+            (late.error, true),
+            (closefailure(true, false, END, END + 1).unwrap().error, false),
+            (closefailure(false, true, END, END + 1).unwrap().error, false),
+            (closefailure(false, false, END, END).unwrap().error, false),
+            (SOFTFAIL_NO_BETS.error, false),
+        ];
+        assert_eq!(branches.iter().map(|(error, _refunded)| *error).collect::<Vec<&str>>(),
+            vec![ERR_BETS_FROZEN, ERR_BET_TOO_LATE, ERR_MARKET_CLOSED, ERR_BETTING_NOT_ENDED, ERR_BETTING_NOT_ENDED, ERR_NO_BETS]);
+        for (error, refunded) in branches.iter() {
+            // strict: fails without IOTA, and records the IOTA sent as pending return instead of failing with them
+            assert_eq!(softfailurehandling(true, 0, *refunded), SoftFailureHandling::Fail, "{}", error);
+            assert_eq!(softfailurehandling(true, 100, *refunded), SoftFailureHandling::PendingReturn, "{}", error);
+            // default: succeeds, a bet refunding what came with it
+            let handling: SoftFailureHandling = if *refunded { SoftFailureHandling::Refund } else { SoftFailureHandling::Log };
+            assert_eq!(softfailurehandling(false, 0, *refunded), handling, "{}", error);
+            assert_eq!(softfailurehandling(false, 100, *refunded), handling, "{}", error);
+        }
+    }
+}
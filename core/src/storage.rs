@@ -0,0 +1,266 @@
+// Layouts of the bets in the state
+//
+// The bets are stored in the map bets of the market, which holds the bet of every betting account under its address in the binary
+// layout of encodebet, and the addresses in the order of their first bet in the array betteraddresses. A bet thus only reads and
+// writes the bet of its own account instead of all bets.
+// Markets stored before the map kept the json of all bets, which can outgrow the size of a single value in the state, in chunks under
+// the keys containerofbetsjson_0 to containerofbetsjson_N, with the number of chunks in containerofbetsjson_chunks, and markets
+// stored before chunking under the key containerofbetsjson. Both are migrated to the map by the first call writing a bet.
+
+use crate::settlement::{Bet, BetEntry};
+use crate::canonical::CanonicalEncoder;
+
+
+// layouts of the bets: the json in a single value, as before chunking, the json in chunks, or one entry per bet in a map
+pub const STORAGE_JSON: &str = "json";
+pub const STORAGE_CHUNKED: &str = "chunked";
+pub const STORAGE_MAP: &str = "map";
+
+// version of the binary layout of a bet, its first byte: version 2 appends the history of the bet, version 3 the campaign of every entry
+const BETLAYOUT_V1: u8 = 1;
+const BETLAYOUT_V2: u8 = 2;
+const BETLAYOUT_V3: u8 = 3;
+
+pub fn chunkkey(index: i64) -> String {
+    "containerofbetsjson_".to_string() + &index.to_string()
+}
+
+// reassemble the json from the number of chunks and a function reading a chunk, or use the json stored without chunks
+pub fn joinchunks(unchunked: String, chunks: i64, chunk: impl Fn(i64) -> String) -> String {
+    if chunks == 0 {
+        return unchunked;
+    }
+    (0..chunks).map(chunk).collect()
+}
+
+// split the json into chunks of at most chunksize bytes, at character boundaries
+pub fn splitchunks(json: &str, chunksize: usize) -> Vec<&str> {
+    let mut chunks: Vec<&str> = Vec::new();
+    let mut start: usize = 0;
+    while start < json.len() {
+        let mut end: usize = json.len().min(start + chunksize);
+        while !json.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == start {
+            // a character longer than the chunk size is not split
+            end = start + json[start..].chars().next().unwrap().len_utf8();
+        }
+        chunks.push(&json[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+// layout recorded for the market, markets stored before the layout was recorded use a single value
+pub fn storageformat(recorded: &str) -> &str {
+    if recorded.is_empty() { STORAGE_JSON } else { recorded }
+}
+
+pub fn storageformatconsistent(recorded: &str, layout: &str, chunks: i64, unchunked: &str) -> bool {
+    let consistent: bool = match layout {
+        STORAGE_JSON => chunks == 0,
+        STORAGE_CHUNKED => unchunked.is_empty(),
+        _ => chunks == 0 && unchunked.is_empty(),
+    };
+    storageformat(recorded) == layout && consistent
+}
+
+// Binary layout of a bet in the map: the version byte followed by the fields like in the canonical encodings, a string as its length
+// as 4 bytes and its utf-8 bytes, an integer as 8 bytes, both big endian. Fields added later are appended with a new version.
+// The history is a list, its length as 4 bytes followed by the timestamp, the amount and the campaign of every entry.
+pub fn encodebet(bet: &Bet) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![BETLAYOUT_V3];
+    let mut encoder = CanonicalEncoder::new("");
+    encoder.int(bet.betamount as i64)
+        .string(&bet.betisforvalue)
+        .int(bet.placedat)
+        .int(bet.attachedamount)
+        .string(&bet.addressencoding)
+        .string(&bet.requestid)
+        .string(&bet.clientid)
+        .string(&bet.campaign)
+        .length(bet.history.len());
+    for entry in bet.history.iter() {
+        encoder.int(entry.placedat).int(entry.betamount).string(&entry.campaign);
+    }
+    bytes.extend(encoder.bytes());
+    bytes
+}
+
+pub fn decodebet(bytes: &[u8]) -> Result<Bet, String> {
+    let mut reader = BetReader { bytes, position: 1 };
+    let version: u8 = match bytes.first() {
+        Some(&BETLAYOUT_V1) => BETLAYOUT_V1,
+        Some(&BETLAYOUT_V2) => BETLAYOUT_V2,
+        Some(&BETLAYOUT_V3) => BETLAYOUT_V3,
+        Some(version) => return Err("unknown layout of a bet: ".to_string() + &version.to_string()),
+        None => return Err("no bet stored".to_string()),
+    };
+    let betamount: i64 = reader.int()?;
+    if betamount < 0 || betamount > i32::MAX as i64 {
+        return Err("bet amount out of range: ".to_string() + &betamount.to_string());
+    }
+    let mut bet = Bet {
+        betamount: betamount as i32,
+        betisforvalue: reader.string()?,
+        placedat: reader.int()?,
+        attachedamount: reader.int()?,
+        addressencoding: reader.string()?,
+        requestid: reader.string()?,
+        clientid: reader.string()?,
+        campaign: reader.string()?,
+        history: Vec::new(),
+    };
+    if version != BETLAYOUT_V1 {
+        for _i in 0..reader.length()? {
+            let placedat: i64 = reader.int()?;
+            let betamount: i64 = reader.int()?;
+            let campaign: String = if version == BETLAYOUT_V3 { reader.string()? } else { "".to_string() };
+            bet.history.push(BetEntry { placedat, betamount, campaign });
+        }
+    }
+    Ok(bet)
+}
+
+struct BetReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BetReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.position < length {
+            return Err("bet is truncated at byte ".to_string() + &self.position.to_string());
+        }
+        let taken: &'a [u8] = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(taken)
+    }
+
+    fn int(&mut self) -> Result<i64, String> {
+        let mut value: [u8; 8] = [0; 8];
+        value.copy_from_slice(self.take(8)?);
+        Ok(i64::from_be_bytes(value))
+    }
+
+    fn length(&mut self) -> Result<usize, String> {
+        let mut length: [u8; 4] = [0; 4];
+        length.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(length) as usize)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length: usize = self.length()?;
+        let bytes: &[u8] = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_error| "bet holds a string that is not utf-8".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storedbet() -> Bet {
+        Bet {
+            betamount: 150,
+            betisforvalue: "yes".to_string(),
+            placedat: 1622563260,
+            attachedamount: 151,
+            addressencoding: "b58".to_string(),
+            requestid: "request".to_string(),
+            clientid: "wallet".to_string(),
+            campaign: "".to_string(),
+            history: vec![
+                BetEntry { placedat: 1622563200, betamount: 100, campaign: "spring".to_string() },
+                BetEntry { placedat: 1622563260, betamount: 50, campaign: "".to_string() },
+            ],
+        }
+    }
+
+    #[test]
+    fn bet_round_trips_through_its_binary_layout() {
+        let bet: Bet = decodebet(&encodebet(&storedbet())).unwrap();
+        assert_eq!(bet.betamount, 150);
+        assert_eq!(bet.betisforvalue, "yes");
+        assert_eq!(bet.placedat, 1622563260);
+        assert_eq!(bet.attachedamount, 151);
+        assert_eq!(bet.addressencoding, "b58");
+        assert_eq!(bet.requestid, "request");
+        assert_eq!(bet.clientid, "wallet");
+        assert_eq!(bet.campaign, "");
+        assert_eq!(bet.history.len(), 2);
+        assert_eq!(bet.history[0].placedat, 1622563200);
+        assert_eq!(bet.history[0].betamount, 100);
+        assert_eq!(bet.history[1].betamount, 50);
+        assert_eq!(bet.history[0].campaign, "spring");
+        assert_eq!(bet.history[1].campaign, "");
+    }
+
+    #[test]
+    fn bet_of_the_second_layout_decodes_without_campaigns() {
+        // version 3 without the campaigns of the entries, empty ones of 4 bytes each, is version 2
+        let bet: Bet = Bet { history: storedbet().history.into_iter().map(|entry| BetEntry { campaign: "".to_string(), ..entry }).collect(), ..storedbet() };
+        let encoded: Vec<u8> = encodebet(&bet);
+        let historystart: usize = encoded.len() - 2 * 20;
+        let mut bytes: Vec<u8> = encoded[..historystart].to_vec();
+        bytes[0] = BETLAYOUT_V2;
+        for entry in 0..2 {
+            bytes.extend_from_slice(&encoded[historystart + entry * 20..historystart + entry * 20 + 16]);
+        }
+        let bet: Bet = decodebet(&bytes).unwrap();
+        assert_eq!(bet.history.len(), 2);
+        assert_eq!(bet.history[1].betamount, 50);
+        assert!(bet.history.iter().all(|entry| entry.campaign.is_empty()));
+    }
+
+    #[test]
+    fn bet_of_the_first_layout_decodes_without_history() {
+        // version 3 without the length of the history is version 1
+        let mut bytes: Vec<u8> = encodebet(&Bet { history: Vec::new(), ..storedbet() });
+        bytes.truncate(bytes.len() - 4);
+        bytes[0] = BETLAYOUT_V1;
+        let bet: Bet = decodebet(&bytes).unwrap();
+        assert_eq!(bet.betamount, 150);
+        assert_eq!(bet.campaign, "");
+        assert!(bet.history.is_empty());
+    }
+
+    #[test]
+    fn malformed_bets_are_rejected() {
+        assert_eq!(decodebet(&[]).err().unwrap(), "no bet stored");
+        assert_eq!(decodebet(&[9]).err().unwrap(), "unknown layout of a bet: 9");
+        let bytes: Vec<u8> = encodebet(&storedbet());
+        assert!(decodebet(&bytes[..bytes.len() - 1]).err().unwrap().starts_with("bet is truncated at byte"));
+        let mut negative: Vec<u8> = vec![BETLAYOUT_V3];
+        negative.extend_from_slice(&(-1i64).to_be_bytes());
+        assert_eq!(decodebet(&negative).err().unwrap(), "bet amount out of range: -1");
+    }
+
+    #[test]
+    fn chunks_split_at_character_boundaries_and_join_again() {
+        let json: &str = "{\"map\":{\"ä\":\"€€\"}}";
+        for chunksize in 1..json.len() + 2 {
+            let chunks: Vec<&str> = splitchunks(json, chunksize);
+            assert!(chunks.iter().all(|chunk| chunk.len() <= chunksize.max(3)));
+            let joined: String = joinchunks("".to_string(), chunks.len() as i64, |index| chunks[index as usize].to_string());
+            assert_eq!(joined, json);
+        }
+        assert!(splitchunks("", 4).is_empty());
+        assert_eq!(joinchunks("unchunked".to_string(), 0, |_index| "chunk".to_string()), "unchunked");
+    }
+
+    #[test]
+    fn layout_flags_have_to_match_the_stored_keys() {
+        assert_eq!(storageformat(""), STORAGE_JSON);
+        assert_eq!(storageformat(STORAGE_MAP), STORAGE_MAP);
+        assert!(storageformatconsistent("", STORAGE_JSON, 0, "{}"));
+        assert!(!storageformatconsistent("", STORAGE_JSON, 2, ""));
+        assert!(storageformatconsistent(STORAGE_CHUNKED, STORAGE_CHUNKED, 2, ""));
+        assert!(!storageformatconsistent(STORAGE_CHUNKED, STORAGE_CHUNKED, 2, "{}"));
+        assert!(storageformatconsistent(STORAGE_MAP, STORAGE_MAP, 0, ""));
+        assert!(!storageformatconsistent(STORAGE_MAP, STORAGE_MAP, 1, ""));
+        assert!(!storageformatconsistent(STORAGE_CHUNKED, STORAGE_MAP, 0, ""));
+        assert_eq!(chunkkey(3), "containerofbetsjson_3");
+    }
+}
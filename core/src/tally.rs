@@ -0,0 +1,193 @@
+// The bettor vote of a market whose resolvers stayed silent, without access to the contract's context
+//
+// A market initialized with VOTEWINDOW falls back to its bettors instead of the refunds of the dead-man switch: once the chain owner's
+// window has passed too, every betting account can vote on the outcome for VOTEWINDOW seconds, weighted by its bet amount, and
+// afterwards anyone can call tallyandsettle, which settles the market on the value with the most weight. Without votes it voids the market.
+// Values tied for the most weight are resolved by the TIEBREAK policy of the market:
+// * REFUND, the default, voids the market and refunds every bet, like a market without a vote,
+// * HASH selects one of the tied values by the hash of the TAG_TIEBREAK encoding of the market id, the tallied totals sorted by value
+//   and the request id of the transaction tallying the votes. The first 8 bytes of the hash, big endian, modulo the number of tied
+//   values are the index of the winner among the tied values sorted ascending. The request id is not known before the tallying
+//   transaction, so nobody can predict the winner, but anybody can derive it again from the inputs stored in the receipt.
+// The hash function is passed in, the contract uses blake2b-256.
+
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+use serde_with::serde_as;
+
+use crate::canonical::{CanonicalEncoder, TAG_TIEBREAK};
+
+pub const TIEBREAK_REFUND: &str = "REFUND";
+pub const TIEBREAK_HASH: &str = "HASH";
+
+// phases of the bettor vote: before the fallback reaches the bettors, while they vote, and once the votes can be tallied
+pub const VOTE_PENDING: &str = "pending";
+pub const VOTE_OPEN: &str = "open";
+pub const VOTE_TALLY: &str = "tally";
+
+// the inputs and the derivation of a winner selected among tied values, stored with the receipt of the market
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TieBreak {
+    // names the recipe above
+    pub recipe: String,
+    pub marketid: String,
+    pub totals: BTreeMap<String, i64>,
+    pub requestid: String,
+    // the tied values, sorted ascending
+    pub tied: Vec<String>,
+    // the hash of the encoding as hex, and the index of the winner among the tied values
+    pub digest: String,
+    pub index: i64,
+    pub winner: String,
+}
+
+// The phase of the vote at currtime, for a vote that opens at voteopensat and lasts votewindow seconds.
+pub fn votephase(currtime: i64, voteopensat: i64, votewindow: i64) -> &'static str {
+    if currtime <= voteopensat {
+        VOTE_PENDING
+    } else if currtime <= voteopensat.saturating_add(votewindow) {
+        VOTE_OPEN
+    } else {
+        VOTE_TALLY
+    }
+}
+
+// The weight of the votes per value, given as the voted value and the bet amount of every voting account.
+pub fn tallyvotes(votes: &[(String, i64)]) -> BTreeMap<String, i64> {
+    let mut totals: BTreeMap<String, i64> = BTreeMap::new();
+    for (betvalue, weight) in votes {
+        let total: &mut i64 = totals.entry(betvalue.to_string()).or_insert(0);
+        *total = total.saturating_add(*weight);
+    }
+    totals
+}
+
+// The values with the most weight, sorted ascending, none if no vote has any weight.
+pub fn pluralityvalues(totals: &BTreeMap<String, i64>) -> Vec<String> {
+    let most: i64 = totals.values().copied().max().unwrap_or(0);
+    if most <= 0 {
+        return Vec::new();
+    }
+    totals.iter().filter(|(_, total)| **total == most).map(|(betvalue, _)| betvalue.to_string()).collect()
+}
+
+// the bytes hashed to select a winner among the tied values
+pub fn tiebreakseed(marketid: &str, totals: &BTreeMap<String, i64>, requestid: &str) -> Vec<u8> {
+    let mut encoder = CanonicalEncoder::new(TAG_TIEBREAK);
+    encoder.string(marketid).length(totals.len());
+    for (betvalue, total) in totals {
+        encoder.string(betvalue).int(*total);
+    }
+    encoder.string(requestid).bytes()
+}
+
+// The winner among the plurality values of totals selected by the recipe above, None unless at least two values tie.
+pub fn tiebreak(marketid: &str, totals: &BTreeMap<String, i64>, requestid: &str, hash: &impl Fn(&[u8]) -> Vec<u8>) -> Option<TieBreak> {
+    let tied: Vec<String> = pluralityvalues(totals);
+    if tied.len() < 2 {
+        return None;
+    }
+    let digest: Vec<u8> = hash(&tiebreakseed(marketid, totals, requestid));
+    let mut prefix: [u8; 8] = [0; 8];
+    for (position, byte) in digest.iter().take(8).enumerate() {
+        prefix[position] = *byte;
+    }
+    let index: usize = (u64::from_be_bytes(prefix) % tied.len() as u64) as usize;
+    Some(TieBreak {
+        recipe: TAG_TIEBREAK.to_string(),
+        marketid: marketid.to_string(),
+        totals: totals.clone(),
+        requestid: requestid.to_string(),
+        winner: tied[index].clone(),
+        tied,
+        digest: digest.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        index: index as i64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // FNV-1a, not a cryptographic hash, but deterministic and sensitive to every byte, which is all the recipe needs here
+    fn hash(bytes: &[u8]) -> Vec<u8> {
+        let mut value: u64 = 0xcbf29ce484222325;
+        for byte in bytes {
+            value ^= *byte as u64;
+            value = value.wrapping_mul(0x100000001b3);
+        }
+        value.to_be_bytes().to_vec()
+    }
+
+    fn votes(entries: &[(&str, i64)]) -> Vec<(String, i64)> {
+        entries.iter().map(|(betvalue, weight)| (betvalue.to_string(), *weight)).collect()
+    }
+
+    #[test]
+    fn the_vote_opens_after_the_fallback_and_is_tallied_after_its_window() {
+        assert_eq!(votephase(1000, 1000, 600), VOTE_PENDING);
+        assert_eq!(votephase(1001, 1000, 600), VOTE_OPEN);
+        assert_eq!(votephase(1600, 1000, 600), VOTE_OPEN);
+        assert_eq!(votephase(1601, 1000, 600), VOTE_TALLY);
+    }
+
+    #[test]
+    fn the_value_with_the_most_weight_wins_without_a_tie_break() {
+        let totals: BTreeMap<String, i64> = tallyvotes(&votes(&[("home", 300), ("away", 200), ("home", 100), ("draw", 350)]));
+        assert_eq!(totals["home"], 400);
+        assert_eq!(pluralityvalues(&totals), vec!["home".to_string()]);
+        assert_eq!(tiebreak("derby", &totals, "request", &hash), None);
+        // votes without weight select nothing, so the market is voided
+        assert!(pluralityvalues(&tallyvotes(&votes(&[("home", 0)]))).is_empty());
+        assert!(pluralityvalues(&BTreeMap::new()).is_empty());
+    }
+
+    #[test]
+    fn the_derivation_reproduces_the_winner_of_an_exact_tie() {
+        let totals: BTreeMap<String, i64> = tallyvotes(&votes(&[("home", 500), ("away", 300), ("away", 200), ("draw", 100)]));
+        let selected: TieBreak = tiebreak("derby", &totals, "3yb7Lu9A", &hash).unwrap();
+        assert_eq!(selected.tied, vec!["away".to_string(), "home".to_string()]);
+        assert!(selected.tied.contains(&selected.winner));
+
+        // the receipt stores the tie break as json, from which the winner is derived again by hand
+        let stored: TieBreak = serde_json::from_str(&serde_json::to_string(&selected).unwrap()).unwrap();
+        let mut encoding: Vec<u8> = Vec::new();
+        for string in [stored.recipe.as_str(), stored.marketid.as_str()] {
+            encoding.extend_from_slice(&(string.len() as u32).to_be_bytes());
+            encoding.extend_from_slice(string.as_bytes());
+        }
+        encoding.extend_from_slice(&(stored.totals.len() as u32).to_be_bytes());
+        for (betvalue, total) in &stored.totals {
+            encoding.extend_from_slice(&(betvalue.len() as u32).to_be_bytes());
+            encoding.extend_from_slice(betvalue.as_bytes());
+            encoding.extend_from_slice(&total.to_be_bytes());
+        }
+        encoding.extend_from_slice(&(stored.requestid.len() as u32).to_be_bytes());
+        encoding.extend_from_slice(stored.requestid.as_bytes());
+        let digest: Vec<u8> = hash(&encoding);
+        assert_eq!(digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>(), stored.digest);
+        let mut prefix: [u8; 8] = [0; 8];
+        prefix.copy_from_slice(&digest[..8]);
+        let index: u64 = u64::from_be_bytes(prefix) % stored.tied.len() as u64;
+        assert_eq!(index as i64, stored.index);
+        assert_eq!(stored.tied[index as usize], stored.winner);
+        assert_eq!(stored, selected);
+    }
+
+    #[test]
+    fn the_tallying_request_changes_the_seed_of_the_tie_break() {
+        let totals: BTreeMap<String, i64> = tallyvotes(&votes(&[("a", 100), ("b", 100), ("c", 100)]));
+        let winners: Vec<String> = ["r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8"].iter()
+            .map(|requestid| tiebreak("market", &totals, requestid, &hash).unwrap().winner)
+            .collect();
+        // the same inputs select the same winner, while different requests spread over the tied values
+        assert_eq!(tiebreak("market", &totals, "r1", &hash).unwrap().winner, winners[0]);
+        let mut distinct: Vec<String> = winners.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert!(distinct.len() > 1);
+        assert_ne!(tiebreakseed("market", &totals, "r1"), tiebreakseed("other", &totals, "r1"));
+    }
+}
@@ -0,0 +1,83 @@
+// Acknowledgements of the terms of a market, without access to the contract's context
+//
+// A market initialized with a TERMSHASH only accepts the first bet of an account that acknowledges the terms by an ACKTERMS matching
+// the hash. The acknowledgement is recorded once per account as "hash|timestamp" in the termsacks map of the market, so that later bets
+// of the account need no ACKTERMS. An acknowledgement of other terms, e.g. of the market a clone was made from, does not count.
+// Without TERMSHASH nothing needs to be acknowledged.
+
+pub const ERR_TERMS_NOT_ACKED: &str = "ERR_TERMS_NOT_ACKED";
+
+// what a bet does about the terms of the market
+#[derive(Debug, PartialEq)]
+pub enum TermsCheck {
+    // the market has no terms, or the account acknowledged them before
+    Acknowledged,
+    // the bet acknowledges the terms, which is recorded as the value
+    Record(String),
+    // the bet is refunded with ERR_TERMS_NOT_ACKED
+    Refuse,
+}
+
+// the recorded acknowledgement of the terms with termshash at timestamp
+pub fn termsack(termshash: &str, timestamp: i64) -> String {
+    termshash.to_string() + "|" + &timestamp.to_string()
+}
+
+// The time the terms with termshash were acknowledged at according to the recorded acknowledgement, None if they were not,
+// Some(0) for a market without terms.
+pub fn acknowledgedat(termshash: &str, recorded: &str) -> Option<i64> {
+    if termshash.is_empty() {
+        return Some(0);
+    }
+    recorded.strip_prefix(termshash)
+        .and_then(|rest| rest.strip_prefix('|'))
+        .map(|timestamp| timestamp.parse::<i64>().unwrap_or(0))
+}
+
+// Check a bet at currtime against the terms with termshash, given the acknowledgement recorded for the account and the ACKTERMS of the bet.
+pub fn checkterms(termshash: &str, recorded: &str, ackterms: &str, currtime: i64) -> TermsCheck {
+    if acknowledgedat(termshash, recorded).is_some() {
+        return TermsCheck::Acknowledged;
+    }
+    if ackterms != termshash {
+        return TermsCheck::Refuse;
+    }
+    TermsCheck::Record(termsack(termshash, currtime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TERMSHASH: &str = "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi";
+    const NOW: i64 = 1622563200;
+
+    #[test]
+    fn the_first_bet_must_acknowledge_the_terms_and_later_ones_need_not() {
+        let mut recorded: String = "".to_string();
+        // the first bet without ACKTERMS and with the wrong hash is refused, nothing being recorded
+        assert_eq!(checkterms(TERMSHASH, &recorded, "", NOW), TermsCheck::Refuse);
+        assert_eq!(checkterms(TERMSHASH, &recorded, "8Ws3Jf2oB5Bm8NRLZuJ1jLi3XzLuKXzq6t9G1h4nMtwX", NOW), TermsCheck::Refuse);
+        assert_eq!(acknowledgedat(TERMSHASH, &recorded), None);
+        // with the correct hash, the acknowledgement is recorded
+        match checkterms(TERMSHASH, &recorded, TERMSHASH, NOW + 10) {
+            TermsCheck::Record(value) => recorded = value,
+            check => panic!("{:?}", check),
+        }
+        assert_eq!(recorded, TERMSHASH.to_string() + "|" + &(NOW + 10).to_string());
+        assert_eq!(acknowledgedat(TERMSHASH, &recorded), Some(NOW + 10));
+        // a subsequent bet is accepted without ACKTERMS, and even with a wrong one, and does not record it again
+        assert_eq!(checkterms(TERMSHASH, &recorded, "", NOW + 20), TermsCheck::Acknowledged);
+        assert_eq!(checkterms(TERMSHASH, &recorded, "wrong", NOW + 30), TermsCheck::Acknowledged);
+    }
+
+    #[test]
+    fn terms_of_another_market_do_not_count_and_without_terms_nothing_is_checked() {
+        let recorded: String = termsack("3xYvJ9", NOW);
+        assert_eq!(checkterms(TERMSHASH, &recorded, "", NOW), TermsCheck::Refuse);
+        // a hash that merely starts with the recorded one is not the recorded one
+        assert_eq!(acknowledgedat("3xYv", &recorded), None);
+        assert_eq!(checkterms("", "", "", NOW), TermsCheck::Acknowledged);
+        assert_eq!(acknowledgedat("", ""), Some(0));
+    }
+}
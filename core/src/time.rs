@@ -0,0 +1,208 @@
+// Times of a market, without access to the contract's context
+//
+// The end time for bets is given as text by the contract owner. The contract parses it with chrono's DateTime::parse_from_str in one of
+// BETENDFORMATS, all of which end with the offset to UTC "%z". Kept here are the rules chrono does not know: a time without offset is UTC
+// and can say so by a trailing "UTC", an offset together with that "UTC" is ambiguous, and an RFC 3339 timestamp ends with "Z" or an offset.
+// The rules at the end time are kept here as well: whether a bet is on time, and by how much a bet extends the end time.
+
+// formats of betendwithoffset's text, tried in this order, e.g. "2021-06-01 18:00+02:00", "2021-06-01 18:00:30 -0930" and
+// "2021-06-01T18:00:30.5+02:00", of which parse_from_str drops fractions of a second
+pub const BETENDFORMATS: [&str; 3] = ["%Y-%m-%d %H:%M%z", "%Y-%m-%d %H:%M:%S%z", "%Y-%m-%dT%H:%M:%S%.f%z"];
+
+// The end time for bets as text to parse in one of BETENDFORMATS. A time like "2021-06-01 18:00", optionally with seconds, is assumed to be
+// UTC and can be followed by "UTC" to state it explicitly, for which the offset "+0000" is appended. Alternatively, an offset to UTC like
+// "+02:00", "-0930" or "+05:30" follows the time, which must not be followed by "UTC" as well. A full RFC 3339 timestamp like
+// "2021-06-01T18:00:30Z" needs its "Z" or offset, "Z" becoming "+0000". Whether the date and time themselves are valid is left to chrono.
+pub fn betendwithoffset(input: &str) -> Result<String, String> {
+    let input: &str = input.trim();
+    let utcword: bool = input.len() >= 3 && input.is_char_boundary(input.len() - 3) && input[input.len() - 3..].eq_ignore_ascii_case("utc");
+    let text: &str = if utcword { input[..input.len() - 3].trim_end() } else { input };
+    // the time follows the date after a space, or after a "T" in an RFC 3339 timestamp
+    let separator: usize = text.find([' ', 'T', 't']).ok_or_else(betendformaterror)?;
+    let (date, time) = text.split_at(separator);
+    let rfc3339: bool = !time.starts_with(' ');
+    let zulu: bool = rfc3339 && (time.ends_with('Z') || time.ends_with('z'));
+    let offset: bool = zulu || time.contains('+') || time.contains('-');
+    if offset && utcword {
+        return Err("is ambiguous: it has both an offset and a trailing UTC".to_string());
+    }
+    if rfc3339 && !offset {
+        return Err(betendformaterror());
+    }
+    let time: String = if rfc3339 { "T".to_string() + &time[1..] } else { time.to_string() };
+    Ok(match (zulu, offset) {
+        (true, _) => date.to_string() + &time[..time.len() - 1] + "+0000",
+        (false, true) => date.to_string() + &time,
+        (false, false) => date.to_string() + &time + "+0000",
+    })
+}
+
+pub fn betendformaterror() -> String {
+    "must be formatted as YYYY-MM-DD HH:MM UTC, with an offset like YYYY-MM-DD HH:MM+HH:MM, or as RFC 3339 timestamp".to_string()
+}
+
+// Whether a bet at currtime is on time for the end time of bets, 0 for a market without end time. A bet in the very second
+// of the end time is on time. The end time is the one stored before the bet, an extension by the bet only applies after it was accepted.
+pub fn betontime(betenddatetime: i64, currtime: i64) -> bool {
+    betenddatetime == 0 || currtime <= betenddatetime
+}
+
+// time after which forcedrefund can refund all bets, resolutiongrace seconds after the end time for bets,
+// 0 without RESOLUTIONGRACE or end time for bets
+pub fn resolutiondeadlineafter(betenddatetime: i64, resolutiongrace: i64) -> i64 {
+    if resolutiongrace <= 0 || betenddatetime == 0 {
+        return 0;
+    }
+    betenddatetime.saturating_add(resolutiongrace)
+}
+
+// The end time for bets after a bet accepted at currtime, by which a bet within the final extensionwindow seconds before the end time
+// extends it to extensionwindow seconds after the bet. Ok(None) if the bet is not within the window or nothing can be extended,
+// and Err with the reason if the extension is refused, because it was already extended maxextensions times or the maxtotalextension
+// seconds of all extensions, 0 for no limit, are used up. The last extension within that limit only adds what is left of it.
+pub fn extendedbetend(betenddatetime: i64, currtime: i64, extensionwindow: i64, extensions: i64, maxextensions: i64, totalextension: i64, maxtotalextension: i64) -> Result<Option<i64>, String> {
+    if extensionwindow <= 0 || betenddatetime == 0 || betenddatetime - currtime >= extensionwindow {
+        return Ok(None);
+    }
+    if extensions >= maxextensions {
+        return Err("it was already extended ".to_string() + &extensions.to_string() + " times");
+    }
+    let mut extended: i64 = currtime.saturating_add(extensionwindow);
+    if maxtotalextension > 0 {
+        let remaining: i64 = maxtotalextension - totalextension;
+        if remaining <= 0 {
+            return Err("the extension budget of ".to_string() + &maxtotalextension.to_string() + " seconds is used up");
+        }
+        extended = extended.min(betenddatetime.saturating_add(remaining));
+    }
+    Ok(Some(extended))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    // the end time for bets like parsebetendutc of the contract parses it
+    fn parseutc(input: &str) -> Result<i64, String> {
+        let text: String = betendwithoffset(input)?;
+        BETENDFORMATS.iter()
+            .find_map(|format| DateTime::parse_from_str(&text, format).ok())
+            .map(|datetime| datetime.timestamp())
+            .ok_or_else(betendformaterror)
+    }
+
+    // 2021-06-01 18:00 UTC
+    const JUNEFIRST: i64 = 1622570400;
+
+    #[test]
+    fn times_are_utc_unless_an_offset_is_given() {
+        let cases: [(&str, i64); 16] = [
+            ("2021-06-01 18:00", JUNEFIRST),
+            ("2021-06-01 18:00 UTC", JUNEFIRST),
+            ("  2021-06-01 18:00utc ", JUNEFIRST),
+            ("2021-6-1 18:00", JUNEFIRST),
+            ("2021-06-01 18:00:30", JUNEFIRST + 30),
+            ("2021-06-01 18:00:30 UTC", JUNEFIRST + 30),
+            ("2021-06-01 18:00+02:00", JUNEFIRST - 7200),
+            ("2021-06-01 18:00 -0930", JUNEFIRST + 34200),
+            ("2021-06-01 18:00+05:30", JUNEFIRST - 19800),
+            ("2021-06-01 18:00-03:30", JUNEFIRST + 12600),
+            ("2021-06-01 18:00:30+05:30", JUNEFIRST + 30 - 19800),
+            ("2021-6-1 18:00:30 -00:30", JUNEFIRST + 30 + 1800),
+            ("2021-06-01T18:00:00Z", JUNEFIRST),
+            ("2021-06-01T18:00:30.25+02:00", JUNEFIRST + 30 - 7200),
+            ("2021-06-01t18:00:00-09:30", JUNEFIRST + 34200),
+            ("1970-01-01 00:00", 0),
+        ];
+        for (input, timestamp) in cases.iter() {
+            assert_eq!(parseutc(input), Ok(*timestamp), "{}", input);
+        }
+    }
+
+    #[test]
+    fn dates_are_counted_across_leap_years() {
+        assert_eq!(parseutc("2000-02-29 00:00"), Ok(951782400));
+        assert_eq!(parseutc("2024-03-01 00:00"), Ok(1709251200));
+        assert_eq!(parseutc("1969-12-31 23:59"), Ok(-60));
+        assert!(parseutc("2021-02-29 00:00").is_err());
+        assert!(parseutc("1900-02-29 00:00").is_err());
+    }
+
+    #[test]
+    fn malformed_times_are_rejected() {
+        let cases: [&str; 13] = [
+            "",
+            "2021-06-01",
+            "2021-06-01 18",
+            "2021-06-01 18:00Z",
+            "2021-13-01 18:00",
+            "2021-06-31 18:00",
+            "2021-06-01 24:00",
+            "2021-06-01 18:00 +2",
+            "2021-06-01 18:00+24:00",
+            "2021-06-01 18:00 CET",
+            "2021-06-01T18:00Z",
+            "2021-06-01T18:00:00",
+            "2021-06-01T18:00:00Z UTC",
+        ];
+        for input in cases.iter() {
+            assert!(parseutc(input).is_err(), "{}", input);
+        }
+    }
+
+    // end time for bets in the boundary tests
+    const END: i64 = JUNEFIRST;
+
+    #[test]
+    fn a_bet_at_the_end_time_is_on_time_and_one_second_later_is_late() {
+        assert!(betontime(END, END - 1));
+        assert!(betontime(END, END));
+        assert!(!betontime(END, END + 1));
+        assert!(betontime(0, END + 1));
+    }
+
+    #[test]
+    fn without_an_extension_window_a_bet_at_the_end_time_does_not_extend_it() {
+        assert_eq!(extendedbetend(END, END, 0, 0, 10, 0, 0), Ok(None));
+        assert!(!betontime(END, END + 1));
+    }
+
+    #[test]
+    fn a_bet_at_the_end_time_is_accepted_against_the_end_time_before_it_extends_it() {
+        // accepted against the end time stored before the bet
+        assert!(betontime(END, END));
+        // then extended by the full window after the bet
+        assert_eq!(extendedbetend(END, END, 300, 0, 10, 0, 0), Ok(Some(END + 300)));
+        // a bet one second after the end time is late, even though it would have been within the window
+        assert!(!betontime(END, END + 1));
+    }
+
+    #[test]
+    fn only_a_bet_within_the_final_window_extends_the_end_time() {
+        assert_eq!(extendedbetend(END, END - 300, 300, 0, 10, 0, 0), Ok(None));
+        assert_eq!(extendedbetend(END, END - 299, 300, 0, 10, 0, 0), Ok(Some(END + 1)));
+        assert_eq!(extendedbetend(0, END, 300, 0, 10, 0, 0), Ok(None));
+    }
+
+    #[test]
+    fn extensions_are_limited_in_number_and_total() {
+        assert!(extendedbetend(END, END, 300, 3, 3, 0, 0).unwrap_err().contains("3 times"));
+        assert_eq!(extendedbetend(END, END, 300, 1, 3, 500, 600), Ok(Some(END + 100)));
+        assert!(extendedbetend(END, END, 300, 2, 3, 600, 600).unwrap_err().contains("used up"));
+    }
+
+    #[test]
+    fn the_resolution_deadline_needs_a_grace_and_an_end_time() {
+        assert_eq!(resolutiondeadlineafter(END, 3600), END + 3600);
+        assert_eq!(resolutiondeadlineafter(END, 0), 0);
+        assert_eq!(resolutiondeadlineafter(0, 3600), 0);
+    }
+
+    #[test]
+    fn an_offset_with_a_trailing_utc_is_ambiguous() {
+        let error: String = parseutc("2021-06-01 18:00+02:00 UTC").unwrap_err();
+        assert!(error.contains("ambiguous"));
+        assert!(parseutc("2021-6-1 18:00:30 -0930 UTC").unwrap_err().contains("ambiguous"));
+    }
+}
@@ -0,0 +1,123 @@
+// Buffered writes to the state of the contract, without access to the contract's context
+//
+// A function that supports a dry run collects its writes in StateWrites instead of writing them right away.
+// A real run applies them at the end, a dry run returns them as its would-be effect and discards them, so both take the same path.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+// a written value, serialized as its plain json value
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum StateValue {
+    Int64(i64),
+    Text(String),
+}
+
+#[derive(Default)]
+pub struct StateWrites {
+    // the writes by key, a later write to a key replaces an earlier one
+    writes: BTreeMap<String, StateValue>,
+    // entries to append to string arrays, in the order they were appended
+    appends: Vec<(String, String)>,
+}
+
+#[derive(Serialize)]
+struct Effect {
+    writes: BTreeMap<String, StateValue>,
+    appends: Vec<BTreeMap<String, String>>,
+}
+
+impl StateWrites {
+    pub fn new() -> StateWrites {
+        StateWrites { writes: BTreeMap::new(), appends: Vec::new() }
+    }
+
+    pub fn set_int64(&mut self, key: &str, value: i64) {
+        self.writes.insert(key.to_string(), StateValue::Int64(value));
+    }
+
+    pub fn set_string(&mut self, key: &str, value: &str) {
+        self.writes.insert(key.to_string(), StateValue::Text(value.to_string()));
+    }
+
+    pub fn append(&mut self, array: &str, entry: &str) {
+        self.appends.push((array.to_string(), entry.to_string()));
+    }
+
+    // Pass everything to write, every write by its key and then every append in its order, to the functions writing the state.
+    pub fn apply(&self, mut write: impl FnMut(&str, &StateValue), mut append: impl FnMut(&str, &str)) {
+        for (key, value) in self.writes.iter() {
+            write(key, value);
+        }
+        for (array, entry) in self.appends.iter() {
+            append(array, entry);
+        }
+    }
+
+    // the would-be effect as json, e.g. {"writes":{"bucket_strays":5},"appends":[{"array":"adminlog","entry":"..."}]}
+    pub fn effect(&self) -> String {
+        let effect = Effect {
+            writes: self.writes.clone(),
+            appends: self.appends.iter().map(|(array, entry)| {
+                let mut append: BTreeMap<String, String> = BTreeMap::new();
+                append.insert("array".to_string(), array.clone());
+                append.insert("entry".to_string(), entry.clone());
+                append
+            }).collect(),
+        };
+        serde_json::to_string(&effect).expect("failed to make json of state writes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the state written by a real run
+    #[derive(Default)]
+    struct State {
+        values: BTreeMap<String, StateValue>,
+        arrays: BTreeMap<String, Vec<String>>,
+    }
+
+    fn realrun(writes: &StateWrites, state: &mut State) {
+        let State { values, arrays } = state;
+        writes.apply(|key, value| { values.insert(key.to_string(), value.clone()); },
+            |array, entry| arrays.entry(array.to_string()).or_default().push(entry.to_string()));
+    }
+
+    #[test]
+    fn a_real_run_writes_what_a_dry_run_reports() {
+        let mut writes = StateWrites::new();
+        writes.set_int64("bucket_strays", 5);
+        writes.set_string("transfersblocked", "false");
+        writes.append("adminlog", "1622563200|reconcile|105|100");
+        writes.append("adminlog", "1622563200|note");
+
+        let mut state = State::default();
+        realrun(&writes, &mut state);
+        assert_eq!(state.values, writes.writes);
+        assert_eq!(state.arrays["adminlog"], vec!["1622563200|reconcile|105|100", "1622563200|note"]);
+        assert_eq!(state.arrays.len(), 1);
+    }
+
+    #[test]
+    fn a_later_write_to_a_key_replaces_an_earlier_one() {
+        let mut writes = StateWrites::new();
+        writes.set_int64("lastreconciled", 1);
+        writes.set_int64("lastreconciled", 2);
+        let mut state = State::default();
+        realrun(&writes, &mut state);
+        assert_eq!(state.values["lastreconciled"], StateValue::Int64(2));
+        assert_eq!(state.values.len(), 1);
+    }
+
+    #[test]
+    fn discarded_writes_write_nothing() {
+        let mut state = State::default();
+        realrun(&StateWrites::default(), &mut state);
+        assert!(state.values.is_empty() && state.arrays.is_empty());
+    }
+}
@@ -0,0 +1,516 @@
+// Stateful model test of the lifecycle of a market
+//
+// Random sequences of operations, i.e. bets of a few accounts with random amounts and values, the passing of time, the early
+// declaration of the outcome that freezes the bets, closing with a random outcome, cancelling, the forced refund after the
+// resolution grace, batched payouts, claims and sweeps, are applied to two markets: a simulation of the contract, which takes
+// every decision with the functions of this crate like the contract does, and a reference model computing the same in the
+// plainest way. After every step the balance of the contract, its buckets, the wallets and the entitlements are compared,
+// and a diverging sequence is shrunk to a minimal one by leaving out operations as long as it still diverges.
+// The contract can neither withdraw a bet nor unfreeze the bets of a market, so neither is an operation here.
+
+use prediction_market_core::betting::*;
+use prediction_market_core::ledger::*;
+use prediction_market_core::payouts::*;
+use prediction_market_core::recording::*;
+use prediction_market_core::settlement::*;
+use prediction_market_core::time::*;
+use std::collections::{BTreeMap, HashMap};
+
+const ACCOUNTS: i64 = 5;
+const WALLET: i64 = 10000;
+const START: i64 = 1622563200;
+const BETEND: i64 = START + 3600;
+const VALUES: [&str; 4] = ["yes", "no", "draw", ""];
+
+// settings of a market, drawn for every sequence
+#[derive(Clone, Copy, Debug)]
+struct Settings {
+    feepercent: i64,
+    maxsharebps: i64,
+    maxshareexemptpool: i64,
+    betgranularity: i64,
+    minbet: i64,
+    resolutiongrace: i64,
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Bet { account: i64, value: &'static str, amount: i64 },
+    Advance { seconds: i64 },
+    Declare,
+    Close { outcome: &'static str },
+    Cancel,
+    ForcedRefund,
+    Payout { maxcount: i64 },
+    Claim { account: i64 },
+    Sweep,
+}
+
+fn address(account: i64) -> String {
+    "account".to_string() + &account.to_string()
+}
+
+// what the contract and the model are compared by after every step
+#[derive(Debug, PartialEq)]
+struct Observed {
+    balance: i64,
+    pools: i64,
+    strays: i64,
+    wallets: BTreeMap<String, i64>,
+    creator: i64,
+    // address, amount and whether it was paid, in the order of recording
+    entitlements: Vec<(String, i64, bool)>,
+    closed: bool,
+}
+
+// the contract, deciding with the functions of this crate
+struct Contract {
+    settings: Settings,
+    now: i64,
+    betsfrozen: bool,
+    closed: bool,
+    containerofbets: ContainerOfBets,
+    totalbetamount: i64,
+    balance: i64,
+    buckets: HashMap<&'static str, i64>,
+    wallets: BTreeMap<String, i64>,
+    creator: i64,
+    entitlements: Vec<(String, i64, bool)>,
+    payoutcursor: i64,
+    violations: Vec<&'static str>,
+}
+
+impl Contract {
+    fn new(settings: Settings) -> Contract {
+        Contract {
+            settings,
+            now: START,
+            betsfrozen: false,
+            closed: false,
+            containerofbets: ContainerOfBets { map: HashMap::new() },
+            totalbetamount: 0,
+            balance: 0,
+            buckets: BUCKETS.iter().map(|bucket| (*bucket, 0)).collect(),
+            wallets: (0..ACCOUNTS).map(|account| (address(account), WALLET)).collect(),
+            creator: 0,
+            entitlements: Vec::new(),
+            payoutcursor: 0,
+            violations: Vec::new(),
+        }
+    }
+
+    // transfer from a bucket, guarded like every transfer of the contract, to an account or to the creator for ""
+    fn transfer(&mut self, to: &str, amount: i64, bucket: &'static str) -> i64 {
+        let (transferred, violations) = guardtransfer(amount, self.balance, Some(self.buckets[bucket]));
+        self.violations.extend(violations);
+        self.balance -= transferred;
+        *self.buckets.get_mut(bucket).unwrap() -= transferred;
+        if to.is_empty() {
+            self.creator += transferred;
+        } else {
+            *self.wallets.get_mut(to).unwrap() += transferred;
+        }
+        transferred
+    }
+
+    fn bet(&mut self, account: i64, value: &str, incoming: i64) {
+        let betteraddress: String = address(account);
+        // the incoming IOTA are strays until the bet is accepted
+        *self.wallets.get_mut(&betteraddress).unwrap() -= incoming;
+        self.balance += incoming;
+        *self.buckets.get_mut(BUCKET_STRAYS).unwrap() += incoming;
+
+        let previousbet: Option<&Bet> = self.containerofbets.map.get(&betteraddress);
+        let previousamount: i64 = previousbet.map(|bet| bet.betamount as i64).unwrap_or(0);
+        let rejected: bool = self.closed
+            || betrejection(self.betsfrozen, BETEND, self.now, value, incoming).is_some()
+            || previousbet.is_some_and(|bet| bet.betisforvalue != value);
+        let betamount: i64 = if rejected { 0 } else {
+            flooredbetamount(cappedbetamount(self.totalbetamount, previousamount, incoming, self.settings.maxsharebps, self.settings.maxshareexemptpool), self.settings.betgranularity)
+        };
+        if betamount == 0 || betamount < self.settings.minbet {
+            self.transfer(&betteraddress, incoming, BUCKET_STRAYS);
+            return;
+        }
+        self.transfer(&betteraddress, incoming - betamount, BUCKET_STRAYS);
+        *self.buckets.get_mut(BUCKET_STRAYS).unwrap() -= betamount;
+        *self.buckets.get_mut(BUCKET_POOLS).unwrap() += betamount;
+        self.totalbetamount += betamount;
+        let bet: &mut Bet = self.containerofbets.map.entry(betteraddress).or_insert_with(|| Bet {
+            betamount: 0,
+            betisforvalue: value.to_string(),
+            placedat: 0,
+            attachedamount: 0,
+            addressencoding: "base58".to_string(),
+            requestid: "".to_string(),
+            clientid: "".to_string(),
+            campaign: "".to_string(),
+            history: Vec::new(),
+        });
+        bet.betamount += betamount as i32;
+        bet.placedat = self.now;
+        bet.attachedamount += incoming;
+        bet.history.push(BetEntry { placedat: self.now, betamount, campaign: "".to_string() });
+    }
+
+    // address and bet amount of every bet, in the order of the addresses
+    fn sortedbets(&self) -> Vec<(String, i64)> {
+        let mut bets: Vec<(String, i64)> = self.containerofbets.map.iter().map(|(address, bet)| (address.clone(), bet.betamount as i64)).collect();
+        bets.sort();
+        bets
+    }
+
+    fn refundallbets(&mut self) {
+        self.closed = true;
+        self.record(&refundterms());
+    }
+
+    // the entitlements of every bet under the terms, in the order of the addresses
+    fn record(&mut self, terms: &RecordingTerms) {
+        for (betteraddress, _betamount) in self.sortedbets() {
+            let amount: i64 = recordedamount(terms, &betteraddress, &self.containerofbets.map[&betteraddress], 0);
+            if amount != 0 {
+                self.entitlements.push((betteraddress, amount, false));
+            }
+        }
+    }
+
+    fn close(&mut self, outcome: &str) {
+        if self.closed || betontime(BETEND, self.now) {
+            return;
+        }
+        let totals: BetTotals = bettotals(&self.containerofbets);
+        let winningtotal: i32 = winningvalues(outcome).iter().map(|betvalue| *totals.betvalue_totalbetamount.get(*betvalue).unwrap_or(&0)).sum();
+        if winningtotal == 0 {
+            self.refundallbets();
+            return;
+        }
+        self.closed = true;
+        let settlement: SettlementResult = settlewithtotals(&self.containerofbets, totals, outcome, self.settings.feepercent);
+        if settlement.operatortake > 0 {
+            self.transfer("", settlement.operatortake, BUCKET_POOLS);
+        }
+        self.record(&settlementterms(&settlement, outcome, false, None));
+    }
+
+    fn pay(&mut self, index: usize) {
+        let (betteraddress, amount, paid) = self.entitlements[index].clone();
+        if !paid {
+            self.transfer(&betteraddress, amount, BUCKET_POOLS);
+            self.entitlements[index].2 = true;
+        }
+    }
+
+    fn apply(&mut self, op: &Op) {
+        match op {
+            Op::Bet { account, value, amount } => self.bet(*account, value, *amount),
+            Op::Advance { seconds } => self.now += seconds,
+            Op::Declare => if !self.closed { self.betsfrozen = true },
+            Op::Close { outcome } => self.close(outcome),
+            Op::Cancel => if !self.closed { self.refundallbets() },
+            Op::ForcedRefund => {
+                let deadline: i64 = resolutiondeadlineafter(BETEND, self.settings.resolutiongrace);
+                if !self.closed && deadline != 0 && self.now > deadline {
+                    self.refundallbets();
+                }
+            }
+            Op::Payout { maxcount } => {
+                let end: i64 = payoutbatchend(self.payoutcursor, self.entitlements.len() as i64, *maxcount, 0);
+                for index in self.payoutcursor..end {
+                    self.pay(index as usize);
+                }
+                self.payoutcursor = self.payoutcursor.max(end);
+            }
+            Op::Claim { account } => {
+                if let Some(index) = self.entitlements.iter().position(|(betteraddress, _amount, _paid)| *betteraddress == address(*account)) {
+                    self.pay(index);
+                }
+            }
+            Op::Sweep => {
+                if self.closed && payoutsdone(self.payoutcursor, self.entitlements.len() as i64, false) {
+                    let unpaid: i64 = self.entitlements.iter().filter(|(_address, _amount, paid)| !paid).map(|(_address, amount, _paid)| amount).sum();
+                    let dust: i64 = self.buckets[BUCKET_POOLS] - unpaid;
+                    let strays: i64 = self.buckets[BUCKET_STRAYS];
+                    self.transfer("", dust, BUCKET_POOLS);
+                    self.transfer("", strays, BUCKET_STRAYS);
+                }
+            }
+        }
+    }
+
+    fn observed(&self) -> Observed {
+        Observed {
+            balance: self.balance,
+            pools: self.buckets[BUCKET_POOLS],
+            strays: self.buckets[BUCKET_STRAYS],
+            wallets: self.wallets.clone(),
+            creator: self.creator,
+            entitlements: self.entitlements.clone(),
+            closed: self.closed,
+        }
+    }
+}
+
+// the reference model, keeping only the stakes and the IOTA of the market
+struct Model {
+    settings: Settings,
+    now: i64,
+    frozen: bool,
+    closed: bool,
+    // value and amount bet by every account
+    stakes: BTreeMap<String, (String, i64)>,
+    pool: i64,
+    wallets: BTreeMap<String, i64>,
+    creator: i64,
+    entitlements: Vec<(String, i64, bool)>,
+    paidupto: usize,
+    // leaves out the fee, to check that a divergence is found and shrunk
+    faulty: bool,
+}
+
+impl Model {
+    fn new(settings: Settings, faulty: bool) -> Model {
+        Model {
+            settings,
+            now: START,
+            frozen: false,
+            closed: false,
+            stakes: BTreeMap::new(),
+            pool: 0,
+            wallets: (0..ACCOUNTS).map(|account| (address(account), WALLET)).collect(),
+            creator: 0,
+            entitlements: Vec::new(),
+            paidupto: 0,
+            faulty,
+        }
+    }
+
+    // whether an account may hold the stake after its bet of amount: at most maxsharebps of the pool after the bet,
+    // unless the bets of the others are no more than the exempt pool
+    fn withinshare(&self, stake: i64, amount: i64) -> bool {
+        let maxsharebps: i64 = self.settings.maxsharebps;
+        let others: i64 = self.pool - stake;
+        maxsharebps <= 0 || maxsharebps >= 10000 || others <= self.settings.maxshareexemptpool || (stake + amount) * 10000 <= maxsharebps * (self.pool + amount)
+    }
+
+    // the largest amount on the grid of the granularity up to the incoming IOTA, within the share
+    fn acceptedamount(&self, stake: i64, incoming: i64) -> i64 {
+        let step: i64 = self.settings.betgranularity.max(1);
+        let mut amount: i64 = incoming - incoming % step;
+        while amount > 0 && !self.withinshare(stake, amount) {
+            amount -= step;
+        }
+        amount
+    }
+
+    fn bet(&mut self, account: i64, value: &str, incoming: i64) {
+        let betteraddress: String = address(account);
+        let stake: Option<(String, i64)> = self.stakes.get(&betteraddress).cloned();
+        if self.closed || self.frozen || self.now > BETEND || value.is_empty() || incoming == 0
+            || stake.as_ref().is_some_and(|(betvalue, _amount)| betvalue != value) {
+            return;
+        }
+        let amount: i64 = self.acceptedamount(stake.as_ref().map_or(0, |(_betvalue, amount)| *amount), incoming);
+        if amount == 0 || amount < self.settings.minbet {
+            return;
+        }
+        *self.wallets.get_mut(&betteraddress).unwrap() -= amount;
+        self.pool += amount;
+        self.stakes.entry(betteraddress).or_insert((value.to_string(), 0)).1 += amount;
+    }
+
+    fn refundallbets(&mut self) {
+        self.closed = true;
+        self.entitlements = self.stakes.iter().map(|(address, (_betvalue, amount))| (address.clone(), *amount, false)).collect();
+    }
+
+    fn close(&mut self, outcome: &str) {
+        if self.closed || self.now <= BETEND {
+            return;
+        }
+        let winning: i64 = self.stakes.values().filter(|(betvalue, _amount)| betvalue == outcome).map(|(_betvalue, amount)| amount).sum();
+        if winning == 0 {
+            self.refundallbets();
+            return;
+        }
+        self.closed = true;
+        // a unanimous market refunds the bets without a fee
+        let fee: i64 = if winning == self.pool || self.faulty { 0 } else { self.pool * self.settings.feepercent / 100 };
+        self.pool -= fee;
+        self.creator += fee;
+        let mut winners: Vec<(String, i64)> = self.stakes.iter()
+            .filter(|(_address, (betvalue, _amount))| betvalue == outcome)
+            .map(|(address, (_betvalue, amount))| (address.clone(), (*amount as i128 * self.pool as i128 / winning as i128) as i64))
+            .collect();
+        // the remainder of rounding down goes to the largest winner, the first address of equal ones
+        let remainder: i64 = self.pool - winners.iter().map(|(_address, winamount)| winamount).sum::<i64>();
+        let mut largest: usize = 0;
+        for (index, (_address, winamount)) in winners.iter().enumerate() {
+            if *winamount > winners[largest].1 {
+                largest = index;
+            }
+        }
+        winners[largest].1 += remainder;
+        self.entitlements = winners.into_iter().filter(|(_address, winamount)| *winamount != 0).map(|(address, winamount)| (address, winamount, false)).collect();
+    }
+
+    fn pay(&mut self, index: usize) {
+        if !self.entitlements[index].2 {
+            self.entitlements[index].2 = true;
+            self.pool -= self.entitlements[index].1;
+            *self.wallets.get_mut(&self.entitlements[index].0).unwrap() += self.entitlements[index].1;
+        }
+    }
+
+    fn apply(&mut self, op: &Op) {
+        match op {
+            Op::Bet { account, value, amount } => self.bet(*account, value, *amount),
+            Op::Advance { seconds } => self.now += seconds,
+            Op::Declare => self.frozen = self.frozen || !self.closed,
+            Op::Close { outcome } => self.close(outcome),
+            Op::Cancel => if !self.closed { self.refundallbets() },
+            Op::ForcedRefund => {
+                if !self.closed && self.settings.resolutiongrace > 0 && self.now > BETEND + self.settings.resolutiongrace {
+                    self.refundallbets();
+                }
+            }
+            Op::Payout { maxcount } => {
+                let end: usize = self.entitlements.len().min(self.paidupto + *maxcount as usize);
+                for index in self.paidupto..end {
+                    self.pay(index);
+                }
+                self.paidupto = self.paidupto.max(end);
+            }
+            Op::Claim { account } => {
+                if let Some(index) = self.entitlements.iter().position(|(betteraddress, _amount, _paid)| *betteraddress == address(*account)) {
+                    self.pay(index);
+                }
+            }
+            Op::Sweep => {
+                if self.closed && self.paidupto >= self.entitlements.len() {
+                    let unpaid: i64 = self.entitlements.iter().filter(|(_address, _amount, paid)| !paid).map(|(_address, amount, _paid)| amount).sum();
+                    self.creator += self.pool - unpaid;
+                    self.pool = unpaid;
+                }
+            }
+        }
+    }
+
+    fn observed(&self) -> Observed {
+        Observed {
+            balance: self.pool,
+            pools: self.pool,
+            strays: 0,
+            wallets: self.wallets.clone(),
+            creator: self.creator,
+            entitlements: self.entitlements.clone(),
+            closed: self.closed,
+        }
+    }
+}
+
+// Apply the operations to the contract and the model, returning the first step they diverge at, or the contract breaks the ledger.
+fn run(settings: Settings, ops: &[Op], faulty: bool) -> Result<(), String> {
+    let mut contract: Contract = Contract::new(settings);
+    let mut model: Model = Model::new(settings, faulty);
+    for (step, op) in ops.iter().enumerate() {
+        contract.apply(op);
+        model.apply(op);
+        let observed: Observed = contract.observed();
+        let expected: Observed = model.observed();
+        if observed != expected {
+            return Err(format!("step {} {:?}: the contract has {:?}, the model {:?}", step, op, observed, expected));
+        }
+        let bucketstotal: i64 = contract.buckets.values().sum();
+        if !ledgerinvariantholds(contract.balance, bucketstotal, 0) || !contract.violations.is_empty() {
+            return Err(format!("step {} {:?}: balance {}, buckets {}, violations {:?}", step, op, contract.balance, bucketstotal, contract.violations));
+        }
+        if observed.wallets.values().sum::<i64>() + observed.creator + observed.balance != ACCOUNTS * WALLET {
+            return Err(format!("step {} {:?}: IOTA were created or lost", step, op));
+        }
+    }
+    Ok(())
+}
+
+// Leave out single operations of a diverging sequence as long as it still diverges.
+fn shrink(settings: Settings, mut ops: Vec<Op>, faulty: bool) -> Vec<Op> {
+    let mut index: usize = 0;
+    while index < ops.len() {
+        let mut candidate: Vec<Op> = ops.clone();
+        candidate.remove(index);
+        if run(settings, &candidate, faulty).is_err() {
+            ops = candidate;
+        } else {
+            index += 1;
+        }
+    }
+    ops
+}
+
+// deterministic random numbers, so that every failure can be reproduced by its seed
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, bound: i64) -> i64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as i64
+    }
+
+    fn pick<T: Copy>(&mut self, choices: &[T]) -> T {
+        choices[self.below(choices.len() as i64) as usize]
+    }
+}
+
+fn generated(seed: u64, length: usize) -> (Settings, Vec<Op>) {
+    let mut random: Random = Random(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1);
+    let settings: Settings = Settings {
+        feepercent: random.pick(&[0, 2, 5]),
+        maxsharebps: random.pick(&[0, 4000, 6000]),
+        maxshareexemptpool: random.pick(&[0, 500]),
+        betgranularity: random.pick(&[0, 10]),
+        minbet: random.pick(&[0, 50]),
+        resolutiongrace: random.pick(&[0, 3600]),
+    };
+    let ops: Vec<Op> = (0..length).map(|_i| match random.below(20) {
+        0..=8 => Op::Bet { account: random.below(ACCOUNTS), value: random.pick(&VALUES), amount: random.below(2000) },
+        9..=10 => Op::Advance { seconds: random.pick(&[60, 1800, 3600, 7200]) },
+        11 => Op::Declare,
+        12..=13 => Op::Close { outcome: random.pick(&VALUES[..3]) },
+        14 => if random.below(2) == 0 { Op::Cancel } else { Op::ForcedRefund },
+        15..=16 => Op::Payout { maxcount: 1 + random.below(3) },
+        17..=18 => Op::Claim { account: random.below(ACCOUNTS) },
+        _ => Op::Sweep,
+    }).collect();
+    (settings, ops)
+}
+
+#[test]
+fn the_contract_follows_the_model_over_random_lifecycles() {
+    for seed in 0..500 {
+        let (settings, ops) = generated(seed, 40);
+        if let Err(divergence) = run(settings, &ops, false) {
+            let shrunk: Vec<Op> = shrink(settings, ops, false);
+            panic!("seed {} with {:?} diverges at {}\nminimal sequence: {:?}", seed, settings, divergence, shrunk);
+        }
+    }
+}
+
+#[test]
+fn a_divergence_shrinks_to_a_minimal_sequence() {
+    // a model leaving out the fee diverges once bets on two values of a market with a fee are closed
+    let (settings, ops) = (0..).map(|seed| generated(seed, 40))
+        .find(|(settings, ops)| settings.feepercent > 0 && run(*settings, ops, true).is_err())
+        .unwrap();
+    let shrunk: Vec<Op> = shrink(settings, ops, true);
+    assert!(run(settings, &shrunk, true).is_err());
+    assert_eq!(shrunk.iter().filter(|op| matches!(op, Op::Bet { .. })).count(), 2, "{:?}", shrunk);
+    assert!(matches!(shrunk.last(), Some(Op::Close { .. })), "{:?}", shrunk);
+    // leaving out any operation of it makes the divergence disappear
+    for index in 0..shrunk.len() {
+        let mut candidate: Vec<Op> = shrunk.clone();
+        candidate.remove(index);
+        assert!(run(settings, &candidate, true).is_ok(), "{:?}", candidate);
+    }
+}
@@ -0,0 +1,113 @@
+// Replay the settlement of an exported market locally
+//
+// Usage: cargo run --example replay -- bets.json --outcome yes [--available 1000]
+//
+// bets.json holds the BETS result of the exportmarket view. The bets are settled for the outcome with the same settlement
+// functions as the contract, and the win amount of every winner, the fee, the dust and a conservation check are printed,
+// followed by the figures of the receipt returned by getreceipt as json.
+// With --available, the win amounts are scaled down to the available IOTA like with the HAIRCUT shortfall policy.
+
+use prediction_market_core::settlement::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::process;
+
+
+// figures of the receipt, named like in the receipt stored by the contract
+#[derive(Serialize)]
+struct ReplayedReceipt {
+    outcome: String,
+    betvaluewinning: String,
+    totalbetamount: i64,
+    totalbetamountforwinningvalue: i64,
+    realizedoddsbps: i64,
+    operatortake: i64,
+    returnper100: i64,
+    shortfall: i64,
+    fractionbps: i64,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: replay <bets.json> --outcome <value> [--available <IOTA>]");
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut betsfile: Option<String> = None;
+    let mut outcome: Option<String> = None;
+    let mut available: Option<i64> = None;
+    let mut i: usize = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--outcome" => {
+                outcome = Some(args.get(i + 1).cloned().unwrap_or_else(|| usage()));
+                i = i + 1;
+            }
+            "--available" => {
+                available = Some(args.get(i + 1).and_then(|value| value.parse::<i64>().ok()).unwrap_or_else(|| usage()));
+                i = i + 1;
+            }
+            file => betsfile = Some(file.to_string()),
+        }
+        i = i + 1;
+    }
+    let (betsfile, outcome) = match (betsfile, outcome) {
+        (Some(betsfile), Some(outcome)) => (betsfile, outcome),
+        _ => usage(),
+    };
+
+    let betsjson: String = std::fs::read_to_string(&betsfile).unwrap_or_else(|error| {
+        eprintln!("cannot read {}: {}", betsfile, error);
+        process::exit(1);
+    });
+    let bets: Vec<ExportedBet> = serde_json::from_str(&betsjson).unwrap_or_else(|error| {
+        eprintln!("{} does not hold exported bets: {}", betsfile, error);
+        process::exit(1);
+    });
+    let containerofbets: ContainerOfBets = containerofexportedbets(bets);
+    let settlement: SettlementResult = settle(&containerofbets, &outcome);
+
+    let totalwinamount: i64 = settlement.winamounts.values().sum();
+    let mut winamounts: HashMap<String, i64> = settlement.winamounts.clone();
+    let mut shortfall: i64 = 0;
+    let mut fractionbps: i64 = 10000;
+    if let Some(available) = available {
+        if totalwinamount > available {
+            let haircut: Haircut = haircut(&settlement.winamounts, available);
+            shortfall = totalwinamount - available;
+            fractionbps = haircut.fractionbps;
+            winamounts = haircut.winamounts;
+        }
+    }
+
+    let mut betteraddresses: Vec<&String> = winamounts.keys().collect();
+    betteraddresses.sort();
+    for betteraddress in betteraddresses {
+        println!("{} {}", betteraddress, winamounts[betteraddress]);
+    }
+    let paid: i64 = winamounts.values().sum();
+    let dust: i64 = settlement.totalbetamount as i64 - settlement.operatortake - paid - shortfall;
+    println!("total bets: {}", settlement.totalbetamount);
+    println!("paid: {}", paid);
+    println!("fee: {}", settlement.operatortake);
+    println!("dust: {}", dust);
+    let conserved: bool = paid + settlement.operatortake + dust + shortfall == settlement.totalbetamount as i64 && dust >= 0;
+    println!("conservation: {}", if conserved { "ok" } else { "VIOLATED" });
+
+    let receipt = ReplayedReceipt {
+        outcome: if settlement.unanimous { "unanimous".to_string() } else { "settled".to_string() },
+        betvaluewinning: outcome,
+        totalbetamount: settlement.totalbetamount as i64,
+        totalbetamountforwinningvalue: settlement.totalbetamountforwinningvalue as i64,
+        realizedoddsbps: settlement.realizedoddsbps,
+        operatortake: settlement.operatortake,
+        returnper100: settlement.returnper100,
+        shortfall,
+        fractionbps,
+    };
+    println!("{}", serde_json::to_string(&receipt).expect("failed to make json of receipt"));
+    if !conserved {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,37 @@
+// Encoding of wallet addresses in the contract's state
+//
+// Addresses are stored as strings, e.g. as keys of the bets and in entitlements, and decoded again for transfers.
+// All encoding and decoding goes through AddressCodec, so that a new address encoding of the chain only needs a new codec here.
+// Every stored entry records the flag of the codec its address was encoded with, so that entries of both encodings are paid
+// correctly after a migration. Entries without a flag were stored in base58, the encoding used before flags were recorded.
+// The encodings themselves are the AddressEncoding of the core crate, to which the codec passes the base58 of the chain.
+
+use wasmlib::*;
+
+use prediction_market_core::addresses::AddressEncoding;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct AddressCodec(pub AddressEncoding);
+
+impl AddressCodec {
+    // codec used for addresses stored from now on
+    pub const CURRENT: AddressCodec = AddressCodec(AddressEncoding::CURRENT);
+
+    // codec for the flag stored with an entry, "" for entries stored before flags were recorded
+    pub fn from_flag(flag: &str) -> Result<AddressCodec, String> {
+        AddressEncoding::from_flag(flag).map(AddressCodec)
+    }
+
+    pub fn flag(&self) -> &'static str {
+        self.0.flag()
+    }
+
+    pub fn encode(&self, utility: &ScUtility, address: &ScAddress) -> String {
+        self.0.encode(address.to_bytes(), &|bytes: &[u8]| utility.base58_encode(bytes))
+    }
+
+    // decode an address for a transfer, rejecting strings that do not decode to the length of an address
+    pub fn decode(&self, utility: &ScUtility, encoded: &str) -> Result<ScAddress, String> {
+        self.0.decode(encoded, &|encoded: &str| utility.base58_decode(encoded)).map(|bytes| ScAddress::from_bytes(&bytes))
+    }
+}
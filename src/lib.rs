@@ -7,7 +7,7 @@
 //   Assume 700 IOTA were bet on "no" and 300 IOTA on "yes", and "yes" is the actual outcome.
 //   A bet on "yes" with 100 IOTA receives (100/300)*1000 = 333 IOTA
 //
-// Assumes only one contract per chain. To allow multiple contracts, bets need to be stored in a map per id of the contract.
+// Several markets can run on the same chain, each initialized with its own MARKETID, under which its state is kept.
 // Note that bets are stored in the contract's state, so in principle, they can be publicly accessed, although the contract itself does not provide a function to do so.
 // Note that when sending IOTA to the betters, a minimum transaction fee of 1 IOTA is deducted.
 //
@@ -18,11 +18,159 @@
 
 
 use wasmlib::*;
-use chrono::{DateTime,  Utc, NaiveDateTime};
+use chrono::{DateTime, NaiveDateTime};
 use serde_with::serde_as;
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 
+mod address;
+mod writes;
+
+pub use prediction_market_core::settlement;
+
+use address::AddressCodec;
+use writes::applywrites;
+use prediction_market_core::actions::*;
+use prediction_market_core::addresses::*;
+use prediction_market_core::alerts::*;
+use prediction_market_core::archive::*;
+use prediction_market_core::betting::*;
+use prediction_market_core::blocklist::*;
+use prediction_market_core::book::*;
+use prediction_market_core::canonical::*;
+use prediction_market_core::commits::*;
+use prediction_market_core::configuration::*;
+use prediction_market_core::costs::*;
+use prediction_market_core::counters::*;
+use prediction_market_core::defaults::*;
+use prediction_market_core::delegates::*;
+use prediction_market_core::disputes::*;
+use prediction_market_core::events;
+use prediction_market_core::externalrefs::*;
+use prediction_market_core::feeds::*;
+use prediction_market_core::fees::*;
+use prediction_market_core::groups::*;
+use prediction_market_core::health::*;
+use prediction_market_core::idempotency::*;
+use prediction_market_core::ledger::*;
+use prediction_market_core::memos::*;
+use prediction_market_core::merkle::*;
+use prediction_market_core::metadata::*;
+use prediction_market_core::migration::*;
+use prediction_market_core::mirrors::*;
+use prediction_market_core::odds::*;
+use prediction_market_core::paging::*;
+use prediction_market_core::payouts::*;
+use prediction_market_core::provenance::*;
+use prediction_market_core::recording::*;
+use prediction_market_core::refusals::*;
+use prediction_market_core::returns::*;
+use prediction_market_core::roles::*;
+use prediction_market_core::schedules::*;
+use prediction_market_core::seeding::*;
+use prediction_market_core::settlement::*;
+use prediction_market_core::softfailures::*;
+use prediction_market_core::storage::*;
+use prediction_market_core::tally::*;
+use prediction_market_core::terms::*;
+use prediction_market_core::time::*;
+use prediction_market_core::writes::*;
+
+
+// error codes of soft failures, which only log a message in the default mode and fail the transaction in strict mode, are kept
+// by softfailures in the core crate; late bets and bets while bets are frozen are refunded in the default mode, see betrejection for their codes
+
+// error codes of rejected bets, whose IOTA are refunded
+const ERR_BAD_NONCE: &str = "ERR_BAD_NONCE";
+const ERR_BAD_SIGNATURE: &str = "ERR_BAD_SIGNATURE";
+const ERR_BET_VALUE_CHANGED: &str = "ERR_BET_VALUE_CHANGED";
+const ERR_MARKET_MIGRATED: &str = "ERR_MARKET_MIGRATED";
+const ERR_BELOW_MINBET: &str = "ERR_BELOW_MINBET";
+const ERR_UNKNOWN_OUTCOME: &str = "ERR_UNKNOWN_OUTCOME";
+const ERR_INVALID_ATTRIBUTION: &str = "ERR_INVALID_ATTRIBUTION";
+const ERR_COUNTERS_REBUILDING: &str = "ERR_COUNTERS_REBUILDING";
+const ERR_WRONG_TOKEN: &str = "ERR_WRONG_TOKEN";
+const ERR_NOT_YET_OPEN: &str = "ERR_NOT_YET_OPEN";
+const ERR_BET_TOO_LARGE: &str = "ERR_BET_TOO_LARGE";
+
+// error code of bets stored in another layout than the one recorded for the market, which fails the transaction
+const ERR_STORAGE_FORMAT_MISMATCH: &str = "ERR_STORAGE_FORMAT_MISMATCH";
+
+// default number of idempotency keys remembered per market, the oldest ones are forgotten first,
+// and the default minimum age in seconds before a key can be forgotten
+const MAXIDEMPOTENCYKEYS: i32 = 128;
+const DEFAULTIDEMPOTENCYMINAGE: i64 = 600;
+
+// maximum number of seconds from the deployment of the contract to an end time for bets, about 10 years
+const MAXBETDURATION: i64 = 10 * 365 * 24 * 3600;
+
+// maximum length in bytes of the CLIENTID and CAMPAIGN of a bet, and maximum number of campaigns counted per market
+const MAXATTRIBUTION: usize = 32;
+const MAXCAMPAIGNS: i32 = 32;
+
+// maximum fee of the market in percent of the overall amount of bets, split between the treasury and the creator of the market when it is closed
+const MAXFEEPERCENT: i64 = 20;
+
+// maximum number of alert rules on implied probabilities, and how far a probability has to move back before a rule can fire again
+
+// policies when the IOTA available at settlement fall short of the winnings: block transfers until reconciled, or pay every winner the same fraction
+const SHORTFALL_HALT: &str = "HALT";
+const SHORTFALL_HAIRCUT: &str = "HAIRCUT";
+
+// maximum number of accounts disputing the outcome of a market
+const MAXDISPUTERS: i32 = 16;
+
+// maximum number of agents per role
+const MAXROLEMEMBERS: i32 = 8;
+
+// who may resolve the market: the contract owner, after its heartbeat window passed without a call the chain owner,
+// and after another window anyone, by voiding the market with refunds
+const RESOLVER_CREATOR: &str = "resolver";
+const RESOLVER_CHAINOWNER: &str = "chainowner";
+const RESOLVER_REFUND: &str = "refund";
+
+// maximum number of entitlements paid out by a single call, keeps the size of a transaction bounded
+const DEFAULTPAYOUTBATCH: i64 = 50;
+// number of bytes of a decoded color
+const COLOR_LENGTH: usize = 32;
+// pending returns paid before the work of every call
+const PIGGYBACKRETURNS: i64 = 5;
+
+// maximum number of bets scanned by a single call of rebuildcounters
+const REBUILDBATCH: i64 = 200;
+
+// payout modes of a market: closing pays the first entitlements and anyone can pay the next by payout,
+// or every account claims its own entitlements and nothing is pushed
+const PAYOUTMODE_PUSH: &str = "push";
+const PAYOUTMODE_CLAIM: &str = "claim";
+
+// reason codes of refunds besides the error codes of rejected bets: IOTA not accepted for a bet or deposit, a bet below the minimum,
+// and the IOTA of a duplicate request
+const REFUND_UNACCEPTED: &str = "unaccepted";
+const REFUND_BELOWMINBET: &str = "belowminbet";
+const REFUND_DUPLICATE: &str = "duplicate";
+
+// reserved outcome that wins when none of the named outcomes happened, selected by RESIDUAL of closemarket,
+// and its aliases, which are accepted wherever a value is passed
+const OUTCOME_OTHER: &str = "__OTHER__";
+const OUTCOME_OTHER_ALIASES: [&str; 2] = ["other", "OTHER"];
+
+// default number of refunds returned by a single call of exportrefunds, and the number of entries of the refund index
+// a single call of exportrefunds or prunerefunds reads at most
+const DEFAULTREFUNDPAGE: i64 = 100;
+const MAXREFUNDSCAN: i64 = 1000;
+// number of refunds kept in the refund index, older ones are pruned into its totals of pruned refunds
+const MAXREFUNDINDEX: i64 = 10000;
+
+// default length in seconds of the window of recent activity, and the number of bets in a window from which the
+// volatility hint of getstats is medium or high
+const DEFAULTACTIVITYWINDOW: i64 = 3600;
+const DEFAULTACTIVITYMEDIUM: i64 = 10;
+const DEFAULTACTIVITYHIGH: i64 = 50;
+
+// number of times a bet in the EXTENSIONWINDOW can extend the end time for bets, unless MAXEXTENSIONS is given
+const DEFAULTMAXEXTENSIONS: i64 = 10;
 
 
 #[no_mangle]
@@ -32,52 +180,803 @@ fn on_load() {
     exports.add_func("bet", bet );
     exports.add_func("initmarket", initmarket);
     exports.add_func("closemarket", closemarket);
+    exports.add_func("declareoutcome", declareoutcome);
+    exports.add_func("linkmarkets", linkmarkets);
+    exports.add_func("unlinkmarket", unlinkmarket);
+    exports.add_func("closegroup", closegroup);
+    exports.add_func("checkpoint", checkpoint);
+    exports.add_func("cancelmarket", cancelmarket);
+    exports.add_func("payout", payout);
+    exports.add_func("claim", claim);
+    exports.add_func("setalerts", setalerts);
+    exports.add_func("reconcile", reconcile);
+    exports.add_func("createchallenge", createchallenge);
+    exports.add_func("acceptchallenge", acceptchallenge);
+    exports.add_func("reclaimchallenge", reclaimchallenge);
+    exports.add_func("closechallenge", closechallenge);
+    exports.add_func("blockaddress", blockaddress);
+    exports.add_func("unblockaddress", unblockaddress);
+    exports.add_func("grantexception", grantexception);
+    exports.add_func("proposeaction", proposeaction);
+    exports.add_func("executeaction", executeaction);
+    exports.add_func("cancelaction", cancelaction);
+    exports.add_func("heartbeat", heartbeat);
+    exports.add_func("setrefusalnotice", setrefusalnotice);
+    exports.add_func("setsweepdest", setsweepdest);
+    exports.add_func("sweep", sweep);
+    exports.add_func("betfor", betfor);
+    exports.add_func("reconcilelegacy", reconcilelegacy);
+    exports.add_func("registermirror", registermirror);
+    exports.add_func("removemirror", removemirror);
+    exports.add_func("addrole", addrole);
+    exports.add_func("removerole", removerole);
+    exports.add_func("dispute", dispute);
+    exports.add_func("exportformigration", exportformigration);
+    exports.add_func("importmarket", importmarket);
+    exports.add_func("rebuildcounters", rebuildcounters);
+    exports.add_func("forcedrefund", forcedrefund);
+    exports.add_func("vote", vote);
+    exports.add_func("tallyandsettle", tallyandsettle);
+    exports.add_func("processreturns", processreturns);
+    exports.add_func("delegateclaim", delegateclaim);
+    exports.add_func("revokedelegate", revokedelegate);
+    exports.add_func("getbetprivate", getbetprivate);
+    exports.add_func("prunerefunds", prunerefunds);
+    exports.add_func("commitbet", commitbet);
+    exports.add_func("revealbet", revealbet);
+    exports.add_func("expirecommits", expirecommits);
+    exports.add_func("schedulemarket", schedulemarket);
+    exports.add_func("cancelschedule", cancelschedule);
+    exports.add_func("tick", tick);
+    exports.add_func("archivemarket", archivemarket);
+    exports.add_func("setodds", setodds);
+    exports.add_func("setdefaults", setdefaults);
+    exports.add_func("claimcreatorfees", claimcreatorfees);
+    exports.add_func("withdrawfees", withdrawfees);
+    exports.add_func("resolvefromoracle", resolvefromoracle);
+    exports.add_func("seedmarket", seedmarket);
+    // views of the smart contract
+    exports.add_view("getexposure", getexposure);
+    exports.add_view("getstats", getstats);
+    exports.add_view("getcheckpoints", getcheckpoints);
+    exports.add_view("exportmarket", exportmarket);
+    exports.add_view("getreconciliation", getreconciliation);
+    exports.add_view("findmarket", findmarket);
+    exports.add_view("gettermsack", gettermsack);
+    exports.add_view("getchallenges", getchallenges);
+    exports.add_view("isblocked", isblocked);
+    exports.add_view("geteventschema", geteventschema);
+    exports.add_view("getexceptions", getexceptions);
+    exports.add_view("getactions", getactions);
+    exports.add_view("getreceipt", getreceipt);
+    exports.add_view("getresolver", getresolver);
+    exports.add_view("getvotes", getvotes);
+    exports.add_view("getoutcomescenarios", getoutcomescenarios);
+    exports.add_view("getbook", getbook);
+    exports.add_view("getnonce", getnonce);
+    exports.add_view("precheck", precheck);
+    exports.add_view("getlegacyinconsistencies", getlegacyinconsistencies);
+    exports.add_view("getmirrors", getmirrors);
+    exports.add_view("exportrefunds", exportrefunds);
+    exports.add_view("healthcheck", healthcheck);
+    exports.add_view("getroles", getroles);
+    exports.add_view("getdisputes", getdisputes);
+    exports.add_view("getpools", getpools);
+    exports.add_view("getidempotencykey", getidempotencykey);
+    exports.add_view("getoutcomes", getoutcomes);
+    exports.add_view("getinclusionproof", getinclusionproof);
+    exports.add_view("getoracle", getoracle);
+    exports.add_view("getcampaignstats", getcampaignstats);
+    exports.add_view("getmybet", getmybet);
+    exports.add_view("getmarketinfo", getmarketinfo);
+    exports.add_view("getbethistory", getbethistory);
+    exports.add_view("getwinners", getwinners);
+    exports.add_view("estimatecost", estimatecost);
+    exports.add_view("getgroup", getgroup);
+    exports.add_view("getgroupusage", getgroupusage);
+    exports.add_view("getcommitusage", getcommitusage);
+    exports.add_view("getschedules", getschedules);
+    exports.add_view("verifytombstone", verifytombstone);
+    exports.add_view("getdefaults", getdefaults);
+    exports.add_view("getdefaultshistory", getdefaultshistory);
+    exports.add_view("getcreatorfees", getcreatorfees);
+    exports.add_view("getfeeds", getfeeds);
+}
+
+// Every market has its own state: a market initialized with a MARKETID keeps its keys in a map of that name, and every function
+// acting on it has to be called with the same MARKETID, so that several markets can run on the same chain independently.
+// Without MARKETID, the keys of the market are in the contract's state itself, like for the single market before markets had ids.
+// The ledger, transfers, admin log, roles and the other records of the contract are kept in the contract's state for all markets.
+fn marketparam<T: ScBaseContext>(context: &T) -> String {
+    context.params().get_string(&"MARKETID".to_string()).value()
+}
+
+fn marketstatekey(marketid: &str) -> String {
+    "market|".to_string() + marketid
+}
+
+fn marketstate(context: &ScFuncContext) -> ScMutableMap {
+    marketstateof(context, &marketparam(context))
+}
+
+// state of the market marketid, for work on another market than the one of the call like piggybacked payouts
+fn marketstateof(context: &ScFuncContext, marketid: &str) -> ScMutableMap {
+    if marketid == "" { context.state() } else { context.state().get_map(&marketstatekey(marketid)) }
+}
+
+fn viewmarketstate(context: &ScViewContext) -> ScImmutableMap {
+    let marketid: String = marketparam(context);
+    if marketid == "" { context.state() } else { context.state().get_map(&marketstatekey(&marketid)) }
+}
+
+// A function acting on a market with a MARKETID requires the market to be initialized.
+fn requiremarket(context: &ScFuncContext) {
+    context.require(marketparam(context) == "" || marketstate(context).get_string(&"marketclosed".to_string()).exists(), "MARKETID is not an initialized market");
+}
+
+// Whether initmarket or importmarket ran for the market. Markets initialized before the flag was recorded have their marketclosed flag.
+fn marketinitialized(market: &ScMutableMap) -> bool {
+    market.get_string(&"initialized".to_string()).value() == "true" || market.get_string(&"marketclosed".to_string()).exists()
+}
+
+// The pools bucket holds the stakes of all markets. A market with a MARKETID counts its share in its own state, and the shares
+// of all of them in namedpools, while the rest of the pools belongs to the market without MARKETID.
+// A market in a colored token, with or without MARKETID, counts its pool in its own state only, since the pools bucket only accounts IOTA.
+fn creditmarketpool(context: &ScFuncContext, amount: i64) {
+    creditmarketpoolof(context, &marketparam(context), amount);
+}
+
+fn creditmarketpoolof(context: &ScFuncContext, marketid: &str, amount: i64) {
+    let market: ScMutableMap = marketstateof(context, marketid);
+    let inpools: bool = tokencolor(context, &market) == ScColor::IOTA;
+    if marketid == "" && inpools {
+        return;
+    }
+    let pool = market.get_int64(&"pool".to_string());
+    pool.set_value(pool.value() + amount);
+    if !inpools {
+        return;
+    }
+    let namedpools = context.state().get_int64(&"namedpools".to_string());
+    namedpools.set_value(namedpools.value() + amount);
+}
+
+fn marketpool(context: &ScFuncContext) -> i64 {
+    if marketparam(context) == "" && tokencolor(context, &marketstate(context)) == ScColor::IOTA {
+        return context.state().get_int64(&bucketkey(BUCKET_POOLS)).value() - context.state().get_int64(&"namedpools".to_string()).value();
+    }
+    marketstate(context).get_int64(&"pool".to_string()).value()
 }
 
 // The contract owner should call this function for initialization and to set an end time for betting 
 // using the parameter BETENDUTC, which is a date and time string in ISO format, assuming UTC.
 fn initmarket(context: &ScFuncContext) {
-    // only contract owner should be able to do this
-    let creator = context.contract_creator();
-    let caller = context.caller();
-    context.require(creator == caller, "Not authorised to init market - only contract creator is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "initmarket") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "initmarket"), "Not authorised to init market - only contract creator or a marketadmin is allowed to do this.");
+    // a market can only be initialized again as long as it is open and nothing was bet on it, so that neither stored bets
+    // nor an already paid out pool are carried into the new market
+    if marketinitialized(&marketstate(context)) {
+        context.require(marketstate(context).get_string("marketclosed").value() != "true", "the market is already initialized and closed");
+        context.require(readcontainerofbets(context).map.is_empty(), "the market is already initialized and has bets");
+    }
+    let previoustimeline: Option<Timeline> = if marketinitialized(&marketstate(context)) { Some(markettimeline(&marketstate(context).immutable())) } else { None };
+    marketstate(context).get_string(&"initialized".to_string()).set_value(&"true".to_string());
+    // bets of new markets are stored in the map layout from the start
+    migratestorage(context);
+
+    // effective configuration of the market with the provenance of every setting, returned, logged and stored
+    let mut configuration: BTreeMap<String, EffectiveSetting> = BTreeMap::new();
 
-    let mut log:String = "INITMARKET is run:".to_string();   context.log(&log);
-    
     // a flag, stating that the closemarket function was not (successfully) run yet
-    context.state().get_string("marketclosed").set_value(&"false".to_string());
+    marketstate(context).get_string("marketclosed").set_value(&"false".to_string());
+    // no outcome declared yet
+    marketstate(context).get_string(&"pendingoutcome".to_string()).set_value("");
+    // no receipt before the market is settled, and no scenarios before betting closed
+    marketstate(context).get_string(&"receiptjson".to_string()).set_value("");
+    marketstate(context).get_string(&"outcomescenariosjson".to_string()).set_value("");
+    marketstate(context).get_string(&"betsfrozen".to_string()).set_value(&"false".to_string());
+    // running totals of the bets, started from the bets already stored when a market is initialized again
+    if marketstate(context).get_string(&"runningtotals".to_string()).value() != "true" {
+        settotals(context, &bettotals(&readcontainerofbets(context)));
+    }
+
+    // default for strict mode, can be overridden per call by the STRICT parameter of bet and closemarket
+    let strictmode: bool = context.params().get_string(&"STRICT".to_string()).value() == "true";
+    marketstate(context).get_string(&"strictmode".to_string()).set_value(&strictmode.to_string());
+    effectivesetting(context, &mut configuration, "strictmode", strictmode.to_string());
+
+    // optional maximum share of the pool per betting account in basis points, e.g. "2500" for 25%, and the pool size up to which it does not apply
+    let maxsharebps: i64 = paramint64(context, "MAXSHAREBPS");
+    let maxshareexemptpool: i64 = paramint64(context, "MAXSHAREEXEMPTPOOL");
+    context.require(maxsharebps >= 0 && maxsharebps <= 10000, "MAXSHAREBPS must be between 0 and 10000");
+    context.require(maxshareexemptpool >= 0, "MAXSHAREEXEMPTPOOL must not be negative");
+    marketstate(context).get_int64(&"maxsharebps".to_string()).set_value(maxsharebps);
+    marketstate(context).get_int64(&"maxshareexemptpool".to_string()).set_value(maxshareexemptpool);
+    effectivesetting(context, &mut configuration, "maxsharebps", maxsharebps.to_string());
+    effectivesetting(context, &mut configuration, "maxshareexemptpool", maxshareexemptpool.to_string());
+
+    // optional limit of the bytes of all metadata of the market, like its external reference, terms, alert rules and notice, up to
+    // the ceiling of the platform defaults, which protects every config read; a market initialized again keeps the ceiling it took
+    let ceiling: i64 = if marketstate(context).get_string(&"creator".to_string()).value() == "" {
+        configceiling(defaultshistory(&context.state().immutable()).current().maxconfigbytes)
+    } else {
+        configceiling(marketstate(context).get_int64(&"configceiling".to_string()).value())
+    };
+    let maxconfigbytes: Result<i64, String> = marketmaxconfigbytes(paramint64(context, "MAXCONFIGBYTES"), ceiling);
+    context.require(maxconfigbytes.is_ok(), maxconfigbytes.as_ref().err().map_or("", |error| error.as_str()));
+    let maxconfigbytes: i64 = maxconfigbytes.unwrap();
+    marketstate(context).get_int64(&"maxconfigbytes".to_string()).set_value(maxconfigbytes);
+    effectivesetting(context, &mut configuration, "maxconfigbytes", maxconfigbytes.to_string());
+
+    // the market is identified by MARKETID, which can only be initialized once, or without MARKETID by the request initializing it
+    let mut marketid: String = marketparam(context);
+    if marketid == "" {
+        marketid = context.request_id().to_string();
+        configuration.insert("marketid".to_string(), marketidsetting(&marketid, false));
+    } else {
+        context.require(marketid.len() <= 64, "MARKETID must not be longer than 64 bytes");
+        context.require(!marketstate(context).get_string(&"marketid".to_string()).exists() && !marketarchived(context, &marketid), "MARKETID is already used by another market");
+        let marketids = context.state().get_string_array(&"marketids".to_string());
+        marketids.get_string(marketids.length()).set_value(&marketid);
+        configuration.insert("marketid".to_string(), marketidsetting(&marketid, true));
+    }
+    marketstate(context).get_string(&"marketid".to_string()).set_value(&marketid);
+    context.results().get_string(&"MARKETID".to_string()).set_value(&marketid);
+
+    // optional reference to the event outside of the chain, e.g. an event id of a sports feed, by which the market can be found
+    let externalref: String = context.params().get_string(&"EXTERNALREF".to_string()).value();
+    // reverse index from the hash of the reference to the market, there is only one market per reference
+    let indexedmarketid = externalrefindex(context, &externalref);
+    let validatedexternalref: Result<(), String> = validateexternalref(&externalref, &indexedmarketid.value(), &marketid);
+    context.require(validatedexternalref.is_ok(), validatedexternalref.as_ref().err().map_or("", |error| error.as_str()));
+    // initializing the market again keeps its own reference, or frees it for other markets when it changes
+    let previousexternalref: String = marketstate(context).get_string(&"externalref".to_string()).value();
+    if let Some(released) = releasedexternalref(&previousexternalref, &externalref) {
+        releaseexternalref(context, released);
+    }
+    setconfigvalue(context, "externalref", &externalref);
+    if externalref != "" {
+        indexedmarketid.set_value(&marketid);
+    }
+    effectivesetting(context, &mut configuration, "externalref", externalref.clone());
+
+    // optional grid of bet amounts, e.g. "10" to accept only multiples of 10 IOTA, and minimum bet amount
+    let betgranularity: i64 = paramint64(context, "BETGRANULARITY");
+    let mut minbet: i64 = paramint64(context, "MINBET");
+    context.require(betgranularity >= 0, "BETGRANULARITY must not be negative");
+    context.require(minbet >= 0, "MINBET must not be negative");
+    // a bet of at least 1 IOTA is required in any case
+    if minbet == 0 {
+        minbet = 1;
+    }
+    marketstate(context).get_int64(&"betgranularity".to_string()).set_value(betgranularity);
+    marketstate(context).get_int64(&"minbet".to_string()).set_value(minbet);
+    effectivesetting(context, &mut configuration, "betgranularity", betgranularity.to_string());
+    effectivesetting(context, &mut configuration, "minbet", minbet.to_string());
+
+    // policy if the IOTA available at settlement fall short of the winnings, HALT (default) or HAIRCUT
+    let mut shortfallpolicy: String = context.params().get_string(&"SHORTFALLPOLICY".to_string()).value();
+    if shortfallpolicy == "" {
+        shortfallpolicy = SHORTFALL_HALT.to_string();
+    }
+    context.require(shortfallpolicy == SHORTFALL_HALT || shortfallpolicy == SHORTFALL_HAIRCUT, "SHORTFALLPOLICY must be HALT or HAIRCUT");
+    marketstate(context).get_string(&"shortfallpolicy".to_string()).set_value(&shortfallpolicy);
+    effectivesetting(context, &mut configuration, "shortfallpolicy", shortfallpolicy.clone());
+
+    // the timelock and the destination of residual funds are treasury settings, which only a treasurer passes, like to setsweepdest;
+    // initializing the market again without them keeps them as they are
+    let treasurer: bool = hasrole(context, ROLE_TREASURER);
+
+    // optional delay in seconds for parameter changes of the contract owner, which then have to be announced by proposeaction
+    let previoustimelock: i64 = marketstate(context).get_int64(&"timelock".to_string()).value();
+    let mut timelock: i64 = previoustimelock;
+    if context.params().get_string(&"TIMELOCK".to_string()).value() != "" {
+        context.require(treasurer, "Not authorised to set TIMELOCK - only contract creator or a treasurer is allowed to do this.");
+        timelock = paramint64(context, "TIMELOCK");
+    }
+    context.require(timelock >= 0, "TIMELOCK must not be negative");
+    // initializing the market again must not shortcut the announcement of pending or future changes
+    context.require(timelock >= previoustimelock, "TIMELOCK cannot be lowered by initializing the market again");
+    marketstate(context).get_int64(&"timelock".to_string()).set_value(timelock);
+    effectivesetting(context, &mut configuration, "timelock", timelock.to_string());
+
+    // optional time in seconds after the end time for bets, after which the market falls back to the chain owner if the contract owner stays silent
+    let heartbeatwindow: i64 = paramint64(context, "HEARTBEATWINDOW");
+    context.require(heartbeatwindow >= 0, "HEARTBEATWINDOW must not be negative");
+    marketstate(context).get_int64(&"heartbeatwindow".to_string()).set_value(heartbeatwindow);
+    effectivesetting(context, &mut configuration, "heartbeatwindow", heartbeatwindow.to_string());
+
+    // optional time in seconds after the end time for bets, after which anyone can refund all bets of a market that was not closed
+    let resolutiongrace: i64 = paramint64(context, "RESOLUTIONGRACE");
+    context.require(resolutiongrace >= 0, "RESOLUTIONGRACE must not be negative");
+    marketstate(context).get_int64(&"resolutiongrace".to_string()).set_value(resolutiongrace);
+    effectivesetting(context, &mut configuration, "resolutiongrace", resolutiongrace.to_string());
+
+    // optional window in seconds before the end time for bets: a bet within it extends the end time so that the full window remains,
+    // at most MAXEXTENSIONS times and by at most MAXTOTALEXTENSION seconds in total if given, against bets sniping the near-final odds
+    let extensionwindow: i64 = paramint64(context, "EXTENSIONWINDOW");
+    let mut maxextensions: i64 = paramint64(context, "MAXEXTENSIONS");
+    let maxtotalextension: i64 = paramint64(context, "MAXTOTALEXTENSION");
+    context.require(extensionwindow >= 0 && maxextensions >= 0 && maxtotalextension >= 0, "EXTENSIONWINDOW, MAXEXTENSIONS and MAXTOTALEXTENSION must not be negative");
+    if maxextensions == 0 {
+        maxextensions = DEFAULTMAXEXTENSIONS;
+    }
+    marketstate(context).get_int64(&"extensionwindow".to_string()).set_value(extensionwindow);
+    marketstate(context).get_int64(&"maxextensions".to_string()).set_value(maxextensions);
+    marketstate(context).get_int64(&"maxtotalextension".to_string()).set_value(maxtotalextension);
+    marketstate(context).get_int64(&"extensions".to_string()).set_value(0);
+    marketstate(context).get_int64(&"totalextension".to_string()).set_value(0);
+    effectivesetting(context, &mut configuration, "extensionwindow", extensionwindow.to_string());
+    effectivesetting(context, &mut configuration, "maxextensions", maxextensions.to_string());
+    effectivesetting(context, &mut configuration, "maxtotalextension", maxtotalextension.to_string());
+
+    // optional announcement period in seconds after initialization, during which the market is visible but bets are refunded
+    let announcedelay: i64 = paramint64(context, "ANNOUNCEDELAY");
+    context.require(announcedelay >= 0, "ANNOUNCEDELAY must not be negative");
+    marketstate(context).get_int64(&"announcedelay".to_string()).set_value(announcedelay);
+    marketstate(context).get_int64(&"opensat".to_string()).set_value(context.timestamp().saturating_add(announcedelay));
+    effectivesetting(context, &mut configuration, "announcedelay", announcedelay.to_string());
+
+    // optional color of the token the market is denominated in, in base58, instead of IOTA
+    let tokencolor: String = context.params().get_string(&"TOKENCOLOR".to_string()).value();
+    if tokencolor != "" {
+        context.require(context.utility().base58_decode(&tokencolor).len() == COLOR_LENGTH, &("TOKENCOLOR must be a base58 encoded color of ".to_string() + &COLOR_LENGTH.to_string() + &" bytes".to_string()));
+    }
+    marketstate(context).get_string(&"tokencolor".to_string()).set_value(&tokencolor);
+    effectivesetting(context, &mut configuration, "tokencolor", tokencolor.clone());
+
+    // optional wallet address receiving the residual funds of the market, e.g. of a charity, instead of the contract owner
+    let mut sweepdest: String = marketstate(context).get_string(&"sweepdest".to_string()).value();
+    if context.params().get_string(&"SWEEPDEST".to_string()).value() != "" {
+        context.require(treasurer, "Not authorised to set SWEEPDEST - only contract creator or a treasurer is allowed to do this.");
+        let changed: bool = context.params().get_string(&"SWEEPDEST".to_string()).value() != sweepdest;
+        // a time locked destination is only changed by an executed admin action, even when initializing the market again
+        context.require(!changed || previoustimelock == 0, "setsweepdest is time locked - use proposeaction instead");
+        sweepdest = context.params().get_string(&"SWEEPDEST".to_string()).value();
+        let validated: Result<(), String> = validatesweepdest(&context.utility(), &sweepdest);
+        context.require(validated.is_ok(), validated.as_ref().err().map_or("", |error| error.as_str()));
+    }
+    marketstate(context).get_string(&"sweepdest".to_string()).set_value(&sweepdest);
+    effectivesetting(context, &mut configuration, "sweepdest", sweepdest.clone());
+
+    // optional bond in IOTA for disputing a declared outcome, and where forfeited bonds go: POOL (default), OWNER or CHARITY
+    let disputebond: i64 = paramint64(context, "DISPUTEBOND");
+    context.require(disputebond >= 0, "DISPUTEBOND must not be negative");
+    marketstate(context).get_int64(&"disputebond".to_string()).set_value(disputebond);
+    effectivesetting(context, &mut configuration, "disputebond", disputebond.to_string());
+    let mut disputeforfeit: String = context.params().get_string(&"DISPUTEFORFEIT".to_string()).value();
+    if disputeforfeit == "" {
+        disputeforfeit = FORFEIT_POOL.to_string();
+    }
+    context.require(disputeforfeit == FORFEIT_POOL || disputeforfeit == FORFEIT_OWNER || disputeforfeit == FORFEIT_CHARITY, "DISPUTEFORFEIT must be POOL, OWNER or CHARITY");
+    marketstate(context).get_string(&"disputeforfeit".to_string()).set_value(&disputeforfeit);
+    effectivesetting(context, &mut configuration, "disputeforfeit", disputeforfeit.clone());
+
+    // optional pool size up to which the views withhold the amounts per value, so that the first bets do not show swinging odds
+    let oddsrevealpool: i64 = paramint64(context, "ODDSREVEALPOOL");
+    context.require(oddsrevealpool >= 0, "ODDSREVEALPOOL must not be negative");
+    marketstate(context).get_int64(&"oddsrevealpool".to_string()).set_value(oddsrevealpool);
+    marketstate(context).get_string(&"oddsrevealed".to_string()).set_value(&(oddsrevealpool == 0).to_string());
+    effectivesetting(context, &mut configuration, "oddsrevealpool", oddsrevealpool.to_string());
+
+    // optional independent result provider, which may close the market besides the contract owner and the resolvers, or alone with ORACLEONLY
+    let oracleparam: String = context.params().get_string(&"ORACLE".to_string()).value();
+    let mut oracle: String = "".to_string();
+    if oracleparam != "" {
+        let agentid: Vec<u8> = context.utility().base58_decode(&oracleparam);
+        context.require(agentid.len() == ADDRESS_LENGTH + 4, "ORACLE must be a base58 encoded agent id");
+        oracle = ScAgentID::from_bytes(&agentid).to_string();
+    }
+    let oracleonly: bool = context.params().get_string(&"ORACLEONLY".to_string()).value() == "true";
+    context.require(!oracleonly || oracle != "", "ORACLEONLY requires an ORACLE");
+    marketstate(context).get_string(&"oracle".to_string()).set_value(&oracle);
+    marketstate(context).get_string(&"oracleonly".to_string()).set_value(&oracleonly.to_string());
+    effectivesetting(context, &mut configuration, "oracle", oracle.clone());
+    effectivesetting(context, &mut configuration, "oracleonly", oracleonly.to_string());
+
+    // optional number of outstanding payouts of the settled market that every call to the contract processes, 0 to wait for payout calls
+    let piggybackpayouts: i64 = paramint64(context, "PIGGYBACKPAYOUTS");
+    context.require(piggybackpayouts >= 0 && piggybackpayouts <= DEFAULTPAYOUTBATCH, &("PIGGYBACKPAYOUTS must be between 0 and ".to_string() + &DEFAULTPAYOUTBATCH.to_string()));
+    marketstate(context).get_int64(&"piggybackpayouts".to_string()).set_value(piggybackpayouts);
+    effectivesetting(context, &mut configuration, "piggybackpayouts", piggybackpayouts.to_string());
+
+    // optional fee in percent of the overall amount of bets, recorded in the configuration only if set so that markets without a fee are unchanged
+    let feepercent: i64 = paramint64(context, "FEEPERCENT");
+    context.require(feepercent >= 0 && feepercent <= MAXFEEPERCENT, &("FEEPERCENT must be between 0 and ".to_string() + &MAXFEEPERCENT.to_string()));
+    marketstate(context).get_int64(&"feepercent".to_string()).set_value(feepercent);
+    if feepercent != 0 {
+        effectivesetting(context, &mut configuration, "feepercent", feepercent.to_string());
+    }
+
+    // whether the entitlements of the market are pushed to the accounts, the default, or only claimed by them
+    let mut payoutmode: String = context.params().get_string(&"PAYOUTMODE".to_string()).value();
+    if payoutmode == "" {
+        payoutmode = PAYOUTMODE_PUSH.to_string();
+    }
+    context.require(payoutmode == PAYOUTMODE_PUSH || payoutmode == PAYOUTMODE_CLAIM, "PAYOUTMODE must be push or claim");
+    marketstate(context).get_string(&"payoutmode".to_string()).set_value(&payoutmode);
+    effectivesetting(context, &mut configuration, "payoutmode", payoutmode.clone());
+
+    // number of idempotency keys the market remembers, and the minimum age in seconds before a key can be forgotten
+    let mut idempotencymaxkeys: i64 = paramint64(context, "IDEMPOTENCYKEYS");
+    let mut idempotencyminage: i64 = paramint64(context, "IDEMPOTENCYMINAGE");
+    context.require(idempotencymaxkeys >= 0 && idempotencyminage >= 0, "IDEMPOTENCYKEYS and IDEMPOTENCYMINAGE must not be negative");
+    if idempotencymaxkeys == 0 {
+        idempotencymaxkeys = MAXIDEMPOTENCYKEYS as i64;
+    }
+    if context.params().get_string(&"IDEMPOTENCYMINAGE".to_string()).value() == "" {
+        idempotencyminage = DEFAULTIDEMPOTENCYMINAGE;
+    }
+    marketstate(context).get_int64(&"idempotencymaxkeys".to_string()).set_value(idempotencymaxkeys);
+    marketstate(context).get_int64(&"idempotencyminage".to_string()).set_value(idempotencyminage);
+    effectivesetting(context, &mut configuration, "idempotencykeys", idempotencymaxkeys.to_string());
+    effectivesetting(context, &mut configuration, "idempotencyminage", idempotencyminage.to_string());
+
+    // length in seconds of the window of recent activity and the numbers of bets in a window from which the volatility is medium or high
+    let mut activitywindow: i64 = paramint64(context, "ACTIVITYWINDOW");
+    let mut activitymedium: i64 = paramint64(context, "ACTIVITYMEDIUM");
+    let mut activityhigh: i64 = paramint64(context, "ACTIVITYHIGH");
+    context.require(activitywindow >= 0 && activitymedium >= 0 && activityhigh >= 0, "ACTIVITYWINDOW, ACTIVITYMEDIUM and ACTIVITYHIGH must not be negative");
+    if activitywindow == 0 {
+        activitywindow = DEFAULTACTIVITYWINDOW;
+    }
+    if activitymedium == 0 {
+        activitymedium = DEFAULTACTIVITYMEDIUM;
+    }
+    if activityhigh == 0 {
+        activityhigh = DEFAULTACTIVITYHIGH;
+    }
+    context.require(activitymedium <= activityhigh, "ACTIVITYMEDIUM must not be above ACTIVITYHIGH");
+    marketstate(context).get_int64(&"activitywindow".to_string()).set_value(activitywindow);
+    marketstate(context).get_int64(&"activitymedium".to_string()).set_value(activitymedium);
+    marketstate(context).get_int64(&"activityhigh".to_string()).set_value(activityhigh);
+    effectivesetting(context, &mut configuration, "activitywindow", activitywindow.to_string());
+    effectivesetting(context, &mut configuration, "activitymedium", activitymedium.to_string());
+    effectivesetting(context, &mut configuration, "activityhigh", activityhigh.to_string());
+
+    // optional hash of the terms of the market, which every betting account has to acknowledge with its first bet
+    let termshash: String = context.params().get_string(&"TERMSHASH".to_string()).value();
+    setconfigvalue(context, "termshash", &termshash);
+    effectivesetting(context, &mut configuration, "termshash", termshash.clone());
+
+    // optional public key of the contract owner in base58, to which betting accounts encrypt the memos of their bets off-chain
+    let memopubkey: String = context.params().get_string(&"MEMOPUBKEY".to_string()).value();
+    let decodedmemopubkey: Vec<u8> = if memopubkey == "" { Vec::new() } else { context.utility().base58_decode(&memopubkey) };
+    let validatedmemopubkey: Result<(), String> = validatememopubkey(&memopubkey, &decodedmemopubkey);
+    context.require(validatedmemopubkey.is_ok(), validatedmemopubkey.as_ref().err().map_or("", |error| error.as_str()));
+    setconfigvalue(context, "memopubkey", &memopubkey);
+    effectivesetting(context, &mut configuration, "memopubkey", memopubkey.clone());
+
+    // optional list of the outcomes bets can be placed on, e.g. "yes,no", otherwise any value is accepted
+    let outcomes: Vec<String> = parseoutcomes(&context.params().get_string(&"OUTCOMES".to_string()).value());
+    let mut uniqueoutcomes: Vec<String> = outcomes.clone();
+    uniqueoutcomes.sort();
+    uniqueoutcomes.dedup();
+    context.require(uniqueoutcomes.len() == outcomes.len(), "OUTCOMES must not list an outcome twice");
+    setconfigvalue(context, "outcomes", &outcomes.join(","));
+    effectivesetting(context, &mut configuration, "outcomes", outcomes.join(","));
+
+    // optional price market resolved by resolvefromoracle from the median of up to 5 FEEDS, e.g. "3e5d2a18:iota-usd,a1b2c3d4:iota-usd",
+    // mapped to the OUTCOMES by BOUNDARIES, e.g. "1000,2000" for the outcomes "below,between,above"; FEEDQUORUM feeds have to report
+    // a value, a majority by default, and a feed deviating more than FEEDDEVIATIONBPS from the median puts the market into the dispute state
+    let feedsparam: String = context.params().get_string(&"FEEDS".to_string()).value();
+    let boundariesparam: String = context.params().get_string(&"BOUNDARIES".to_string()).value();
+    let mut feeds: Vec<OracleFeed> = Vec::new();
+    let mut boundaries: Vec<i64> = Vec::new();
+    if feedsparam != "" {
+        let parsed: Result<Vec<OracleFeed>, String> = parsefeeds(&feedsparam);
+        context.require(parsed.is_ok(), parsed.as_ref().err().map_or("", |error| error.as_str()));
+        feeds = parsed.unwrap();
+        context.require(boundariesparam != "", "FEEDS requires BOUNDARIES to map the median to the OUTCOMES");
+    }
+    if boundariesparam != "" {
+        let parsed: Result<Vec<i64>, String> = parseboundaries(&boundariesparam, &outcomes);
+        context.require(parsed.is_ok(), parsed.as_ref().err().map_or("", |error| error.as_str()));
+        boundaries = parsed.unwrap();
+    }
+    let mut feedquorum: i64 = paramint64(context, "FEEDQUORUM");
+    if context.params().get_string(&"FEEDQUORUM".to_string()).value() == "" {
+        feedquorum = feeds.len() as i64 / 2 + 1;
+    }
+    let feeddeviationbps: i64 = paramint64(context, "FEEDDEVIATIONBPS");
+    context.require(feeds.is_empty() || (feedquorum >= 1 && feedquorum <= feeds.len() as i64), "FEEDQUORUM must be from 1 to the number of FEEDS");
+    context.require(feeddeviationbps >= 0, "FEEDDEVIATIONBPS must not be negative");
+    let boundariesvalue: String = boundaries.iter().map(|boundary| boundary.to_string()).collect::<Vec<String>>().join(",");
+    marketstate(context).get_string(&"feedsjson".to_string()).set_value(&serde_json::to_string(&feeds).expect("failed to make json of feeds"));
+    marketstate(context).get_string(&"boundaries".to_string()).set_value(&boundariesvalue);
+    marketstate(context).get_int64(&"feedquorum".to_string()).set_value(feedquorum);
+    marketstate(context).get_int64(&"feeddeviationbps".to_string()).set_value(feeddeviationbps);
+    marketstate(context).get_string(&"feedreportjson".to_string()).set_value("");
+    marketstate(context).get_string(&"feeddisputed".to_string()).set_value(&"false".to_string());
+    effectivesetting(context, &mut configuration, "feeds", feeds.iter().map(|feed| feed.hname.clone() + &":".to_string() + &feed.key).collect::<Vec<String>>().join(","));
+    effectivesetting(context, &mut configuration, "boundaries", boundariesvalue);
+    effectivesetting(context, &mut configuration, "feedquorum", feedquorum.to_string());
+    effectivesetting(context, &mut configuration, "feeddeviationbps", feeddeviationbps.to_string());
 
     if context.params().get_string(&"BETENDUTC".to_string()).value()==""  {
         // default: do not use end time for bets
-        context.state().get_int64(&"betenddatetime".to_string()).set_value(0);
-
-        log = "Do not use specific end time for bets".to_string();  context.log(&log);
+        marketstate(context).get_int64(&"betenddatetime".to_string()).set_value(0);
     }
     else {
-        // parse ISO datetime string, e.g. "2021-01-01 02:00" (in UTC) and convert to UNIX timestamp
-        let betenddatetime:i64 = DateTime::<Utc>::from_utc(NaiveDateTime::parse_from_str(&context.params().get_string(&"BETENDUTC".to_string()).value(), "%Y-%m-%d %H:%M").expect("failed to execute"), Utc).timestamp();
+        // parse ISO datetime string, e.g. "2021-01-01 02:00" (in UTC) or "2021-01-01 04:00+02:00", and convert to UNIX timestamp
+        let parsed: Result<i64, String> = parsebetendutc(&context.params().get_string(&"BETENDUTC".to_string()).value())
+            .and_then(|betenddatetime| boundedbetend(betenddatetime, deployedat(context)));
+        context.require(parsed.is_ok(), parsed.as_ref().err().map_or("", |error| error.as_str()));
+        let betenddatetime:i64 = parsed.unwrap();
+        context.require(betenddatetime > context.timestamp(), &("BETENDUTC must be in the future, it is ".to_string() + &betenddatetime.to_string()
+            + &" but the time of the request is ".to_string() + &context.timestamp().to_string()));
 
-        log = "Bet end timestamp (UTC): ".to_string() + &betenddatetime.to_string();     context.log(&log);
+        // echo the interpreted time, so that the operator can verify that it is the intended end time
+        let betendutc: String = NaiveDateTime::from_timestamp(betenddatetime, 0).format("%Y-%m-%d %H:%M").to_string() + &" UTC".to_string();
+        context.results().get_string(&"BETENDUTC".to_string()).set_value(&betendutc);
+        context.results().get_int64(&"BETENDDATETIME".to_string()).set_value(betenddatetime);
 
         // store state
-        context.state().get_int64(&"betenddatetime".to_string()).set_value(betenddatetime);
+        marketstate(context).get_int64(&"betenddatetime".to_string()).set_value(betenddatetime);
+    }
+    effectivesetting(context, &mut configuration, "betenddatetime", marketstate(context).get_int64(&"betenddatetime".to_string()).value().to_string());
+
+    // optional bettor vote replacing the refunds of the dead-man switch: for VOTEWINDOW seconds after the chain owner's window the betting
+    // accounts vote on the outcome, and tallyandsettle settles on the value with the most weight; TIEBREAK decides a tie, REFUND (default) or HASH
+    let votewindow: i64 = paramint64(context, "VOTEWINDOW");
+    context.require(votewindow >= 0, "VOTEWINDOW must not be negative");
+    if votewindow > 0 {
+        context.require(heartbeatwindow > 0 && marketstate(context).get_int64(&"betenddatetime".to_string()).value() != 0, "VOTEWINDOW requires a HEARTBEATWINDOW and a BETENDUTC");
+    }
+    let mut tiebreakpolicy: String = context.params().get_string(&"TIEBREAK".to_string()).value();
+    if tiebreakpolicy == "" {
+        tiebreakpolicy = TIEBREAK_REFUND.to_string();
+    }
+    context.require(tiebreakpolicy == TIEBREAK_REFUND || tiebreakpolicy == TIEBREAK_HASH, "TIEBREAK must be REFUND or HASH");
+    marketstate(context).get_int64(&"votewindow".to_string()).set_value(votewindow);
+    marketstate(context).get_string(&"tiebreak".to_string()).set_value(&tiebreakpolicy);
+    effectivesetting(context, &mut configuration, "votewindow", votewindow.to_string());
+    effectivesetting(context, &mut configuration, "tiebreak", tiebreakpolicy.clone());
+
+    // optional commit-reveal mode, in which bets are committed sealed by commitbet and revealed by revealbet within REVEALWINDOW seconds
+    // after the end time for bets; MAXCOMMITS and MAXCOMMITESCROW limit the unrevealed commitments of an address, and COMMITFORFEIT
+    // decides whether the escrow of an unrevealed commitment is refunded (REFUND, default) or kept by the contract owner (OWNER)
+    let commitreveal: bool = context.params().get_string(&"COMMITREVEAL".to_string()).value() == "true";
+    context.require(marketstate(context).get_int64(&"unrevealedcommits".to_string()).value() == 0, "the market is already initialized and has unrevealed commitments");
+    if commitreveal {
+        context.require(marketstate(context).get_int64(&"betenddatetime".to_string()).value() != 0, "COMMITREVEAL requires a BETENDUTC");
+        context.require(tokencolor == "", "COMMITREVEAL requires a market in IOTA");
+    }
+    let mut revealwindow: i64 = paramint64(context, "REVEALWINDOW");
+    if context.params().get_string(&"REVEALWINDOW".to_string()).value() == "" {
+        revealwindow = DEFAULTREVEALWINDOW;
+    }
+    let mut maxcommits: i64 = paramint64(context, "MAXCOMMITS");
+    if context.params().get_string(&"MAXCOMMITS".to_string()).value() == "" {
+        maxcommits = DEFAULTMAXCOMMITS;
+    }
+    let maxcommitescrow: i64 = paramint64(context, "MAXCOMMITESCROW");
+    context.require(revealwindow > 0, "REVEALWINDOW must be positive");
+    context.require(maxcommits > 0, "MAXCOMMITS must be positive");
+    context.require(maxcommitescrow >= 0, "MAXCOMMITESCROW must not be negative");
+    let mut commitforfeit: String = context.params().get_string(&"COMMITFORFEIT".to_string()).value();
+    if commitforfeit == "" {
+        commitforfeit = COMMITFORFEIT_REFUND.to_string();
+    }
+    context.require(commitforfeit == COMMITFORFEIT_REFUND || commitforfeit == FORFEIT_OWNER, "COMMITFORFEIT must be REFUND or OWNER");
+    marketstate(context).get_string(&"commitreveal".to_string()).set_value(&commitreveal.to_string());
+    marketstate(context).get_int64(&"revealwindow".to_string()).set_value(revealwindow);
+    marketstate(context).get_int64(&"maxcommits".to_string()).set_value(maxcommits);
+    marketstate(context).get_int64(&"maxcommitescrow".to_string()).set_value(maxcommitescrow);
+    marketstate(context).get_string(&"commitforfeit".to_string()).set_value(&commitforfeit);
+    effectivesetting(context, &mut configuration, "commitreveal", commitreveal.to_string());
+    effectivesetting(context, &mut configuration, "revealwindow", revealwindow.to_string());
+    effectivesetting(context, &mut configuration, "maxcommits", maxcommits.to_string());
+    effectivesetting(context, &mut configuration, "maxcommitescrow", maxcommitescrow.to_string());
+    effectivesetting(context, &mut configuration, "commitforfeit", commitforfeit.clone());
+
+    // optional fixed-odds mode instead of the parimutuel pool, in which the odds quoted by setodds are locked with every bet;
+    // every book has to keep the sum of its implied probabilities from OVERROUNDMINBPS to OVERROUNDMAXBPS
+    let book: bool = context.params().get_string(&"BOOK".to_string()).value() == "true";
+    if book != (marketstate(context).get_string(&"book".to_string()).value() == "true") {
+        context.require(marketstate(context).get_int64(&"totalbetamount".to_string()).value() == 0, "BOOK cannot be changed once the market has bets");
+    }
+    let mut overroundminbps: i64 = paramint64(context, "OVERROUNDMINBPS");
+    if context.params().get_string(&"OVERROUNDMINBPS".to_string()).value() == "" {
+        overroundminbps = DEFAULTOVERROUNDMINBPS;
+    }
+    let mut overroundmaxbps: i64 = paramint64(context, "OVERROUNDMAXBPS");
+    if context.params().get_string(&"OVERROUNDMAXBPS".to_string()).value() == "" {
+        overroundmaxbps = DEFAULTOVERROUNDMAXBPS;
+    }
+    if book {
+        context.require(!outcomes.is_empty(), "BOOK requires OUTCOMES to quote odds for");
+        context.require(tokencolor == "", "BOOK requires a market in IOTA");
+        context.require(!commitreveal, "BOOK cannot be combined with COMMITREVEAL");
+        context.require(feepercent == 0, "BOOK takes no FEEPERCENT, its margin is the overround");
+    }
+    context.require(overroundminbps >= 10000 && overroundmaxbps >= overroundminbps, "OVERROUNDMINBPS must be at least 10000 and OVERROUNDMAXBPS at least OVERROUNDMINBPS");
+    marketstate(context).get_string(&"book".to_string()).set_value(&book.to_string());
+    marketstate(context).get_int64(&"overroundminbps".to_string()).set_value(overroundminbps);
+    marketstate(context).get_int64(&"overroundmaxbps".to_string()).set_value(overroundmaxbps);
+    effectivesetting(context, &mut configuration, "book", book.to_string());
+    effectivesetting(context, &mut configuration, "overroundminbps", overroundminbps.to_string());
+    effectivesetting(context, &mut configuration, "overroundmaxbps", overroundmaxbps.to_string());
+
+    // the creator of the market and its share of the fee of the market, taken from the platform defaults when the market is first initialized,
+    // so that initializing it again or changing the defaults afterwards keeps both, and the version of the defaults they were taken from
+    if marketstate(context).get_string(&"creator".to_string()).value() == "" {
+        marketstate(context).get_string(&"creator".to_string()).set_value(&AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address()));
+        snapshotdefaults(context, &marketstate(context));
+    }
+    configuration.insert("creator".to_string(), builtinsetting(marketstate(context).get_string(&"creator".to_string()).value()));
+    platformsettings(&mut configuration, &marketstate(context));
+
+    // settings of every market, which cannot be changed by a parameter
+    configuration.insert("addressencoding".to_string(), builtinsetting(AddressCodec::CURRENT.flag().to_string()));
+    configuration.insert("payoutbatch".to_string(), builtinsetting(DEFAULTPAYOUTBATCH.to_string()));
+    configuration.insert("maxrefusalnotice".to_string(), builtinsetting(MAXREFUSALNOTICE.to_string()));
+
+    // contradicting settings reject the market, suspicious ones are returned and stored as warnings
+    let issues: Vec<ConfigurationIssue> = configurationissues(|key| settingint64(&configuration, key), context.timestamp());
+    let contradictions: Vec<String> = issues.iter().filter(|issue| issue.fatal).map(|issue| issue.rule.to_string() + &": ".to_string() + &issue.message).collect();
+    context.require(contradictions.is_empty(), &("inconsistent parameters: ".to_string() + &contradictions.join("; ")));
+    let warningsjson: String = serde_json::to_string(&issues).expect("failed to make json of warnings");
+    marketstate(context).get_string(&"configwarningsjson".to_string()).set_value(&warningsjson);
+    context.results().get_string(&"WARNINGS".to_string()).set_value(&warningsjson);
+
+    // one summary line instead of a line per setting, e.g. "INITMARKET is run: betenddatetime=0 (default), ..."
+    let summary: Vec<String> = configuration.iter().map(|(key, setting)| key.to_string() + &"=".to_string() + &setting.value + &" (".to_string() + &setting.provenance + &")".to_string()).collect();
+    let log: String = "INITMARKET is run: ".to_string() + &summary.join(", ") + &", warnings: ".to_string() + &issues.len().to_string();  context.log(&log);
+
+    let configjson: String = serde_json::to_string(&configuration).expect("failed to make json of configuration");
+    marketstate(context).get_string(&"configjson".to_string()).set_value(&configjson);
+    context.results().get_string(&"CONFIG".to_string()).set_value(&configjson);
+
+    recordtransition(context, &marketstate(context), "initialized");
+    context.event(&events::marketinitialized(&marketid, marketstate(context).get_int64(&"betenddatetime".to_string()).value(), &externalref));
+    if let Some(previoustimeline) = previoustimeline {
+        emittimelinechange(context, &previoustimeline);
     }
 }
 
-#[serde_as]
-#[derive(Deserialize, Serialize)]
-struct Bet {
-    // bet size in IOTA
-    betamount: i32,
-    // value for which the bet is valid, e.g., "yes" or "no" regarding a question or an outcome of an event
-    betisforvalue: String,
+// Add the setting key of a parameter of initmarket to the effective configuration, see INITMARKETSETTINGS for its parameter,
+// whose provenance is the one of a parameter if it was given and the default otherwise.
+fn effectivesetting<T: ScBaseContext>(context: &T, configuration: &mut BTreeMap<String, EffectiveSetting>, key: &str, value: String) {
+    let param: &str = settingparam(key).expect("setting without a parameter of initmarket");
+    configuration.insert(key.to_string(), paramsetting(value, context.params().get_string(param).value() != ""));
 }
 
-#[serde_as]
-#[derive(Deserialize, Serialize)]
-struct ContainerOfBets {
-    // map betting account's wallet address (string) to a Bet
-    map: HashMap<String,Bet>,
+fn settingint64(configuration: &BTreeMap<String, EffectiveSetting>, key: &str) -> i64 {
+    configuration.get(key).and_then(|setting| setting.value.parse::<i64>().ok()).unwrap_or(0)
+}
+
+// Parse the end time for bets and convert it to a UNIX timestamp, in the formats accepted by betendwithoffset of the core crate,
+// e.g. "2021-06-01 18:00 UTC", "2021-06-01 18:00+02:00" or "2021-06-01T18:00:30Z".
+fn parsebetendutc(betendutc: &str) -> Result<i64, String> {
+    let text: String = betendwithoffset(betendutc).map_err(|error| "BETENDUTC ".to_string() + &error + &": ".to_string() + betendutc)?;
+    BETENDFORMATS.iter()
+        .find_map(|format| DateTime::parse_from_str(&text, format).ok())
+        .map(|datetime| datetime.timestamp())
+        .ok_or_else(|| "BETENDUTC ".to_string() + &betendformaterror() + &": ".to_string() + betendutc)
+}
+
+// Timestamp of the deployment of the contract, recorded by the first call needing it, i.e. the first initmarket or challenge.
+fn deployedat(context: &ScFuncContext) -> i64 {
+    let deployedat = context.state().get_int64(&"deployedat".to_string());
+    if deployedat.value() == 0 {
+        deployedat.set_value(context.timestamp());
+    }
+    deployedat.value()
+}
+
+// Reject an end time for bets before the deployment of the contract, which includes the dates before 1970 whose negative timestamps
+// would collide with the arithmetic on end times, or more than MAXBETDURATION seconds after it, like the year 9999.
+// The end time at the deployment itself is accepted.
+fn boundedbetend(betenddatetime: i64, deployedat: i64) -> Result<i64, String> {
+    let quoted: String = NaiveDateTime::from_timestamp_opt(betenddatetime, 0)
+        .map_or("".to_string(), |naivedatetime| naivedatetime.format("%Y-%m-%d %H:%M").to_string() + &" UTC ".to_string())
+        + &"(".to_string() + &betenddatetime.to_string() + &")".to_string();
+    if betenddatetime < deployedat {
+        return Err("BETENDUTC ".to_string() + &quoted + &" is before the deployment of the contract at ".to_string() + &deployedat.to_string());
+    }
+    if betenddatetime > deployedat.saturating_add(MAXBETDURATION) {
+        return Err("BETENDUTC ".to_string() + &quoted + &" is more than ".to_string() + &MAXBETDURATION.to_string() + &" seconds after the deployment of the contract at ".to_string() + &deployedat.to_string());
+    }
+    Ok(betenddatetime)
+}
+
+// The layouts of the bets are described in the storage module of the core crate. Which of the layouts is used is recorded in
+// storageformat, which only migratestorage writes. Every read and write asserts that the keys match the recorded layout
+// instead of guessing the layout from the keys that exist.
+// Fail the transaction unless the keys of the bets match the layout about to be used: there must be no chunks in the single
+// value layout, no single value in the chunked layout, and neither in the map layout.
+fn requirestorageformat<T: ScBaseContext>(context: &T, recorded: &str, layout: &str, chunks: i64, unchunked: &str) {
+    let recorded: &str = storageformat(recorded);
+    if !storageformatconsistent(recorded, layout, chunks, unchunked) {
+        context.panic(&(ERR_STORAGE_FORMAT_MISMATCH.to_string() + &": the bets are recorded in the layout ".to_string() + recorded
+            + &" but accessed in the layout ".to_string() + layout + &" with ".to_string() + &chunks.to_string() + &" chunks and ".to_string()
+            + &unchunked.len().to_string() + &" bytes in a single value".to_string()));
+    }
+}
+
+// all bets of the market in the recorded layout
+fn loadcontainerofbets<T: ScBaseContext>(context: &T, state: &ScImmutableMap) -> ContainerOfBets {
+    let recorded: String = state.get_string(&"storageformat".to_string()).value();
+    let layout: &str = storageformat(&recorded);
+    let unchunked: String = state.get_string(&"containerofbetsjson".to_string()).value();
+    let chunks: i64 = state.get_int64(&"containerofbetsjson_chunks".to_string()).value();
+    requirestorageformat(context, &recorded, layout, chunks, &unchunked);
+    if layout == STORAGE_MAP {
+        let betteraddresses = state.get_string_array(&"betteraddresses".to_string());
+        let bets = state.get_map(&"bets".to_string());
+        let map: HashMap<String, Bet> = (0..betteraddresses.length())
+            .map(|i| betteraddresses.get_string(i).value())
+            .map(|betteraddress| {
+                let bet: Bet = decodebet(&bets.get_bytes(&betteraddress).value()).expect("failed to get bet");
+                (betteraddress, bet)
+            })
+            .collect();
+        return ContainerOfBets { map };
+    }
+    parsecontainerofbets(&joinchunks(unchunked, chunks, |index| state.get_string(&chunkkey(index)).value()))
+}
+
+fn readcontainerofbets(context: &ScFuncContext) -> ContainerOfBets {
+    loadcontainerofbets(context, &marketstate(context).immutable())
+}
+
+fn viewcontainerofbets(context: &ScViewContext) -> ContainerOfBets {
+    loadcontainerofbets(context, &viewmarketstate(context))
+}
+
+// Migrate the bets of a market stored as json, in a single value or in chunks, to the map. The json is cleared in the same call
+// that records the map layout, so no call sees the bets in two layouts or in none. Running totals are started if the market has none.
+fn migratestorage(context: &ScFuncContext) {
+    let state = marketstate(context);
+    let recorded: String = state.get_string(&"storageformat".to_string()).value();
+    if storageformat(&recorded) == STORAGE_MAP {
+        return;
+    }
+    let containerofbets: ContainerOfBets = readcontainerofbets(context);
+    let chunkcount = state.get_int64(&"containerofbetsjson_chunks".to_string());
+    for index in 0..chunkcount.value() {
+        state.get_string(&chunkkey(index)).set_value("");
+    }
+    chunkcount.set_value(0);
+    state.get_string(&"containerofbetsjson".to_string()).set_value("");
+    state.get_string(&"storageformat".to_string()).set_value(STORAGE_MAP);
+    for betteraddress in sortedbetteraddresses(&containerofbets) {
+        writebet(context, &betteraddress, &containerofbets.map[&betteraddress]);
+    }
+    if state.get_string(&"runningtotals".to_string()).value() != "true" {
+        settotals(context, &bettotals(&containerofbets));
+    }
+
+    let log: String = "Bets migrated to the map layout: ".to_string() + &containerofbets.map.len().to_string();  context.log(&log);
+}
+
+// the bet of the betting account, read from the map layout, which migratestorage has to have established
+fn readbet(context: &ScFuncContext, betteraddress: &str) -> Option<Bet> {
+    let stored: Vec<u8> = marketstate(context).get_map(&"bets".to_string()).get_bytes(betteraddress).value();
+    if stored.is_empty() {
+        return None;
+    }
+    Some(decodebet(&stored).expect("failed to get bet"))
+}
+
+// Store the bet of the betting account in the map layout, appending its address on its first bet, which the number of betting accounts counts.
+fn writebet(context: &ScFuncContext, betteraddress: &str, bet: &Bet) {
+    let state = marketstate(context);
+    requirestorageformat(context, &state.get_string(&"storageformat".to_string()).value(), STORAGE_MAP,
+        state.get_int64(&"containerofbetsjson_chunks".to_string()).value(), &state.get_string(&"containerofbetsjson".to_string()).value());
+    let stored = state.get_map(&"bets".to_string()).get_bytes(betteraddress);
+    if !stored.exists() {
+        let betteraddresses = state.get_string_array(&"betteraddresses".to_string());
+        betteraddresses.get_string(betteraddresses.length()).set_value(betteraddress);
+        state.get_int64(&COUNTER_NUMBETTORS.to_string()).set_value(betteraddresses.length() as i64);
+    }
+    stored.set_value(&encodebet(bet));
 }
 
 
@@ -85,151 +984,5469 @@ struct ContainerOfBets {
 // the amount to bet is the amount of IOTA sent with the function call
 // bets must be placed in time before the betenddatetime has passed set on initialization
 fn bet(context: &ScFuncContext) {
+    // account for IOTA sent with the call, which are accepted by the function itself if at all
+    openledger(context);
+    let _recorder = match idempotent(context, "bet") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+
+    let caller: ScAddress = context.caller().address();
+    placebet(context, &caller, &caller, None);
+}
+
+// Place a bet for the betting account better with the IOTA sent by funder, who gets refunded whatever is not accepted.
+// Both are the caller of bet, and differ for a bet relayed by betfor. A sealed bet of a commit-reveal market is placed by revealbet
+// with the revealed amount of its escrow, which was moved to the strays like IOTA sent with a bet; it was on time when it was committed.
+fn placebet(context: &ScFuncContext, better: &ScAddress, funder: &ScAddress, revealed: Option<i64>) {
+    requiremarket(context);
+    context.require(marketinitialized(&marketstate(context)), "the market is not initialized, bets are only accepted after initmarket");
     let currtime:i64 = context.timestamp();  // transaction timestamp?!
-    let betenddatetime:i64 = context.state().get_int64(&"betenddatetime".to_string()).value();
-
-    // either we don't use a fixed end time - or we check if the end time is not exceeded
-    if betenddatetime==0 || (betenddatetime!=0 && currtime <= betenddatetime) {
-        let mut log:String = "BET is placed:".to_string(); context.log(&log);
-
-        // how much IOTA were sent with the transaction?
-        let incoming = context.incoming().balance(&ScColor::IOTA);
-        log = "bet amount (IOTA): ".to_string() + &incoming.to_string();   context.log(&log);
-      
-        // get outcome value on which the bet was placed
-        let betvalue = context.params().get_string(&"BETVALUE".to_string());
-        // require parameter exists
-        context.require(betvalue.exists(), "bet value parameter not found");
-
-        // get wallet address of betting account
-        let caller = context.caller().address();
-        // store the value the bet refers to, e.g., "yes" or "no" - per betting account
-        context.state().get_map(&caller.to_string()).get_string(&"betvalue".to_string()).set_value(&betvalue.to_string());
-        
-        // store all bets as jsonified hashmap in the state, which does not allow iterating over a map
-        let containerofbetsjson = context.state().get_string(&"containerofbetsjson".to_string()).value();
-        let mut containerofbets : ContainerOfBets;
-
-        // already stored?
-        if containerofbetsjson == "" {
-            containerofbets = ContainerOfBets {
-                map : HashMap::new()
-            };
+    let betenddatetime:i64 = marketstate(context).get_int64(&"betenddatetime".to_string()).value();
+
+    // bets can be frozen once the outcome was declared early
+    let betsfrozen: bool = marketstate(context).get_string(&"betsfrozen".to_string()).value() == "true";
+
+    let strict: bool = isstrict(context);
+
+    // optional UNIX timestamp after which the bet must not be executed anymore, e.g. when a congested chain delays the request
+    let notafter: i64 = paramint64(context, "NOTAFTER");
+    // the whole call is returned if it carries other tokens than the market is denominated in
+    if returnforeigntokens(context, funder) {
+        return;
+    }
+    // how much IOTA, or tokens of the market's color, were sent with the transaction
+    let incoming: i64 = revealed.unwrap_or_else(|| incomingtokens(context));
+    if revealed.is_none() && marketstate(context).get_string(&"commitreveal".to_string()).value() == "true" {
+        rejectbet(context, funder, incoming, ERR_BETS_SEALED, "bet was not accepted, the bets of the market are committed by commitbet and revealed by revealbet");
+        return;
+    }
+    if let Some(expiry) = intentexpiry(notafter, currtime) {
+        rejectbet(context, funder, incoming, expiry.error, expiry.message);
+        return;
+    }
+
+    if marketstate(context).get_string(&"migrationhash".to_string()).value() != "" {
+        rejectbet(context, funder, incoming, ERR_MARKET_MIGRATED, "bet was not accepted, the market was migrated to another contract");
+        return;
+    }
+    if marketstate(context).get_string(&"rebuilding".to_string()).value() == "true" {
+        rejectbet(context, funder, incoming, ERR_COUNTERS_REBUILDING, "bet was not accepted, the counters of the market are being rebuilt");
+        return;
+    }
+
+    // no bets during the announcement period of the market
+    let opensat: i64 = marketstate(context).get_int64(&"opensat".to_string()).value();
+    if currtime < opensat {
+        rejectbet(context, funder, incoming, ERR_NOT_YET_OPEN, &("bet was not accepted, the market opens for bets at ".to_string() + &opensat.to_string()));
+        return;
+    }
+
+    // the outcome value on which the bet was placed
+    let betvalue: String = context.params().get_string(&"BETVALUE".to_string()).value();
+    // a bet while bets are frozen, after the end time, without BETVALUE or without IOTA is refunded, see betrejection;
+    // a bet in the very second of the end time is on time; the check is against the end time stored before the bet,
+    // so that anything extending the end time has to do so after the bet was accepted
+    let ontimeuntil: i64 = if revealed.is_some() { 0 } else { betenddatetime };
+    if let Some(rejection) = betrejection(betsfrozen && revealed.is_none(), ontimeuntil, currtime, &betvalue, incoming) {
+        if rejection.soft {
+            if rejection.error == ERR_BET_TOO_LATE {
+                snapshotscenarios(context, &readcontainerofbets(context));
+            }
+            softrejectbet(context, strict, funder, rejection.error, rejection.message);
+        } else {
+            rejectbet(context, funder, incoming, rejection.error, rejection.message);
+        }
+        return;
+    }
+    let mut log:String = "BET is placed:".to_string(); context.log(&log);
+    log = "bet amount (IOTA): ".to_string() + &incoming.to_string();   context.log(&log);
+
+    let betvalue: String = canonicaloutcome(&betvalue);
+    if !allowedoutcome(&marketstate(context).get_string(&"outcomes".to_string()).value(), &betvalue) {
+        rejectbet(context, funder, incoming, ERR_UNKNOWN_OUTCOME, &("bet was not accepted, \"".to_string() + &betvalue + &"\" is not one of the OUTCOMES of the market".to_string()));
+        return;
+    }
+    // optional attribution of the bet to a client and a campaign of a marketing partner
+    let clientid: String = context.params().get_string(&"CLIENTID".to_string()).value();
+    let mut campaign: String = context.params().get_string(&"CAMPAIGN".to_string()).value();
+    if !validattribution(&clientid) || !validattribution(&campaign) {
+        rejectbet(context, funder, incoming, ERR_INVALID_ATTRIBUTION, &("bet was not accepted, CLIENTID and CAMPAIGN must have up to ".to_string() + &MAXATTRIBUTION.to_string() + &" letters, digits, '-', '_' or '.'".to_string()));
+        return;
+    }
+    // optional memo of the bet in base58, encrypted off-chain to the MEMOPUBKEY of the market; the contract never decrypts it
+    let memoparam: String = context.params().get_string(&"MEMOCIPHER".to_string()).value();
+    let memocipher: Vec<u8> = if memoparam == "" { Vec::new() } else { context.utility().base58_decode(&memoparam) };
+    if let Some(rejection) = memorejection(&memoparam, &memocipher, &marketstate(context).get_string(&"memopubkey".to_string()).value()) {
+        rejectbet(context, funder, incoming, rejection.error, rejection.message);
+        return;
+    }
+
+    // wallet address of the betting account
+    let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), better);
+
+    // blocked accounts cannot bet
+    if addressblocked(context, &betteraddress) {
+        rejectbet(context, funder, incoming, ERR_ADDRESS_BLOCKED, "bet was not accepted, the betting account is blocked");
+        return;
+    }
+
+    // the first bet of an account must acknowledge the market's terms, if configured
+    let termshash: String = marketstate(context).get_string(&"termshash".to_string()).value();
+    let recordedack = marketstate(context).get_map(&"termsacks".to_string()).get_string(&betteraddress);
+    match checkterms(&termshash, &recordedack.value(), &context.params().get_string(&"ACKTERMS".to_string()).value(), currtime) {
+        TermsCheck::Acknowledged => {}
+        TermsCheck::Refuse => {
+            rejectbet(context, funder, incoming, ERR_TERMS_NOT_ACKED, "bet was not accepted, the terms of the market must be acknowledged by ACKTERMS");
+            return;
         }
-        else {
-            // de-serialize and re-create the struct from string
-            containerofbets = serde_json::from_str(&containerofbetsjson).expect("failed to get container of bets");
+        TermsCheck::Record(acknowledgement) => {
+            // record the acknowledgement once as "hash|timestamp"
+            recordedack.set_value(&acknowledgement);
+            log = "terms acknowledged by ".to_string() + &betteraddress;   context.log(&log);
         }
+    }
 
-        // create Bet struct and store in map under the betting account's (wallet) address
-        let bet = Bet  {
-            betamount: incoming.to_string().parse::<i32>().unwrap(),
-            betisforvalue: betvalue.to_string(),
-        };
-        containerofbets.map.insert(caller.to_string(), bet);
+    // the bets are stored in a map under the address of the betting account, so a bet only reads and writes the bet of its account
+    migratestorage(context);
+    let storedbet: Option<Bet> = readbet(context, &betteraddress);
 
-        // serialize all bets to a json string
-        let containerofbetsjson = serde_json::to_string(&containerofbets).expect("failed to make json of container of bets");
-        // store state as a string
-        context.state().get_string(&"containerofbetsjson".to_string()).set_value(&containerofbetsjson);
-    } else {
-        let log:String = "bet was not provided on time".to_string();
-        context.log(&log);
+    // a further bet of the same account adds to its previous bet, which has to be on the same value
+    let previousbet: Option<&Bet> = storedbet.as_ref();
+    let previousamount: i64 = previousbet.map(|bet| bet.betamount as i64).unwrap_or(0);
+    let previousattached: i64 = previousbet.map(|bet| bet.attachedamount).unwrap_or(0);
+    if let Some(previousbet) = previousbet {
+        if previousbet.betisforvalue != betvalue {
+            rejectbet(context, funder, incoming, ERR_BET_VALUE_CHANGED, &("bet was not accepted, the betting account already bet on ".to_string() + &previousbet.betisforvalue + &", a further bet has to be on the same value".to_string()));
+            return;
+        }
     }
-}
 
+    // limit the share of the pool the betting account holds after the bet
+    let mut cappedamount: i64 = cappedbetamount(marketstate(context).get_int64(&"totalbetamount".to_string()).value(), previousamount, incoming,
+        marketstate(context).get_int64(&"maxsharebps".to_string()).value(),
+        marketstate(context).get_int64(&"maxshareexemptpool".to_string()).value());
+    let sharecappedamount: i64 = cappedamount;
+    if cappedamount < incoming {
+        // an exception granted by the contract owner lifts the limit once, up to the granted amount
+        let exceptionamount: i64 = betexception(context, &betteraddress, currtime);
+        if exceptionamount > cappedamount {
+            cappedamount = incoming.min(exceptionamount);
+            log = "bet amount is accepted by an exception up to (IOTA): ".to_string() + &exceptionamount.to_string();   context.log(&log);
+        }
+    }
+    if cappedamount < incoming {
+        log = "bet amount is limited by the maximum share of the pool to (IOTA): ".to_string() + &cappedamount.to_string();   context.log(&log);
+    }
+    // limit the stake of the betting account across all markets of the group the market is linked to
+    let groupid: String = marketstate(context).get_string(&"groupid".to_string()).value();
+    if groupid != "" {
+        let group: ScMutableMap = groupstate(context, &groupid);
+        let groupcapped: i64 = groupcappedamount(cappedamount, group.get_map(&"stakes".to_string()).get_int64(&betteraddress).value(), group.get_int64(&"maxstake".to_string()).value());
+        if groupcapped < cappedamount {
+            cappedamount = groupcapped;
+            log = "bet amount is limited by the maximum stake across the group ".to_string() + &groupid + &" to (IOTA): ".to_string() + &cappedamount.to_string();   context.log(&log);
+        }
+    }
+    // a bet in a market with BOOK is locked at the odds quoted now, and limited to what the book can cover if its value wins
+    let book: bool = marketstate(context).get_string(&"book".to_string()).value() == "true";
+    let mut oddsbps: i64 = 0;
+    if book {
+        oddsbps = *bookodds(&marketstate(context).immutable()).get(&betvalue).unwrap_or(&0);
+        if oddsbps == 0 {
+            rejectbet(context, funder, incoming, ERR_NO_ODDS, &("bet was not accepted, the book quotes no odds for \"".to_string() + &betvalue + &"\"".to_string()));
+            return;
+        }
+        let liability: i64 = marketstate(context).get_map(&"bookliability".to_string()).get_int64(&betvalue).value();
+        let bookcapped: i64 = bookcappedamount(cappedamount, oddsbps, liability, bookfunds(&marketstate(context).immutable()));
+        if bookcapped == 0 {
+            rejectbet(context, funder, incoming, ERR_BOOK_LIABILITY, &("bet was not accepted, the book cannot cover another bet on \"".to_string() + &betvalue + &"\"".to_string()));
+            return;
+        }
+        if bookcapped < cappedamount {
+            cappedamount = bookcapped;
+            log = "bet amount is limited by the liability the book can cover to (IOTA): ".to_string() + &cappedamount.to_string();   context.log(&log);
+        }
+    }
+    // round down to the grid of bet amounts, the rest of the incoming IOTA is refunded
+    let betamount: i64 = flooredbetamount(cappedamount, marketstate(context).get_int64(&"betgranularity".to_string()).value());
+    context.results().get_int64(&"ATTACHED".to_string()).set_value(incoming);
+    context.results().get_int64(&"ACCEPTED".to_string()).set_value(betamount);
 
-// Function to close the prediction market, to be called by the contract owner.
-// The function requires a BETVALUE parameter, specifying the winning outcome, e.g., "yes".
-// The functions runs through the stored bets, determines winning bets and the amount of IOTA the receive, and sends the IOTA to the wallets of the winners.
-fn closemarket(context: &ScFuncContext) {
-    // only contract owner should be able to do this
-    let creator = context.contract_creator();
-    let caller = context.caller();
-    context.require(creator == caller, "You are not authorised to close the prediction market - only contract creator is allowed to close the market.");
+    let minbet: i64 = marketstate(context).get_int64(&"minbet".to_string()).value();
+    if betamount == 0 || betamount < minbet {
+        log = "bet was not accepted, the accepted bet amount of ".to_string() + &betamount.to_string() + &" IOTA is below the minimum bet amount".to_string();   context.log(&log);
+        context.results().get_int64(&"ACCEPTED".to_string()).set_value(0);
+        addrefusal(context, ERR_BELOW_MINBET);
+        refund(context, funder, incoming, REFUND_BELOWMINBET);
+        return;
+    }
+    // the settlement counts bet amounts in i32, a bet taking the account's bet or the pool beyond it is refunded instead
+    let totalbetamount: i64 = marketstate(context).get_int64(&"totalbetamount".to_string()).value();
+    let accumulatedamount: Result<i32, _> = i32::try_from(previousamount + betamount);
+    if accumulatedamount.is_err() || i32::try_from(totalbetamount + betamount).is_err() {
+        rejectbet(context, funder, incoming, ERR_BET_TOO_LARGE, &("bet was not accepted, bets of an account and the pool are limited to ".to_string() + &i32::MAX.to_string() + &" IOTA".to_string()));
+        return;
+    }
+    // the bet is accepted now, the exception is used up only if it let the bet accept more than the limit
+    if betamount > flooredbetamount(sharecappedamount, marketstate(context).get_int64(&"betgranularity".to_string()).value()) {
+        usebetexception(context, &betteraddress);
+    }
+    if betamount < incoming {
+        log = "accepted bet amount (IOTA): ".to_string() + &betamount.to_string();   context.log(&log);
+        refund(context, funder, incoming - betamount, REFUND_UNACCEPTED);
+    }
 
-    // the value that won, e.g., "yes" or "no"
-    let betvaluewinning = context.params().get_string(&"BETVALUE".to_string());
-    // require parameter exists
-    context.require(betvaluewinning.exists(), "winning bet value parameter not found");
+    // the accepted bet amount is part of the pools now, the ledger buckets only account IOTA
+    if tokencolor(context, &marketstate(context)) == ScColor::IOTA {
+        movebucket(context, BUCKET_STRAYS, BUCKET_POOLS, betamount);
+    }
+    creditmarketpool(context, betamount);
+    if groupid != "" {
+        let staked = groupstate(context, &groupid).get_map(&"stakes".to_string()).get_int64(&betteraddress);
+        staked.set_value(staked.value() + betamount);
+    }
+    recordactivity(context, currtime, betamount);
+    addtotals(context, &betvalue, betamount);
+    if book {
+        let payout: i64 = lockedpayout(betamount, oddsbps);
+        let lockedpayouts = marketstate(context).get_map(&"bookpayouts".to_string()).get_int64(&betteraddress);
+        lockedpayouts.set_value(lockedpayouts.value() + payout);
+        let liability = marketstate(context).get_map(&"bookliability".to_string()).get_int64(&betvalue);
+        liability.set_value(liability.value() + payout);
+        context.results().get_int64(&"ODDSBPS".to_string()).set_value(oddsbps);
+        context.results().get_int64(&"LOCKEDPAYOUT".to_string()).set_value(payout);
+        log = "bet is locked at odds of ".to_string() + &oddsbps.to_string() + &" basis points, winning (IOTA): ".to_string() + &payout.to_string();   context.log(&log);
+    }
+    if campaign != "" && !recordcampaign(context, &campaign, betamount) {
+        log = "campaign ".to_string() + &campaign + &" is not counted, the market already counts ".to_string() + &MAXCAMPAIGNS.to_string() + &" campaigns".to_string();   context.log(&log);
+        campaign = "".to_string();
+    }
 
-    // only close market after end time for bets, specified on initalization
+    // store the value the bet refers to, e.g., "yes" or "no" - per betting account
+    marketstate(context).get_map(&betteraddress).get_string(&"betvalue".to_string()).set_value(&betvalue.to_string());
+
+    if previousamount > 0 {
+        log = "bet is added to the previous bet of (IOTA): ".to_string() + &previousamount.to_string();   context.log(&log);
+    }
+    context.results().get_int64(&"TOTALBETAMOUNT".to_string()).set_value(previousamount + betamount);
+
+    // create Bet struct and store in map under the betting account's (wallet) address, holding the amounts of all its bets
+    let bet = Bet  {
+        betamount: accumulatedamount.unwrap(),
+        betisforvalue: betvalue.to_string(),
+        placedat: currtime,
+        attachedamount: previousattached + incoming,
+        addressencoding: AddressCodec::CURRENT.flag().to_string(),
+        requestid: context.request_id().to_string(),
+        // a further bet without attribution keeps the attribution of the previous bet
+        clientid: if clientid != "" { clientid.clone() } else { previousbet.map_or("".to_string(), |bet| bet.clientid.clone()) },
+        campaign: if campaign != "" { campaign.clone() } else { previousbet.map_or("".to_string(), |bet| bet.campaign.clone()) },
+        history: bethistory(previousbet, currtime, betamount, &campaign),
+    };
+    writebet(context, &betteraddress, &bet);
+    // the memo is kept apart from the bet, so that no view or export of the bets includes it
+    if !memocipher.is_empty() {
+        marketstate(context).get_map(&"memociphers".to_string()).get_bytes(&betteraddress).set_value(&memocipher);
+    }
+    let betsequence = marketstate(context).get_int64(&"betsequence".to_string());
+    betsequence.set_value(betsequence.value() + 1);
+    if revealed.is_none() {
+        extendbetend(context, currtime, betsequence.value());
+    }
+    settimelineresults(context, &markettimeline(&marketstate(context).immutable()));
+    context.event(&events::betplaced(&marketstate(context).get_string(&"marketid".to_string()).value(), &betteraddress, &betvalue.to_string(), betamount, currtime));
+    evaluatebetalerts(context, &betvalue.to_string());
+    revealodds(context);
+}
+
+// Function for the contract owner or a marketadmin to seed every outcome of a market with the IOTA sent with the call, split by SEEDSPLIT,
+// either "uniform" or weights like "weights:yes=70,no=30" in percent of every outcome of the market, see seeding. The IOTA the rounding leaves
+// go to the outcomes with the largest remainders. Every seed is a bet of the caller on its outcome, stored under a key of its own, see seedkey,
+// which the settlement treats like every other bet and whose winnings and refunds are entitlements of the caller, as is its stake across
+// the group of the market. A further seed of an outcome adds to its seed bet. The seeds bypass the limits of the bets of accounts, like the maximum share and the bet granularity,
+// and are returned per outcome as json in SEEDS.
+fn seedmarket(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "seedmarket") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(authorisedfor(context, "seedmarket"), "Not authorised to seed the market - only contract creator or a marketadmin is allowed to do this.");
+    requiremarket(context);
+    let market: ScMutableMap = marketstate(context);
+    context.require(marketinitialized(&market), "the market is not initialized, it can only be seeded after initmarket");
+    context.require(market.get_string(&"marketclosed".to_string()).value() == "false", "the prediction market was already closed");
+    context.require(market.get_string(&"migrationhash".to_string()).value() == "", "the market was migrated to another contract");
+    context.require(market.get_string(&"rebuilding".to_string()).value() != "true", "the counters of the market are being rebuilt");
+    context.require(market.get_string(&"betsfrozen".to_string()).value() != "true", "the bets of the market are frozen");
     let currtime: i64 = context.timestamp();
-    let betenddatetime: i64 = context.state().get_int64(&"betenddatetime".to_string()).value();
+    context.require(market.get_string(&"book".to_string()).value() != "true", "a market with BOOK is funded by the bankroll of setodds");
+    context.require(market.get_string(&"commitreveal".to_string()).value() != "true", "the bets of a commit-reveal market are sealed");
+    context.require(tokencolor(context, &market) == ScColor::IOTA, "only a market in IOTA can be seeded");
+
+    let seedsplit: String = context.params().get_string(&"SEEDSPLIT".to_string()).value();
+    let weights: Result<Vec<(String, i64)>, String> = parseseedsplit(&seedsplit, &parseoutcomes(&market.get_string(&"outcomes".to_string()).value()));
+    context.require(weights.is_ok(), weights.as_ref().err().map_or("", |error| error.as_str()));
+    let incoming: i64 = incomingtokens(context);
+    context.require(incoming > 0, "the IOTA to seed the market with are required");
+    context.require(i32::try_from(market.get_int64(&"totalbetamount".to_string()).value() + incoming).is_ok(),
+        &("the pool of the market is limited to ".to_string() + &i32::MAX.to_string() + &" IOTA".to_string()));
+    let seeds: Vec<(String, i64)> = seedamounts(incoming, &seedsplit, &weights.unwrap());
 
+    // the seed bets are on time like a bet, see betontime, so a market without end time can always be seeded
+    migratestorage(context);
+    let seeder: String = AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address());
+    let seeded: Result<Vec<(String, Bet)>, String> = seedbets(|betteraddress: &str| readbet(context, betteraddress), &seeder, &seeds,
+        market.get_int64(&"betenddatetime".to_string()).value(), currtime, AddressCodec::CURRENT.flag(), &context.request_id().to_string());
+    context.require(seeded.is_ok(), seeded.as_ref().err().map_or("", |error| error.as_str()));
+
+    movebucket(context, BUCKET_STRAYS, BUCKET_POOLS, incoming);
+    creditmarketpool(context, incoming);
+    // the seeds count for the stakes of the seeding account across the group of the market, without being limited by them
+    let groupid: String = market.get_string(&"groupid".to_string()).value();
+    if groupid != "" {
+        let staked = groupstate(context, &groupid).get_map(&"stakes".to_string()).get_int64(&seeder);
+        staked.set_value(staked.value() + incoming);
+    }
+    let marketid: String = market.get_string(&"marketid".to_string()).value();
     let mut log:String;
+    for (betteraddress, bet) in seeded.unwrap().iter() {
+        let seed: i64 = bet.history.last().map_or(0, |entry| entry.betamount);
+        writebet(context, betteraddress, bet);
+        recordactivity(context, currtime, seed);
+        addtotals(context, &bet.betisforvalue, seed);
+        market.get_map(betteraddress).get_string(&"betvalue".to_string()).set_value(&bet.betisforvalue);
+        let betsequence = market.get_int64(&"betsequence".to_string());
+        betsequence.set_value(betsequence.value() + 1);
+        context.event(&events::betplaced(&marketid, &seeder, &bet.betisforvalue, seed, currtime));
+        log = "outcome ".to_string() + &bet.betisforvalue + &" is seeded with (IOTA): ".to_string() + &seed.to_string();  context.log(&log);
+    }
+    let seeded: BTreeMap<String, i64> = seeds.into_iter().collect();
+    let seedsjson: String = serde_json::to_string(&seeded).expect("failed to make json of seeds");
+    appendadminlog(context, &("seedmarket|".to_string() + &seedsplit + &"|".to_string() + &seedsjson));
+    context.results().get_int64(&"ATTACHED".to_string()).set_value(incoming);
+    context.results().get_string(&"SEEDS".to_string()).set_value(&seedsjson);
+    settimelineresults(context, &markettimeline(&market.immutable()));
+}
 
-    // a flag to check whether the closemarket function was run
-    let marketclosed: String = context.state().get_string("marketclosed").to_string();
-    if marketclosed.eq(&"false".to_string()) {
-        // either we don't use a fixed end time - or we check if the end time is exceeded
-        if betenddatetime == 0 || (betenddatetime != 0 && currtime > betenddatetime) {
-            log = "CLOSEMARKET is executed:".to_string(); context.log(&log);
-            log = "the winning value is: \"".to_string() + &betvaluewinning.to_string() + &"\"".to_string(); context.log(&log);
-
-            // set flag stating that the closemarket function was run
-            context.state().get_string("marketclosed").set_value(&"true".to_string());
-
-            // get all bets from global state
-            // Note that the stat is not specific to a contract but to the whole chain on which it is deployed.
-            let containerofbetsjson = context.state().get_string(&"containerofbetsjson".to_string()).value();
-            let containerofbets: ContainerOfBets;
-
-            if containerofbetsjson != "" {
-                // get bets from json
-                containerofbets = serde_json::from_str(&containerofbetsjson).expect("failed to fetch container of bets");
-                // we require more than one bet
-                if containerofbets.map.keys().len() >= 1 {
-                    // determine total amount of bet amounts per value, e.g., 500 IOTA on "yes" and 2000 IOTA on "no"
-                    let mut betvalue_totalbetamount: HashMap<String, i32> = HashMap::new();
-                    // overall amount in bets, regardless on which outcome value the bet was placed
-                    let mut totalbetamount:i32 = 0;
-                    for (_betteraddress, bet) in &containerofbets.map {
-                        totalbetamount = totalbetamount + bet.betamount;
-                        if betvalue_totalbetamount.contains_key(&bet.betisforvalue) {
-                            betvalue_totalbetamount.insert((&bet.betisforvalue).parse().unwrap(), betvalue_totalbetamount.get(&bet.betisforvalue).unwrap() + bet.betamount);
-                        } else {
-                            betvalue_totalbetamount.insert((&bet.betisforvalue).parse().unwrap(), bet.betamount);
-                        }
-                    }
-
-                    // log output
-                    for (betvalue, totalbetamount) in & betvalue_totalbetamount{
-                        log = "total amount of bets placed on \"".to_string() + &betvalue.to_string() + &"\" is ".to_string() + &totalbetamount.to_string() + &" IOTA".to_string(); context.log(&log);
-                    }
-                    log = "total amount of bets over all values: ".to_string() + &totalbetamount.to_string() + &" IOTA".to_string(); context.log(&log);
-
-                    let mut totalbetamountforvalue: Option<&i32>;
-                    let mut winamount:i64;
-                    let mut recipientaddress:ScAddress;
-                    // send coins to winners
-                    for (betteraddress, bet) in &containerofbets.map {
-                        if bet.betisforvalue.eq(&betvaluewinning.to_string()) {
-                            log = betteraddress.to_string() + &" placed a bet on \"".to_string() + &bet.betisforvalue.to_string() + &"\", which is a WIN".to_string(); context.log(&log);
-                            totalbetamountforvalue  = betvalue_totalbetamount.get(&bet.betisforvalue);
-                            winamount = ((bet.betamount as f32/ *totalbetamountforvalue.unwrap() as f32) * totalbetamount as f32) as i64;
-                            log = "bet amount: ".to_string() + &bet.betamount.to_string() + &" IOTA; won amount: " + &winamount.to_string() + &" IOTA; of total amount placed a bet on " + &totalbetamount.to_string() + &"; where total amount per winning value: " + &totalbetamountforvalue.unwrap().to_string();    context.log(&log);
-                            if winamount>0 {
-                                recipientaddress = ScAddress::from_bytes(&*context.utility().base58_decode(&betteraddress.to_string()));
-                                log = "transferring won amount of IOTA to: ".to_string() +  &recipientaddress.to_string();  context.log(&log);
-                                context.transfer_to_address( &recipientaddress, ScTransfers::new(&ScColor::IOTA, winamount))
-                            }
-                        }
-                        else  {
-                            log = betteraddress.to_string() + &" placed a bet on \"".to_string() + &bet.betisforvalue.to_string() + &"\", which is not a win".to_string(); context.log(&log);
-                        }
-                    }
-                } else {
-                    log  = "at least one bet is required".to_string(); context.log(&log);
-                }
-            } else {
-                log  = "no bets stored".to_string(); context.log(&log);
-            }
+// A sealed bet of a commit-reveal market, stored under its commitment in the commits map of the market.
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct Commitment {
+    // wallet address of the committing account
+    address: String,
+    // IOTA held in escrow for the bet
+    amount: i64,
+    committedat: i64,
+    // "pending" until it is revealed or expired
+    status: String,
+}
+
+fn commitusage(market: &ScMutableMap, betteraddress: &str) -> CommitUsage {
+    CommitUsage {
+        commits: market.get_map(&"commitcounts".to_string()).get_int64(betteraddress).value(),
+        escrow: market.get_map(&"commitescrows".to_string()).get_int64(betteraddress).value(),
+    }
+}
+
+fn setcommitusage(context: &ScFuncContext, betteraddress: &str, usage: CommitUsage) {
+    let market: ScMutableMap = marketstate(context);
+    let unrevealed = market.get_int64(&"unrevealedcommits".to_string());
+    unrevealed.set_value(unrevealed.value() + usage.commits - commitusage(&market, betteraddress).commits);
+    market.get_map(&"commitcounts".to_string()).get_int64(betteraddress).set_value(usage.commits);
+    market.get_map(&"commitescrows".to_string()).get_int64(betteraddress).set_value(usage.escrow);
+}
+
+// time until which the commitments of the market can be revealed, see revealdeadline in the core crate
+fn marketrevealdeadline(market: &ScMutableMap) -> i64 {
+    revealdeadline(market.get_int64(&"betenddatetime".to_string()).value(), market.get_int64(&"revealwindow".to_string()).value())
+}
+
+// Function to commit a sealed bet in a market initialized with COMMITREVEAL: COMMITMENT is the base58 blake2b hash of canonicalcommitment
+// of the market id, the wallet address of the caller, the bet value and a secret SALT, and the IOTA sent with the call are held in escrow
+// until the bet is revealed by revealbet. Commitments are accepted like bets, i.e. not after the end time or while bets are frozen.
+// An address can have up to MAXCOMMITS (default 3) unrevealed commitments holding up to MAXCOMMITESCROW IOTA, 0 for no limit:
+// a commitment beyond either limit is refunded with the error ERR_COMMIT_LIMIT. The results COMMITS and COMMITESCROW return the usage after the call.
+fn commitbet(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "commitbet") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    context.require(marketstate(context).get_string(&"commitreveal".to_string()).value() == "true", "the market does not take sealed bets, bets are placed by bet");
+    let caller: ScAddress = context.caller().address();
+    let incoming: i64 = context.incoming().balance(&ScColor::IOTA);
+    let commitment: String = context.params().get_string(&"COMMITMENT".to_string()).value();
+    let currtime: i64 = context.timestamp();
+    if currtime < marketstate(context).get_int64(&"opensat".to_string()).value() {
+        rejectbet(context, &caller, incoming, ERR_NOT_YET_OPEN, "commitment was not accepted, the market is not open for bets yet");
+        return;
+    }
+    let betsfrozen: bool = marketstate(context).get_string(&"betsfrozen".to_string()).value() == "true";
+    // the sealed value counts as given, so only a commitment while bets are frozen, after the end time or without IOTA is rejected
+    if let Some(rejection) = betrejection(betsfrozen, marketstate(context).get_int64(&"betenddatetime".to_string()).value(), currtime, &commitment, incoming) {
+        if rejection.soft {
+            softrejectbet(context, isstrict(context), &caller, rejection.error, rejection.message);
         } else {
-            log  = "closing the market can be only done after the end time for placing bets has passed".to_string(); context.log(&log);
-        }    
-    } else {
-        log  = "the prediction market was already closed".to_string(); context.log(&log);
+            rejectbet(context, &caller, incoming, rejection.error, rejection.message);
+        }
+        return;
+    }
+    let stored = marketstate(context).get_map(&"commits".to_string()).get_string(&commitment);
+    if stored.exists() {
+        rejectbet(context, &caller, incoming, ERR_COMMIT_LIMIT, "commitment was not accepted, the same commitment was already made");
+        return;
+    }
+    let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &caller);
+    let admitted: Result<CommitUsage, String> = commitusage(&marketstate(context), &betteraddress).admit(incoming,
+        marketstate(context).get_int64(&"maxcommits".to_string()).value(),
+        marketstate(context).get_int64(&"maxcommitescrow".to_string()).value());
+    let usage: CommitUsage = match admitted {
+        Ok(usage) => usage,
+        Err(reason) => {
+            rejectbet(context, &caller, incoming, ERR_COMMIT_LIMIT, &("commitment was not accepted, ".to_string() + &reason));
+            return;
+        }
+    };
+
+    movebucket(context, BUCKET_STRAYS, BUCKET_ESCROWS, incoming);
+    let record = Commitment { address: betteraddress.clone(), amount: incoming, committedat: currtime, status: "pending".to_string() };
+    stored.set_value(&serde_json::to_string(&record).expect("failed to make json of commitment"));
+    let commitsof = marketstate(context).get_map(&"commitsof".to_string()).get_string_array(&betteraddress);
+    commitsof.get_string(commitsof.length()).set_value(&commitment);
+    setcommitusage(context, &betteraddress, usage);
+    let log:String = "sealed bet of ".to_string() + &incoming.to_string() + &" IOTA committed by ".to_string() + &betteraddress;  context.log(&log);
+    context.results().get_int64(&"COMMITS".to_string()).set_value(usage.commits);
+    context.results().get_int64(&"COMMITESCROW".to_string()).set_value(usage.escrow);
+}
+
+// Function to reveal a sealed bet committed by the caller with commitbet, by its BETVALUE and SALT, until REVEALWINDOW seconds after
+// the end time for bets. The escrow of the commitment is then placed as a bet on BETVALUE like by bet, where whatever is not accepted,
+// e.g. beyond the maximum share of the pool, is refunded to the caller. A revealed commitment no longer counts towards MAXCOMMITS and MAXCOMMITESCROW.
+fn revealbet(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "revealbet") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    context.require(marketstate(context).get_string(&"commitreveal".to_string()).value() == "true", "the market does not take sealed bets, bets are placed by bet");
+    context.require(context.incoming().balance(&ScColor::IOTA) == 0, "revealbet accepts no IOTA, the bet is placed with the escrow of the commitment");
+    context.require(revealontime(marketrevealdeadline(&marketstate(context)), context.timestamp()), "the reveal deadline of the market has passed");
+    let caller: ScAddress = context.caller().address();
+    let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &caller);
+    let marketid: String = marketstate(context).get_string(&"marketid".to_string()).value();
+    let commitment: String = context.utility().hash_blake2b(&canonicalcommitment(&marketid, &betteraddress,
+        &context.params().get_string(&"BETVALUE".to_string()).value(), &context.params().get_string(&"SALT".to_string()).value())).to_string();
+    let stored = marketstate(context).get_map(&"commits".to_string()).get_string(&commitment);
+    context.require(stored.exists(), "no commitment of the caller matches BETVALUE and SALT");
+    let mut record: Commitment = serde_json::from_str(&stored.value()).expect("failed to get commitment");
+    context.require(record.status == "pending", &("the commitment is already ".to_string() + &record.status));
+
+    record.status = "revealed".to_string();
+    stored.set_value(&serde_json::to_string(&record).expect("failed to make json of commitment"));
+    let usage: CommitUsage = commitusage(&marketstate(context), &betteraddress).release(record.amount);
+    setcommitusage(context, &betteraddress, usage);
+    let log:String = "sealed bet of ".to_string() + &record.amount.to_string() + &" IOTA revealed by ".to_string() + &betteraddress;  context.log(&log);
+    movebucket(context, BUCKET_ESCROWS, BUCKET_STRAYS, record.amount);
+    placebet(context, &caller, &caller, Some(record.amount));
+}
+
+// Function for anyone to expire the unrevealed commitments of the wallet address ADDRESS once the reveal deadline has passed:
+// with COMMITFORFEIT=REFUND (default) their escrow is returned to the address, with OWNER it is kept by the contract owner as fee.
+// The expired commitments no longer count towards MAXCOMMITS and MAXCOMMITESCROW. EXPIRED returns their number, ESCROW their IOTA.
+fn expirecommits(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "expirecommits") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    let deadline: i64 = marketrevealdeadline(&marketstate(context));
+    context.require(marketstate(context).get_string(&"commitreveal".to_string()).value() == "true", "the market does not take sealed bets");
+    context.require(!revealontime(deadline, context.timestamp()), &("commitments can only be expired after the reveal deadline of ".to_string() + &deadline.to_string()));
+    let betteraddress: String = context.params().get_string(&"ADDRESS".to_string()).value();
+    let decoded: Result<ScAddress, String> = AddressCodec::CURRENT.decode(&context.utility(), &betteraddress);
+    context.require(decoded.is_ok(), decoded.as_ref().err().map_or("", |error| error.as_str()));
+    let recipient: ScAddress = decoded.unwrap();
+    let forfeited: bool = marketstate(context).get_string(&"commitforfeit".to_string()).value() == FORFEIT_OWNER;
+
+    let commitsof = marketstate(context).get_map(&"commitsof".to_string()).get_string_array(&betteraddress);
+    let mut usage: CommitUsage = commitusage(&marketstate(context), &betteraddress);
+    let mut expired: i64 = 0;
+    let mut escrow: i64 = 0;
+    for i in 0..commitsof.length() {
+        let stored = marketstate(context).get_map(&"commits".to_string()).get_string(&commitsof.get_string(i).value());
+        let mut record: Commitment = serde_json::from_str(&stored.value()).expect("failed to get commitment");
+        if record.status != "pending" {
+            continue;
+        }
+        record.status = "expired".to_string();
+        stored.set_value(&serde_json::to_string(&record).expect("failed to make json of commitment"));
+        usage = usage.release(record.amount);
+        expired += 1;
+        escrow += record.amount;
+    }
+    setcommitusage(context, &betteraddress, usage);
+    if forfeited {
+        movebucket(context, BUCKET_ESCROWS, BUCKET_FEES, escrow);
+    } else if escrow > 0 {
+        guardedtransfer(context, &recipient, escrow, "commitrefund", BUCKET_ESCROWS);
+    }
+    let log:String = expired.to_string() + &" unrevealed commitments of ".to_string() + &betteraddress + &" expired, their escrow of ".to_string() + &escrow.to_string()
+        + &if forfeited { " IOTA is kept by the contract owner" } else { " IOTA is refunded" }.to_string();  context.log(&log);
+    context.results().get_int64(&"EXPIRED".to_string()).set_value(expired);
+    context.results().get_int64(&"ESCROW".to_string()).set_value(escrow);
+}
+
+// A winner of a settled market as recorded by closemarket, in the winners array of the market in the order of the addresses.
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct Winner {
+    // wallet address of the betting account
+    address: String,
+    // IOTA the account receives, after the shortfall policy was applied
+    winamount: i64,
+}
+
+// View of the outcome of the market: STATUS is "open" until closemarket, then "voided" if the bets were refunded or "settled",
+// WINNINGVALUE the value closemarket settled on, and WINNERS a json list of up to LIMIT (default 100) winners with their win amounts,
+// e.g. [{"address":"<address>","winamount":250}], starting at the index CURSOR. NEXTCURSOR is the index to continue with, or -1.
+// WINNERSRECORDED is "false" for markets settled before the winners were recorded, which return no WINNERS.
+fn getwinners(context: &ScViewContext) {
+    let market = viewmarketstate(context);
+    let cursor: i64 = paramint64(context, "CURSOR");
+    let mut limit: i64 = paramint64(context, "LIMIT");
+    if limit <= 0 {
+        limit = DEFAULTREFUNDPAGE;
+    }
+    context.require(cursor >= 0, "CURSOR must not be negative");
+
+    let status: &str = if market.get_string(&"marketclosed".to_string()).value() != "true" {
+        "open"
+    } else if market.get_string(&"marketvoided".to_string()).value() == "true" {
+        "voided"
+    } else {
+        "settled"
+    };
+    let winnersarray = market.get_string_array(&"winners".to_string());
+    let mut winners: Vec<Winner> = Vec::new();
+    let mut nextcursor: i64 = -1;
+    for i in cursor..winnersarray.length() as i64 {
+        if winners.len() as i64 == limit {
+            nextcursor = i;
+            break;
+        }
+        winners.push(serde_json::from_str(&winnersarray.get_string(i as i32).value()).expect("failed to get winner"));
+    }
+    context.results().get_string(&"STATUS".to_string()).set_value(status);
+    context.results().get_string(&"WINNINGVALUE".to_string()).set_value(&market.get_string(&"winningvalue".to_string()).value());
+    context.results().get_string(&"WINNERSRECORDED".to_string()).set_value(&(market.get_string(&"winnersrecorded".to_string()).value() == "true").to_string());
+    context.results().get_string(&"WINNERS".to_string()).set_value(&serde_json::to_string(&winners).expect("failed to make json of winners"));
+    context.results().get_int64(&"NEXTCURSOR".to_string()).set_value(nextcursor);
+}
+
+// Extend the end time for bets after the bet with the given sequence number was accepted within the EXTENSIONWINDOW before it,
+// so that the full window remains for others to react to the bet. The number of extensions is capped by MAXEXTENSIONS and the seconds
+// added over all extensions, counted in totalextension, by MAXTOTALEXTENSION, see extendedbetend. Every extension emits
+// betend.extended with the end time before and after it and the sequence number of the bet.
+fn extendbetend(context: &ScFuncContext, currtime: i64, betsequence: i64) {
+    let market = marketstate(context);
+    let extensionwindow: i64 = market.get_int64(&"extensionwindow".to_string()).value();
+    let betenddatetime: i64 = market.get_int64(&"betenddatetime".to_string()).value();
+    let extensions = market.get_int64(&"extensions".to_string());
+    let totalextension = market.get_int64(&"totalextension".to_string());
+    let log:String;
+    let extendedbetend: i64 = match extendedbetend(betenddatetime, currtime, extensionwindow, extensions.value(),
+        market.get_int64(&"maxextensions".to_string()).value(), totalextension.value(), market.get_int64(&"maxtotalextension".to_string()).value()) {
+        Ok(Some(extendedbetend)) => extendedbetend,
+        Ok(None) => return,
+        Err(reason) => {
+            log = "the end time for bets is not extended, ".to_string() + &reason;  context.log(&log);
+            return;
+        }
+    };
+    let previoustimeline: Timeline = markettimeline(&market.immutable());
+    market.get_int64(&"betenddatetime".to_string()).set_value(extendedbetend);
+    extensions.set_value(extensions.value() + 1);
+    totalextension.set_value(totalextension.value() + extendedbetend - betenddatetime);
+    log = "the end time for bets is extended from ".to_string() + &betenddatetime.to_string() + &" to ".to_string() + &extendedbetend.to_string() + &" by a bet in the final ".to_string() + &extensionwindow.to_string() + &" seconds".to_string();  context.log(&log);
+    context.event(&events::betendextended(&market.get_string(&"marketid".to_string()).value(), betenddatetime, extendedbetend, betsequence));
+    emittimelinechange(context, &previoustimeline);
+}
+
+// Function returning the encrypted memo of the bet of the account with the wallet address ADDRESS, to be called by the contract owner
+// or a marketadmin: FOUND is "true" with MEMOCIPHER in base58, or "false" if the bet has no memo. It is a function rather than a view,
+// because a view cannot tell who calls it. The state of the contract is public, so the memo is only private by its encryption.
+fn getbetprivate(context: &ScFuncContext) {
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "getbetprivate"), "Not authorised to read memos - only contract creator or a marketadmin is allowed to do this.");
+    openledger(context);
+    requiremarket(context);
+    let address = context.params().get_string(&"ADDRESS".to_string());
+    // require parameter exists
+    context.require(address.exists(), "address parameter not found");
+
+    let memocipher: Vec<u8> = marketstate(context).get_map(&"memociphers".to_string()).get_bytes(&address.value()).value();
+    context.results().get_string(&"FOUND".to_string()).set_value(&(!memocipher.is_empty()).to_string());
+    if !memocipher.is_empty() {
+        context.results().get_string(&"MEMOCIPHER".to_string()).set_value(&context.utility().base58_encode(&memocipher));
+    }
+}
+
+// What the extensions of the end time for bets can still add: every extension adds at most the EXTENSIONWINDOW, namely for a bet in
+// the very second of the end time, for at most the remaining MAXEXTENSIONS, and in total at most what is left of MAXTOTALEXTENSION.
+// Bets in the last second of every extended end time thus reach the latest end time exactly.
+struct ExtensionBudget {
+    // seconds the end time can still be extended by
+    remaining: i64,
+    // latest end time for bets the extensions can reach, 0 without an end time
+    latestbetend: i64,
+}
+
+fn extensionbudget(market: &ScImmutableMap) -> ExtensionBudget {
+    let betenddatetime: i64 = market.get_int64(&"betenddatetime".to_string()).value();
+    let extensionwindow: i64 = market.get_int64(&"extensionwindow".to_string()).value();
+    let remainingextensions: i64 = (market.get_int64(&"maxextensions".to_string()).value() - market.get_int64(&"extensions".to_string()).value()).max(0);
+    let maxtotalextension: i64 = market.get_int64(&"maxtotalextension".to_string()).value();
+    let mut remaining: i64 = if betenddatetime == 0 { 0 } else { extensionwindow.max(0).saturating_mul(remainingextensions) };
+    if maxtotalextension > 0 {
+        remaining = remaining.min((maxtotalextension - market.get_int64(&"totalextension".to_string()).value()).max(0));
+    }
+    ExtensionBudget {
+        remaining,
+        latestbetend: if betenddatetime == 0 { 0 } else { betenddatetime.saturating_add(remaining) },
+    }
+}
+
+fn setextensionresults(context: &ScViewContext, budget: &ExtensionBudget) {
+    context.results().get_int64(&"EXTENSIONBUDGET".to_string()).set_value(budget.remaining);
+    context.results().get_int64(&"LATESTBETEND".to_string()).set_value(budget.latestbetend);
+}
+
+// Refund a bet that came too late or while bets are frozen, or fail the transaction in strict mode.
+// A strict rejection of a bet with IOTA attached records them as pending return to the funder instead, see recordpendingreturn.
+fn softrejectbet(context: &ScFuncContext, strict: bool, funder: &ScAddress, error: &str, message: &str) {
+    match softfailurehandling(strict, context.incoming().balance(&ScColor::IOTA), true) {
+        SoftFailureHandling::Fail => context.panic(&(error.to_string() + &": ".to_string() + message)),
+        SoftFailureHandling::PendingReturn => recordpendingreturn(context, funder, error, message),
+        _ => rejectbet(context, funder, incomingtokens(context), error, message),
+    }
+}
+
+
+// Reject a bet by refunding all incoming IOTA, or tokens of the market's color, logging the message and returning the error code in the result ERROR.
+// Unlike a soft failure, a rejected bet never fails the transaction, otherwise the refund would be undone.
+// Every rejection is recorded as "timestamp|error|address|amount" in the rejectedbets array in the contract's state.
+fn rejectbet(context: &ScFuncContext, caller: &ScAddress, incoming: i64, error: &str, message: &str) {
+    let log:String = error.to_string() + &": ".to_string() + message;  context.log(&log);
+    addrefusal(context, error);
+    refundtoken(context, caller, &tokencolor(context, &marketstate(context)), incoming, error);
+    let calleraddress: String = AddressCodec::CURRENT.encode(&context.utility(), caller);
+    if incoming > 0 {
+        context.event(&events::betrefunded(error, &calleraddress, incoming));
+    }
+    let rejectedbets = context.state().get_string_array(&"rejectedbets".to_string());
+    rejectedbets.get_string(rejectedbets.length()).set_value(&(context.timestamp().to_string() + &"|".to_string() + error + &"|".to_string() + &calleraddress + &"|".to_string() + &incoming.to_string()));
+}
+
+// Send IOTA back to the wallet of the caller, e.g. the part of a bet that cannot be accepted, taken from the strays bucket.
+// The reason is a code like "unaccepted" or the error code of a rejected bet, under which the transferred IOTA are indexed by recordrefund.
+// Note that a minimum transaction fee of 1 IOTA is deducted.
+fn refund(context: &ScFuncContext, recipientaddress: &ScAddress, amount: i64, reason: &str) {
+    refundtoken(context, recipientaddress, &ScColor::IOTA, amount, reason);
+}
+
+// refund tokens of the given color, e.g. of a bet in a market denominated in a colored token
+fn refundtoken(context: &ScFuncContext, recipientaddress: &ScAddress, color: &ScColor, amount: i64, reason: &str) {
+    if amount != 0 {
+        let log:String = "refunding ".to_string() + &amount.to_string() + &" ".to_string() + &colorname(color) + &" to: ".to_string() + &recipientaddress.to_string();  context.log(&log);
+        let transferred: i64 = guardedtransfertoken(context, recipientaddress, color, amount, "refund", BUCKET_STRAYS);
+        recordrefund(context, &marketstate(context), &AddressCodec::CURRENT.encode(&context.utility(), recipientaddress), transferred, reason);
+    }
+}
+
+// Refund everything sent with a call that is not executed, the tokens of every color, whatever the market is denominated in.
+fn refundincoming(context: &ScFuncContext, recipientaddress: &ScAddress, reason: &str) {
+    let incomingcolors = context.incoming().colors();
+    for i in 0..incomingcolors.length() {
+        let color: ScColor = incomingcolors.get_color(i).value();
+        refundtoken(context, recipientaddress, &color, context.incoming().balance(&color), reason);
+    }
+}
+
+// Color of the token the bets and payouts of a market are denominated in, as set by TOKENCOLOR on initialization, or IOTA.
+fn tokencolor(context: &ScFuncContext, market: &ScMutableMap) -> ScColor {
+    let tokencolor: String = market.get_string(&"tokencolor".to_string()).value();
+    if tokencolor == "" {
+        return ScColor::IOTA;
+    }
+    ScColor::from_bytes(&context.utility().base58_decode(&tokencolor))
+}
+
+// the tokens of the market's color sent with the call
+fn incomingtokens(context: &ScFuncContext) -> i64 {
+    context.incoming().balance(&tokencolor(context, &marketstate(context)))
+}
+
+fn colorname(color: &ScColor) -> String {
+    if *color == ScColor::IOTA {
+        return "IOTA".to_string();
+    }
+    "tokens of color ".to_string() + &color.to_string()
+}
+
+// Reject a bet that carries tokens of another color than the market is denominated in, e.g. IOTA for a market in a colored token,
+// by returning all tokens sent with it with the error ERR_WRONG_TOKEN. Returns whether the bet was rejected.
+fn returnforeigntokens(context: &ScFuncContext, funder: &ScAddress) -> bool {
+    let color: ScColor = tokencolor(context, &marketstate(context));
+    let incomingcolors = context.incoming().colors();
+    let mut colors: Vec<ScColor> = Vec::new();
+    for i in 0..incomingcolors.length() {
+        colors.push(incomingcolors.get_color(i).value());
+    }
+    if !colors.iter().any(|incomingcolor| *incomingcolor != color && context.incoming().balance(incomingcolor) > 0) {
+        return false;
+    }
+    let log:String = ERR_WRONG_TOKEN.to_string() + &": bet was not accepted, the market only accepts ".to_string() + &colorname(&color);  context.log(&log);
+    addrefusal(context, ERR_WRONG_TOKEN);
+    for incomingcolor in colors.iter() {
+        refundtoken(context, funder, incomingcolor, context.incoming().balance(incomingcolor), ERR_WRONG_TOKEN);
+    }
+    let rejectedbets = context.state().get_string_array(&"rejectedbets".to_string());
+    rejectedbets.get_string(rejectedbets.length()).set_value(&(context.timestamp().to_string() + &"|".to_string() + ERR_WRONG_TOKEN + &"|".to_string() + &AddressCodec::CURRENT.encode(&context.utility(), funder) + &"|".to_string() + &context.incoming().balance(&ScColor::IOTA).to_string()));
+    true
+}
+
+// Transfer IOTA from the contract to a wallet, used by every payout and refund.
+// The amount is guarded against what a bug in a formula could produce: a negative amount is never transferred,
+// and an amount exceeding the contract's balance is capped at the balance. Both are recorded as invariant violation
+// in the state, together with the reason of the transfer, e.g. "payout", "refund".
+// The IOTA are taken from the given ledger bucket, and the amount is capped at that bucket as well.
+// While the ledger's invariant is violated, no IOTA are transferred at all until the contract owner runs reconcile.
+// Counters of the transfers and the transferred amount are kept in the state for reconciliation.
+// Returns the transferred amount.
+fn guardedtransfer(context: &ScFuncContext, recipientaddress: &ScAddress, amount: i64, reason: &str, bucket: &str) -> i64 {
+    guardedtransfertoken(context, recipientaddress, &ScColor::IOTA, amount, reason, bucket)
+}
+
+// Guarded transfer of tokens of the given color. The ledger buckets only account IOTA, so colored tokens are capped
+// at the contract's balance of their color only, and the bucket is neither checked nor debited.
+fn guardedtransfertoken(context: &ScFuncContext, recipientaddress: &ScAddress, color: &ScColor, amount: i64, reason: &str, bucket: &str) -> i64 {
+    let mut log:String;
+    if transfersblocked(context) {
+        log = "transfers are blocked until reconcile is run, ".to_string() + reason + &" amount of ".to_string() + &amount.to_string() + &" IOTA for ".to_string() + &recipientaddress.to_string() + &" is not transferred".to_string();  context.log(&log);
+        recordtransferviolation(context, "blocked", reason, recipientaddress, amount);
+        return 0;
+    }
+
+    let balance: i64 = context.balances().balance(color);
+    let iota: bool = *color == ScColor::IOTA;
+    let bucketbalance: i64 = context.state().get_int64(&bucketkey(bucket)).value();
+    let (transferamount, violations) = guardtransfer(amount, balance, if iota { Some(bucketbalance) } else { None });
+    for violation in violations {
+        log = match violation {
+            VIOLATION_NEGATIVE => "invariant violation: negative ".to_string() + reason + &" amount of ".to_string() + &amount.to_string() + &" IOTA for ".to_string() + &recipientaddress.to_string() + &" is not transferred".to_string(),
+            VIOLATION_EXCEEDSBALANCE => "invariant violation: ".to_string() + reason + &" amount of ".to_string() + &amount.to_string() + &" IOTA exceeds the balance of the contract of ".to_string() + &balance.to_string() + &" IOTA".to_string(),
+            _ => "invariant violation: ".to_string() + reason + &" amount of ".to_string() + &amount.to_string() + &" IOTA exceeds the ".to_string() + bucket + &" bucket of ".to_string() + &bucketbalance.to_string() + &" IOTA".to_string(),
+        };
+        context.log(&log);
+        recordtransferviolation(context, violation, reason, recipientaddress, amount);
+    }
+    if transferamount == 0 {
+        return 0;
+    }
+
+    context.transfer_to_address(recipientaddress, ScTransfers::new(color, transferamount));
+    let transferscount = context.state().get_int64(&"transferscount".to_string());
+    transferscount.set_value(transferscount.value() + 1);
+    if iota {
+        creditbucket(context, bucket, -transferamount);
+        let transferredtotal = context.state().get_int64(&"transferredtotal".to_string());
+        transferredtotal.set_value(transferredtotal.value() + transferamount);
+    }
+    transferamount
+}
+
+// record an invariant violation of a transfer as "timestamp|violation|reason|address|amount"
+fn recordtransferviolation(context: &ScFuncContext, violation: &str, reason: &str, recipientaddress: &ScAddress, amount: i64) {
+    let transferviolations = context.state().get_string_array(&"transferviolations".to_string());
+    transferviolations.get_string(transferviolations.length()).set_value(&(context.timestamp().to_string() + &"|".to_string() + violation + &"|".to_string() + reason + &"|".to_string() + &recipientaddress.to_string() + &"|".to_string() + &amount.to_string()));
+}
+
+
+// Function to close the prediction market, to be called by the contract owner.
+// The function requires a BETVALUE parameter, specifying the winning outcome, e.g., "yes".
+// The functions runs through the stored bets, determines winning bets and the amount of IOTA the receive, and records the entitlements of the winners.
+// The IOTA are sent to the wallets of the first winners right away, the remaining winners are paid by calling payout or claim.
+fn closemarket(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "closemarket") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    // only the contract owner, a resolver or the oracle should be able to do this, or others once it is their turn,
+    // and anyone for a member of a group whose outcomes closegroup declared
+    let caller = context.caller();
+    let turn: &str = currentresolver(context);
+    let groupid: String = marketstate(context).get_string(&"groupid".to_string()).value();
+    let groupoutcome: String = marketstate(context).get_string(&"groupoutcome".to_string()).value();
+    context.require(groupid == "" || groupoutcome != "", &("the market is a member of the group ".to_string() + &groupid + &", whose outcomes have to be declared by closegroup first".to_string()));
+    context.require(groupoutcome != "" || mayresolve(context) || (turn == RESOLVER_CHAINOWNER && caller == context.chain_owner_id()),
+        "You are not authorised to close the prediction market - only contract creator or a resolver is allowed to close the market.");
+
+    // the value that won, e.g., "yes" or "no", or a comma separated list of values that tie and split the pool, e.g., "home,draw"
+    // if the parameter is missing, an outcome declared before via declareoutcome is used
+    let mut betvaluewinning: String = context.params().get_string(&"BETVALUE".to_string()).value();
+    if betvaluewinning != "" {
+        let values: Vec<String> = winningvalues(&betvaluewinning).iter().map(|betvalue| canonicaloutcome(betvalue)).collect();
+        context.require(values.iter().all(|betvalue| betvalue != ""), "the list of winning bet values must not contain an empty value");
+        let mut uniquevalues: Vec<String> = values.clone();
+        uniquevalues.sort();
+        uniquevalues.dedup();
+        context.require(uniquevalues.len() == values.len(), "the list of winning bet values must not list a value twice");
+        betvaluewinning = values.join(",");
+    }
+    let pendingoutcome: String = marketstate(context).get_string(&"pendingoutcome".to_string()).value();
+    // RESIDUAL=true states that none of the named outcomes happened, which selects the reserved outcome __OTHER__
+    let residual: bool = context.params().get_string(&"RESIDUAL".to_string()).value() == "true";
+    if residual {
+        context.require(betvaluewinning == "" || betvaluewinning == OUTCOME_OTHER, "RESIDUAL cannot be combined with another BETVALUE");
+        betvaluewinning = OUTCOME_OTHER.to_string();
+    }
+    if betvaluewinning == "" {
+        betvaluewinning = pendingoutcome.clone();
+    }
+    // require parameter exists
+    context.require(betvaluewinning != "", "winning bet value parameter not found");
+    context.require(groupoutcome == "" || betvaluewinning == groupoutcome, &("the outcome of the market was declared by closegroup as ".to_string() + &groupoutcome));
+    let outcomes: String = marketstate(context).get_string(&"outcomes".to_string()).value();
+    context.require(winningvalues(&betvaluewinning).iter().all(|betvalue| allowedoutcome(&outcomes, betvalue)), "the winning bet value is not one of the OUTCOMES of the market");
+
+    settlemarket(context, "closemarket", &betvaluewinning, &pendingoutcome, None);
+}
+
+// Settle the market on betvaluewinning for the function named function, once the end time for bets has passed: record the entitlements
+// of the winners and the receipt, which includes the tie break if the value was selected among tied votes, or void the market without
+// stakes on the winning value. pendingoutcome is the outcome declared before, if any, for the admin log.
+fn settlemarket(context: &ScFuncContext, function: &str, betvaluewinning: &str, pendingoutcome: &str, tiebreak: Option<&TieBreak>) {
+    let betvaluewinning: String = betvaluewinning.to_string();
+    // a book pays the odds locked by the bets on one winning value, and keeps the stakes of the others even if nobody bet on it
+    let book: bool = marketstate(context).get_string(&"book".to_string()).value() == "true";
+    context.require(!book || winningvalues(&betvaluewinning).len() == 1, "a market with BOOK settles on a single winning value");
+    // only close market after end time for bets, specified on initalization
+    let currtime: i64 = context.timestamp();
+    let betenddatetime: i64 = marketstate(context).get_int64(&"betenddatetime".to_string()).value();
+
+    let mut log:String;
+    let strict: bool = isstrict(context);
+
+    // a flag to check whether the closemarket function was run, then either we don't use a fixed end time - or we check if the end time
+    // is exceeded, and for sealed bets their reveal deadline
+    let marketclosed: bool = marketstate(context).get_string("marketclosed").value() != "false";
+    let commitreveal: bool = marketstate(context).get_string(&"commitreveal".to_string()).value() == "true";
+    let revealpending: bool = commitreveal && revealontime(marketrevealdeadline(&marketstate(context)), currtime);
+    if let Some(failure) = closefailure(marketclosed, revealpending, betenddatetime, currtime) {
+        softfail(context, strict, failure.error, failure.message);
+        return;
+    }
+    log = function.to_uppercase() + &" is executed:".to_string(); context.log(&log);
+    log = "the winning value is: \"".to_string() + &winningvalues(&betvaluewinning).join("\", \"") + &"\"".to_string(); context.log(&log);
+
+    // set flag stating that the closemarket function was run
+    marketstate(context).get_string("marketclosed").set_value(&"true".to_string());
+    marketstate(context).get_string(&"winningvalue".to_string()).set_value(&betvaluewinning);
+    appendadminlog(context, &(function.to_string() + &"|".to_string() + &betvaluewinning + &"|pending:".to_string() + pendingoutcome));
+
+    // get all bets from global state
+    // Note that the stat is not specific to a contract but to the whole chain on which it is deployed.
+    let containerofbets: ContainerOfBets = readcontainerofbets(context);
+
+    if containerofbets.map.is_empty() {
+        // the market is closed nevertheless, so the bonds of its disputes are settled instead of staying in the escrows
+        settledisputes(context, Some(&betvaluewinning), &HashMap::new());
+        softfail(context, strict, SOFTFAIL_NO_BETS.error, SOFTFAIL_NO_BETS.message);
+        return;
+    }
+    let totals: BetTotals = runningtotals(context).unwrap_or_else(|| bettotals(&containerofbets));
+    // without stakes on the winning value nobody could be paid, so the market is voided and all bets are refunded,
+    // which also applies to a residual outcome without stakes on the catch-all outcome
+    let winningtotal: i32 = winningvalues(&betvaluewinning).iter().map(|betvalue| *totals.betvalue_totalbetamount.get(*betvalue).unwrap_or(&0)).sum();
+    if containerofbets.map.len() >= 1 && winningtotal == 0 && !book {
+        log = "no bet was placed on the winning value \"".to_string() + &betvaluewinning + &"\", all bets are refunded".to_string(); context.log(&log);
+        marketstate(context).get_string(&"marketvoided".to_string()).set_value(&"true".to_string());
+        refundallbets(context);
+        log = "total amount refunded: ".to_string() + &totals.totalbetamount.to_string() + &" IOTA to ".to_string() + &containerofbets.map.len().to_string() + &" betting accounts".to_string(); context.log(&log);
+        settledisputes(context, None, &HashMap::new());
+        pushpayouts(context);
+        return;
+    }
+    // determine the totals per value and the win amounts
+    let feepercent: i64 = marketstate(context).get_int64(&"feepercent".to_string()).value();
+    let settlement: SettlementResult = if book {
+        booksettlement(&containerofbets, &bookpayouts(&marketstate(context).immutable(), &containerofbets), &betvaluewinning)
+    } else {
+        settlewithtotals(&containerofbets, totals, &betvaluewinning, feepercent)
+    };
+    // compared before the snapshot, so that only a snapshot taken by an earlier call is checked
+    requirescenario(context, &betvaluewinning, &settlement);
+    snapshotscenarios(context, &containerofbets);
+
+    // log output
+    for (betvalue, totalbetamount) in & settlement.betvalue_totalbetamount{
+        log = "total amount of bets placed on \"".to_string() + &betvalue.to_string() + &"\" is ".to_string() + &totalbetamount.to_string() + &" IOTA".to_string(); context.log(&log);
+    }
+    log = "total amount of bets over all values: ".to_string() + &settlement.totalbetamount.to_string() + &" IOTA".to_string(); context.log(&log);
+    let mut split: FeeSplit = FeeSplit::default();
+    if settlement.operatortake > 0 {
+        log = "pool: ".to_string() + &settlement.totalbetamount.to_string() + &" IOTA; fee of ".to_string() + &feepercent.to_string() + &" percent: ".to_string() + &settlement.operatortake.to_string()
+            + &" IOTA; distributable: ".to_string() + &(settlement.totalbetamount as i64 - settlement.operatortake).to_string() + &" IOTA".to_string(); context.log(&log);
+        split = payfee(context, settlement.operatortake);
+    }
+    if settlement.remainder > 0 {
+        log = "the remainder of rounding down the win amounts, ".to_string() + &settlement.remainder.to_string() + &" IOTA, goes to the largest winner ".to_string() + &settlement.remainderrecipient; context.log(&log);
+    }
+
+    let shortfall: Shortfall = applyshortfallpolicy(context, &settlement.winamounts);
+    let winamounts: &HashMap<String, i64> = &shortfall.winamounts;
+    storereceipt(context, &betvaluewinning, &settlement, &split, &shortfall, tiebreak);
+    // The settlement is computed once from all bets, since the receipt, the scenarios and the disputes take all its win amounts,
+    // but the entitlements of the winners are recorded from the bets in batches by the calls driving the payouts, see recordentitlements.
+    log = "the winners of ".to_string() + &settlement.totalbetamountforwinningvalue.to_string() + &" IOTA of bets on the winning value share ".to_string() + &winamounts.values().sum::<i64>().to_string() + &" IOTA".to_string(); context.log(&log);
+    startrecording(context, &settlementterms(&settlement, &betvaluewinning, book, shortfall.haircut));
+    settledisputes(context, Some(&betvaluewinning), winamounts);
+    // send coins to the first winners, the remaining winners are paid by calling payout or claim
+    pushpayouts(context);
+}
+
+
+// Split the fee of the market between the treasury of the contract and the creator of the market by the creator share the market was
+// created with, see fees in the core crate, before the shortfall policy is applied to the winnings. In IOTA, the treasury share moves
+// from the pools to the fees bucket, which the treasurer withdraws by withdrawfees, and the creator share to the creatorfees bucket,
+// from which the creator claims it by claimcreatorfees. The buckets only account IOTA, so the shares of a market in a colored token
+// are transferred at once, to the contract creator and to the creator of the market.
+fn payfee(context: &ScFuncContext, fee: i64) -> FeeSplit {
+    let creator: String = marketstate(context).get_string(&"creator".to_string()).value();
+    // a market initialized before creators were recorded has no creator to credit
+    let creatorsharebps: i64 = if creator == "" { 0 } else { marketstate(context).get_int64(&"creatorsharebps".to_string()).value() };
+    let split: FeeSplit = feesplit(fee, creatorsharebps);
+    let color: ScColor = tokencolor(context, &marketstate(context));
+    let log:String;
+    if color != ScColor::IOTA {
+        let contractcreator: ScAddress = context.contract_creator().address();
+        let treasury: i64 = guardedtransfertoken(context, &contractcreator, &color, split.treasury, "fee", BUCKET_POOLS);
+        let creatorshare: i64 = match AddressCodec::CURRENT.decode(&context.utility(), &creator) {
+            Ok(creatoraddress) if split.creator > 0 => guardedtransfertoken(context, &creatoraddress, &color, split.creator, "creatorfee", BUCKET_POOLS),
+            _ => 0,
+        };
+        creditmarketpool(context, -(treasury + creatorshare));
+        log = "transferred the fee of ".to_string() + &(treasury + creatorshare).to_string() + &" ".to_string() + &colorname(&color) + &": ".to_string() + &treasury.to_string()
+            + &" to the contract creator ".to_string() + &contractcreator.to_string() + &" and ".to_string() + &creatorshare.to_string() + &" to the creator of the market ".to_string() + &creator;  context.log(&log);
+        return FeeSplit { creator: creatorshare, treasury };
+    }
+    movebucket(context, BUCKET_POOLS, BUCKET_FEES, split.treasury);
+    movebucket(context, BUCKET_POOLS, BUCKET_CREATORFEES, split.creator);
+    creditmarketpool(context, -fee);
+    if split.creator > 0 {
+        let claimable = context.state().get_map(&"creatorfees".to_string()).get_int64(&creator);
+        claimable.set_value(claimable.value() + split.creator);
+    }
+    log = "fee of ".to_string() + &fee.to_string() + &" IOTA: ".to_string() + &split.treasury.to_string() + &" IOTA to the treasury, ".to_string()
+        + &split.creator.to_string() + &" IOTA claimable by the creator of the market ".to_string() + &creator;  context.log(&log);
+    split
+}
+
+
+// Apply the shortfall policy of the market if the IOTA available for the winnings, i.e., the pools bucket but at most the contract's balance,
+// are less than the win amounts. With HALT, transfers are blocked until the owner adds the missing IOTA and runs reconcile.
+// With HAIRCUT, every win amount is scaled down by the same fraction. The policy, the shortfall and the applied fraction in basis points
+// are returned in the results SHORTFALLPOLICY, SHORTFALL and FRACTIONBPS of closemarket, and emitted as event on a shortfall.
+fn applyshortfallpolicy(context: &ScFuncContext, winamounts: &HashMap<String, i64>) -> Shortfall {
+    let policy: String = marketstate(context).get_string(&"shortfallpolicy".to_string()).value();
+    let totalwinamount: i64 = winamounts.values().sum();
+    let available: i64 = marketpool(context).min(context.balances().balance(&tokencolor(context, &marketstate(context))));
+    let shortfall: i64 = (totalwinamount - available).max(0);
+    let mut fractionbps: i64 = 10000;
+    let mut appliedwinamounts: HashMap<String, i64> = winamounts.clone();
+    let mut scaledto: Option<(i64, i64)> = None;
+
+    if shortfall > 0 {
+        let mut log:String = "the winnings of ".to_string() + &totalwinamount.to_string() + &" IOTA exceed the available ".to_string() + &available.to_string() + &" IOTA by ".to_string() + &shortfall.to_string() + &" IOTA".to_string();  context.log(&log);
+        if policy == SHORTFALL_HAIRCUT {
+            let haircut: Haircut = haircut(winamounts, available);
+            fractionbps = haircut.fractionbps;
+            appliedwinamounts = haircut.winamounts;
+            scaledto = Some((available, totalwinamount));
+            log = "every win amount is paid to ".to_string() + &fractionbps.to_string() + &" basis points, the residue of ".to_string() + &haircut.residue.to_string() + &" IOTA stays in the pools".to_string();  context.log(&log);
+        } else {
+            // same as the insolvency guard of the ledger, payouts wait for reconcile
+            context.state().get_string(&"transfersblocked".to_string()).set_value(&"true".to_string());
+            log = "transfers are blocked until the missing IOTA are added and reconcile is run".to_string();  context.log(&log);
+        }
+        appendadminlog(context, &("shortfall|".to_string() + &policy + &"|".to_string() + &shortfall.to_string() + &"|".to_string() + &fractionbps.to_string()));
+        context.event(&events::settlementshortfall(&policy, shortfall, fractionbps));
+    }
+
+    context.results().get_string(&"SHORTFALLPOLICY".to_string()).set_value(&policy);
+    context.results().get_int64(&"SHORTFALL".to_string()).set_value(shortfall);
+    context.results().get_int64(&"FRACTIONBPS".to_string()).set_value(fractionbps);
+    Shortfall {
+        winamounts: appliedwinamounts,
+        policy,
+        shortfall,
+        fractionbps,
+        haircut: scaledto,
+    }
+}
+
+// win amounts after applying the shortfall policy
+struct Shortfall {
+    winamounts: HashMap<String, i64>,
+    policy: String,
+    // IOTA missing for the win amounts, 0 if there is no shortfall
+    shortfall: i64,
+    // fraction of the win amounts paid in basis points
+    fractionbps: i64,
+    // IOTA the win amounts were scaled down to and their total before, None without a haircut
+    haircut: Option<(i64, i64)>,
+}
+
+
+// Function to void the prediction market, to be called by the contract owner, e.g. if the event was cancelled.
+// Every betting account is entitled to get back its bet amount. The refunds are paid out like winnings:
+// the first ones right away, the remaining ones by calling payout or claim.
+fn cancelmarket(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "cancelmarket") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    // only the contract owner or a resolver should be able to do this, or others once it is their turn
+    let caller = context.caller();
+    let turn: &str = currentresolver(context);
+    context.require(authorisedfor(context, "cancelmarket") || (turn == RESOLVER_CHAINOWNER && caller == context.chain_owner_id())
+        || (turn == RESOLVER_REFUND && marketstate(context).get_int64(&"votewindow".to_string()).value() == 0),
+        "You are not authorised to cancel the prediction market - only contract creator or a resolver is allowed to cancel the market.");
+
+    let marketclosed = marketstate(context).get_string("marketclosed");
+    context.require(marketclosed.exists(), "the prediction market is not initialized yet");
+    if marketclosed.value() != "false" {
+        softfail(context, isstrict(context), SOFTFAIL_MARKET_CLOSED.error, SOFTFAIL_MARKET_CLOSED.message);
+        return;
+    }
+
+    let log:String = "CANCELMARKET is executed:".to_string(); context.log(&log);
+    marketstate(context).get_string("marketclosed").set_value(&"true".to_string());
+    marketstate(context).get_string(&"marketvoided".to_string()).set_value(&"true".to_string());
+    recordtransition(context, &marketstate(context), "voided");
+    appendadminlog(context, "cancelmarket");
+    // the event of a cancelled market can be offered again by another market
+    releaseexternalref(context, &marketstate(context).get_string(&"externalref".to_string()).value());
+
+    refundallbets(context);
+    settledisputes(context, None, &HashMap::new());
+    pushpayouts(context);
+}
+
+// Function for anyone to void a market whose resolver disappeared: once RESOLUTIONGRACE seconds have passed after the end time for bets
+// without the market being closed, every betting account is entitled to get back its bet amount like with cancelmarket.
+// Refuses to run before that resolution deadline, for a market without RESOLUTIONGRACE, and for a closed market.
+fn forcedrefund(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "forcedrefund") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    let marketclosed = marketstate(context).get_string("marketclosed");
+    context.require(marketclosed.exists(), "the prediction market is not initialized yet");
+    context.require(marketclosed.value() == "false", "the prediction market was already closed");
+    let deadline: i64 = resolutiondeadline(&marketstate(context).immutable());
+    context.require(deadline != 0, "the market has no RESOLUTIONGRACE, its bets cannot be refunded by force");
+    context.require(context.timestamp() > deadline, &("the bets can only be refunded by force after the resolution deadline of ".to_string() + &deadline.to_string()));
+
+    let caller: String = context.caller().to_string();
+    let log:String = "FORCEDREFUND is triggered by ".to_string() + &caller + &" after the resolution deadline of ".to_string() + &deadline.to_string(); context.log(&log);
+    marketstate(context).get_string("marketclosed").set_value(&"true".to_string());
+    marketstate(context).get_string(&"marketvoided".to_string()).set_value(&"true".to_string());
+    recordtransition(context, &marketstate(context), "voided");
+    appendadminlog(context, &("forcedrefund|".to_string() + &caller));
+
+    refundallbets(context);
+    settledisputes(context, None, &HashMap::new());
+    pushpayouts(context);
+}
+
+// Time after which the betting accounts of a market with VOTEWINDOW vote on its outcome: when the chain owner's window of the dead-man
+// switch has passed, see resolverturn. A call of the contract owner or a resolver moves it, like the fallback.
+fn voteopensat(market: &ScImmutableMap) -> i64 {
+    let heartbeatwindow: i64 = market.get_int64(&"heartbeatwindow".to_string()).value();
+    let silentsince: i64 = market.get_int64(&"resolverlastseen".to_string()).value().max(market.get_int64(&"betenddatetime".to_string()).value());
+    silentsince.saturating_add(heartbeatwindow.saturating_mul(2))
+}
+
+// the weight of the votes per value, every voting account weighing with the bet amount it has in the market
+fn tallyofvotes(market: &ScImmutableMap, containerofbets: &ContainerOfBets) -> BTreeMap<String, i64> {
+    let voters = market.get_string_array(&"voters".to_string());
+    let votes: Vec<(String, i64)> = (0..voters.length())
+        .map(|index| voters.get_string(index).value())
+        .filter_map(|betteraddress| containerofbets.map.get(&betteraddress)
+            .map(|bet| (market.get_map(&"votes".to_string()).get_string(&betteraddress).value(), bet.betamount as i64)))
+        .collect();
+    tallyvotes(&votes)
+}
+
+// Function for a betting account to vote with the weight of its bet on the outcome of a market whose resolvers stayed silent,
+// passed as BETVALUE, while the vote of a market with VOTEWINDOW is open. A later vote of the same account replaces its earlier one.
+fn vote(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "vote") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    context.require(marketstate(context).get_string(&"marketclosed".to_string()).value() == "false", "the prediction market was already closed");
+    let votewindow: i64 = marketstate(context).get_int64(&"votewindow".to_string()).value();
+    context.require(votewindow > 0, "the market has no VOTEWINDOW, its bettors do not vote on the outcome");
+    let opensat: i64 = voteopensat(&marketstate(context).immutable());
+    context.require(votephase(context.timestamp(), opensat, votewindow) == VOTE_OPEN,
+        &("the bettors can only vote after ".to_string() + &opensat.to_string() + &" for ".to_string() + &votewindow.to_string() + &" seconds".to_string()));
+
+    let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address());
+    let containerofbets: ContainerOfBets = readcontainerofbets(context);
+    context.require(containerofbets.map.contains_key(&betteraddress), "only a betting account of the market can vote");
+    let betvalue: String = canonicaloutcome(&context.params().get_string(&"BETVALUE".to_string()).value());
+    context.require(betvalue != "", "bet value parameter not found");
+    context.require(allowedoutcome(&marketstate(context).get_string(&"outcomes".to_string()).value(), &betvalue), "the bet value is not one of the OUTCOMES of the market");
+
+    let ballot = marketstate(context).get_map(&"votes".to_string()).get_string(&betteraddress);
+    if !ballot.exists() {
+        let voters = marketstate(context).get_string_array(&"voters".to_string());
+        voters.get_string(voters.length()).set_value(&betteraddress);
+    }
+    ballot.set_value(&betvalue);
+    let log:String = betteraddress + &" votes for \"".to_string() + &betvalue + &"\"".to_string();  context.log(&log);
+}
+
+// Function for anyone to settle a market on the outcome its bettors voted for, once its vote is over: the value with the most weight wins,
+// values tied for it are decided by the TIEBREAK of the market, and without votes the market is voided. The winning value is returned
+// in WINNINGVALUE, "" for a voided market; a tie broken by HASH is recorded with its seed inputs in the receipt.
+fn tallyandsettle(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "tallyandsettle") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    context.require(marketstate(context).get_string(&"marketclosed".to_string()).value() == "false", "the prediction market was already closed");
+    let votewindow: i64 = marketstate(context).get_int64(&"votewindow".to_string()).value();
+    context.require(votewindow > 0, "the market has no VOTEWINDOW, its bettors do not vote on the outcome");
+    let opensat: i64 = voteopensat(&marketstate(context).immutable());
+    context.require(votephase(context.timestamp(), opensat, votewindow) == VOTE_TALLY,
+        &("the votes can only be tallied after ".to_string() + &opensat.saturating_add(votewindow).to_string()));
+    let groupid: String = marketstate(context).get_string(&"groupid".to_string()).value();
+    context.require(groupid == "", &("the market is a member of the group ".to_string() + &groupid + &", whose outcomes have to be declared by closegroup".to_string()));
+
+    let marketid: String = marketstate(context).get_string(&"marketid".to_string()).value();
+    let totals: BTreeMap<String, i64> = tallyofvotes(&marketstate(context).immutable(), &readcontainerofbets(context));
+    let mut log:String;
+    for (betvalue, total) in &totals {
+        log = "votes for \"".to_string() + betvalue + &"\" weigh ".to_string() + &total.to_string() + &" IOTA".to_string();  context.log(&log);
+    }
+    let plurality: Vec<String> = pluralityvalues(&totals);
+    let policy: String = marketstate(context).get_string(&"tiebreak".to_string()).value();
+    let utility = context.utility();
+    let hash = |bytes: &[u8]| utility.hash_blake2b(bytes).to_bytes().to_vec();
+    let selected: Option<TieBreak> = if policy == TIEBREAK_HASH { tiebreak(&marketid, &totals, &context.request_id().to_string(), &hash) } else { None };
+    let winningvalue: String = match (plurality.len(), &selected) {
+        (1, _) => plurality[0].clone(),
+        (_, Some(selected)) => selected.winner.clone(),
+        _ => "".to_string(),
+    };
+    context.results().get_string(&"WINNINGVALUE".to_string()).set_value(&winningvalue);
+    if winningvalue == "" {
+        log = if plurality.is_empty() { "no votes were cast, all bets are refunded".to_string() } else { "the votes tie between \"".to_string() + &plurality.join("\", \"") + &"\", all bets are refunded".to_string() };
+        context.log(&log);
+        marketstate(context).get_string("marketclosed").set_value(&"true".to_string());
+        marketstate(context).get_string(&"marketvoided".to_string()).set_value(&"true".to_string());
+        recordtransition(context, &marketstate(context), "voided");
+        appendadminlog(context, &("tallyandsettle|tie:".to_string() + &plurality.join(",")));
+        releaseexternalref(context, &marketstate(context).get_string(&"externalref".to_string()).value());
+        refundallbets(context);
+        settledisputes(context, None, &HashMap::new());
+        pushpayouts(context);
+        return;
+    }
+    if let Some(selected) = &selected {
+        log = "the votes tie between \"".to_string() + &selected.tied.join("\", \"") + &"\", the tie break with digest ".to_string() + &selected.digest
+            + &" selects \"".to_string() + &selected.winner + &"\"".to_string();  context.log(&log);
+    }
+    settlemarket(context, "tallyandsettle", &winningvalue, "", selected.as_ref());
+}
+
+// View of the bettor vote of a market: its PHASE, "pending", "open" or "tally", the time VOTEOPENSAT after which it opens, its VOTEWINDOW,
+// the TIEBREAK policy and the weight of the votes so far per value as json in TOTALS.
+fn getvotes(context: &ScViewContext) {
+    let market: ScImmutableMap = viewmarketstate(context);
+    let votewindow: i64 = market.get_int64(&"votewindow".to_string()).value();
+    let opensat: i64 = voteopensat(&market);
+    let totals: BTreeMap<String, i64> = tallyofvotes(&market, &loadcontainerofbets(context, &market));
+    context.results().get_string(&"PHASE".to_string()).set_value(votephase(context.timestamp(), opensat, votewindow));
+    context.results().get_int64(&"VOTEOPENSAT".to_string()).set_value(opensat);
+    context.results().get_int64(&"VOTEWINDOW".to_string()).set_value(votewindow);
+    context.results().get_string(&"TIEBREAK".to_string()).set_value(&market.get_string(&"tiebreak".to_string()).value());
+    context.results().get_string(&"TOTALS".to_string()).set_value(&serde_json::to_string(&totals).expect("failed to make json of vote totals"));
+}
+
+// time after which forcedrefund can refund all bets of the market, see resolutiondeadlineafter
+fn resolutiondeadline(market: &ScImmutableMap) -> i64 {
+    resolutiondeadlineafter(market.get_int64(&"betenddatetime".to_string()).value(), market.get_int64(&"resolutiongrace".to_string()).value())
+}
+
+// Entitle every bet of a voided market to the refund of its bet amount. The entitlements are recorded in batches by the calls
+// driving the payouts, see recordentitlements, so that voiding a market of any size takes a single call.
+fn refundallbets(context: &ScFuncContext) {
+    startrecording(context, &refundterms());
+    let log:String = "the bets of ".to_string() + &marketstate(context).get_string_array(&"betteraddresses".to_string()).length().to_string() + &" betting accounts are refunded".to_string(); context.log(&log);
+}
+
+// Store the terms the entitlements of the closed market follow from, see recording in the core crate, and start recording them at
+// the first bet. The bets are migrated to the map layout first, whose addresses the recording cursor walks. The running total of the
+// unpaid entitlements starts with them, see unpaidentitlements.
+fn startrecording(context: &ScFuncContext, terms: &RecordingTerms) {
+    migratestorage(context);
+    let market: ScMutableMap = marketstate(context);
+    market.get_string(&"recordingtermsjson".to_string()).set_value(&serde_json::to_string(terms).expect("failed to make json of recording terms"));
+    market.get_int64(&"recordingcursor".to_string()).set_value(0);
+    market.get_string(&"unpaidrunning".to_string()).set_value(&"true".to_string());
+}
+
+// Record the entitlements of up to maxcount further bets of the closed market marketid, continuing at the recording cursor in the
+// order the accounts first bet, and list the winners among them. Once every bet is recorded, WINNERSRECORDED of getwinners is true.
+// Returns the number of bets recorded, 0 once all are or for a market whose entitlements were recorded when it was closed.
+fn recordentitlements(context: &ScFuncContext, marketid: &str, maxcount: i64) -> i64 {
+    let market: ScMutableMap = marketstateof(context, marketid);
+    let termsjson: String = market.get_string(&"recordingtermsjson".to_string()).value();
+    if termsjson == "" {
+        return 0;
+    }
+    let terms: RecordingTerms = serde_json::from_str(&termsjson).expect("failed to get recording terms");
+    let betteraddresses = market.get_string_array(&"betteraddresses".to_string());
+    let bets = market.get_map(&"bets".to_string());
+    let bookpayouts = market.get_map(&"bookpayouts".to_string());
+    let winners = market.get_string_array(&"winners".to_string());
+    let recordingcursor = market.get_int64(&"recordingcursor".to_string());
+    let betscount: i64 = betteraddresses.length() as i64;
+    let start: i64 = recordingcursor.value();
+    let end: i64 = recordingbatchend(start, betscount, maxcount);
+    for index in start..end {
+        let betteraddress: String = betteraddresses.get_string(index as i32).value();
+        let bet: Bet = decodebet(&bets.get_bytes(&betteraddress).value()).expect("failed to get bet");
+        let amount: i64 = recordedamount(&terms, &betteraddress, &bet, bookpayouts.get_int64(&betteraddress).value());
+        if amount == 0 {
+            continue;
+        }
+        let log:String = betteraddress.to_string() + &" is entitled to ".to_string() + &amount.to_string() + &" IOTA (".to_string() + &terms.reason
+            + &") for the bet of ".to_string() + &bet.betamount.to_string() + &" IOTA on \"".to_string() + &bet.betisforvalue + &"\"".to_string(); context.log(&log);
+        recordentitlementof(context, marketid, BUCKET_POOLS, &betteraddress, &bet.addressencoding, &bet.requestid, amount, &terms.reason);
+        if !terms.refundall {
+            let winner = Winner { address: betteraddress.to_string(), winamount: amount };
+            winners.get_string(winners.length()).set_value(&serde_json::to_string(&winner).expect("failed to make json of winner"));
+        }
+    }
+    recordingcursor.set_value(end);
+    if !terms.refundall && recordingdone(end, betscount) {
+        market.get_string(&"winnersrecorded".to_string()).set_value(&"true".to_string());
+    }
+    end - start
+}
+
+// whether the entitlements of every bet of the closed market are recorded, always for a market whose entitlements were recorded when it was closed
+fn recordingcomplete(market: &ScImmutableMap) -> bool {
+    market.get_string(&"recordingtermsjson".to_string()).value() == ""
+        || recordingdone(market.get_int64(&"recordingcursor".to_string()).value(), market.get_string_array(&"betteraddresses".to_string()).length() as i64)
+}
+
+// whether every entitlement of the market is recorded and processed, and none of its failed payouts is still to be attempted again
+fn payoutscomplete(market: &ScImmutableMap) -> bool {
+    let retrying: bool = market.get_int64(&"payoutretryhead".to_string()).value() < market.get_string_array(&"payoutretries".to_string()).length() as i64;
+    recordingcomplete(market) && payoutsdone(market.get_int64(&"payoutcursor".to_string()).value(), market.get_string_array(&"entitlements".to_string()).length() as i64, retrying)
+}
+
+// Outcomes of a comma separated list like "yes,no", trimmed and with aliases replaced, for an empty list none.
+fn parseoutcomes(outcomes: &str) -> Vec<String> {
+    outcomes.split(',').map(|outcome| canonicaloutcome(outcome.trim())).filter(|outcome| outcome != "").collect()
+}
+
+// Whether a value can be bet on or win: any value if the market has no OUTCOMES, otherwise one of them or the catch-all outcome.
+fn allowedoutcome(outcomes: &str, betvalue: &str) -> bool {
+    outcomes == "" || betvalue == OUTCOME_OTHER || parseoutcomes(outcomes).iter().any(|outcome| outcome == betvalue)
+}
+
+// View of the OUTCOMES of the market as json list, empty if any value can be bet on, and the catch-all outcome as RESIDUALOUTCOME.
+fn getoutcomes(context: &ScViewContext) {
+    let outcomes: Vec<String> = parseoutcomes(&viewmarketstate(context).get_string(&"outcomes".to_string()).value());
+    context.results().get_string(&"OUTCOMES".to_string()).set_value(&serde_json::to_string(&outcomes).expect("failed to make json of outcomes"));
+    context.results().get_string(&"RESIDUALOUTCOME".to_string()).set_value(OUTCOME_OTHER);
+}
+
+// The reserved catch-all outcome for any of its aliases, every other value as it is.
+fn canonicaloutcome(betvalue: &str) -> String {
+    if OUTCOME_OTHER_ALIASES.contains(&betvalue) {
+        return OUTCOME_OTHER.to_string();
+    }
+    betvalue.to_string()
+}
+
+// Function to pay out the next entitlements of winnings or refunds, which can be called by anyone until all entitlements are paid.
+// The optional parameter BATCHSIZE limits the number of bets recorded and entitlements paid by the call, by default 50.
+// In PAYOUTMODE claim, it only records the entitlements of the next bets, which their accounts then claim.
+fn payout(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "payout") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    let mut batchsize: i64 = paramint64(context, "BATCHSIZE");
+    if batchsize <= 0 || batchsize > DEFAULTPAYOUTBATCH {
+        batchsize = DEFAULTPAYOUTBATCH;
+    }
+    if marketstate(context).get_string(&"payoutmode".to_string()).value() == PAYOUTMODE_CLAIM {
+        let recorded: i64 = recordentitlements(context, &marketparam(context), batchsize);
+        let log:String = "PAYOUT recorded the entitlements of bets: ".to_string() + &recorded.to_string() + &", which are paid by claim only".to_string(); context.log(&log);
+        return;
+    }
+    let processed: i64 = processpayouts(context, &marketparam(context), batchsize);
+    let log:String = "PAYOUT processed entitlements: ".to_string() + &processed.to_string(); context.log(&log);
+}
+
+// Pay the first entitlements right after the market was settled or voided, unless its accounts claim them in PAYOUTMODE claim.
+fn pushpayouts(context: &ScFuncContext) {
+    if marketstate(context).get_string(&"payoutmode".to_string()).value() == PAYOUTMODE_CLAIM {
+        recordentitlements(context, &marketparam(context), DEFAULTPAYOUTBATCH);
+        let log:String = "entitlements are paid when their accounts claim them".to_string(); context.log(&log);
+        return;
+    }
+    processpayouts(context, &marketparam(context), DEFAULTPAYOUTBATCH);
+    queuepiggybackpayouts(context);
+}
+
+// Queue a settled market with PIGGYBACKPAYOUTS and outstanding payouts, so that the following calls to the contract pay them.
+// The queue holds the ids of the markets in the contract's state, and a market is queued once.
+fn queuepiggybackpayouts(context: &ScFuncContext) {
+    let market: ScMutableMap = marketstate(context);
+    if market.get_int64(&"piggybackpayouts".to_string()).value() <= 0 || market.get_string(&"piggybackqueued".to_string()).value() == "true" {
+        return;
+    }
+    if payoutscomplete(&market.immutable()) {
+        return;
+    }
+    market.get_string(&"piggybackqueued".to_string()).set_value(&"true".to_string());
+    let tail = context.state().get_int64(&"piggybacktail".to_string());
+    context.state().get_map(&"piggybackqueue".to_string()).get_string(&tail.value().to_string()).set_value(&marketparam(context));
+    tail.set_value(tail.value() + 1);
+}
+
+// Before its own work, every call to the contract pays up to PIGGYBACKRETURNS pending returns and processes up to PIGGYBACKPAYOUTS
+// outstanding payouts of the first queued market, of any market, unless it is called with NOPIGGYBACK=true. A market leaves the queue once all its entitlements are processed.
+// Like the payout function, the payouts continue after the last processed entitlement and never pay an entitlement twice.
+fn piggybackpayouts(context: &ScFuncContext) {
+    if context.params().get_string(&"NOPIGGYBACK".to_string()).value() == "true" {
+        return;
+    }
+    let returned: i64 = payreturns(context, PIGGYBACKRETURNS);
+    if returned > 0 {
+        let log:String = "piggybacked pending returns: ".to_string() + &returned.to_string(); context.log(&log);
+    }
+    let head = context.state().get_int64(&"piggybackhead".to_string());
+    if head.value() >= context.state().get_int64(&"piggybacktail".to_string()).value() {
+        return;
+    }
+    let marketid: String = context.state().get_map(&"piggybackqueue".to_string()).get_string(&head.value().to_string()).value();
+    let market: ScMutableMap = marketstateof(context, &marketid);
+    let processed: i64 = processpayouts(context, &marketid, market.get_int64(&"piggybackpayouts".to_string()).value());
+    if processed > 0 {
+        let log:String = "piggybacked payouts of market \"".to_string() + &marketid + &"\": ".to_string() + &processed.to_string(); context.log(&log);
+    }
+    if payoutscomplete(&market.immutable()) {
+        context.state().get_map(&"piggybackqueue".to_string()).get_string(&head.value().to_string()).set_value("");
+        head.set_value(head.value() + 1);
+    }
+}
+
+// Function for a betting account to get paid its own outstanding entitlements right away, instead of waiting for payout.
+// The delegate registered by delegateclaim can claim them on behalf of the account with the account's wallet address as FOR,
+// the IOTA are transferred to the account in any case.
+fn claim(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "claim") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    let caller = context.caller().address();
+    let calleraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &caller);
+    let claimfor = context.params().get_string(&"FOR".to_string());
+    let claimfor: Option<String> = if claimfor.exists() { Some(claimfor.value()) } else { None };
+    let delegate: String = claimfor.as_ref().map_or("".to_string(), |claimfor| marketstate(context).get_map(&"claimdelegates".to_string()).get_string(claimfor).value());
+    let claimed: Result<String, String> = claimedaddress(&calleraddress, &context.caller().to_string(), claimfor.as_deref(), &delegate);
+    context.require(claimed.is_ok(), claimed.as_ref().err().map_or("", |error| error.as_str()));
+    let entitledaddress: String = claimed.unwrap();
+    if entitledaddress != calleraddress {
+        let log:String = "claim for ".to_string() + &entitledaddress + &" by its delegate ".to_string() + &delegate; context.log(&log);
+    }
+    // entitlements of blocked accounts stay frozen until the account is unblocked
+    if addressblocked(context, &entitledaddress) {
+        let log:String = ERR_ADDRESS_BLOCKED.to_string() + &": the entitlements of ".to_string() + &entitledaddress + &" are frozen".to_string(); context.log(&log);
+        addrefusal(context, ERR_ADDRESS_BLOCKED);
+        refundincoming(context, &caller, ERR_ADDRESS_BLOCKED);
+        return;
+    }
+    // the claim records the entitlements of the next bets first, the account's own may still be among the bets to record
+    recordentitlements(context, &marketparam(context), DEFAULTPAYOUTBATCH);
+    let indices = marketstate(context).get_map(&"entitlementsbyaddress".to_string()).get_string_array(&entitledaddress);
+    let mut claimed: i64 = 0;
+    for i in 0..indices.length() {
+        let index: i32 = indices.get_string(i).value().parse::<i32>().unwrap();
+        claimed = claimed + payentitlement(context, &marketparam(context), index);
+    }
+    if claimed == 0 {
+        let mut log:String = "no outstanding entitlements for ".to_string() + &entitledaddress;
+        // tell a losing account why it gets nothing
+        let betvalue: String = marketstate(context).get_map(&entitledaddress).get_string(&"betvalue".to_string()).value();
+        let receipt: String = marketstate(context).get_string(&"receiptjson".to_string()).value();
+        if !recordingcomplete(&marketstate(context).immutable()) {
+            log = log + &" yet, the entitlements of the market are still being recorded by payout and claim".to_string();
+        } else if betvalue != "" && receipt != "" && indices.length() == 0 {
+            log = log + &", the bet on \"".to_string() + &betvalue + &"\" did not win".to_string();
+        }
+        context.log(&log);
+    }
+    context.results().get_int64(&"CLAIMED".to_string()).set_value(claimed);
+}
+
+// Function for a betting account to let the agent DELEGATE claim its entitlements of the market, e.g. a hot wallet for an account
+// on a hardware wallet. It can be called before or after settlement, a later call replaces the delegate. The delegate only
+// triggers the claim: the IOTA are always transferred to the account, and the entitlements and sweeps are not affected.
+fn delegateclaim(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "delegateclaim") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    let delegate: String = context.params().get_string(&"DELEGATE".to_string()).value();
+
+    let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address());
+    let entitled: bool = marketstate(context).get_map(&"entitlementsbyaddress".to_string()).get_string_array(&betteraddress).length() > 0;
+    let validated: Result<(), String> = validatedelegate(&delegate, &context.caller().to_string(), entitled || readbet(context, &betteraddress).is_some());
+    context.require(validated.is_ok(), validated.as_ref().err().map_or("", |error| error.as_str()));
+    marketstate(context).get_map(&"claimdelegates".to_string()).get_string(&betteraddress).set_value(&delegate);
+    let log:String = "claims of ".to_string() + &betteraddress + &" are delegated to ".to_string() + &delegate; context.log(&log);
+}
+
+// Function for a betting account to remove the delegate registered by delegateclaim.
+fn revokedelegate(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "revokedelegate") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address());
+    let delegate = marketstate(context).get_map(&"claimdelegates".to_string()).get_string(&betteraddress);
+    let validated: Result<(), String> = validaterevocation(&delegate.value());
+    context.require(validated.is_ok(), validated.as_ref().err().map_or("", |error| error.as_str()));
+    let log:String = "delegate ".to_string() + &delegate.value() + &" of ".to_string() + &betteraddress + &" is revoked".to_string(); context.log(&log);
+    delegate.set_value("");
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct Entitlement {
+    // wallet address of the betting account receiving the IOTA
+    address: String,
+    // amount of IOTA to receive
+    amount: i64,
+    // why the IOTA are received, "win" or "refund"
+    reason: String,
+    // true once all IOTA are transferred
+    paid: bool,
+    // flag of the codec the address was encoded with, "" for entitlements stored before it was recorded
+    #[serde(default)]
+    addressencoding: String,
+    // id of the request that sent the IOTA of the bet the entitlement results from
+    #[serde(default)]
+    fundingrequestid: String,
+    // id of the request that transferred the IOTA, "" while not paid
+    #[serde(default)]
+    paidbyrequestid: String,
+    // number of failed attempts to pay the entitlement
+    #[serde(default)]
+    attempts: i64,
+    // timestamp from which a failed payout is attempted again
+    #[serde(default)]
+    nextattemptat: i64,
+    // true once the payout failed MAXPAYOUTATTEMPTS times or cannot succeed, until it is paid by a claim
+    #[serde(default)]
+    manual: bool,
+    // ledger bucket the IOTA are paid from, "" for the pools, which is omitted from the json
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    bucket: String,
+    // IOTA transferred so far, less than the amount while a transfer was capped
+    #[serde(default)]
+    paidamount: i64,
+    // true while the entitlement is queued in frozenentitlements of its blocked account
+    #[serde(default)]
+    frozen: bool,
+}
+
+// IOTA of an entitlement not transferred yet
+fn outstandingamount(entitlement: &Entitlement) -> i64 {
+    if entitlement.paid { 0 } else { entitlement.amount - entitlement.paidamount }
+}
+
+// bucket an entitlement is paid from, the pools unless recorded otherwise, e.g. the escrows for the stakes of challenges
+fn entitlementbucket(entitlement: &Entitlement) -> &str {
+    if entitlement.bucket.is_empty() { BUCKET_POOLS } else { &entitlement.bucket }
+}
+
+// Record the entitlement of a betting account to receive IOTA.
+// Entitlements are stored in the order of recording in the entitlements array, with their indices per address
+// so that an account can claim its own entitlements.
+fn recordentitlement(context: &ScFuncContext, betteraddress: &str, addressencoding: &str, fundingrequestid: &str, amount: i64, reason: &str) {
+    recordentitlementfrom(context, BUCKET_POOLS, betteraddress, addressencoding, fundingrequestid, amount, reason);
+}
+
+// Record an entitlement like recordentitlement, which is paid from the given bucket instead of the pools.
+fn recordentitlementfrom(context: &ScFuncContext, bucket: &str, betteraddress: &str, addressencoding: &str, fundingrequestid: &str, amount: i64, reason: &str) {
+    recordentitlementof(context, &marketparam(context), bucket, betteraddress, addressencoding, fundingrequestid, amount, reason);
+}
+
+// Record an entitlement like recordentitlementfrom for the market marketid, e.g. by the recording piggybacked on a call for another market.
+// An entitlement paid from the pools adds to the running total of the unpaid entitlements.
+fn recordentitlementof(context: &ScFuncContext, marketid: &str, bucket: &str, betteraddress: &str, addressencoding: &str, fundingrequestid: &str, amount: i64, reason: &str) {
+    // the winnings and refunds of a seed bet are owed to the account that seeded the market
+    let betteraddress: &str = seedowner(betteraddress);
+    let entitlement = Entitlement {
+        address: betteraddress.to_string(),
+        amount,
+        reason: reason.to_string(),
+        paid: false,
+        addressencoding: addressencoding.to_string(),
+        fundingrequestid: fundingrequestid.to_string(),
+        paidbyrequestid: "".to_string(),
+        attempts: 0,
+        nextattemptat: 0,
+        manual: false,
+        bucket: if bucket == BUCKET_POOLS { "".to_string() } else { bucket.to_string() },
+        paidamount: 0,
+        frozen: false,
+    };
+    let market: ScMutableMap = marketstateof(context, marketid);
+    let entitlements = market.get_string_array(&"entitlements".to_string());
+    let index: i32 = entitlements.length();
+    entitlements.get_string(index).set_value(&serde_json::to_string(&entitlement).expect("failed to make json of entitlement"));
+    let indices = market.get_map(&"entitlementsbyaddress".to_string()).get_string_array(betteraddress);
+    indices.get_string(indices.length()).set_value(&index.to_string());
+    if bucket == BUCKET_POOLS {
+        addunpaid(&market, amount);
+    }
+    if reason == "refund" {
+        recordrefund(context, &market, betteraddress, amount, reason);
+    }
+}
+
+// Pay the entitlement at index of the market marketid unless already paid, marking it as paid. Returns the transferred amount.
+// A payout of which not everything could be transferred, e.g. because the transfer was capped at the balance, keeps the rest
+// outstanding and is recorded as a failed attempt, so that the rest is paid by a retry or claim.
+fn payentitlement(context: &ScFuncContext, marketid: &str, index: i32) -> i64 {
+    let entitlementjson = marketstateof(context, marketid).get_string_array(&"entitlements".to_string()).get_string(index);
+    let mut entitlement: Entitlement = serde_json::from_str(&entitlementjson.value()).expect("failed to get entitlement");
+    if entitlement.paid || transfersblocked(context) {
+        return 0;
+    }
+    // the entitlement of a blocked account is frozen: it stays unpaid, and is queued for retry once the account is unblocked
+    match frozenpayout(addressblocked(context, &entitlement.address), entitlement.frozen) {
+        FrozenPayout::Pay => {}
+        FrozenPayout::Freeze => {
+            freezeentitlement(context, marketid, index, &mut entitlement);
+            entitlementjson.set_value(&serde_json::to_string(&entitlement).expect("failed to make json of entitlement"));
+            return 0;
+        }
+        FrozenPayout::Skip => return 0,
+    }
+    // an address that cannot be decoded stays unpaid instead of being transferred to a wrong address
+    let decoded: Result<ScAddress, String> = AddressCodec::from_flag(&entitlement.addressencoding)
+        .and_then(|codec| codec.decode(&context.utility(), &entitlement.address));
+    if let Err(error) = decoded {
+        let log:String = "entitlement ".to_string() + &index.to_string() + &" is not paid: ".to_string() + &error;  context.log(&log);
+        // retrying cannot decode the address either
+        recordpayoutfailure(context, marketid, index, &mut entitlement, true);
+        entitlementjson.set_value(&serde_json::to_string(&entitlement).expect("failed to make json of entitlement"));
+        return 0;
+    }
+    let recipientaddress: ScAddress = decoded.unwrap();
+    let outstanding: i64 = outstandingamount(&entitlement);
+    // mark as paid before transferring, so that an entitlement is never paid twice
+    entitlement.paid = true;
+    entitlement.paidbyrequestid = context.request_id().to_string();
+    entitlementjson.set_value(&serde_json::to_string(&entitlement).expect("failed to make json of entitlement"));
+
+    let log:String = "transferring ".to_string() + &outstanding.to_string() + &" IOTA (".to_string() + &entitlement.reason + &") to: ".to_string() + &recipientaddress.to_string();  context.log(&log);
+    let reason: &str = if entitlement.reason == "refund" { "refund" } else { "payout" };
+    let color: ScColor = tokencolor(context, &marketstateof(context, marketid));
+    let bucket: &str = entitlementbucket(&entitlement);
+    let transferred: i64 = guardedtransfertoken(context, &recipientaddress, &color, outstanding, reason, bucket);
+    if bucket == BUCKET_POOLS {
+        creditmarketpoolof(context, marketid, -transferred);
+        addunpaid(&marketstateof(context, marketid), -transferred);
+    }
+    entitlement.paidamount = entitlement.paidamount + transferred;
+    if transferred < outstanding {
+        entitlement.paid = false;
+        entitlement.paidbyrequestid = "".to_string();
+        recordpayoutfailure(context, marketid, index, &mut entitlement, false);
+        entitlementjson.set_value(&serde_json::to_string(&entitlement).expect("failed to make json of entitlement"));
+        if transferred > 0 {
+            context.event(&events::payouttransferred(&entitlement.address, transferred, &entitlement.reason));
+        }
+        return transferred;
+    }
+    entitlementjson.set_value(&serde_json::to_string(&entitlement).expect("failed to make json of entitlement"));
+    if entitlement.manual {
+        entitlement.manual = false;
+        let manual = marketstateof(context, marketid).get_int64(&"payoutsmanual".to_string());
+        manual.set_value(manual.value() - 1);
+        entitlementjson.set_value(&serde_json::to_string(&entitlement).expect("failed to make json of entitlement"));
+    }
+    if transferred > 0 {
+        context.event(&events::payouttransferred(&entitlement.address, transferred, &entitlement.reason));
+    }
+    transferred
+}
+
+// Record a failed attempt to pay the entitlement at index of the market marketid. The entitlement is queued in payoutretries
+// on its first failure and attempted again once the backoff has passed, which doubles with every attempt up to PAYOUTRETRYMAXBACKOFF.
+// After MAXPAYOUTATTEMPTS attempts, or at once if permanent, it is flagged for manual intervention, which healthcheck reports.
+fn recordpayoutfailure(context: &ScFuncContext, marketid: &str, index: i32, entitlement: &mut Entitlement, permanent: bool) {
+    if entitlement.manual {
+        return;
+    }
+    let market: ScMutableMap = marketstateof(context, marketid);
+    if entitlement.attempts == 0 {
+        let retries = market.get_string_array(&"payoutretries".to_string());
+        retries.get_string(retries.length()).set_value(&index.to_string());
+    }
+    entitlement.attempts = entitlement.attempts + 1;
+    let nextattemptat: Option<i64> = nextpayoutattempt(entitlement.attempts, permanent, context.timestamp());
+    if nextattemptat.is_none() {
+        entitlement.manual = true;
+        entitlement.nextattemptat = 0;
+        let manual = market.get_int64(&"payoutsmanual".to_string());
+        manual.set_value(manual.value() + 1);
+        let log:String = "entitlement ".to_string() + &index.to_string() + &" needs manual intervention after ".to_string() + &entitlement.attempts.to_string() + &" failed attempts".to_string(); context.log(&log);
+        context.event(&events::payoutmanual(&entitlement.address, outstandingamount(entitlement), entitlement.attempts));
+        return;
+    }
+    entitlement.nextattemptat = nextattemptat.unwrap();
+    let log:String = "entitlement ".to_string() + &index.to_string() + &" is attempted again from ".to_string() + &entitlement.nextattemptat.to_string(); context.log(&log);
+    context.event(&events::payoutfailed(&entitlement.address, outstandingamount(entitlement), entitlement.attempts, entitlement.nextattemptat));
+}
+
+// Attempt again up to maxcount failed payouts of the market marketid whose backoff has passed, in the order of their first failure.
+// Entitlements at the front of payoutretries which are paid or flagged for manual intervention are passed for good.
+// Returns the number of attempted entitlements.
+fn retrypayouts(context: &ScFuncContext, marketid: &str, maxcount: i64) -> i64 {
+    let market: ScMutableMap = marketstateof(context, marketid);
+    let retries = market.get_string_array(&"payoutretries".to_string());
+    let head = market.get_int64(&"payoutretryhead".to_string());
+    let entitlements = market.get_string_array(&"entitlements".to_string());
+    let currtime: i64 = context.timestamp();
+    let mut attempted: i64 = 0;
+    let mut resolvedhead: bool = true;
+    for i in head.value() as i32..retries.length() {
+        if attempted >= maxcount {
+            break;
+        }
+        let index: i32 = retries.get_string(i).value().parse::<i32>().unwrap();
+        let entitlement: Entitlement = serde_json::from_str(&entitlements.get_string(index).value()).expect("failed to get entitlement");
+        if entitlement.paid || entitlement.manual {
+            if resolvedhead {
+                head.set_value(i as i64 + 1);
+            }
+            continue;
+        }
+        resolvedhead = false;
+        if currtime < entitlement.nextattemptat {
+            continue;
+        }
+        payentitlement(context, marketid, index);
+        attempted = attempted + 1;
+    }
+    attempted
+}
+
+// Pay out up to maxcount entitlements of the market marketid, continuing after the last processed one. Already claimed entitlements are skipped,
+// as are the frozen entitlements of blocked accounts, which unblockaddress queues for retry.
+// The bets whose entitlements are still to be recorded come first, see recordentitlements, then failed payouts due for a retry are
+// attempted before new entitlements, and all of them count towards maxcount.
+// Emits an event with the progress, e.g. "v1|payout.progress|50|200" when 50 of 200 entitlements are processed.
+// Returns the number of recorded bets and processed entitlements.
+fn processpayouts(context: &ScFuncContext, marketid: &str, maxcount: i64) -> i64 {
+    let recorded: i64 = recordentitlements(context, marketid, maxcount);
+    if transfersblocked(context) {
+        let log:String = "payouts are blocked until reconcile is run".to_string(); context.log(&log);
+        return recorded;
+    }
+    let maxcount: i64 = maxcount - recorded;
+    let retried: i64 = retrypayouts(context, marketid, maxcount);
+    let market: ScMutableMap = marketstateof(context, marketid);
+    let entitlementscount: i64 = market.get_string_array(&"entitlements".to_string()).length() as i64;
+    let payoutcursor = market.get_int64(&"payoutcursor".to_string());
+    let start: i64 = payoutcursor.value();
+    let end: i64 = payoutbatchend(start, entitlementscount, maxcount, retried);
+    for index in start..end {
+        payentitlement(context, marketid, index as i32);
+    }
+    payoutcursor.set_value(end);
+    if end > start {
+        context.event(&events::payoutprogress(end, entitlementscount));
+    }
+    recorded + retried + end - start
+}
+
+// addresses of the betting accounts in ascending order, so that all entitlements are recorded in the same order on every node
+fn sortedbetteraddresses(containerofbets: &ContainerOfBets) -> Vec<String> {
+    let mut betteraddresses: Vec<String> = containerofbets.map.keys().cloned().collect();
+    betteraddresses.sort();
+    betteraddresses
+}
+
+
+// Function to register alert rules on implied probabilities, to be called by the contract owner.
+// The parameter ALERT holds the rules, e.g. "yes>8000,no<1000", and replaces all rules registered before; an empty ALERT removes them.
+// After each bet, the rules on the value of the bet are evaluated and an event "v1|market.alert|yes|>|8000|8125" is emitted
+// when a rule becomes met. The rule only fires again after the probability moved back beyond the threshold by 100 basis points.
+fn setalerts(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "setalerts") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "setalerts"), "Not authorised to set alerts - only contract creator or a marketadmin is allowed to do this.");
+    requirenotimelock(context, "setalerts");
+
+    applyalerts(context, &context.params().get_string(&"ALERT".to_string()).value());
+}
+
+// Replace the alert rules, directly by setalerts or by an executed admin action.
+fn applyalerts(context: &ScFuncContext, alert: &str) {
+    let alertrules: Result<Vec<AlertRule>, String> = parsealertrules(alert);
+    context.require(alertrules.is_ok(), alertrules.as_ref().err().map_or("", |error| error.as_str()));
+    let alertrules: Vec<AlertRule> = alertrules.unwrap();
+
+    setconfigvalue(context, "alertrulesjson", &serde_json::to_string(&alertrules).expect("failed to make json of alert rules"));
+    // one flag per rule, "1" while the rule is met
+    marketstate(context).get_string(&"alertflags".to_string()).set_value(&initialalertflags(&alertrules));
+
+    let log:String = "alert rules set: ".to_string() + &alertrules.len().to_string(); context.log(&log);
+    appendadminlog(context, &("setalerts|".to_string() + alert));
+}
+
+// Evaluate the alert rules on the value a bet was placed on against its implied probability after the bet.
+fn evaluatebetalerts(context: &ScFuncContext, betvalue: &str) {
+    let alertrulesjson: String = marketstate(context).get_string(&"alertrulesjson".to_string()).value();
+    if alertrulesjson == "" {
+        return;
+    }
+    let alertrules: Vec<AlertRule> = serde_json::from_str(&alertrulesjson).expect("failed to get alert rules");
+
+    let totalbetamount: i64 = marketstate(context).get_int64(&"totalbetamount".to_string()).value();
+    let totalbetamountforvalue: i64 = marketstate(context).get_int64(&("total_".to_string() + betvalue)).value();
+    let alertflags = marketstate(context).get_string(&"alertflags".to_string());
+    let (flags, alerts) = evaluatealerts(&alertrules, &alertflags.value(), betvalue, totalbetamount, totalbetamountforvalue);
+    for alert in alerts.iter() {
+        context.event(&events::marketalert(&alert.betvalue, alert.above, alert.thresholdbps, alert.probabilitybps));
+    }
+    alertflags.set_value(&flags);
+}
+
+
+// Function to declare the winning outcome early, to be called by the contract owner acting as resolver.
+// The function requires a BETVALUE parameter, specifying the winning outcome, e.g., "yes".
+// Sometimes the outcome of an event is known before the end time for bets has passed. The declared outcome is only stored as pending:
+// the market is not settled, and closemarket uses the pending outcome after the end time for bets, unless closemarket is given another BETVALUE.
+// Calling the function again replaces the pending outcome.
+// With the optional parameter FREEZE set to "true", no further bets are accepted once an outcome is declared.
+fn declareoutcome(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "declareoutcome") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    // only the contract owner, a resolver or the oracle should be able to do this
+    context.require(mayresolve(context), "Not authorised to declare the outcome - only contract creator or a resolver is allowed to do this.");
+
+    // the market must be initialized and still open
+    let marketclosed = marketstate(context).get_string("marketclosed");
+    context.require(marketclosed.exists(), "the prediction market is not initialized yet");
+    context.require(marketclosed.value() == "false", "the prediction market was already closed");
+
+    context.require(marketstate(context).get_string(&"groupid".to_string()).value() == "", "the outcome of a member of a group is declared by closegroup");
+
+    let betvaluedeclared = context.params().get_string(&"BETVALUE".to_string());
+    // require parameter exists
+    context.require(betvaluedeclared.exists(), "declared bet value parameter not found");
+
+    let mut log:String = "DECLAREOUTCOME is run:".to_string();   context.log(&log);
+
+    let betvaluedeclared: String = canonicaloutcome(&betvaluedeclared.value());
+    context.require(allowedoutcome(&marketstate(context).get_string(&"outcomes".to_string()).value(), &betvaluedeclared), "the declared bet value is not one of the OUTCOMES of the market");
+    marketstate(context).get_string(&"pendingoutcome".to_string()).set_value(&betvaluedeclared);
+    log = "the pending winning value is: \"".to_string() + &betvaluedeclared + &"\"".to_string(); context.log(&log);
+
+    // optionally stop accepting bets now that the outcome is known
+    if context.params().get_string(&"FREEZE".to_string()).value() == "true" {
+        let previoustimeline: Timeline = markettimeline(&marketstate(context).immutable());
+        marketstate(context).get_string(&"betsfrozen".to_string()).set_value(&"true".to_string());
+        marketstate(context).get_int64(&"betsfrozenat".to_string()).set_value(context.timestamp());
+        log = "bets are frozen".to_string(); context.log(&log);
+        emittimelinechange(context, &previoustimeline);
+    }
+
+    recordtransition(context, &marketstate(context), "declared");
+    appendadminlog(context, &("declareoutcome|".to_string() + &betvaluedeclared));
+}
+
+fn groupstate(context: &ScFuncContext, groupid: &str) -> ScMutableMap {
+    context.state().get_map(&("group|".to_string() + groupid))
+}
+
+// Function to link mutually exclusive markets into a group, to be called by the contract owner, e.g. "Team A wins", "Team B wins"
+// and "Draw" as three binary markets. The parameter GROUPID names the group, up to 64 bytes, and MARKETIDS lists its members
+// comma separated, see groupmembers in the core crate. Every member has to be an open market with MARKETID not linked to another group.
+// The outcomes of a group are then declared by closegroup for all members together, not by declareoutcome.
+// With GROUPMAXSTAKE, an address can stake up to that many IOTA across all members together, counting the bets placed before the link.
+fn linkmarkets(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "linkmarkets") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(authorisedfor(context, "linkmarkets"), "Not authorised to link markets - only contract creator is allowed to do this.");
+    let groupid: String = context.params().get_string(&"GROUPID".to_string()).value();
+    context.require(groupid != "" && groupid.len() <= MAXGROUPID, &("GROUPID must have from 1 to ".to_string() + &MAXGROUPID.to_string() + &" bytes".to_string()));
+    context.require(groupstate(context, &groupid).get_string_array(&"members".to_string()).length() == 0, "GROUPID is already used by another group");
+    let members: Result<Vec<String>, String> = groupmembers(&context.params().get_string(&"MARKETIDS".to_string()).value());
+    context.require(members.is_ok(), members.as_ref().err().map_or("", |error| error.as_str()));
+    let members: Vec<String> = members.unwrap();
+    let groupmaxstake: i64 = paramint64(context, "GROUPMAXSTAKE");
+    context.require(groupmaxstake >= 0, "GROUPMAXSTAKE must not be negative");
+    for marketid in members.iter() {
+        let market: ScMutableMap = marketstateof(context, marketid);
+        context.require(marketinitialized(&market), &("the member ".to_string() + marketid + &" is not an initialized market".to_string()));
+        context.require(market.get_string(&"marketclosed".to_string()).value() == "false", &("the member ".to_string() + marketid + &" is already closed".to_string()));
+        context.require(market.get_string(&"groupid".to_string()).value() == "", &("the member ".to_string() + marketid + &" is already linked to a group".to_string()));
+    }
+
+    let group: ScMutableMap = groupstate(context, &groupid);
+    let groupmarketids = group.get_string_array(&"members".to_string());
+    group.get_int64(&"maxstake".to_string()).set_value(groupmaxstake);
+    for marketid in members.iter() {
+        groupmarketids.get_string(groupmarketids.length()).set_value(marketid);
+        let market: ScMutableMap = marketstateof(context, marketid);
+        market.get_string(&"groupid".to_string()).set_value(&groupid);
+        addgroupstakes(context, &group, &market, 1);
+    }
+    let log:String = "linked the markets ".to_string() + &members.join(", ") + &" into the group ".to_string() + &groupid;  context.log(&log);
+    appendadminlog(context, &("linkmarkets|".to_string() + &groupid + &"|".to_string() + &members.join(",") + &"|".to_string() + &groupmaxstake.to_string()));
+}
+
+// Add the bets of the member market to the stakes of their addresses in the group, or subtract them with sign -1, so that the stake
+// of an address is always the sum of its bets in the current members.
+fn addgroupstakes(context: &ScFuncContext, group: &ScMutableMap, market: &ScMutableMap, sign: i64) {
+    let stakes = group.get_map(&"stakes".to_string());
+    // the seed bets of a market count for the account that seeded it
+    for (betteraddress, stake) in accountstakes(&loadcontainerofbets(context, &market.immutable())).iter() {
+        let staked = stakes.get_int64(betteraddress);
+        staked.set_value(staked.value() + sign * stake);
+    }
+}
+
+// Function to unlink the market MARKETID from its group, to be called by the contract owner before the outcomes of the group are declared.
+// Its bets no longer count for the stakes across the group, and its outcome is declared by declareoutcome again. At least 2 members remain.
+fn unlinkmarket(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "unlinkmarket") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(authorisedfor(context, "unlinkmarket"), "Not authorised to unlink markets - only contract creator is allowed to do this.");
+    requiremarket(context);
+    let marketid: String = marketparam(context);
+    let market: ScMutableMap = marketstate(context);
+    let groupid: String = market.get_string(&"groupid".to_string()).value();
+    context.require(marketid != "" && groupid != "", "MARKETID is not linked to a group");
+    let group: ScMutableMap = groupstate(context, &groupid);
+    context.require(!group.get_int64(&"declaredat".to_string()).exists(), "the outcomes of the group were already declared");
+    let groupmarketids = group.get_string_array(&"members".to_string());
+    let remaining: Vec<String> = (0..groupmarketids.length()).map(|i| groupmarketids.get_string(i).value()).filter(|member| *member != marketid).collect();
+    context.require(remaining.len() >= 2, "a group links at least 2 markets");
+    groupmarketids.clear();
+    for (i, member) in remaining.iter().enumerate() {
+        groupmarketids.get_string(i as i32).set_value(member);
+    }
+    addgroupstakes(context, &group, &market, -1);
+    market.get_string(&"groupid".to_string()).set_value("");
+    let log:String = "unlinked the market ".to_string() + &marketid + &" from the group ".to_string() + &groupid;  context.log(&log);
+    appendadminlog(context, &("unlinkmarket|".to_string() + &groupid + &"|".to_string() + &marketid));
+}
+
+// Function to declare the outcomes of all members of the group GROUPID in one call, to be called by the contract owner or a resolver.
+// OUTCOMEMAP gives the outcome of every member, e.g. "teama:no,teamb:yes,draw:no", see groupoutcomes in the core crate. All outcomes
+// are validated before any is declared: a missing or unknown member, a closed member or an outcome not in the OUTCOMES of its member
+// fails the call, so that a group is declared completely or not at all. Every member then has its outcome pending and its bets frozen
+// like with declareoutcome, and is settled by closemarket once its end time for bets has passed, with the batched payouts of every market.
+// Since the outcome is fixed, anyone can call closemarket for a member of a declared group, which cannot be closed with another outcome.
+fn closegroup(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "closegroup") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(authorisedfor(context, "closegroup"), "Not authorised to close a group - only contract creator or a resolver is allowed to do this.");
+    let groupid: String = context.params().get_string(&"GROUPID".to_string()).value();
+    let group: ScMutableMap = groupstate(context, &groupid);
+    let groupmarketids = group.get_string_array(&"members".to_string());
+    context.require(groupmarketids.length() > 0, "GROUPID is not a group of linked markets");
+    context.require(!group.get_int64(&"declaredat".to_string()).exists(), "the outcomes of the group were already declared");
+    let members: Vec<String> = (0..groupmarketids.length()).map(|i| groupmarketids.get_string(i).value()).collect();
+    let outcomes: Result<BTreeMap<String, String>, String> = groupoutcomes(&members, &context.params().get_string(&"OUTCOMEMAP".to_string()).value());
+    context.require(outcomes.is_ok(), outcomes.as_ref().err().map_or("", |error| error.as_str()));
+    let outcomes: BTreeMap<String, String> = outcomes.unwrap().into_iter().map(|(marketid, outcome)| (marketid, canonicaloutcome(&outcome))).collect();
+
+    // validate every member before declaring any
+    for marketid in members.iter() {
+        let market: ScMutableMap = marketstateof(context, marketid);
+        context.require(market.get_string(&"marketclosed".to_string()).value() == "false", &("the member ".to_string() + marketid + &" is already closed".to_string()));
+        context.require(allowedoutcome(&market.get_string(&"outcomes".to_string()).value(), &outcomes[marketid]),
+            &("the outcome of the member ".to_string() + marketid + &" is not one of its OUTCOMES".to_string()));
+    }
+
+    let mut log:String = "CLOSEGROUP is run for the group ".to_string() + &groupid;  context.log(&log);
+    for marketid in members.iter() {
+        let market: ScMutableMap = marketstateof(context, marketid);
+        market.get_string(&"pendingoutcome".to_string()).set_value(&outcomes[marketid]);
+        market.get_string(&"groupoutcome".to_string()).set_value(&outcomes[marketid]);
+        recordtransition(context, &market, "declared");
+        if market.get_string(&"betsfrozen".to_string()).value() != "true" {
+            market.get_string(&"betsfrozen".to_string()).set_value(&"true".to_string());
+            market.get_int64(&"betsfrozenat".to_string()).set_value(context.timestamp());
+        }
+        log = "the pending winning value of ".to_string() + marketid + &" is: \"".to_string() + &outcomes[marketid] + &"\"".to_string(); context.log(&log);
+    }
+    group.get_int64(&"declaredat".to_string()).set_value(context.timestamp());
+    let outcomemap: Vec<String> = members.iter().map(|marketid| marketid.to_string() + &":".to_string() + &outcomes[marketid]).collect();
+    appendadminlog(context, &("closegroup|".to_string() + &groupid + &"|".to_string() + &outcomemap.join(",")));
+}
+
+// settings a market created by a schedule takes over from the template of the schedule, by their keys in the market's state;
+// the bets, the per-account values, the external reference and everything recorded while the template ran are not taken over
+const CLONEDSETTINGS: [&str; 23] = ["alertrulesjson", "book", "bookjson", "boundaries", "commitforfeit", "commitreveal", "configjson", "configwarningsjson", "creator", "disputeforfeit", "feedsjson", "memopubkey",
+    "oracle", "oracleonly", "outcomes", "payoutmode", "refusalnotice", "shortfallpolicy", "strictmode", "sweepdest", "termshash", "tiebreak", "tokencolor"];
+const CLONEDINTSETTINGS: [&str; 29] = ["activityhigh", "activitymedium", "activitywindow", "announcedelay", "betgranularity", "disputebond", "extensionwindow",
+    "feeddeviationbps", "feedquorum", "feepercent", "heartbeatwindow", "idempotencymaxkeys", "idempotencyminage", "maxcommitescrow", "maxcommits", "maxconfigbytes", "maxextensions",
+    "maxsharebps", "maxshareexemptpool", "maxtotalextension", "minbet", "oddsrevealpool", "overroundmaxbps", "overroundminbps", "piggybackpayouts", "resolutiongrace", "revealwindow", "timelock", "votewindow"];
+
+// Open the market marketid with the settings of the market templateid and the end time for bets betenddatetime, like initmarket would
+// with the parameters the template was initialized with. Its announcement period, if the template has one, starts now.
+fn clonemarket(context: &ScFuncContext, templateid: &str, marketid: &str, betenddatetime: i64) {
+    let template: ScMutableMap = marketstateof(context, templateid);
+    let market: ScMutableMap = marketstateof(context, marketid);
+    for key in CLONEDSETTINGS.iter() {
+        market.get_string(*key).set_value(&template.get_string(*key).value());
+    }
+    for key in CLONEDINTSETTINGS.iter() {
+        market.get_int64(*key).set_value(template.get_int64(*key).value());
+    }
+    let configbytes: i64 = configbytesof(&CLONEDCONFIGVALUES, |key| template.get_string(key).value());
+    market.get_int64(&"configbytes".to_string()).set_value(configbytes);
+    market.get_int64(&"betenddatetime".to_string()).set_value(betenddatetime);
+    market.get_int64(&"opensat".to_string()).set_value(context.timestamp().saturating_add(template.get_int64(&"announcedelay".to_string()).value()));
+    // the effective configuration of the template, with the id and end time of the new market
+    let mut configuration: BTreeMap<String, EffectiveSetting> = serde_json::from_str(&template.get_string(&"configjson".to_string()).value()).unwrap_or_default();
+    configuration.insert("marketid".to_string(), marketidsetting(marketid, false));
+    if let Some(setting) = configuration.get_mut("betenddatetime") {
+        setting.value = betenddatetime.to_string();
+    }
+    // the creator of the template creates the series, with the platform defaults at the time of the new market
+    snapshotdefaults(context, &market);
+    platformsettings(&mut configuration, &market);
+    market.get_string(&"configjson".to_string()).set_value(&serde_json::to_string(&configuration).expect("failed to make json of configuration"));
+
+    market.get_string(&"marketid".to_string()).set_value(marketid);
+    let marketids = context.state().get_string_array(&"marketids".to_string());
+    marketids.get_string(marketids.length()).set_value(marketid);
+    market.get_string(&"storageformat".to_string()).set_value(STORAGE_MAP);
+    market.get_int64(&"totalbetamount".to_string()).set_value(0);
+    market.get_string(&"runningtotals".to_string()).set_value(&"true".to_string());
+    market.get_string(&"pendingoutcome".to_string()).set_value("");
+    market.get_string(&"receiptjson".to_string()).set_value("");
+    market.get_string(&"feedreportjson".to_string()).set_value("");
+    market.get_string(&"feeddisputed".to_string()).set_value(&"false".to_string());
+    market.get_string(&"outcomescenariosjson".to_string()).set_value("");
+    market.get_string(&"betsfrozen".to_string()).set_value(&"false".to_string());
+    market.get_string(&"marketclosed".to_string()).set_value(&"false".to_string());
+    market.get_string(&"initialized".to_string()).set_value(&"true".to_string());
+    recordtransition(context, &market, "initialized");
+    context.event(&events::marketinitialized(marketid, betenddatetime, ""));
+}
+
+fn schedulestate(context: &ScFuncContext, scheduleid: &str) -> ScMutableMap {
+    context.state().get_map(&("schedule|".to_string() + scheduleid))
+}
+
+// Function to register a schedule, to be called by the contract owner: the market MARKETID is the template of a series of markets,
+// e.g. of a weekly market, and once it is settled or voided, the first following call to the contract creates the next market of the
+// series, with the settings of the template and its end time for bets INTERVAL seconds later, as long as COUNT markets remain to create.
+// The schedule is named by SCHEDULEID, up to 48 bytes, and its markets by SCHEDULEID and their number, see nextrollover in the core crate.
+fn schedulemarket(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "schedulemarket") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(authorisedfor(context, "schedulemarket"), "Not authorised to schedule markets - only contract creator is allowed to do this.");
+    let scheduleid: String = context.params().get_string(&"SCHEDULEID".to_string()).value();
+    context.require(scheduleid != "" && scheduleid.len() <= MAXSCHEDULEID, &("SCHEDULEID must have from 1 to ".to_string() + &MAXSCHEDULEID.to_string() + &" bytes".to_string()));
+    context.require(!schedulestate(context, &scheduleid).get_string(&"templateid".to_string()).exists(), "SCHEDULEID is already used by another schedule");
+    let scheduleids = context.state().get_string_array(&"scheduleids".to_string());
+    context.require(scheduleids.length() < MAXSCHEDULES, &("up to ".to_string() + &MAXSCHEDULES.to_string() + &" schedules can be active at once".to_string()));
+    let templateid: String = marketparam(context);
+    let template: ScMutableMap = marketstate(context);
+    context.require(templateid != "" && marketinitialized(&template), "MARKETID is not an initialized market");
+    let betenddatetime: i64 = template.get_int64(&"betenddatetime".to_string()).value();
+    context.require(betenddatetime != 0, "the template of a schedule needs an end time for bets");
+    let interval: i64 = paramint64(context, "INTERVAL");
+    let count: i64 = paramint64(context, "COUNT");
+    context.require(interval > 0, "INTERVAL must be positive");
+    context.require(count > 0, "COUNT must be positive");
+
+    let schedule: ScMutableMap = schedulestate(context, &scheduleid);
+    schedule.get_string(&"templateid".to_string()).set_value(&templateid);
+    schedule.get_string(&"current".to_string()).set_value(&templateid);
+    schedule.get_int64(&"instance".to_string()).set_value(0);
+    schedule.get_int64(&"betenddatetime".to_string()).set_value(betenddatetime);
+    schedule.get_int64(&"interval".to_string()).set_value(interval);
+    schedule.get_int64(&"remaining".to_string()).set_value(count);
+    schedule.get_string(&"status".to_string()).set_value(&"active".to_string());
+    scheduleids.get_string(scheduleids.length()).set_value(&scheduleid);
+    let log:String = "schedule ".to_string() + &scheduleid + &" of the template ".to_string() + &templateid + &" registered, interval: ".to_string() + &interval.to_string() + &", count: ".to_string() + &count.to_string();  context.log(&log);
+    appendadminlog(context, &("schedulemarket|".to_string() + &scheduleid + &"|".to_string() + &templateid + &"|".to_string() + &interval.to_string() + &"|".to_string() + &count.to_string()));
+}
+
+// Function to cancel the schedule SCHEDULEID, to be called by the contract owner. The markets it created are not affected,
+// it only creates no further market.
+fn cancelschedule(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "cancelschedule") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(authorisedfor(context, "cancelschedule"), "Not authorised to cancel schedules - only contract creator is allowed to do this.");
+    let scheduleid: String = context.params().get_string(&"SCHEDULEID".to_string()).value();
+    context.require(schedulestate(context, &scheduleid).get_string(&"status".to_string()).value() == "active", "SCHEDULEID is not an active schedule");
+    endschedule(context, &scheduleid, "cancelled");
+    let log:String = "schedule ".to_string() + &scheduleid + &" cancelled".to_string();  context.log(&log);
+    appendadminlog(context, &("cancelschedule|".to_string() + &scheduleid));
+}
+
+// Function for anyone, like a keeper, to create the next markets of the schedules whose current market is settled or voided.
+// Every other call to the contract does so as well, unless it is called with NOPIGGYBACK=true. ROLLED is the number of markets created.
+fn tick(context: &ScFuncContext) {
+    let rollovers = context.state().get_int64(&"rollovers".to_string());
+    let before: i64 = rollovers.value();
+    openledger(context);
+    let _recorder = match idempotent(context, "tick") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    rollschedules(context);
+    context.results().get_int64(&"ROLLED".to_string()).set_value(rollovers.value() - before);
+}
+
+// end a schedule, which leaves the schedules checked by every call
+fn endschedule(context: &ScFuncContext, scheduleid: &str, status: &str) {
+    schedulestate(context, scheduleid).get_string(&"status".to_string()).set_value(status);
+    let scheduleids = context.state().get_string_array(&"scheduleids".to_string());
+    let remaining: Vec<String> = (0..scheduleids.length()).map(|i| scheduleids.get_string(i).value()).filter(|id| id != scheduleid).collect();
+    scheduleids.clear();
+    for (i, id) in remaining.iter().enumerate() {
+        scheduleids.get_string(i as i32).set_value(id);
+    }
+}
+
+// Create the next market of every active schedule whose current market is settled or voided. A market exported for migration
+// is closed as well, but not settled, so its schedule waits. A MARKETID already taken by another market, or archived, is skipped.
+// A schedule without remaining markets ends as completed.
+fn rollschedules(context: &ScFuncContext) {
+    let scheduleids = context.state().get_string_array(&"scheduleids".to_string());
+    let active: Vec<String> = (0..scheduleids.length()).map(|i| scheduleids.get_string(i).value()).collect();
+    for scheduleid in active.iter() {
+        let schedule: ScMutableMap = schedulestate(context, scheduleid);
+        let current: ScMutableMap = marketstateof(context, &schedule.get_string(&"current".to_string()).value());
+        if current.get_string(&"marketclosed".to_string()).value() != "true" || current.get_string(&"migrationhash".to_string()).value() != "" {
+            continue;
+        }
+        let rollover: Option<Rollover> = nextrollover(scheduleid, schedule.get_int64(&"instance".to_string()).value(), schedule.get_int64(&"betenddatetime".to_string()).value(),
+            schedule.get_int64(&"interval".to_string()).value(), schedule.get_int64(&"remaining".to_string()).value(), context.timestamp());
+        let rollover: Rollover = match rollover {
+            Some(rollover) => rollover,
+            None => {
+                endschedule(context, scheduleid, "completed");
+                continue;
+            }
+        };
+        let mut instance: i64 = rollover.instance;
+        let mut marketid: String = rollover.marketid.clone();
+        while marketstateof(context, &marketid).get_string(&"marketid".to_string()).exists() || marketarchived(context, &marketid) {
+            instance += 1;
+            marketid = scheduledmarketid(scheduleid, instance);
+        }
+        clonemarket(context, &schedule.get_string(&"templateid".to_string()).value(), &marketid, rollover.betenddatetime);
+        schedule.get_string(&"current".to_string()).set_value(&marketid);
+        schedule.get_int64(&"instance".to_string()).set_value(instance);
+        schedule.get_int64(&"betenddatetime".to_string()).set_value(rollover.betenddatetime);
+        schedule.get_int64(&"remaining".to_string()).set_value(rollover.remaining);
+        let skipped = schedule.get_int64(&"skipped".to_string());
+        skipped.set_value(skipped.value() + rollover.skipped);
+        let rollovers = context.state().get_int64(&"rollovers".to_string());
+        rollovers.set_value(rollovers.value() + 1);
+        context.event(&events::marketscheduled(scheduleid, &marketid, rollover.betenddatetime, rollover.skipped, rollover.remaining));
+        let log:String = "schedule ".to_string() + scheduleid + &" created the market ".to_string() + &marketid + &", skipped intervals: ".to_string() + &rollover.skipped.to_string() + &", remaining: ".to_string() + &rollover.remaining.to_string();  context.log(&log);
+        if rollover.remaining == 0 {
+            endschedule(context, scheduleid, "completed");
+        }
+    }
+}
+
+
+// add an amount of IOTA to a ledger bucket, or take it from the bucket with a negative amount
+fn creditbucket(context: &ScFuncContext, bucket: &str, amount: i64) {
+    let bucketbalance = context.state().get_int64(&bucketkey(bucket));
+    bucketbalance.set_value(bucketbalance.value() + amount);
+}
+
+fn movebucket(context: &ScFuncContext, frombucket: &str, tobucket: &str, amount: i64) {
+    creditbucket(context, frombucket, -amount);
+    creditbucket(context, tobucket, amount);
+}
+
+fn transfersblocked(context: &ScFuncContext) -> bool {
+    context.state().get_string(&"transfersblocked".to_string()).value() == "true"
+}
+
+// Called first by every function: the IOTA sent with the call are credited to the strays bucket, from where a function moves
+// what it accepts, e.g. the amount of a bet. Then the ledger's invariant is checked, and a violation blocks all transfers.
+// Otherwise, queued payouts of settled markets are piggybacked on the call, and due markets of schedules are created.
+fn openledger(context: &ScFuncContext) {
+    // every call of the contract owner or a resolver proves the resolver is alive
+    if hasrole(context, ROLE_RESOLVER) {
+        marketstate(context).get_int64(&"resolverlastseen".to_string()).set_value(context.timestamp());
+    }
+    creditbucket(context, BUCKET_STRAYS, context.incoming().balance(&ScColor::IOTA));
+    if transfersblocked(context) {
+        return;
+    }
+    let balance: i64 = context.balances().balance(&ScColor::IOTA);
+    let bucketstotal: i64 = BUCKETS.iter().map(|bucket| context.state().get_int64(&bucketkey(bucket)).value()).sum();
+    if !ledgerinvariantholds(balance, bucketstotal, context.state().get_int64(&"transferscount".to_string()).value()) {
+        let log:String = "invariant violation: the balance of ".to_string() + &balance.to_string() + &" IOTA does not match the ledger of ".to_string() + &bucketstotal.to_string() + &" IOTA, transfers are blocked until reconcile is run".to_string();  context.log(&log);
+        context.state().get_string(&"transfersblocked".to_string()).set_value(&"true".to_string());
+        appendadminlog(context, &("transfersblocked|".to_string() + &balance.to_string() + &"|".to_string() + &bucketstotal.to_string()));
+        return;
+    }
+    piggybackpayouts(context);
+    if context.params().get_string(&"NOPIGGYBACK".to_string()).value() != "true" {
+        rollschedules(context);
+    }
+}
+
+// Function to reconcile the ledger with the IOTA held by the contract, to be called by the contract owner.
+// Any difference is absorbed by the strays bucket and transfers are unblocked. If the contract holds less than the pools, fees,
+// escrows, deposits and pending returns require, transfers stay blocked: the missing IOTA can be sent with the call.
+// With DRYRUN=true nothing is written: the call returns the would-be writes as EFFECT, see StateWrites, and the change of the strays
+// bucket as DIFFERENCE like a real run. A dry run cannot account for IOTA sent with it, so it accepts none.
+fn reconcile(context: &ScFuncContext) {
+    // only the contract owner or a treasurer should be able to do this
+    context.require(authorisedfor(context, "reconcile"), "Not authorised to reconcile - only contract creator or a treasurer is allowed to do this.");
+    let dryrun: bool = context.params().get_string(&"DRYRUN".to_string()).value() == "true";
+    let _recorder: Option<IdempotencyRecorder> = if dryrun {
+        context.require(context.incoming().balance(&ScColor::IOTA) == 0, "a dry run of reconcile cannot accept IOTA");
+        None
+    } else {
+        openledger(context);
+        match idempotent(context, "reconcile") {
+            Some(recorder) => Some(recorder),
+            None => return,
+        }
+    };
+
+    let balance: i64 = context.balances().balance(&ScColor::IOTA);
+    let requiredtotal: i64 = [BUCKET_POOLS, BUCKET_FEES, BUCKET_CREATORFEES, BUCKET_ESCROWS, BUCKET_DEPOSITS, BUCKET_RETURNS].iter().map(|bucket| context.state().get_int64(&bucketkey(bucket)).value()).sum();
+    let strays: i64 = balance - requiredtotal;
+    let mut writes = StateWrites::new();
+    let mut log:String;
+    if strays >= 0 {
+        writes.set_int64(&bucketkey(BUCKET_STRAYS), strays);
+        writes.set_string("transfersblocked", "false");
+        writes.set_int64("lastreconciled", context.timestamp());
+        context.results().get_int64(&"DIFFERENCE".to_string()).set_value(strays - context.state().get_int64(&bucketkey(BUCKET_STRAYS)).value());
+        log = "ledger reconciled, strays bucket: ".to_string() + &strays.to_string() + &" IOTA".to_string();  context.log(&log);
+    } else {
+        log = "the contract holds ".to_string() + &(-strays).to_string() + &" IOTA less than required, transfers stay blocked".to_string();  context.log(&log);
+    }
+    writes.append("adminlog", &(context.timestamp().to_string() + &"|reconcile|".to_string() + &balance.to_string() + &"|".to_string() + &requiredtotal.to_string()));
+    context.results().get_string(&"EFFECT".to_string()).set_value(&writes.effect());
+    if dryrun {
+        log = "dry run of reconcile, nothing is written".to_string();  context.log(&log);
+        return;
+    }
+    applywrites(&writes, &context.state());
+}
+
+// Replay protection for retried requests: every function accepts an optional parameter IDEMPOTENCYKEY. The first call with a key
+// for a function and market is executed, and any later call with the same key is not, but returns STATUS "duplicate" together with the
+// ORIGINALREQUESTID and the results of the first call, and gets everything sent with it refunded. Recording happens within the call
+// itself, so a failed call does not use up its key. Every market remembers its last IDEMPOTENCYKEYS keys, see rememberidempotencykey.
+// Returns None for a duplicate, and otherwise the recorder storing the results of the call once the function returns.
+fn idempotent<'a>(context: &'a ScFuncContext, function: &str) -> Option<IdempotencyRecorder<'a>> {
+    let idempotencykey: String = context.params().get_string(&"IDEMPOTENCYKEY".to_string()).value();
+    if idempotencykey == "" {
+        return Some(IdempotencyRecorder { context, scopedkey: "".to_string() });
+    }
+    let scopedkey: String = function.to_string() + &"|".to_string() + &marketstate(context).get_string(&"marketid".to_string()).value() + &"|".to_string() + &idempotencykey;
+    let originalrequestid = marketstate(context).get_map(&"idempotencykeys".to_string()).get_string(&scopedkey);
+    if originalrequestid.value() != "" && originalrequestid.value() != IDEMPOTENCY_EVICTED {
+        let log:String = "duplicate request for ".to_string() + function + &" with idempotency key ".to_string() + &idempotencykey + &" is not executed".to_string();  context.log(&log);
+        replayresults(context, &scopedkey);
+        context.results().get_string(&"STATUS".to_string()).set_value(&"duplicate".to_string());
+        context.results().get_string(&"ORIGINALREQUESTID".to_string()).set_value(&originalrequestid.value());
+        refundincoming(context, &context.caller().address(), REFUND_DUPLICATE);
+        return None;
+    }
+    originalrequestid.set_value(&context.request_id().to_string());
+    rememberidempotencykey(context, &scopedkey);
+    Some(IdempotencyRecorder { context, scopedkey })
+}
+
+// results of functions that a duplicate call returns again, every result a function sets has to be listed here
+const REPLAYEDSTRINGRESULTS: [&str; 21] = [
+    "BETENDUTC", "BETSCLOSEATUTC", "CONFIG", "DEFAULTS", "DIFF", "DIGEST", "EFFECT", "EFFECTIVE", "ERROR", "FEEDREPORT", "FOUND", "HASH", "MARKETID", "MEMOCIPHER", "NOTICE",
+    "RESOLVEBYUTC", "SEEDS", "SHORTFALLPOLICY", "SOURCE", "WARNINGS", "WINNINGVALUE"
+];
+const REPLAYEDINT64RESULTS: [&str; 36] = [
+    "ACCEPTED", "ACTIONID", "ATTACHED", "BANKROLL", "BETENDDATETIME", "BETS", "BETSCLOSEAT", "BOND", "CHALLENGEID", "CHUNKS", "CLAIMED", "COMMITESCROW", "COMMITS", "DEFAULTSVERSION", "DIFFERENCE",
+    "EFFECTIVEAT", "ESCROW", "EXPIRED", "FRACTIONBPS", "FROZENREMAINING", "LOCKEDPAYOUT", "MIRRORID", "ODDSBPS", "OVERROUNDBPS", "PENDINGRETURN", "PENDINGRETURNS", "POOL", "PROCESSED", "PRUNEDUNTIL", "RELEASED", "RESOLVEBY", "ROLLED",
+    "SCANNED", "SHORTFALL", "TOTALBETAMOUNT", "WITHDRAWN"
+];
+
+// Results of a call with an IDEMPOTENCYKEY, stored with its key in idempotencyresults.
+#[serde_as]
+#[derive(Default, Deserialize, Serialize)]
+struct IdempotentResults {
+    #[serde(default)]
+    strings: BTreeMap<String, String>,
+    #[serde(default)]
+    int64s: BTreeMap<String, i64>,
+}
+
+// Stores the results of a call with an IDEMPOTENCYKEY when it is dropped at the return of the function, see idempotent.
+// A failed call stores nothing, as its changes to the state are reverted anyway.
+struct IdempotencyRecorder<'a> {
+    context: &'a ScFuncContext,
+    scopedkey: String,
+}
+
+impl Drop for IdempotencyRecorder<'_> {
+    fn drop(&mut self) {
+        if self.scopedkey.is_empty() {
+            return;
+        }
+        let results: ScMutableMap = self.context.results();
+        let mut recorded: IdempotentResults = IdempotentResults::default();
+        for key in REPLAYEDSTRINGRESULTS.iter().filter(|key| results.get_string(**key).exists()) {
+            recorded.strings.insert(key.to_string(), results.get_string(*key).value());
+        }
+        for key in REPLAYEDINT64RESULTS.iter().filter(|key| results.get_int64(**key).exists()) {
+            recorded.int64s.insert(key.to_string(), results.get_int64(*key).value());
+        }
+        marketstate(self.context).get_map(&"idempotencyresults".to_string()).get_string(&self.scopedkey)
+            .set_value(&serde_json::to_string(&recorded).expect("failed to make json of results"));
+    }
+}
+
+// set the results stored for the key again, none for keys recorded before the results were stored
+fn replayresults(context: &ScFuncContext, scopedkey: &str) {
+    let resultsjson: String = marketstate(context).get_map(&"idempotencyresults".to_string()).get_string(scopedkey).value();
+    if resultsjson == "" {
+        return;
+    }
+    let recorded: IdempotentResults = serde_json::from_str(&resultsjson).expect("failed to get results");
+    for (key, value) in recorded.strings.iter() {
+        context.results().get_string(key).set_value(value);
+    }
+    for (key, value) in recorded.int64s.iter() {
+        context.results().get_int64(key).set_value(*value);
+    }
+}
+
+// The keys of a market are kept in insertion order in a queue of "timestamp|key" entries, indexed from idempotencyhead to idempotencytail.
+// Once more than IDEMPOTENCYKEYS keys are queued, each insert evicts up to two of the oldest keys, but never one younger than
+// IDEMPOTENCYMINAGE seconds, see evictedkey in the core crate. Evicted keys are queued once more, up to the same number,
+// so that getidempotencykey can tell an evicted key from an unknown one. Every insert takes constant time and the state stays bounded.
+fn rememberidempotencykey(context: &ScFuncContext, scopedkey: &str) {
+    let market = marketstate(context);
+    let mut maxkeys: i64 = market.get_int64(&"idempotencymaxkeys".to_string()).value();
+    if maxkeys <= 0 {
+        maxkeys = MAXIDEMPOTENCYKEYS as i64;
+    }
+    let minage: i64 = market.get_int64(&"idempotencyminage".to_string()).value();
+    let keys = market.get_map(&"idempotencykeys".to_string());
+
+    pushidempotencyqueue(context, "idempotency", &queuedkey(context.timestamp(), scopedkey));
+    for _eviction in 0..MAXEVICTIONSPERINSERT {
+        let oldest: String = peekidempotencyqueue(context, "idempotency");
+        let oldestkey: &str = match evictedkey(idempotencyqueuelength(context, "idempotency"), maxkeys, &oldest, context.timestamp(), minage) {
+            Some(oldestkey) => oldestkey,
+            None => break,
+        };
+        popidempotencyqueue(context, "idempotency");
+        keys.get_string(oldestkey).set_value(IDEMPOTENCY_EVICTED);
+        // the results of an evicted key are not returned anymore
+        market.get_map(&"idempotencyresults".to_string()).get_string(oldestkey).set_value("");
+        pushidempotencyqueue(context, "idempotencyevicted", oldestkey);
+        if idempotencyqueuelength(context, "idempotencyevicted") > maxkeys {
+            let forgotten: String = popidempotencyqueue(context, "idempotencyevicted");
+            // a key can have been used again after it was evicted
+            if keys.get_string(&forgotten).value() == IDEMPOTENCY_EVICTED {
+                keys.get_string(&forgotten).set_value("");
+            }
+        }
+    }
+}
+
+fn idempotencyqueuelength(context: &ScFuncContext, queue: &str) -> i64 {
+    let market = marketstate(context);
+    market.get_int64(&(queue.to_string() + "tail")).value() - market.get_int64(&(queue.to_string() + "head")).value()
+}
+
+fn pushidempotencyqueue(context: &ScFuncContext, queue: &str, entry: &str) {
+    let tail = marketstate(context).get_int64(&(queue.to_string() + "tail"));
+    marketstate(context).get_map(&(queue.to_string() + "queue")).get_string(&tail.value().to_string()).set_value(entry);
+    tail.set_value(tail.value() + 1);
+}
+
+fn peekidempotencyqueue(context: &ScFuncContext, queue: &str) -> String {
+    let head: i64 = marketstate(context).get_int64(&(queue.to_string() + "head")).value();
+    marketstate(context).get_map(&(queue.to_string() + "queue")).get_string(&head.to_string()).value()
+}
+
+fn popidempotencyqueue(context: &ScFuncContext, queue: &str) -> String {
+    let head = marketstate(context).get_int64(&(queue.to_string() + "head"));
+    let entry = marketstate(context).get_map(&(queue.to_string() + "queue")).get_string(&head.value().to_string());
+    let value: String = entry.value();
+    entry.set_value("");
+    head.set_value(head.value() + 1);
+    value
+}
+
+// View of an idempotency key of a function, passed as IDEMPOTENCYKEY and FUNCTION, e.g. "bet". Returns STATUS "executed" together with
+// the ORIGINALREQUESTID, "evicted" if the key was used but is not remembered anymore, so a retry would be executed again, or "not_found".
+fn getidempotencykey(context: &ScViewContext) {
+    let function: String = context.params().get_string(&"FUNCTION".to_string()).value();
+    let idempotencykey: String = context.params().get_string(&"IDEMPOTENCYKEY".to_string()).value();
+    let market = viewmarketstate(context);
+    let scopedkey: String = function + &"|".to_string() + &market.get_string(&"marketid".to_string()).value() + &"|".to_string() + &idempotencykey;
+    let originalrequestid: String = market.get_map(&"idempotencykeys".to_string()).get_string(&scopedkey).value();
+    let status: &str = idempotencystatus(&originalrequestid);
+    context.results().get_string(&"STATUS".to_string()).set_value(status);
+    if status == "executed" {
+        context.results().get_string(&"ORIGINALREQUESTID".to_string()).set_value(&originalrequestid);
+    }
+}
+
+// Append an entry to the admin log in the contract's state.
+// Each entry is prefixed by the timestamp of the transaction, e.g., "1630000000|declareoutcome|yes".
+// An entry of a call with a MARKETID is also appended to the admin log of that market, which the digest of its tombstone covers.
+fn appendadminlog(context: &ScFuncContext, entry: &str) {
+    let adminlog = context.state().get_string_array(&"adminlog".to_string());
+    adminlog.get_string(adminlog.length()).set_value(&(context.timestamp().to_string() + &"|".to_string() + entry));
+    if marketparam(context) != "" {
+        let marketadminlog = marketstate(context).get_string_array(&"adminlog".to_string());
+        marketadminlog.get_string(marketadminlog.length()).set_value(&(context.timestamp().to_string() + &"|".to_string() + entry));
+    }
+}
+
+// Append a transition of the market like "1622563200|settled" to its transitions, which the digest of its tombstone covers.
+fn recordtransition(context: &ScFuncContext, market: &ScMutableMap, transition: &str) {
+    let transitions = market.get_string_array(&"transitions".to_string());
+    transitions.get_string(transitions.length()).set_value(&(context.timestamp().to_string() + &"|".to_string() + transition));
+}
+
+
+// Read an optional integer parameter, which is passed as a string like all other parameters. A missing parameter is 0.
+fn paramint64<T: ScBaseContext>(context: &T, name: &str) -> i64 {
+    let param: String = context.params().get_string(name).value();
+    if param == "" {
+        return 0;
+    }
+    let value: Result<i64, _> = param.trim().parse::<i64>();
+    context.require(value.is_ok(), &(name.to_string() + &" must be an integer".to_string()));
+    value.unwrap()
+}
+
+// Strict mode is requested per call by the parameter STRICT ("true" or "false"),
+// otherwise the default set by initmarket is used.
+fn isstrict(context: &ScFuncContext) -> bool {
+    let strictparam = context.params().get_string(&"STRICT".to_string());
+    if strictparam.exists() {
+        return strictparam.value() == "true";
+    }
+    marketstate(context).get_string(&"strictmode".to_string()).value() == "true"
+}
+
+// Handle a soft failure, i.e., a call that cannot have any effect.
+// In strict mode the transaction fails with the error code, so that callers can rely on failed transactions for retrying.
+// Otherwise the message is only logged and the transaction succeeds without any effect.
+// Note that no soft failure refunds the sent IOTA in-band: in strict mode they are recorded as pending return to the caller,
+// see recordpendingreturn, otherwise the IOTA stay with the contract. softfailurehandling of the core crate decides which applies.
+fn softfail(context: &ScFuncContext, strict: bool, error: &str, message: &str) {
+    match softfailurehandling(strict, context.incoming().balance(&ScColor::IOTA), false) {
+        SoftFailureHandling::Fail => context.panic(&(error.to_string() + &": ".to_string() + message)),
+        SoftFailureHandling::PendingReturn => recordpendingreturn(context, &context.caller().address(), error, message),
+        _ => {
+            context.log(message);
+            addrefusal(context, error);
+        }
+    }
+}
+
+// Record the IOTA sent with a request rejected in strict mode, to be returned to the sender later by processreturns.
+// A failed request would undo the record along with everything else, so the rejection does not fail the request once IOTA are attached:
+// it ends without any other effect, the IOTA are moved from the strays to the returns bucket, and the results are ERROR, STATUS "rejected"
+// and PENDINGRETURN with the returned amount. Without IOTA sent, the strict request fails instead, see softfailurehandling.
+fn recordpendingreturn(context: &ScFuncContext, sender: &ScAddress, error: &str, message: &str) {
+    let amount: i64 = context.incoming().balance(&ScColor::IOTA);
+    let pendingreturn = PendingReturn {
+        requestid: context.request_id().to_string(),
+        address: AddressCodec::CURRENT.encode(&context.utility(), sender),
+        addressencoding: AddressCodec::CURRENT.flag().to_string(),
+        amount,
+        error: error.to_string(),
+    };
+    let pendingreturns = context.state().get_string_array(&"pendingreturns".to_string());
+    pendingreturns.get_string(pendingreturns.length()).set_value(&serde_json::to_string(&pendingreturn).expect("failed to make json of pending return"));
+    movebucket(context, BUCKET_STRAYS, BUCKET_RETURNS, amount);
+    let log:String = error.to_string() + &": ".to_string() + message + &", ".to_string() + &amount.to_string() + &" IOTA are returned to ".to_string() + &sender.to_string() + &" by processreturns".to_string();  context.log(&log);
+    addrefusal(context, error);
+    context.results().get_string(&"STATUS".to_string()).set_value(&"rejected".to_string());
+    context.results().get_int64(&"PENDINGRETURN".to_string()).set_value(amount);
+}
+
+// Function to pay back up to BATCHSIZE pending returns in the order they were recorded, which anybody may call.
+// Returns PROCESSED, the number of returns paid, and PENDINGRETURNS, the number still outstanding.
+fn processreturns(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "processreturns") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let mut batchsize: i64 = paramint64(context, "BATCHSIZE");
+    if batchsize <= 0 || batchsize > DEFAULTPAYOUTBATCH {
+        batchsize = DEFAULTPAYOUTBATCH;
+    }
+    let processed: i64 = payreturns(context, batchsize);
+    let log:String = "PROCESSRETURNS processed returns: ".to_string() + &processed.to_string(); context.log(&log);
+    context.results().get_int64(&"PROCESSED".to_string()).set_value(processed);
+    context.results().get_int64(&"PENDINGRETURNS".to_string()).set_value(pendingreturnsleft(context.state().immutable()));
+}
+
+// Pay up to maxcount pending returns through guardedtransfer, from the returns bucket. A return whose transfer fails, e.g. while
+// transfers are blocked, stays first in line. Returns the number of returns paid.
+fn payreturns(context: &ScFuncContext, maxcount: i64) -> i64 {
+    let pendingreturns = context.state().get_string_array(&"pendingreturns".to_string());
+    let head = context.state().get_int64(&"pendingreturnshead".to_string());
+    let end: i64 = (pendingreturns.length() as i64).min(head.value().saturating_add(maxcount));
+    let queued: Vec<PendingReturn> = (head.value()..end)
+        .map(|index| serde_json::from_str(&pendingreturns.get_string(index as i32).value()).expect("failed to parse pending return"))
+        .collect();
+    let batch: ReturnsBatch = payreturnbatch(&queued, maxcount, &mut |pendingreturn: &PendingReturn| {
+        let recipientaddress: ScAddress = AddressCodec::from_flag(&pendingreturn.addressencoding).and_then(|codec| codec.decode(&context.utility(), &pendingreturn.address))?;
+        let transferred: i64 = guardedtransfer(context, &recipientaddress, pendingreturn.amount, "return", BUCKET_RETURNS);
+        if transferred > 0 {
+            let log:String = "returned ".to_string() + &transferred.to_string() + &" IOTA of request ".to_string() + &pendingreturn.requestid + &" to: ".to_string() + &recipientaddress.to_string();  context.log(&log);
+        }
+        Ok(transferred)
+    });
+    for (pendingreturn, error) in batch.dropped.iter() {
+        // an address that does not decode cannot ever be paid, its IOTA go to the strays
+        let log:String = "pending return of request ".to_string() + &pendingreturn.requestid + &" is dropped: ".to_string() + error;  context.log(&log);
+        movebucket(context, BUCKET_RETURNS, BUCKET_STRAYS, pendingreturn.amount);
+    }
+    for index in head.value()..head.value() + batch.advanced {
+        pendingreturns.get_string(index as i32).set_value("");
+    }
+    head.set_value(head.value() + batch.advanced);
+    batch.paid
+}
+
+fn pendingreturnsleft(state: ScImmutableMap) -> i64 {
+    pendingreturnscount(state.get_string_array(&"pendingreturns".to_string()).length() as i64, state.get_int64(&"pendingreturnshead".to_string()).value())
+}
+
+// Write a metadata value of the market to the state. The bytes of all metadata values are counted in configbytes,
+// and a value that would exceed the limit of the market fails the transaction with ERR_CONFIG_TOO_LARGE.
+fn setconfigvalue(context: &ScFuncContext, key: &str, value: &str) {
+    let configvalue = marketstate(context).get_string(key);
+    let configbytes = marketstate(context).get_int64(&"configbytes".to_string());
+    let maxconfigbytes: i64 = marketstate(context).get_int64(&"maxconfigbytes".to_string()).value();
+    let newconfigbytes: Result<i64, String> = configbytesafter(configbytes.value(), &configvalue.value(), value, maxconfigbytes);
+    if let Err(error) = &newconfigbytes {
+        context.panic(error);
+    }
+    configvalue.set_value(value);
+    configbytes.set_value(newconfigbytes.unwrap());
+}
+
+// Return the error code of a refused call in the result ERROR, and the notice of the contract owner in the result NOTICE,
+// if one is set by setrefusalnotice.
+fn addrefusal(context: &ScFuncContext, error: &str) {
+    for (key, value) in refusalresults(error, &marketstate(context).get_string(&"refusalnotice".to_string()).value()) {
+        context.results().get_string(key).set_value(&value);
+    }
+}
+
+// Function to set the notice returned with every refused call, e.g. "the match was abandoned, the bets are refunded", to be called by
+// the contract owner. The parameter NOTICE is limited to 280 bytes, an empty NOTICE removes the notice.
+fn setrefusalnotice(context: &ScFuncContext) {
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "setrefusalnotice"), "Not authorised to set the refusal notice - only contract creator or a marketadmin is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "setrefusalnotice") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let notice: String = context.params().get_string(&"NOTICE".to_string()).value();
+    let validated: Result<(), String> = validaterefusalnotice(&notice);
+    context.require(validated.is_ok(), validated.as_ref().err().map_or("", |error| error.as_str()));
+    setconfigvalue(context, "refusalnotice", &notice);
+    appendadminlog(context, &("setrefusalnotice|".to_string() + &notice));
+}
+
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct Exposure {
+    // outcome value the exposure is computed for
+    betvalue: String,
+    // total amount of bets placed on the value
+    totalbetamount: i32,
+    // what the contract would pay out if the value wins
+    payout: i64,
+    // payout minus the IOTA held by the contract, negative while the contract is solvent for this outcome
+    net: i64,
+    // true if the payout would exceed the IOTA held by the contract
+    insolvent: bool,
+}
+
+// View for risk monitoring: for every value bets were placed on, compute the settlement as if the value won,
+// compare its payout with the IOTA currently held by the contract and flag outcomes which could not be paid.
+// The result EXPOSURE is a json list of Exposure entries, AVAILABLE is the contract's IOTA balance.
+fn getexposure(context: &ScViewContext) {
+    let containerofbets: ContainerOfBets = viewcontainerofbets(context);
+    let available: i64 = context.balances().balance(&ScColor::IOTA);
+    context.results().get_int64(&"AVAILABLE".to_string()).set_value(available);
+    // while the odds are hidden, the exposure per value would reveal them
+    if oddshidden(context) {
+        context.results().get_string(&"EXPOSURE".to_string()).set_value("[]");
+        return;
+    }
+
+    // outcome values in a deterministic order
+    let mut betvalues: Vec<String> = containerofbets.map.values().map(|bet| bet.betisforvalue.clone()).collect();
+    betvalues.sort();
+    betvalues.dedup();
+
+    let feepercent: i64 = viewmarketstate(context).get_int64(&"feepercent".to_string()).value();
+    let mut exposures: Vec<Exposure> = Vec::new();
+    for betvalue in betvalues {
+        let settlement: SettlementResult = settlewithfee(&containerofbets, &betvalue, feepercent);
+        let payout: i64 = settlement.winamounts.values().sum();
+        exposures.push(Exposure {
+            betvalue,
+            totalbetamount: settlement.totalbetamountforwinningvalue,
+            payout,
+            net: payout - available,
+            insolvent: payout > available,
+        });
+    }
+
+    context.results().get_string(&"EXPOSURE".to_string()).set_value(&serde_json::to_string(&exposures).expect("failed to make json of exposures"));
+}
+
+// Reveal the odds once the pool of the market reaches ODDSREVEALPOOL, emitting market.oddsrevealed exactly once.
+// Markets initialized before ODDSREVEALPOOL existed have no flag, and their odds are never hidden.
+fn revealodds(context: &ScFuncContext) {
+    let oddsrevealed = marketstate(context).get_string(&"oddsrevealed".to_string());
+    let pool: i64 = marketstate(context).get_int64(&"totalbetamount".to_string()).value();
+    if revealsodds(&oddsrevealed.value(), pool, marketstate(context).get_int64(&"oddsrevealpool".to_string()).value()) {
+        oddsrevealed.set_value(&"true".to_string());
+        let log:String = "the odds are revealed at a pool of ".to_string() + &pool.to_string() + &" IOTA".to_string();  context.log(&log);
+        context.event(&events::marketoddsrevealed(&marketstate(context).get_string(&"marketid".to_string()).value(), pool));
+    }
+}
+
+// Whether the views withhold the amounts per value, before the pool reached ODDSREVEALPOOL. They return ODDSHIDDEN in any case.
+fn oddshidden(context: &ScViewContext) -> bool {
+    let hidden: bool = viewmarketstate(context).get_string(&"oddsrevealed".to_string()).value() == "false";
+    context.results().get_string(&"ODDSHIDDEN".to_string()).set_value(&hidden.to_string());
+    hidden
+}
+
+// View of the amount bet on every value, summed like the settlement does, returned as POOLS, e.g. {"no":700,"total":1000,"yes":300}.
+// Without bets, POOLS is an empty object. Bets on the catch-all outcome are listed under __OTHER__.
+fn getpools(context: &ScViewContext) {
+    // the running totals make the pools available without reading the bets
+    let totals: BetTotals = if viewmarketstate(context).get_string(&"runningtotals".to_string()).value() == "true" {
+        readtotals(&viewmarketstate(context))
+    } else {
+        bettotals(&viewcontainerofbets(context))
+    };
+
+    let pools: BTreeMap<String, i64> = totals.betvalue_totalbetamount.iter().map(|(betvalue, total)| (betvalue.clone(), *total as i64)).collect();
+    // while the odds are hidden, only the total is returned
+    let pools: BTreeMap<String, i64> = visiblepools(pools, oddshidden(context));
+    context.results().get_string(&"POOLS".to_string()).set_value(&serde_json::to_string(&pools).expect("failed to make json of pools"));
+    // name of the catch-all outcome, so that UIs can label its pool as "none of the named outcomes"
+    context.results().get_string(&"RESIDUALOUTCOME".to_string()).set_value(OUTCOME_OTHER);
+}
+
+
+// Running totals of the bets of the market, kept at bet time so that closemarket and getpools do not have to add up the bets:
+// totalbetamount is the overall amount, total_<value> the amount per value, and betvalues lists the values bet on.
+// Markets initialized before the running totals existed have no runningtotals flag until they are initialized again.
+fn settotals(context: &ScFuncContext, totals: &BetTotals) {
+    let market = marketstate(context);
+    let betvalues = market.get_string_array(&"betvalues".to_string());
+    for i in 0..betvalues.length() {
+        market.get_int64(&("total_".to_string() + &betvalues.get_string(i).value())).set_value(0);
+    }
+    betvalues.clear();
+    let mut sortedbetvalues: Vec<&String> = totals.betvalue_totalbetamount.keys().collect();
+    sortedbetvalues.sort();
+    for betvalue in sortedbetvalues {
+        betvalues.get_string(betvalues.length()).set_value(betvalue);
+        market.get_int64(&("total_".to_string() + betvalue)).set_value(totals.betvalue_totalbetamount[betvalue] as i64);
+    }
+    market.get_int64(&"totalbetamount".to_string()).set_value(totals.totalbetamount as i64);
+    market.get_string(&"runningtotals".to_string()).set_value(&"true".to_string());
+}
+
+// add an accepted bet amount, which may add to a previous bet of the same account on the same value
+fn addtotals(context: &ScFuncContext, betvalue: &str, betamount: i64) {
+    let market = marketstate(context);
+    if market.get_string(&"runningtotals".to_string()).value() != "true" {
+        return;
+    }
+    let total = market.get_int64(&("total_".to_string() + betvalue));
+    if !total.exists() {
+        let betvalues = market.get_string_array(&"betvalues".to_string());
+        betvalues.get_string(betvalues.length()).set_value(betvalue);
+    }
+    total.set_value(total.value() + betamount);
+    let totalbetamount = market.get_int64(&"totalbetamount".to_string());
+    totalbetamount.set_value(totalbetamount.value() + betamount);
+}
+
+fn runningtotals(context: &ScFuncContext) -> Option<BetTotals> {
+    let market = marketstate(context);
+    if market.get_string(&"runningtotals".to_string()).value() != "true" {
+        return None;
+    }
+    Some(readtotals(&market.immutable()))
+}
+
+fn readtotals(market: &ScImmutableMap) -> BetTotals {
+    let betvalues = market.get_string_array(&"betvalues".to_string());
+    let mut betvalue_totalbetamount: HashMap<String, i32> = HashMap::new();
+    for i in 0..betvalues.length() {
+        let betvalue: String = betvalues.get_string(i).value();
+        let total: i64 = market.get_int64(&("total_".to_string() + &betvalue)).value();
+        if total > 0 {
+            betvalue_totalbetamount.insert(betvalue, total as i32);
+        }
+    }
+    BetTotals { totalbetamount: market.get_int64(&"totalbetamount".to_string()).value() as i32, betvalue_totalbetamount }
+}
+
+// Count an accepted bet and its amount for the recent activity of the market, shown by getstats.
+fn recordactivity(context: &ScFuncContext, currtime: i64, betamount: i64) {
+    let market = marketstate(context);
+    let windowstart = market.get_int64(&"activitywindowstart".to_string());
+    let recentbets = market.get_int64(&"recentbets".to_string());
+    let recentvolume = market.get_int64(&"recentvolume".to_string());
+    if currtime - windowstart.value() >= market.get_int64(&"activitywindow".to_string()).value() {
+        windowstart.set_value(currtime);
+        recentbets.set_value(0);
+        recentvolume.set_value(0);
+    }
+    recentbets.set_value(recentbets.value() + 1);
+    recentvolume.set_value(recentvolume.value() + betamount);
+}
+
+// A CLIENTID or CAMPAIGN is short and made of letters, digits, '-', '_' and '.', so that it can be used as a key and in logs.
+fn validattribution(attribution: &str) -> bool {
+    attribution.len() <= MAXATTRIBUTION && attribution.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+// Count an accepted bet and its amount for its campaign. At most MAXCAMPAIGNS campaigns are counted per market, a bet of a further
+// campaign is not counted and false is returned.
+fn recordcampaign(context: &ScFuncContext, campaign: &str, betamount: i64) -> bool {
+    let market = marketstate(context);
+    let bets = market.get_map(&"campaignbets".to_string()).get_int64(campaign);
+    if !bets.exists() {
+        let campaigns = market.get_string_array(&"campaigns".to_string());
+        if campaigns.length() >= MAXCAMPAIGNS {
+            return false;
+        }
+        campaigns.get_string(campaigns.length()).set_value(campaign);
+    }
+    bets.set_value(bets.value() + 1);
+    let volume = market.get_map(&"campaignvolume".to_string()).get_int64(campaign);
+    volume.set_value(volume.value() + betamount);
+    true
+}
+
+// The stored counter of the market under its name in countervalues of the core crate.
+fn storedcounter(market: &ScMutableMap, counter: &str) -> ScMutableInt64 {
+    for (prefix, key) in [(campaignbetscounter(""), "campaignbets"), (campaignvolumecounter(""), "campaignvolume")].iter() {
+        if counter.starts_with(prefix.as_str()) {
+            return market.get_map(*key).get_int64(&counter[prefix.len()..]);
+        }
+    }
+    market.get_int64(counter)
+}
+
+// Function for the contract owner to recompute the counters derived from the bets of the market, in case they drifted from the bets
+// after a bug or a partial migration: the running totals per value, the total bet amount, the number of betting accounts, the campaign
+// stats and, while a market with MARKETID is open, its pool, see counters in the core crate.
+// Every call scans up to BATCHSIZE stored bets in the order the accounts first bet, continuing at the cursor of the previous call, and returns SCANNED and BETS.
+// Bets are rejected with ERR_COUNTERS_REBUILDING until the scan is complete. The call completing it compares the recomputed counters
+// with the stored ones, emits counter.diff for every difference and market.countersrebuilt, overwrites the stored counters and returns
+// the differences as DIFF.
+fn rebuildcounters(context: &ScFuncContext) {
+    // only contract owner should be able to do this
+    let creator = context.contract_creator();
+    let caller = context.caller();
+    context.require(creator == caller, "Not authorised to rebuild counters - only contract creator is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "rebuildcounters") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    let market: ScMutableMap = marketstate(context);
+    let rebuilding = market.get_string(&"rebuilding".to_string());
+    let cursor = market.get_int64(&"rebuildcursor".to_string());
+    let progress = market.get_string(&"rebuildprogressjson".to_string());
+    if rebuilding.value() != "true" {
+        // the scan walks the bets in the map layout
+        migratestorage(context);
+        rebuilding.set_value(&"true".to_string());
+        cursor.set_value(0);
+        progress.set_value("");
+    }
+    let mut batchsize: i64 = paramint64(context, "BATCHSIZE");
+    if batchsize <= 0 || batchsize > REBUILDBATCH {
+        batchsize = REBUILDBATCH;
+    }
+
+    let mut rebuilt: RebuiltCounters = if progress.value() == "" {
+        RebuiltCounters::default()
+    } else {
+        serde_json::from_str(&progress.value()).expect("failed to get rebuilt counters")
+    };
+    let betteraddresses = market.get_string_array(&"betteraddresses".to_string());
+    let storedbets = market.get_map(&"bets".to_string());
+    let bets: i64 = betteraddresses.length() as i64;
+    let start: i64 = cursor.value().min(bets);
+    let end: i64 = bets.min(start.saturating_add(batchsize));
+    for index in start..end {
+        let bet: Bet = decodebet(&storedbets.get_bytes(&betteraddresses.get_string(index as i32).value()).value()).expect("failed to get bet");
+        addrebuiltbet(&mut rebuilt, &bet);
+    }
+    cursor.set_value(end);
+    context.results().get_int64(&"SCANNED".to_string()).set_value(end);
+    context.results().get_int64(&"BETS".to_string()).set_value(bets);
+    if end < bets {
+        progress.set_value(&serde_json::to_string(&rebuilt).expect("failed to make json of rebuilt counters"));
+        let log:String = "rebuilding counters, scanned ".to_string() + &end.to_string() + &" of ".to_string() + &bets.to_string() + &" bets".to_string(); context.log(&log);
+        return;
+    }
+
+    // the stored counters are compared by the names of the rebuilt ones and of those the market already keeps
+    let rebuiltvalues: BTreeMap<String, i64> = countervalues(&rebuilt);
+    let mut counters: Vec<String> = rebuiltvalues.keys().cloned().collect();
+    counters.push(COUNTER_TOTALBETAMOUNT.to_string());
+    counters.push(COUNTER_NUMBETTORS.to_string());
+    let storedbetvalues = market.get_string_array(&"betvalues".to_string());
+    for i in 0..storedbetvalues.length() {
+        counters.push(totalcounter(&storedbetvalues.get_string(i).value()));
+    }
+    let campaigns = market.get_string_array(&"campaigns".to_string());
+    for i in 0..campaigns.length() {
+        counters.push(campaignbetscounter(&campaigns.get_string(i).value()));
+        counters.push(campaignvolumecounter(&campaigns.get_string(i).value()));
+    }
+    let stored: BTreeMap<String, i64> = counters.iter().map(|counter| (counter.clone(), storedcounter(&market, counter).value())).collect();
+    let mut diffs: Vec<CounterDiff> = counterdiffs(&stored, &rebuiltvalues);
+    // the pool only equals the bets until payouts start
+    if marketparam(context) != "" && market.get_string(&"marketclosed".to_string()).value() != "true" {
+        let storedpool: i64 = market.get_int64(&"pool".to_string()).value();
+        if storedpool != rebuilt.totalbetamount {
+            diffs.push(CounterDiff { counter: "pool".to_string(), stored: storedpool, rebuilt: rebuilt.totalbetamount });
+            creditmarketpool(context, rebuilt.totalbetamount - storedpool);
+        }
+    }
+
+    settotals(context, &BetTotals {
+        totalbetamount: rebuilt.totalbetamount as i32,
+        betvalue_totalbetamount: rebuilt.betvalue_totalbetamount.iter().map(|(betvalue, total)| (betvalue.clone(), *total as i32)).collect(),
+    });
+    // the pool was credited above, every other counter takes its rebuilt value
+    for diff in diffs.iter().filter(|diff| diff.counter != "pool") {
+        storedcounter(&market, &diff.counter).set_value(diff.rebuilt);
+    }
+    let counted: Vec<String> = (0..campaigns.length()).map(|i| campaigns.get_string(i).value()).collect();
+    for campaign in rebuilt.campaignbets.keys().filter(|campaign| !counted.contains(campaign)) {
+        campaigns.get_string(campaigns.length()).set_value(campaign);
+    }
+    let marketid: String = market.get_string(&"marketid".to_string()).value();
+    for diff in &diffs {
+        context.event(&events::counterdiff(&marketid, &diff.counter, diff.stored, diff.rebuilt));
+    }
+    context.event(&events::countersrebuilt(&marketid, bets, diffs.len() as i64));
+    let diffjson: String = serde_json::to_string(&diffs).expect("failed to make json of counter differences");
+    context.results().get_string(&"DIFF".to_string()).set_value(&diffjson);
+    rebuilding.set_value(&"false".to_string());
+    progress.set_value("");
+    let log:String = "counters rebuilt from ".to_string() + &bets.to_string() + &" bets, differences: ".to_string() + &diffjson; context.log(&log);
+    appendadminlog(context, &("rebuildcounters|".to_string() + &bets.to_string() + &"|".to_string() + &diffs.len().to_string()));
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct CampaignStats {
+    campaign: String,
+    // number of accepted bets, a further bet of an account counts again
+    bets: i64,
+    // accepted IOTA
+    volume: i64,
+}
+
+// View of the number of bets and their volume per campaign of the market as json list CAMPAIGNS, in the order the campaigns
+// were first seen, with their totals as TOTALBETS and TOTALVOLUME. No addresses or client ids are returned.
+fn getcampaignstats(context: &ScViewContext) {
+    let market = viewmarketstate(context);
+    let campaigns = market.get_string_array(&"campaigns".to_string());
+    let mut stats: Vec<CampaignStats> = Vec::new();
+    for i in 0..campaigns.length() {
+        let campaign: String = campaigns.get_string(i).value();
+        stats.push(CampaignStats {
+            bets: market.get_map(&"campaignbets".to_string()).get_int64(&campaign).value(),
+            volume: market.get_map(&"campaignvolume".to_string()).get_int64(&campaign).value(),
+            campaign,
+        });
+    }
+    context.results().get_int64(&"TOTALBETS".to_string()).set_value(stats.iter().map(|stat| stat.bets).sum());
+    context.results().get_int64(&"TOTALVOLUME".to_string()).set_value(stats.iter().map(|stat| stat.volume).sum());
+    context.results().get_string(&"CAMPAIGNS".to_string()).set_value(&serde_json::to_string(&stats).expect("failed to make json of campaign stats"));
+}
+
+// Volatility hint for the number of bets in the current window: low, medium from ACTIVITYMEDIUM bets, or high from ACTIVITYHIGH bets.
+fn volatilityhint(recentbets: i64, activitymedium: i64, activityhigh: i64) -> &'static str {
+    if recentbets >= activityhigh {
+        "high"
+    } else if recentbets >= activitymedium {
+        "medium"
+    } else {
+        "low"
+    }
+}
+
+// View for statistics of the bets placed so far:
+// NUMBETTORS is the number of betting accounts, TOTALBETAMOUNT the overall amount of bets,
+// and LARGESTSHAREBPS the largest share of the pool held by a single betting account in basis points.
+// The recent activity is counted per window of ACTIVITYWINDOW seconds: the first bet after a window has passed starts a new window,
+// so that a bet only updates two counters and the start of the window.
+fn getstats(context: &ScViewContext) {
+    let containerofbets: ContainerOfBets = viewcontainerofbets(context);
+
+    let totalbetamount: i64 = containerofbets.map.values().map(|bet| bet.betamount as i64).sum();
+    let largestbetamount: i64 = containerofbets.map.values().map(|bet| bet.betamount as i64).max().unwrap_or(0);
+    let largestsharebps: i64 = if totalbetamount > 0 { largestbetamount * 10000 / totalbetamount } else { 0 };
+
+    context.results().get_int64(&"NUMBETTORS".to_string()).set_value(containerofbets.map.len() as i64);
+    context.results().get_int64(&"TOTALBETAMOUNT".to_string()).set_value(totalbetamount);
+    context.results().get_int64(&"LARGESTSHAREBPS".to_string()).set_value(largestsharebps);
+    oddshidden(context);
+
+    // activity of the current window, none once the window has passed without a bet
+    let market = viewmarketstate(context);
+    let mut recentbets: i64 = market.get_int64(&"recentbets".to_string()).value();
+    let mut recentvolume: i64 = market.get_int64(&"recentvolume".to_string()).value();
+    if context.timestamp() - market.get_int64(&"activitywindowstart".to_string()).value() >= market.get_int64(&"activitywindow".to_string()).value() {
+        recentbets = 0;
+        recentvolume = 0;
+    }
+    let hint: &str = volatilityhint(recentbets, market.get_int64(&"activitymedium".to_string()).value(), market.get_int64(&"activityhigh".to_string()).value());
+    context.results().get_int64(&"RECENTBETS".to_string()).set_value(recentbets);
+    context.results().get_int64(&"RECENTVOLUME".to_string()).set_value(recentvolume);
+    context.results().get_string(&"VOLATILITYHINT".to_string()).set_value(hint);
+}
+
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct Checkpoint {
+    // timestamp of the transaction taking the checkpoint
+    timestamp: i64,
+    // hash of the bets at that time, see betsroot
+    betsroot: String,
+    // tag of the canonical encoding the bets root was computed over, "" for checkpoints taken over the json of the bets
+    #[serde(default)]
+    encoding: String,
+}
+
+// The bets root is the blake2b hash of the canonical encoding of the bets sorted by address, as exported by exportmarket.
+// Checkpoints taken before the canonical encoding have their roots over the json of the bets, which an encoding of "" reproduces.
+fn betsroot(utility: &ScUtility, bets: &[ExportedBet], encoding: &str) -> String {
+    if encoding == "" {
+        let betsjson: String = serde_json::to_string(bets).expect("failed to make json of bets");
+        return utility.hash_blake2b(betsjson.as_bytes()).to_string();
+    }
+    utility.hash_blake2b(&canonicalbets(bets)).to_string()
+}
+
+// The Merkle root of the bets, built by the merkle module of the core crate with blake2b-256 as hash, in base58, "" without bets.
+// Unlike the bets root, a single bet can be proven to be included by getinclusionproof without revealing the others.
+fn merklebetsroot(utility: &ScUtility, containerofbets: &ContainerOfBets) -> String {
+    let hash = |bytes: &[u8]| utility.hash_blake2b(bytes).to_bytes().to_vec();
+    utility.base58_encode(&merkleroot(&merkleleaves(containerofbets), &hash))
+}
+
+// step of an inclusion proof as returned by getinclusionproof, with the sibling hash in base58
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct InclusionStep {
+    sibling: String,
+    left: bool,
+}
+
+// View of the proof that the bet of ADDRESS is included in the bets: LEAF is the canonical encoding of its address, value and amount in base58,
+// PATH the json list of the sibling hashes from the leaf up to ROOT, the Merkle root of all bets, and whether each sibling is the left one.
+// MERKLEROOT is the root published by the event market.bettingclosed, "" while betting is open.
+fn getinclusionproof(context: &ScViewContext) {
+    let address: String = context.params().get_string(&"ADDRESS".to_string()).value();
+    let containerofbets: ContainerOfBets = viewcontainerofbets(context);
+    let leaves: Vec<Vec<u8>> = merkleleaves(&containerofbets);
+    let index: Option<usize> = sortedbetteraddresses(&containerofbets).iter().position(|betteraddress| *betteraddress == address);
+    context.require(index.is_some(), "no bet of ADDRESS found");
+    let utility: ScUtility = context.utility();
+    let hash = |bytes: &[u8]| utility.hash_blake2b(bytes).to_bytes().to_vec();
+    let path: Vec<InclusionStep> = merkleproof(&leaves, index.unwrap(), &hash).iter()
+        .map(|step| InclusionStep { sibling: utility.base58_encode(&step.sibling), left: step.left })
+        .collect();
+    context.results().get_string(&"LEAF".to_string()).set_value(&utility.base58_encode(&leaves[index.unwrap()]));
+    context.results().get_string(&"PATH".to_string()).set_value(&serde_json::to_string(&path).expect("failed to make json of inclusion proof"));
+    context.results().get_string(&"ROOT".to_string()).set_value(&merklebetsroot(&utility, &containerofbets));
+    context.results().get_string(&"MERKLEROOT".to_string()).set_value(&viewmarketstate(context).get_string(&"merkleroot".to_string()).value());
+}
+
+// View of the bet of the account with the wallet address given by the parameter ADDRESS as recorded by the contract, so that a betting account
+// or a block explorer can verify it: FOUND is "true" with BETAMOUNT and BETISFORVALUE, or "false" if there is no bet of ADDRESS.
+// A view has no caller, so a betting account passes its own address.
+fn getmybet(context: &ScViewContext) {
+    let address = context.params().get_string(&"ADDRESS".to_string());
+    // require parameter exists
+    context.require(address.exists(), "address parameter not found");
+
+    let containerofbets: ContainerOfBets = viewcontainerofbets(context);
+    match containerofbets.map.get(&address.value()) {
+        Some(bet) => {
+            context.results().get_string(&"FOUND".to_string()).set_value(&"true".to_string());
+            context.results().get_int64(&"BETAMOUNT".to_string()).set_value(bet.betamount as i64);
+            context.results().get_string(&"BETISFORVALUE".to_string()).set_value(&bet.betisforvalue);
+        }
+        None => {
+            context.results().get_string(&"FOUND".to_string()).set_value(&"false".to_string());
+        }
+    }
+}
+
+// View of rough cost estimates for wallet integrators, passed as FUNCTION, "bet", "closemarket" or "payout", and N, the number of bets
+// stored in the market for bet and closemarket or of winners to pay for payout. COSTS is a json list of the estimate for the map layout
+// of the bets, which every market uses from its first bet on, and for the json layout it was migrated from, see costestimates of the core crate.
+fn estimatecost(context: &ScViewContext) {
+    let function: String = context.params().get_string(&"FUNCTION".to_string()).value();
+    let n: i64 = paramint64(context, "N");
+    context.require(n >= 0, "N must not be negative");
+    let estimates: Vec<CostEstimate> = costestimates(&function, n);
+    context.require(!estimates.is_empty(), &("no cost estimates for FUNCTION ".to_string() + &function + &", only for bet, closemarket and payout".to_string()));
+    context.results().get_string(&"COSTS".to_string()).set_value(&serde_json::to_string(&estimates).expect("failed to make json of cost estimates"));
+}
+
+// View of everything a client needs to render the market: STATUS is "uninitialized" before initmarket, "announced" during its ANNOUNCEDELAY,
+// "open" while bets are accepted, "ended" once the end time for bets has passed or bets are frozen, and "closed" after closemarket.
+// OPENSAT is the time from which bets are accepted and OPENSIN the seconds left until then, 0 once open. BETENDDATETIME is the end time,
+// CLOSED and VOIDED the flags of the market, WINNINGVALUE the value closemarket settled on, "" before closing and for markets closed
+// before it was recorded, TOTALBETAMOUNT the pool of the bets and NUMBETTORS the number of betting accounts.
+// EXTENSIONBUDGET and LATESTBETEND are returned like by precheck, CONFIGBYTES and MAXCONFIGBYTES the bytes of the metadata used and allowed.
+fn getmarketinfo(context: &ScViewContext) {
+    let market = viewmarketstate(context);
+    let closed: bool = market.get_string(&"marketclosed".to_string()).value() == "true";
+    let betenddatetime: i64 = market.get_int64(&"betenddatetime".to_string()).value();
+    let opensat: i64 = market.get_int64(&"opensat".to_string()).value();
+    let status: &str = marketstatus(&market, context.timestamp());
+
+    // the counters kept at bet time, for markets stored before them the bets are added up
+    let (totalbetamount, numbettors): (i64, i64) = if market.get_string(&"runningtotals".to_string()).value() == "true" && market.get_int64(&COUNTER_NUMBETTORS.to_string()).exists() {
+        (market.get_int64(&"totalbetamount".to_string()).value(), market.get_int64(&COUNTER_NUMBETTORS.to_string()).value())
+    } else {
+        let containerofbets: ContainerOfBets = viewcontainerofbets(context);
+        (containerofbets.map.values().map(|bet| bet.betamount as i64).sum(), containerofbets.map.len() as i64)
+    };
+    context.results().get_string(&"STATUS".to_string()).set_value(status);
+    context.results().get_int64(&"OPENSAT".to_string()).set_value(opensat);
+    context.results().get_int64(&"OPENSIN".to_string()).set_value((opensat - context.timestamp()).max(0));
+    context.results().get_int64(&"BETENDDATETIME".to_string()).set_value(betenddatetime);
+    context.results().get_string(&"CLOSED".to_string()).set_value(&closed.to_string());
+    context.results().get_string(&"VOIDED".to_string()).set_value(&(market.get_string(&"marketvoided".to_string()).value() == "true").to_string());
+    context.results().get_string(&"WINNINGVALUE".to_string()).set_value(&market.get_string(&"winningvalue".to_string()).value());
+    context.results().get_int64(&"TOTALBETAMOUNT".to_string()).set_value(totalbetamount);
+    context.results().get_int64(&"NUMBETTORS".to_string()).set_value(numbettors);
+    context.results().get_int64(&"CONFIGBYTES".to_string()).set_value(market.get_int64(&"configbytes".to_string()).value());
+    context.results().get_int64(&"MAXCONFIGBYTES".to_string()).set_value(market.get_int64(&"maxconfigbytes".to_string()).value());
+    setextensionresults(context, &extensionbudget(&market));
+}
+
+#[derive(Serialize)]
+struct GroupMember {
+    marketid: String,
+    // status of the market, see marketstatus, or "archived"
+    status: String,
+    // outcome declared by closegroup, "" before
+    outcome: String,
+    winningvalue: String,
+    voided: bool,
+    // whether every entitlement of the closed market was paid out or processed
+    paidout: bool,
+}
+
+// View of the group GROUPID of linked markets: MEMBERS is a json list of the members in the order they were linked, with their
+// status, the outcome declared for them, the winning value they were closed with and whether they were voided and paid out.
+// DECLAREDAT is when closegroup declared the outcomes, 0 before, and CLOSED the number of members closed since.
+fn getgroup(context: &ScViewContext) {
+    let groupid: String = context.params().get_string(&"GROUPID".to_string()).value();
+    let group = context.state().get_map(&("group|".to_string() + &groupid));
+    let groupmarketids = group.get_string_array(&"members".to_string());
+    context.require(groupmarketids.length() > 0, "GROUPID is not a group of linked markets");
+    let mut members: Vec<GroupMember> = Vec::new();
+    for i in 0..groupmarketids.length() {
+        let marketid: String = groupmarketids.get_string(i).value();
+        let market = context.state().get_map(&marketstatekey(&marketid));
+        let archived: bool = context.state().get_map(&("tombstone|".to_string() + &marketid)).get_string(&"digest".to_string()).exists();
+        let status: &str = if archived { "archived" } else { marketstatus(&market, context.timestamp()) };
+        members.push(GroupMember {
+            marketid,
+            status: status.to_string(),
+            outcome: market.get_string(&"groupoutcome".to_string()).value(),
+            winningvalue: market.get_string(&"winningvalue".to_string()).value(),
+            voided: market.get_string(&"marketvoided".to_string()).value() == "true",
+            paidout: archived || status == "closed" && payoutscomplete(&market),
+        });
+    }
+    context.results().get_string(&"MEMBERS".to_string()).set_value(&serde_json::to_string(&members).expect("failed to make json of members"));
+    context.results().get_int64(&"DECLAREDAT".to_string()).set_value(group.get_int64(&"declaredat".to_string()).value());
+    context.results().get_int64(&"CLOSED".to_string()).set_value(members.iter().filter(|member| member.status == "closed").count() as i64);
+}
+
+#[derive(Serialize)]
+struct ScheduleRecord {
+    scheduleid: String,
+    templateid: String,
+    // the market created last, or the template before the first rollover
+    current: String,
+    // number of the current market in the series, 0 for the template
+    instance: i64,
+    betenddatetime: i64,
+    interval: i64,
+    remaining: i64,
+    // intervals skipped because the markets before were settled too late
+    skipped: i64,
+    // "active", "completed" or "cancelled"
+    status: String,
+}
+
+// View of the schedules: SCHEDULES is a json list of the active schedules in the order they were registered, with the market each
+// created last, or of the schedule SCHEDULEID alone if it is given, also once it is completed or cancelled.
+fn getschedules(context: &ScViewContext) {
+    let scheduleid: String = context.params().get_string(&"SCHEDULEID".to_string()).value();
+    let scheduleids: Vec<String> = if scheduleid != "" {
+        vec![scheduleid]
+    } else {
+        let active = context.state().get_string_array(&"scheduleids".to_string());
+        (0..active.length()).map(|i| active.get_string(i).value()).collect()
+    };
+    let mut schedules: Vec<ScheduleRecord> = Vec::new();
+    for scheduleid in scheduleids {
+        let schedule = context.state().get_map(&("schedule|".to_string() + &scheduleid));
+        context.require(schedule.get_string(&"templateid".to_string()).exists(), "SCHEDULEID is not a schedule");
+        schedules.push(ScheduleRecord {
+            templateid: schedule.get_string(&"templateid".to_string()).value(),
+            current: schedule.get_string(&"current".to_string()).value(),
+            instance: schedule.get_int64(&"instance".to_string()).value(),
+            betenddatetime: schedule.get_int64(&"betenddatetime".to_string()).value(),
+            interval: schedule.get_int64(&"interval".to_string()).value(),
+            remaining: schedule.get_int64(&"remaining".to_string()).value(),
+            skipped: schedule.get_int64(&"skipped".to_string()).value(),
+            status: schedule.get_string(&"status".to_string()).value(),
+            scheduleid,
+        });
+    }
+    context.results().get_string(&"SCHEDULES".to_string()).set_value(&serde_json::to_string(&schedules).expect("failed to make json of schedules"));
+}
+
+// View of the stake of the wallet address ADDRESS across the group GROUPID: STAKED is the sum of its bets in all members, GROUPMAXSTAKE
+// the cap of the group, 0 for none, and REMAINING what it can still stake, -1 without a cap.
+fn getgroupusage(context: &ScViewContext) {
+    let groupid: String = context.params().get_string(&"GROUPID".to_string()).value();
+    let group = context.state().get_map(&("group|".to_string() + &groupid));
+    context.require(group.get_string_array(&"members".to_string()).length() > 0, "GROUPID is not a group of linked markets");
+    let betteraddress: String = context.params().get_string(&"ADDRESS".to_string()).value();
+    context.require(betteraddress != "", "address parameter not found");
+    let staked: i64 = group.get_map(&"stakes".to_string()).get_int64(&betteraddress).value();
+    let groupmaxstake: i64 = group.get_int64(&"maxstake".to_string()).value();
+    context.results().get_int64(&"STAKED".to_string()).set_value(staked);
+    context.results().get_int64(&"GROUPMAXSTAKE".to_string()).set_value(groupmaxstake);
+    context.results().get_int64(&"REMAINING".to_string()).set_value(groupallowance(staked, groupmaxstake).unwrap_or(-1));
+}
+
+// View of the sealed bets of the wallet address ADDRESS in a commit-reveal market: COMMITS is the number of its unrevealed commitments
+// and COMMITESCROW the IOTA they hold, against the limits MAXCOMMITS and MAXCOMMITESCROW, 0 for no limit, and REVEALDEADLINE is the time
+// until which they can be revealed.
+fn getcommitusage(context: &ScViewContext) {
+    let market = viewmarketstate(context);
+    let betteraddress: String = context.params().get_string(&"ADDRESS".to_string()).value();
+    context.require(betteraddress != "", "address parameter not found");
+    context.results().get_int64(&"COMMITS".to_string()).set_value(market.get_map(&"commitcounts".to_string()).get_int64(&betteraddress).value());
+    context.results().get_int64(&"COMMITESCROW".to_string()).set_value(market.get_map(&"commitescrows".to_string()).get_int64(&betteraddress).value());
+    context.results().get_int64(&"MAXCOMMITS".to_string()).set_value(market.get_int64(&"maxcommits".to_string()).value());
+    context.results().get_int64(&"MAXCOMMITESCROW".to_string()).set_value(market.get_int64(&"maxcommitescrow".to_string()).value());
+    context.results().get_int64(&"REVEALDEADLINE".to_string()).set_value(revealdeadline(market.get_int64(&"betenddatetime".to_string()).value(), market.get_int64(&"revealwindow".to_string()).value()));
+}
+
+// status of the market at currtime: "uninitialized", "announced" before it opens, "open", "ended" once bets are no longer accepted, or "closed"
+fn marketstatus(market: &ScImmutableMap, currtime: i64) -> &'static str {
+    let betenddatetime: i64 = market.get_int64(&"betenddatetime".to_string()).value();
+    let ended: bool = market.get_string(&"betsfrozen".to_string()).value() == "true" || (betenddatetime != 0 && currtime > betenddatetime);
+    if !(market.get_string(&"initialized".to_string()).value() == "true" || market.get_string(&"marketclosed".to_string()).exists()) {
+        "uninitialized"
+    } else if market.get_string(&"marketclosed".to_string()).value() == "true" {
+        "closed"
+    } else if ended {
+        "ended"
+    } else if currtime < market.get_int64(&"opensat".to_string()).value() {
+        "announced"
+    } else {
+        "open"
+    }
+}
+
+// View of the history of the bet of the account with the wallet address given by the parameter ADDRESS: HISTORY is a json list
+// of the bets added up to its bet amount in the order they were placed, e.g. [{"placedat":1622563200,"betamount":100}].
+// A bet stored before the history was recorded is listed as one entry at the time of its last bet, and an address without bet as [].
+fn getbethistory(context: &ScViewContext) {
+    let address = context.params().get_string(&"ADDRESS".to_string());
+    // require parameter exists
+    context.require(address.exists(), "address parameter not found");
+
+    let containerofbets: ContainerOfBets = viewcontainerofbets(context);
+    let history: Vec<BetEntry> = match containerofbets.map.get(&address.value()) {
+        Some(bet) if bet.history.is_empty() => vec![BetEntry { placedat: bet.placedat, betamount: bet.betamount as i64, campaign: "".to_string() }],
+        // like the attribution of the bet, the campaigns of its entries are not returned
+        Some(bet) => bet.history.iter().map(|entry| BetEntry { campaign: "".to_string(), ..entry.clone() }).collect(),
+        None => Vec::new(),
+    };
+    context.results().get_string(&"HISTORY".to_string()).set_value(&serde_json::to_string(&history).expect("failed to make json of bet history"));
+}
+
+fn readcheckpoints(checkpointsjson: &str) -> Vec<Checkpoint> {
+    if checkpointsjson == "" {
+        return Vec::new();
+    }
+    serde_json::from_str(checkpointsjson).expect("failed to get checkpoints")
+}
+
+// Function to take a checkpoint of the bets, to be called by the contract owner.
+// The bets root and the timestamp are appended to the list of checkpoints, so that exportmarket can later reproduce the bets at that time.
+fn checkpoint(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "checkpoint") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "checkpoint"), "Not authorised to take a checkpoint - only contract creator or a marketadmin is allowed to do this.");
+
+    let containerofbets: ContainerOfBets = readcontainerofbets(context);
+    let timestamp: i64 = context.timestamp();
+    let root: String = betsroot(&context.utility(), &exportedbets(&containerofbets, None), TAG_BETS);
+
+    let mut checkpoints: Vec<Checkpoint> = readcheckpoints(&marketstate(context).get_string(&"checkpointsjson".to_string()).value());
+    let index: usize = checkpoints.len();
+    checkpoints.push(Checkpoint {
+        timestamp,
+        betsroot: root.clone(),
+        encoding: TAG_BETS.to_string(),
+    });
+    marketstate(context).get_string(&"checkpointsjson".to_string()).set_value(&serde_json::to_string(&checkpoints).expect("failed to make json of checkpoints"));
+
+    let log:String = "checkpoint ".to_string() + &index.to_string() + &" taken with bets root: ".to_string() + &root;  context.log(&log);
+    context.event(&events::marketcheckpoint(index, timestamp, &root));
+}
+
+// View listing all checkpoints as json in the result CHECKPOINTS, the index of a checkpoint is its position in the list.
+fn getcheckpoints(context: &ScViewContext) {
+    let checkpointsjson: String = viewmarketstate(context).get_string(&"checkpointsjson".to_string()).value();
+    let checkpoints: Vec<Checkpoint> = readcheckpoints(&checkpointsjson);
+    context.results().get_string(&"CHECKPOINTS".to_string()).set_value(&serde_json::to_string(&checkpoints).expect("failed to make json of checkpoints"));
+}
+
+// View exporting the market: the end time for bets, whether the market is closed, the bets as json in the result BETS and their BETSROOT.
+// With the optional parameter ASOF, the index of a checkpoint, only the bets placed until the checkpoint are exported with their stakes
+// at that time, whose bets root then equals the one stored with the checkpoint unless an account added to its bet later.
+fn exportmarket(context: &ScViewContext) {
+    let containerofbets: ContainerOfBets = viewcontainerofbets(context);
+
+    let mut asof: Option<i64> = None;
+    let mut encoding: String = TAG_BETS.to_string();
+    if context.params().get_string(&"ASOF".to_string()).exists() {
+        let index: i64 = paramint64(context, "ASOF");
+        let checkpoints: Vec<Checkpoint> = readcheckpoints(&viewmarketstate(context).get_string(&"checkpointsjson".to_string()).value());
+        context.require(index >= 0 && (index as usize) < checkpoints.len(), "checkpoint not found");
+        asof = Some(checkpoints[index as usize].timestamp);
+        encoding = checkpoints[index as usize].encoding.clone();
+    }
+
+    let bets: Vec<ExportedBet> = exportedbets(&containerofbets, asof);
+    context.results().get_int64(&"BETENDDATETIME".to_string()).set_value(viewmarketstate(context).get_int64(&"betenddatetime".to_string()).value());
+    context.results().get_string(&"MARKETCLOSED".to_string()).set_value(&viewmarketstate(context).get_string(&"marketclosed".to_string()).value());
+    context.results().get_string(&"REFUSALNOTICE".to_string()).set_value(&viewmarketstate(context).get_string(&"refusalnotice".to_string()).value());
+    context.results().get_string(&"CONFIG".to_string()).set_value(&viewmarketstate(context).get_string(&"configjson".to_string()).value());
+    context.results().get_string(&"STORAGEFORMAT".to_string()).set_value(storageformat(&viewmarketstate(context).get_string(&"storageformat".to_string()).value()));
+    context.results().get_int64(&"CONFIGBYTES".to_string()).set_value(viewmarketstate(context).get_int64(&"configbytes".to_string()).value());
+    context.results().get_int64(&"MAXCONFIGBYTES".to_string()).set_value(viewmarketstate(context).get_int64(&"maxconfigbytes".to_string()).value());
+    context.results().get_string(&"BETSROOT".to_string()).set_value(&betsroot(&context.utility(), &bets, &encoding));
+    context.results().get_string(&"BETSROOTENCODING".to_string()).set_value(&encoding);
+    context.results().get_string(&"BETS".to_string()).set_value(&serde_json::to_string(&bets).expect("failed to make json of bets"));
+}
+
+
+// View for reconciliation of the IOTA held by the contract with the IOTA it transferred:
+// BALANCE is the contract's balance, TRANSFERSCOUNT and TRANSFERREDTOTAL count the payouts and refunds,
+// TRANSFERVIOLATIONS is the number of transfers rejected or capped by guardedtransfer,
+// ENTITLEMENTS the number of recorded winnings and refunds, of which the first PAYOUTCURSOR were processed by payouts.
+// POOLS, FEES, ESCROWS, DEPOSITS, STRAYS and RETURNS are the ledger buckets, PENDINGRETURNS the number of returns not yet paid from RETURNS, TRANSFERSBLOCKED states whether the ledger's invariant was violated,
+// and LASTRECONCILED is the timestamp of the last reconcile.
+fn getreconciliation(context: &ScViewContext) {
+    context.results().get_int64(&"BALANCE".to_string()).set_value(context.balances().balance(&ScColor::IOTA));
+    context.results().get_int64(&"TRANSFERSCOUNT".to_string()).set_value(context.state().get_int64(&"transferscount".to_string()).value());
+    context.results().get_int64(&"TRANSFERREDTOTAL".to_string()).set_value(context.state().get_int64(&"transferredtotal".to_string()).value());
+    context.results().get_int64(&"TRANSFERVIOLATIONS".to_string()).set_value(context.state().get_string_array(&"transferviolations".to_string()).length() as i64);
+    for bucket in BUCKETS.iter() {
+        context.results().get_int64(&bucket.to_uppercase()).set_value(context.state().get_int64(&bucketkey(bucket)).value());
+    }
+    context.results().get_int64(&"PENDINGRETURNS".to_string()).set_value(pendingreturnsleft(context.state()));
+    context.results().get_string(&"TRANSFERSBLOCKED".to_string()).set_value(&(context.state().get_string(&"transfersblocked".to_string()).value() == "true").to_string());
+    context.results().get_int64(&"LASTRECONCILED".to_string()).set_value(context.state().get_int64(&"lastreconciled".to_string()).value());
+    context.results().get_int64(&"ENTITLEMENTS".to_string()).set_value(viewmarketstate(context).get_string_array(&"entitlements".to_string()).length() as i64);
+    context.results().get_int64(&"PAYOUTCURSOR".to_string()).set_value(viewmarketstate(context).get_int64(&"payoutcursor".to_string()).value());
+}
+
+
+// entry of the reverse index from the hash of an external reference to the market using it, "" if the reference is free
+fn externalrefindex(context: &ScFuncContext, externalref: &str) -> ScMutableString {
+    let externalrefhash: String = context.utility().hash_blake2b(externalref.as_bytes()).to_string();
+    context.state().get_map(&"externalrefs".to_string()).get_string(&externalrefhash)
+}
+
+// Free the external reference of the market, so that another market can be initialized with it.
+fn releaseexternalref(context: &ScFuncContext, externalref: &str) {
+    if externalref != "" {
+        externalrefindex(context, externalref).set_value("");
+    }
+}
+
+// View to look up the market for a reference outside of the chain given by the parameter EXTERNALREF, as set on initialization.
+// FOUND is "true" or "false", MARKETID is the id of the market if found.
+fn findmarket(context: &ScViewContext) {
+    let externalref = context.params().get_string(&"EXTERNALREF".to_string());
+    // require parameter exists
+    context.require(externalref.exists(), "external reference parameter not found");
+
+    let externalrefhash: String = context.utility().hash_blake2b(externalref.value().as_bytes()).to_string();
+    let indexedmarketid: String = context.state().get_map(&"externalrefs".to_string()).get_string(&externalrefhash).value();
+    let marketid: Option<&str> = foundmarket(&indexedmarketid);
+    context.results().get_string(&"FOUND".to_string()).set_value(&marketid.is_some().to_string());
+    if let Some(marketid) = marketid {
+        context.results().get_string(&"MARKETID".to_string()).set_value(marketid);
+    }
+}
+
+
+// View whether the account with the wallet address given by the parameter ADDRESS acknowledged the current terms of the market:
+// ACKNOWLEDGED is "true" or "false", ACKNOWLEDGEDAT the timestamp of the acknowledgement. Without terms, nothing needs to be acknowledged.
+fn gettermsack(context: &ScViewContext) {
+    let address = context.params().get_string(&"ADDRESS".to_string());
+    // require parameter exists
+    context.require(address.exists(), "address parameter not found");
+
+    let termshash: String = viewmarketstate(context).get_string(&"termshash".to_string()).value();
+    let recordedack: String = viewmarketstate(context).get_map(&"termsacks".to_string()).get_string(&address.value()).value();
+    let acknowledged: Option<i64> = acknowledgedat(&termshash, &recordedack);
+    context.results().get_string(&"ACKNOWLEDGED".to_string()).set_value(&acknowledged.is_some().to_string());
+    if termshash != "" && acknowledged.is_some() {
+        context.results().get_int64(&"ACKNOWLEDGEDAT".to_string()).set_value(acknowledged.unwrap());
+    }
+}
+
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct Challenge {
+    // wallet address of the account creating the challenge
+    challenger: String,
+    // wallet address of the account challenged
+    opponent: String,
+    // value predicted by the challenger, e.g. "yes"
+    challengervalue: String,
+    // value predicted by the opponent on accepting, which has to differ from challengervalue
+    opponentvalue: String,
+    // stake in IOTA of each side
+    stake: i64,
+    // end time as UNIX timestamp, after which the challenge can be settled
+    betenddatetime: i64,
+    // UNIX timestamp until which the opponent can accept
+    acceptdeadline: i64,
+    // "pending", "accepted", "reclaimed" or "settled"
+    status: String,
+    // flag of the codec the addresses were encoded with, "" for challenges stored before it was recorded
+    #[serde(default)]
+    addressencoding: String,
+    // ids of the requests sending the stakes of the challenger and the opponent
+    #[serde(default)]
+    challengerrequestid: String,
+    #[serde(default)]
+    opponentrequestid: String,
+    // true if the stakes are held in the escrows, false for challenges created while they were held in the pools
+    #[serde(default)]
+    escrowed: bool,
+}
+
+// The stakes of a challenge are held in the escrows, apart from the pools of the markets, and paid out from there.
+fn challengebucket(challenge: &Challenge) -> &'static str {
+    if challenge.escrowed { BUCKET_ESCROWS } else { BUCKET_POOLS }
+}
+
+fn getchallenge(context: &ScFuncContext, challengeid: i64) -> Challenge {
+    let challenges = context.state().get_string_array(&"challenges".to_string());
+    context.require(challengeid >= 0 && challengeid < challenges.length() as i64, "challenge not found");
+    serde_json::from_str(&challenges.get_string(challengeid as i32).value()).expect("failed to get challenge")
+}
+
+fn storechallenge(context: &ScFuncContext, challengeid: i64, challenge: &Challenge) {
+    context.state().get_string_array(&"challenges".to_string()).get_string(challengeid as i32).set_value(&serde_json::to_string(challenge).expect("failed to make json of challenge"));
+}
+
+// Function to challenge another account to a head-to-head bet, callable by anyone.
+// The caller sends the stake and names the OPPONENT by wallet address, the own predicted BETVALUE and the end time BETENDUTC.
+// The opponent can accept with acceptchallenge until the end time, or within ACCEPTWINDOW seconds if given.
+// If the challenge was not accepted in time, the challenger gets the stake back with reclaimchallenge.
+// Returns the CHALLENGEID, which identifies the challenge for the other functions.
+fn createchallenge(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "createchallenge") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(marketparam(context) == "", "challenges do not belong to a market with MARKETID");
+    context.require(!addressblocked(context, &AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address())), ERR_ADDRESS_BLOCKED);
+
+    let stake: i64 = context.incoming().balance(&ScColor::IOTA);
+    context.require(stake > 0, "a stake has to be sent with the challenge");
+    // both stakes are settled as bets, whose amounts are counted in i32
+    context.require(stake <= i32::MAX as i64 / 2, &("the stake of a challenge is limited to ".to_string() + &(i32::MAX / 2).to_string() + &" IOTA".to_string()));
+    let opponentparam: String = context.params().get_string(&"OPPONENT".to_string()).value();
+    context.require(opponentparam != "", "opponent parameter not found");
+    // stored in the encoding of the challenger, so that the opponent is recognised when accepting
+    let decoded: Result<ScAddress, String> = AddressCodec::CURRENT.decode(&context.utility(), &opponentparam);
+    context.require(decoded.is_ok(), decoded.as_ref().err().map_or("", |error| error.as_str()));
+    let opponent: String = AddressCodec::CURRENT.encode(&context.utility(), &decoded.unwrap());
+    let challenger: String = AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address());
+    context.require(opponent != challenger, "an account cannot challenge itself");
+    let challengervalue: String = context.params().get_string(&"BETVALUE".to_string()).value();
+    context.require(challengervalue != "", "bet value parameter not found");
+
+    let parsed: Result<i64, String> = parsebetendutc(&context.params().get_string(&"BETENDUTC".to_string()).value())
+        .and_then(|betenddatetime| boundedbetend(betenddatetime, deployedat(context)));
+    context.require(parsed.is_ok(), parsed.as_ref().err().map_or("", |error| error.as_str()));
+    let betenddatetime: i64 = parsed.unwrap();
+    context.require(betenddatetime > context.timestamp(), "BETENDUTC must be in the future");
+    let acceptwindow: i64 = paramint64(context, "ACCEPTWINDOW");
+    context.require(acceptwindow >= 0, "ACCEPTWINDOW must not be negative");
+    let acceptdeadline: i64 = if acceptwindow > 0 { betenddatetime.min(context.timestamp().saturating_add(acceptwindow)) } else { betenddatetime };
+
+    let challenge = Challenge {
+        challenger: challenger.clone(),
+        opponent: opponent.clone(),
+        challengervalue,
+        opponentvalue: "".to_string(),
+        stake,
+        betenddatetime,
+        acceptdeadline,
+        status: "pending".to_string(),
+        addressencoding: AddressCodec::CURRENT.flag().to_string(),
+        challengerrequestid: context.request_id().to_string(),
+        opponentrequestid: "".to_string(),
+        escrowed: true,
+    };
+    let challengeid: i64 = context.state().get_string_array(&"challenges".to_string()).length() as i64;
+    storechallenge(context, challengeid, &challenge);
+    let challengeids = context.state().get_map(&"challengesbyopponent".to_string()).get_string_array(&opponent);
+    challengeids.get_string(challengeids.length()).set_value(&challengeid.to_string());
+    movebucket(context, BUCKET_STRAYS, challengebucket(&challenge), stake);
+
+    let log:String = "CHALLENGE ".to_string() + &challengeid.to_string() + &" created by ".to_string() + &challenger + &" for ".to_string() + &opponent + &" with a stake of ".to_string() + &stake.to_string() + &" IOTA".to_string();  context.log(&log);
+    context.results().get_int64(&"CHALLENGEID".to_string()).set_value(challengeid);
+    context.event(&events::challengecreated(challengeid, &challenger, &opponent, stake));
+}
+
+// Function for the opponent to accept the challenge CHALLENGEID by sending the same stake and the own predicted BETVALUE,
+// which has to differ from the value predicted by the challenger.
+fn acceptchallenge(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "acceptchallenge") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(marketparam(context) == "", "challenges do not belong to a market with MARKETID");
+    let acceptor: String = AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address());
+    context.require(!addressblocked(context, &acceptor), ERR_ADDRESS_BLOCKED);
+
+    let challengeid: i64 = paramint64(context, "CHALLENGEID");
+    let mut challenge: Challenge = getchallenge(context, challengeid);
+    context.require(challenge.status == "pending", "the challenge is not pending");
+    context.require(acceptor == challenge.opponent, "only the opponent can accept the challenge");
+    context.require(context.timestamp() <= challenge.acceptdeadline, "the time to accept the challenge has passed");
+    context.require(context.incoming().balance(&ScColor::IOTA) == challenge.stake, "the stake has to equal the stake of the challenger");
+    let opponentvalue: String = context.params().get_string(&"BETVALUE".to_string()).value();
+    context.require(opponentvalue != "" && opponentvalue != challenge.challengervalue, "the bet value has to differ from the value of the challenger");
+
+    challenge.opponentvalue = opponentvalue;
+    challenge.opponentrequestid = context.request_id().to_string();
+    challenge.status = "accepted".to_string();
+    storechallenge(context, challengeid, &challenge);
+    movebucket(context, BUCKET_STRAYS, challengebucket(&challenge), challenge.stake);
+
+    let log:String = "CHALLENGE ".to_string() + &challengeid.to_string() + &" accepted by ".to_string() + &challenge.opponent;  context.log(&log);
+    context.event(&events::challengeaccepted(challengeid));
+}
+
+// Function for the challenger to get the stake back, if the challenge CHALLENGEID was not accepted in time.
+fn reclaimchallenge(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "reclaimchallenge") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(marketparam(context) == "", "challenges do not belong to a market with MARKETID");
+
+    let challengeid: i64 = paramint64(context, "CHALLENGEID");
+    let mut challenge: Challenge = getchallenge(context, challengeid);
+    context.require(challenge.status == "pending", "the challenge is not pending");
+    context.require(context.timestamp() > challenge.acceptdeadline, "the challenge can still be accepted");
+
+    challenge.status = "reclaimed".to_string();
+    storechallenge(context, challengeid, &challenge);
+    recordentitlementfrom(context, challengebucket(&challenge), &challenge.challenger, &challenge.addressencoding, &challenge.challengerrequestid, challenge.stake, "refund");
+    processpayouts(context, "", DEFAULTPAYOUTBATCH);
+    context.event(&events::challengereclaimed(challengeid));
+}
+
+// Function to settle the accepted challenge CHALLENGEID after its end time with the winning BETVALUE, to be called by the contract owner.
+// Like a market with exactly two bets, the side predicting the winning value receives both stakes; if neither side predicted it, both are refunded.
+fn closechallenge(context: &ScFuncContext) {
+    // only the contract owner or a resolver should be able to do this
+    context.require(authorisedfor(context, "closechallenge"), "You are not authorised to close the challenge - only contract creator or a resolver is allowed to close challenges.");
+    openledger(context);
+    let _recorder = match idempotent(context, "closechallenge") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(marketparam(context) == "", "challenges do not belong to a market with MARKETID");
+
+    let challengeid: i64 = paramint64(context, "CHALLENGEID");
+    let mut challenge: Challenge = getchallenge(context, challengeid);
+    context.require(challenge.status == "accepted", "the challenge is not accepted");
+    context.require(context.timestamp() > challenge.betenddatetime, "closing the challenge can be only done after its end time has passed");
+    let betvaluewinning: String = context.params().get_string(&"BETVALUE".to_string()).value();
+    context.require(betvaluewinning != "", "winning bet value parameter not found");
+
+    // settle both stakes as bets, exactly like a market
+    let mut containerofbets = ContainerOfBets {
+        map : HashMap::new()
+    };
+    for (betteraddress, betvalue, requestid) in [(&challenge.challenger, &challenge.challengervalue, &challenge.challengerrequestid),
+        (&challenge.opponent, &challenge.opponentvalue, &challenge.opponentrequestid)].iter() {
+        containerofbets.map.insert(betteraddress.to_string(), Bet {
+            betamount: i32::try_from(challenge.stake).expect("stake of the challenge exceeds the bet amount"),
+            betisforvalue: betvalue.to_string(),
+            placedat: 0,
+            attachedamount: challenge.stake,
+            addressencoding: challenge.addressencoding.clone(),
+            requestid: requestid.to_string(),
+            clientid: "".to_string(),
+            campaign: "".to_string(),
+            history: Vec::new(),
+        });
+    }
+    let settlement: SettlementResult = settle(&containerofbets, &betvaluewinning);
+    if settlement.winamounts.is_empty() {
+        recordentitlementfrom(context, challengebucket(&challenge), &challenge.challenger, &challenge.addressencoding, &challenge.challengerrequestid, challenge.stake, "refund");
+        recordentitlementfrom(context, challengebucket(&challenge), &challenge.opponent, &challenge.addressencoding, &challenge.opponentrequestid, challenge.stake, "refund");
+    } else {
+        for betteraddress in sortedbetteraddresses(&containerofbets) {
+            if let Some(winamount) = settlement.winamounts.get(&betteraddress) {
+                recordentitlementfrom(context, challengebucket(&challenge), &betteraddress, &challenge.addressencoding, &containerofbets.map[&betteraddress].requestid, *winamount, "win");
+            }
+        }
+    }
+
+    challenge.status = "settled".to_string();
+    storechallenge(context, challengeid, &challenge);
+    processpayouts(context, "", DEFAULTPAYOUTBATCH);
+
+    let log:String = "CHALLENGE ".to_string() + &challengeid.to_string() + &" settled, the winning value is: \"".to_string() + &betvaluewinning + &"\"".to_string();  context.log(&log);
+    appendadminlog(context, &("closechallenge|".to_string() + &challengeid.to_string() + &"|".to_string() + &betvaluewinning));
+    context.event(&events::challengesettled(challengeid, &betvaluewinning));
+}
+
+// View listing the pending challenges addressed to the wallet address given by the parameter ADDRESS,
+// as json object in the result CHALLENGES mapping the challenge ids to the challenges.
+fn getchallenges(context: &ScViewContext) {
+    let address = context.params().get_string(&"ADDRESS".to_string());
+    // require parameter exists
+    context.require(address.exists(), "address parameter not found");
+
+    let challenges = context.state().get_string_array(&"challenges".to_string());
+    let challengeids = context.state().get_map(&"challengesbyopponent".to_string()).get_string_array(&address.value());
+    let mut pendingchallenges: BTreeMap<i64, Challenge> = BTreeMap::new();
+    for i in 0..challengeids.length() {
+        let challengeid: i64 = challengeids.get_string(i).value().parse::<i64>().unwrap();
+        let challenge: Challenge = serde_json::from_str(&challenges.get_string(challengeid as i32).value()).expect("failed to get challenge");
+        if challenge.status == "pending" && context.timestamp() <= challenge.acceptdeadline {
+            pendingchallenges.insert(challengeid, challenge);
+        }
+    }
+    context.results().get_string(&"CHALLENGES".to_string()).set_value(&serde_json::to_string(&pendingchallenges).expect("failed to make json of challenges"));
+}
+
+
+fn addressblocked(context: &ScFuncContext, address: &str) -> bool {
+    context.state().get_map(&"blockedaddresses".to_string()).get_string(address).value() == "true"
+}
+
+// Remember the entitlement at index of the market marketid, which a payout skipped because its account is blocked.
+// The entitlements of an account are queued in the contract's state across markets, as "marketid|index", until unblockaddress releases them.
+fn freezeentitlement(context: &ScFuncContext, marketid: &str, index: i32, entitlement: &mut Entitlement) {
+    entitlement.frozen = true;
+    let frozen = context.state().get_map(&"frozenentitlements".to_string()).get_string_array(&entitlement.address);
+    frozen.get_string(frozen.length()).set_value(&frozenentry(marketid, index));
+    let log:String = "entitlement ".to_string() + &index.to_string() + &" of blocked account ".to_string() + &entitlement.address + &" is frozen".to_string(); context.log(&log);
+}
+
+// Queue up to MAXFROZENRELEASE frozen entitlements of the unblocked address for retry in the payouts of their markets,
+// continuing after the ones released by a previous call. Returns the number of released entitlements and of those still frozen.
+fn releasefrozenentitlements(context: &ScFuncContext, address: &str) -> (i64, i64) {
+    let frozen = context.state().get_map(&"frozenentitlements".to_string()).get_string_array(address);
+    let head = context.state().get_map(&"frozenentitlementshead".to_string()).get_int64(address);
+    let released: std::ops::Range<i64> = releaserange(head.value(), frozen.length() as i64);
+    for i in released.clone() {
+        let entry: String = frozen.get_string(i as i32).value();
+        let (marketid, index) = parsefrozenentry(&entry).expect("failed to get frozen entitlement");
+        let market: ScMutableMap = marketstateof(context, marketid);
+        let entitlementjson = market.get_string_array(&"entitlements".to_string()).get_string(index);
+        let mut entitlement: Entitlement = serde_json::from_str(&entitlementjson.value()).expect("failed to get entitlement");
+        entitlement.frozen = false;
+        // an entitlement that failed before is still queued in payoutretries, and one flagged for manual intervention is claimed
+        if retryonrelease(entitlement.paid, entitlement.manual, entitlement.attempts) {
+            let retries = market.get_string_array(&"payoutretries".to_string());
+            retries.get_string(retries.length()).set_value(&index.to_string());
+        }
+        entitlementjson.set_value(&serde_json::to_string(&entitlement).expect("failed to make json of entitlement"));
+        frozen.get_string(i as i32).set_value("");
+    }
+    head.set_value(released.end);
+    (released.end - released.start, frozen.length() as i64 - released.end)
+}
+
+// Function to block the wallet address given by the parameter ADDRESS from betting and claiming, to be called by the contract owner.
+// Blocking never confiscates: the entitlements of a blocked account stay recorded but frozen, and are queued for retry once it is unblocked.
+fn blockaddress(context: &ScFuncContext) {
+    setaddressblocked(context, true);
+}
+
+// Function to unblock the wallet address given by the parameter ADDRESS, to be called by the contract owner.
+// The entitlements frozen while it was blocked are queued for retry, up to MAXFROZENRELEASE per call: RELEASED is the number queued
+// by the call and FROZENREMAINING the number still frozen, which calling unblockaddress again releases.
+fn unblockaddress(context: &ScFuncContext) {
+    setaddressblocked(context, false);
+}
+
+fn setaddressblocked(context: &ScFuncContext, blocked: bool) {
+    // only contract owner should be able to do this
+    let creator = context.contract_creator();
+    let caller = context.caller();
+    context.require(creator == caller, "Not authorised to block addresses - only contract creator is allowed to do this.");
+    openledger(context);
+    let function: &str = if blocked { "blockaddress" } else { "unblockaddress" };
+    let _recorder = match idempotent(context, function) {
+        Some(recorder) => recorder,
+        None => return,
+    };
+
+    let address: String = context.params().get_string(&"ADDRESS".to_string()).value();
+    context.require(address != "", "address parameter not found");
+    context.state().get_map(&"blockedaddresses".to_string()).get_string(&address).set_value(&blocked.to_string());
+    if !blocked {
+        let (released, remaining) = releasefrozenentitlements(context, &address);
+        context.results().get_int64(&"RELEASED".to_string()).set_value(released);
+        context.results().get_int64(&"FROZENREMAINING".to_string()).set_value(remaining);
+    }
+
+    let log:String = function.to_string() + &": ".to_string() + &address;  context.log(&log);
+    appendadminlog(context, &(function.to_string() + &"|".to_string() + &address));
+}
+
+// View whether the wallet address given by the parameter ADDRESS is blocked: BLOCKED is "true" or "false",
+// FROZEN the amount of IOTA of its unpaid entitlements, which are frozen while it is blocked, and FROZENQUEUED the number of
+// entitlements of any market skipped by payouts, which unblockaddress still has to queue for retry.
+fn isblocked(context: &ScViewContext) {
+    let address = context.params().get_string(&"ADDRESS".to_string());
+    // require parameter exists
+    context.require(address.exists(), "address parameter not found");
+
+    let blocked: bool = context.state().get_map(&"blockedaddresses".to_string()).get_string(&address.value()).value() == "true";
+    let entitlements = viewmarketstate(context).get_string_array(&"entitlements".to_string());
+    let indices = viewmarketstate(context).get_map(&"entitlementsbyaddress".to_string()).get_string_array(&address.value());
+    let mut unpaid: i64 = 0;
+    for i in 0..indices.length() {
+        let index: i32 = indices.get_string(i).value().parse::<i32>().unwrap();
+        let entitlement: Entitlement = serde_json::from_str(&entitlements.get_string(index).value()).expect("failed to get entitlement");
+        unpaid = unpaid + outstandingamount(&entitlement);
+    }
+    context.results().get_string(&"BLOCKED".to_string()).set_value(&blocked.to_string());
+    context.results().get_int64(&"FROZEN".to_string()).set_value(if blocked { unpaid } else { 0 });
+    let frozen: i64 = context.state().get_map(&"frozenentitlements".to_string()).get_string_array(&address.value()).length() as i64;
+    let head: i64 = context.state().get_map(&"frozenentitlementshead".to_string()).get_int64(&address.value()).value();
+    context.results().get_int64(&"FROZENQUEUED".to_string()).set_value(frozen - head);
+}
+
+
+// View describing the events emitted by the contract: EVENTSCHEMA is a json list with the name, the current version
+// and the ordered field names of every event kind.
+fn geteventschema(context: &ScViewContext) {
+    context.results().get_string(&"EVENTSCHEMA".to_string()).set_value(&serde_json::to_string(&events::SCHEMAS[..]).expect("failed to make json of event schemas"));
+}
+
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct BetException {
+    // amount of IOTA a single bet may be accepted with beyond the maximum share of the pool
+    amount: i64,
+    // UNIX timestamp after which the exception cannot be used anymore
+    expires: i64,
+    // true once a bet used the exception
+    used: bool,
+}
+
+// Function to grant the betting account with the wallet address ADDRESS a one-time exception from the maximum share of the pool,
+// to be called by the contract owner. Its next bet limited by the share is accepted with up to AMOUNT IOTA instead,
+// if placed until the UNIX timestamp EXPIRES. Granting again replaces an unused exception.
+fn grantexception(context: &ScFuncContext) {
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "grantexception"), "Not authorised to grant exceptions - only contract creator or a marketadmin is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "grantexception") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requirenotimelock(context, "grantexception");
+
+    applyexception(context, &context.params().get_string(&"ADDRESS".to_string()).value(), paramint64(context, "AMOUNT"), paramint64(context, "EXPIRES"));
+}
+
+// Grant an exception, directly by grantexception or by an executed admin action.
+fn applyexception(context: &ScFuncContext, address: &str, amount: i64, expires: i64) {
+    context.require(address != "", "address parameter not found");
+    context.require(amount > 0, "AMOUNT must be positive");
+    context.require(expires > context.timestamp(), "EXPIRES must be in the future");
+
+    let exception = BetException {
+        amount,
+        expires,
+        used: false,
+    };
+    let betexceptions = marketstate(context).get_map(&"betexceptions".to_string());
+    if !betexceptions.get_string(address).exists() {
+        let addresses = marketstate(context).get_string_array(&"betexceptionaddresses".to_string());
+        addresses.get_string(addresses.length()).set_value(address);
+    }
+    betexceptions.get_string(address).set_value(&serde_json::to_string(&exception).expect("failed to make json of exception"));
+
+    let log:String = "exception granted to ".to_string() + address + &" for bets up to (IOTA): ".to_string() + &amount.to_string();  context.log(&log);
+    appendadminlog(context, &("grantexception|".to_string() + address + &"|".to_string() + &amount.to_string() + &"|".to_string() + &expires.to_string()));
+}
+
+// Granted amount of the exception of a betting account, if it has an unused one that is not expired, 0 without exception.
+fn betexception(context: &ScFuncContext, betteraddress: &str, currtime: i64) -> i64 {
+    let exceptionjson: String = marketstate(context).get_map(&"betexceptions".to_string()).get_string(betteraddress).value();
+    if exceptionjson == "" {
+        return 0;
+    }
+    let exception: BetException = serde_json::from_str(&exceptionjson).expect("failed to get exception");
+    if exception.used || currtime > exception.expires {
+        return 0;
+    }
+    exception.amount
+}
+
+// Use up the exception of a betting account once a bet accepted by it is placed.
+fn usebetexception(context: &ScFuncContext, betteraddress: &str) {
+    let exceptionjson = marketstate(context).get_map(&"betexceptions".to_string()).get_string(betteraddress);
+    let mut exception: BetException = serde_json::from_str(&exceptionjson.value()).expect("failed to get exception");
+    exception.used = true;
+    exceptionjson.set_value(&serde_json::to_string(&exception).expect("failed to make json of exception"));
+    appendadminlog(context, &("useexception|".to_string() + betteraddress + &"|".to_string() + &exception.amount.to_string()));
+}
+
+// View listing the exceptions that can still be used: EXCEPTIONS is a json map from the wallet address to the granted amount and expiry.
+fn getexceptions(context: &ScViewContext) {
+    let betexceptions = viewmarketstate(context).get_map(&"betexceptions".to_string());
+    let addresses = viewmarketstate(context).get_string_array(&"betexceptionaddresses".to_string());
+    let mut exceptions: BTreeMap<String, BetException> = BTreeMap::new();
+    for i in 0..addresses.length() {
+        let address: String = addresses.get_string(i).value();
+        let exception: BetException = serde_json::from_str(&betexceptions.get_string(&address).value()).expect("failed to get exception");
+        if !exception.used && context.timestamp() <= exception.expires {
+            exceptions.insert(address, exception);
+        }
+    }
+    context.results().get_string(&"EXCEPTIONS".to_string()).set_value(&serde_json::to_string(&exceptions).expect("failed to make json of exceptions"));
+}
+
+
+// If the market was initialized with a TIMELOCK, parameter changes of the contract owner cannot be applied directly,
+// but have to be announced by proposeaction.
+fn requirenotimelock(context: &ScFuncContext, function: &str) {
+    let timelock: i64 = marketstate(context).get_int64(&"timelock".to_string()).value();
+    context.require(timelock == 0, &(function.to_string() + &" is time locked - use proposeaction instead".to_string()));
+}
+
+// Check the payload of an action when it is proposed, so that a pending action can be executed unless its effect became invalid meanwhile.
+fn validateaction(utility: &ScUtility, kind: &str, payload: &str) -> Result<(), String> {
+    if kind == ACTION_SETALERTS {
+        return parsealertrules(payload).map(|_alertrules| ());
+    }
+    if kind == ACTION_GRANTEXCEPTION {
+        return parseexceptionpayload(payload).map(|_exception| ());
+    }
+    if kind == ACTION_SETSWEEPDEST {
+        return validatesweepdest(utility, payload);
+    }
+    Err("unknown action kind: ".to_string() + kind)
+}
+
+fn getaction(context: &ScFuncContext, actionid: i64) -> AdminAction {
+    let actionjson: String = context.state().get_string_array(&"adminactions".to_string()).get_string(actionid as i32).value();
+    context.require(actionjson != "", "action not found");
+    serde_json::from_str(&actionjson).expect("failed to get action")
+}
+
+fn storeaction(context: &ScFuncContext, actionid: i64, action: &AdminAction) {
+    context.state().get_string_array(&"adminactions".to_string()).get_string(actionid as i32).set_value(&serde_json::to_string(action).expect("failed to make json of action"));
+}
+
+// Function to announce a parameter change, to be called by the contract owner. The action of kind KIND, "setalerts", "grantexception" or "setsweepdest",
+// with the parameters PAYLOAD can be executed by anyone after the time lock has passed. Returns ACTIONID and EFFECTIVEAT.
+fn proposeaction(context: &ScFuncContext) {
+    // only the contract owner or the role of the kind of action should be able to do this
+    let kind: String = context.params().get_string(&"KIND".to_string()).value();
+    context.require(hasrole(context, actionrole(&kind)), "Not authorised to propose actions - only contract creator or the role of the action is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "proposeaction") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+
+    let payload: String = context.params().get_string(&"PAYLOAD".to_string()).value();
+    let validated: Result<(), String> = validateaction(&context.utility(), &kind, &payload);
+    context.require(validated.is_ok(), validated.as_ref().err().map_or("", |error| error.as_str()));
+
+    let action: AdminAction = proposedaction(&kind, &payload, context.timestamp(), marketstate(context).get_int64(&"timelock".to_string()).value(), &marketparam(context));
+    let effectiveat: i64 = action.effectiveat;
+    let actionid: i64 = context.state().get_string_array(&"adminactions".to_string()).length() as i64;
+    storeaction(context, actionid, &action);
+
+    context.results().get_int64(&"ACTIONID".to_string()).set_value(actionid);
+    context.results().get_int64(&"EFFECTIVEAT".to_string()).set_value(effectiveat);
+    let log:String = "action ".to_string() + &actionid.to_string() + &" (".to_string() + &kind + &") takes effect at ".to_string() + &effectiveat.to_string();  context.log(&log);
+    appendadminlog(context, &("proposeaction|".to_string() + &actionid.to_string() + &"|".to_string() + &kind + &"|".to_string() + &payload));
+    context.event(&events::actionproposed(actionid, &kind, &payload, effectiveat));
+}
+
+// Function to apply the pending action ACTIONID once its time lock has passed, which can be called by anyone.
+fn executeaction(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "executeaction") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let actionid: i64 = paramint64(context, "ACTIONID");
+    let mut action: AdminAction = getaction(context, actionid);
+    let executed: Result<(), String> = executeadminaction(&mut action, context.timestamp(), &marketparam(context));
+    context.require(executed.is_ok(), executed.as_ref().err().map_or("", |error| error.as_str()));
+    storeaction(context, actionid, &action);
+    if action.kind == ACTION_SETALERTS {
+        applyalerts(context, &action.payload);
+    } else if action.kind == ACTION_GRANTEXCEPTION {
+        let (address, amount, expires) = parseexceptionpayload(&action.payload).unwrap();
+        applyexception(context, &address, amount, expires);
+    } else if action.kind == ACTION_SETSWEEPDEST {
+        applysweepdest(context, &action.payload);
+    }
+
+    let log:String = "action ".to_string() + &actionid.to_string() + &" (".to_string() + &action.kind + &") is executed".to_string();  context.log(&log);
+    appendadminlog(context, &("executeaction|".to_string() + &actionid.to_string()));
+    context.event(&events::actionexecuted(actionid));
+}
+
+// Function to abort the pending action ACTIONID before it is executed, to be called by the contract owner.
+fn cancelaction(context: &ScFuncContext) {
+    // only the contract owner or the role of the kind of action should be able to do this
+    let actionid: i64 = paramint64(context, "ACTIONID");
+    let mut action: AdminAction = getaction(context, actionid);
+    context.require(hasrole(context, actionrole(&action.kind)), "Not authorised to cancel actions - only contract creator or the role of the action is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "cancelaction") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let cancelled: Result<(), String> = canceladminaction(&mut action);
+    context.require(cancelled.is_ok(), cancelled.as_ref().err().map_or("", |error| error.as_str()));
+    storeaction(context, actionid, &action);
+
+    let log:String = "action ".to_string() + &actionid.to_string() + &" is cancelled".to_string();  context.log(&log);
+    appendadminlog(context, &("cancelaction|".to_string() + &actionid.to_string()));
+    context.event(&events::actioncancelled(actionid));
+}
+
+// View listing all proposed actions: ACTIONS is a json list of their kind, payload, time of effect and status, indexed by ACTIONID.
+fn getactions(context: &ScViewContext) {
+    let adminactions = context.state().get_string_array(&"adminactions".to_string());
+    let mut actions: Vec<AdminAction> = Vec::new();
+    for i in 0..adminactions.length() {
+        actions.push(serde_json::from_str(&adminactions.get_string(i).value()).expect("failed to get action"));
+    }
+    context.results().get_string(&"ACTIONS".to_string()).set_value(&serde_json::to_string(&actions).expect("failed to make json of actions"));
+}
+
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct SettlementReceipt {
+    // timestamp of the transaction closing the market
+    closedat: i64,
+    // "settled", or "unanimous" if all bets were placed on the winning value and are paid back as refunds
+    #[serde(default)]
+    outcome: String,
+    betvaluewinning: String,
+    totalbetamount: i64,
+    totalbetamountforwinningvalue: i64,
+    realizedoddsbps: i64,
+    operatortake: i64,
+    returnper100: i64,
+    shortfallpolicy: String,
+    shortfall: i64,
+    fractionbps: i64,
+    // the split of the operator's take between the creator of the market and the treasury, see payfee
+    #[serde(default)]
+    creator: String,
+    #[serde(default)]
+    creatorshare: i64,
+    #[serde(default)]
+    treasuryshare: i64,
+    // every feed and the value it reported, if the market was resolved from its FEEDS, see resolvefromoracle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    feeds: Option<FeedReport>,
+    // the seed inputs and the derivation of the winner, if tallyandsettle selected it among tied votes, see tally
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tiebreak: Option<TieBreak>,
+}
+
+// Store the summary of the settlement as receipt of the market and emit it as event. The figures are taken from the settlement
+// as computed by settle, so the receipt cannot diverge from the recorded entitlements.
+fn storereceipt(context: &ScFuncContext, betvaluewinning: &str, settlement: &SettlementResult, split: &FeeSplit, shortfall: &Shortfall, tiebreak: Option<&TieBreak>) {
+    let receipt = SettlementReceipt {
+        closedat: context.timestamp(),
+        outcome: if settlement.unanimous { "unanimous".to_string() } else { "settled".to_string() },
+        betvaluewinning: betvaluewinning.to_string(),
+        totalbetamount: settlement.totalbetamount as i64,
+        totalbetamountforwinningvalue: settlement.totalbetamountforwinningvalue as i64,
+        realizedoddsbps: settlement.realizedoddsbps,
+        operatortake: settlement.operatortake,
+        returnper100: settlement.returnper100,
+        shortfallpolicy: shortfall.policy.clone(),
+        shortfall: shortfall.shortfall,
+        fractionbps: shortfall.fractionbps,
+        creator: marketstate(context).get_string(&"creator".to_string()).value(),
+        creatorshare: split.creator,
+        treasuryshare: split.treasury,
+        feeds: serde_json::from_str(&marketstate(context).get_string(&"feedreportjson".to_string()).value()).ok(),
+        tiebreak: tiebreak.cloned(),
+    };
+    marketstate(context).get_string(&"receiptjson".to_string()).set_value(&serde_json::to_string(&receipt).expect("failed to make json of receipt"));
+    recordtransition(context, &marketstate(context), "settled");
+
+    let mut log:String = "realized odds (basis points): ".to_string() + &settlement.realizedoddsbps.to_string() + &"; return for a bet of 100 IOTA: ".to_string() + &settlement.returnper100.to_string() + &" IOTA".to_string();  context.log(&log);
+    if settlement.unanimous {
+        log = "all bets were placed on the winning value, every bet is refunded".to_string();  context.log(&log);
+        context.event(&events::marketunanimous(&marketstate(context).get_string(&"marketid".to_string()).value(), betvaluewinning, settlement.totalbetamount as i64));
+    }
+    context.event(&events::marketclosed(&marketstate(context).get_string(&"marketid".to_string()).value(), betvaluewinning,
+        settlement.realizedoddsbps, settlement.operatortake, settlement.returnper100, settlement.totalbetamount as i64, settlement.winamounts.len()));
+}
+
+// View returning the receipt of the settled market as json in RECEIPT, "" while the market is not settled.
+fn getreceipt(context: &ScViewContext) {
+    context.results().get_string(&"RECEIPT".to_string()).set_value(&viewmarketstate(context).get_string(&"receiptjson".to_string()).value());
+}
+
+
+// Dead-man switch for resolving the market: with a HEARTBEATWINDOW, the contract owner has to call the contract within the window
+// after the end time for bets or after its last call, whichever is later. Otherwise the chain owner may close or cancel the market
+// for another window, after which anyone may cancel it, refunding all bets. A market without end time or window is resolved by its owner only.
+fn resolverturn(currtime: i64, betenddatetime: i64, resolverlastseen: i64, heartbeatwindow: i64) -> &'static str {
+    if heartbeatwindow <= 0 || betenddatetime == 0 {
+        return RESOLVER_CREATOR;
+    }
+    let silentsince: i64 = resolverlastseen.max(betenddatetime);
+    if currtime <= silentsince.saturating_add(heartbeatwindow) {
+        RESOLVER_CREATOR
+    } else if currtime <= silentsince.saturating_add(heartbeatwindow.saturating_mul(2)) {
+        RESOLVER_CHAINOWNER
+    } else {
+        RESOLVER_REFUND
+    }
+}
+
+fn currentresolver(context: &ScFuncContext) -> &'static str {
+    resolverturn(context.timestamp(),
+        marketstate(context).get_int64(&"betenddatetime".to_string()).value(),
+        marketstate(context).get_int64(&"resolverlastseen".to_string()).value(),
+        marketstate(context).get_int64(&"heartbeatwindow".to_string()).value())
+}
+
+// Function for the contract owner to show it is alive without any other effect, which delays the fallback of the dead-man switch.
+fn heartbeat(context: &ScFuncContext) {
+    // only the contract owner or a resolver should be able to do this
+    context.require(authorisedfor(context, "heartbeat"), "Not authorised to send heartbeats - only contract creator or a resolver is allowed to do this.");
+    // recorded by openledger, as for any call of the contract owner or a resolver
+    openledger(context);
+    let _recorder = match idempotent(context, "heartbeat") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let log:String = "heartbeat of the contract owner".to_string();  context.log(&log);
+}
+
+// Future times of the market as known now, for wallets to show with a bet: bets are accepted until betscloseat, the end time for bets
+// or the time bets were frozen, 0 without end time. With a HEARTBEATWINDOW, resolveby is when the fallback resolution of resolverturn
+// can start at the earliest if the contract owner has not resolved the market, 0 otherwise. Entitlements can be claimed without deadline.
+#[derive(PartialEq)]
+struct Timeline {
+    betscloseat: i64,
+    resolveby: i64,
+}
+
+fn markettimeline(market: &ScImmutableMap) -> Timeline {
+    let betenddatetime: i64 = market.get_int64(&"betenddatetime".to_string()).value();
+    let heartbeatwindow: i64 = market.get_int64(&"heartbeatwindow".to_string()).value();
+    let betscloseat: i64 = if market.get_string(&"betsfrozen".to_string()).value() == "true" {
+        market.get_int64(&"betsfrozenat".to_string()).value()
+    } else {
+        betenddatetime
+    };
+    let resolveby: i64 = if heartbeatwindow > 0 && betenddatetime != 0 { betenddatetime.saturating_add(heartbeatwindow) } else { 0 };
+    Timeline { betscloseat, resolveby }
+}
+
+// timestamp as ISO 8601 in UTC, e.g. "2021-09-08T23:00:00Z", "" for 0
+fn isotimestamp(timestamp: i64) -> String {
+    if timestamp == 0 {
+        return "".to_string();
+    }
+    NaiveDateTime::from_timestamp_opt(timestamp, 0).map_or("".to_string(), |naivedatetime| naivedatetime.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+}
+
+// return the timeline as BETSCLOSEAT and RESOLVEBY in unix timestamps, and with the suffix UTC as ISO strings
+fn settimelineresults(context: &ScFuncContext, timeline: &Timeline) {
+    context.results().get_int64(&"BETSCLOSEAT".to_string()).set_value(timeline.betscloseat);
+    context.results().get_string(&"BETSCLOSEATUTC".to_string()).set_value(&isotimestamp(timeline.betscloseat));
+    context.results().get_int64(&"RESOLVEBY".to_string()).set_value(timeline.resolveby);
+    context.results().get_string(&"RESOLVEBYUTC".to_string()).set_value(&isotimestamp(timeline.resolveby));
+}
+
+// emit market.timelinechanged if the timeline differs from the one before an admin action, so that wallets refresh what they show with bets
+fn emittimelinechange(context: &ScFuncContext, previoustimeline: &Timeline) {
+    let timeline: Timeline = markettimeline(&marketstate(context).immutable());
+    if timeline != *previoustimeline {
+        context.event(&events::markettimelinechanged(&marketstate(context).get_string(&"marketid".to_string()).value(), timeline.betscloseat, timeline.resolveby));
+    }
+}
+
+// View whose turn it is to resolve the market: TURN is "resolver" for the contract owner, "chainowner" or "refund" for anyone,
+// RESOLVERLASTSEEN the timestamp of the last call of the contract owner, and RESOLUTIONDEADLINE the time after which anyone can
+// call forcedrefund, 0 without RESOLUTIONGRACE.
+fn getresolver(context: &ScViewContext) {
+    let resolverlastseen: i64 = viewmarketstate(context).get_int64(&"resolverlastseen".to_string()).value();
+    let turn: &str = resolverturn(context.timestamp(),
+        viewmarketstate(context).get_int64(&"betenddatetime".to_string()).value(),
+        resolverlastseen,
+        viewmarketstate(context).get_int64(&"heartbeatwindow".to_string()).value());
+    context.results().get_string(&"TURN".to_string()).set_value(turn);
+    context.results().get_int64(&"RESOLVERLASTSEEN".to_string()).set_value(resolverlastseen);
+    context.results().get_int64(&"RESOLUTIONDEADLINE".to_string()).set_value(resolutiondeadline(&viewmarketstate(context)));
+}
+
+
+// The destination of residual funds must decode to an address, so that sweeps cannot be sent to a mistyped address.
+fn validatesweepdest(utility: &ScUtility, sweepdest: &str) -> Result<(), String> {
+    AddressCodec::CURRENT.decode(utility, sweepdest).map(|_address| ())
+}
+
+// Set the destination of residual funds, directly by setsweepdest or by an executed admin action.
+fn applysweepdest(context: &ScFuncContext, sweepdest: &str) {
+    let validated: Result<(), String> = validatesweepdest(&context.utility(), sweepdest);
+    context.require(validated.is_ok(), validated.as_ref().err().map_or("", |error| error.as_str()));
+    marketstate(context).get_string(&"sweepdest".to_string()).set_value(sweepdest);
+    let log:String = "residual funds are swept to ".to_string() + sweepdest;  context.log(&log);
+    appendadminlog(context, &("setsweepdest|".to_string() + sweepdest));
+}
+
+// Function to change the destination of residual funds to the wallet address SWEEPDEST, e.g. of a charity, to be called by the contract owner.
+fn setsweepdest(context: &ScFuncContext) {
+    // only the contract owner or a treasurer should be able to do this
+    context.require(authorisedfor(context, "setsweepdest"), "Not authorised to set the sweep destination - only contract creator or a treasurer is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "setsweepdest") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requirenotimelock(context, "setsweepdest");
+    applysweepdest(context, &context.params().get_string(&"SWEEPDEST".to_string()).value());
+}
+
+// Add to the running total of the entitlements not paid yet from the pools, see unpaidentitlements.
+fn addunpaid(market: &ScMutableMap, amount: i64) {
+    let unpaid = market.get_int64(&"unpaidentitlements".to_string());
+    unpaid.set_value(unpaid.value() + amount);
+}
+
+// Sum of the entitlements not paid yet from the pools, including the frozen ones of blocked accounts. It is kept as running total
+// from the close of the market on, see startrecording, while the entitlements of markets closed before it existed are added up.
+fn unpaidentitlements(context: &ScFuncContext) -> i64 {
+    if marketstate(context).get_string(&"unpaidrunning".to_string()).value() == "true" {
+        return marketstate(context).get_int64(&"unpaidentitlements".to_string()).value();
+    }
+    let entitlements = marketstate(context).get_string_array(&"entitlements".to_string());
+    let mut unpaid: i64 = 0;
+    for i in 0..entitlements.length() {
+        let entitlement: Entitlement = serde_json::from_str(&entitlements.get_string(i).value()).expect("failed to get entitlement");
+        if entitlementbucket(&entitlement) == BUCKET_POOLS {
+            unpaid = unpaid + outstandingamount(&entitlement);
+        }
+    }
+    unpaid
+}
+
+// Function to send the residual funds of a closed market to the sweep destination, which can be called by anyone once all payouts
+// were processed: the dust left in the pools by rounding down win amounts beyond the unpaid entitlements, and the strays, i.e. IOTA sent
+// to the contract without being accepted. Without a sweep destination set by initmarket or setsweepdest, the contract owner receives them.
+// Every sweep is emitted as event with its bucket.
+fn sweep(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "sweep") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    context.require(marketstate(context).get_string("marketclosed").value() == "true", "residual funds can only be swept after the market is closed");
+    let entitlementscount: i64 = marketstate(context).get_string_array(&"entitlements".to_string()).length() as i64;
+    context.require(recordingcomplete(&marketstate(context).immutable()) && marketstate(context).get_int64(&"payoutcursor".to_string()).value() >= entitlementscount,
+        "residual funds can only be swept after all entitlements were recorded and all payouts were processed");
+
+    let sweepdest: String = marketstate(context).get_string(&"sweepdest".to_string()).value();
+    let destination: ScAddress = if sweepdest == "" {
+        context.contract_creator().address()
+    } else {
+        AddressCodec::CURRENT.decode(&context.utility(), &sweepdest).unwrap()
+    };
+    let dust: i64 = marketpool(context) - unpaidentitlements(context);
+    let strays: i64 = context.state().get_int64(&bucketkey(BUCKET_STRAYS)).value();
+    for (bucket, amount) in [(BUCKET_POOLS, dust), (BUCKET_STRAYS, strays)].iter() {
+        if *amount > 0 {
+            // the dust of the pool is in the market's token, the strays are IOTA
+            let color: ScColor = if *bucket == BUCKET_POOLS { tokencolor(context, &marketstate(context)) } else { ScColor::IOTA };
+            let swept: i64 = guardedtransfertoken(context, &destination, &color, *amount, "sweep", bucket);
+            if *bucket == BUCKET_POOLS {
+                creditmarketpool(context, -swept);
+            }
+            let log:String = "swept ".to_string() + &swept.to_string() + &" ".to_string() + &colorname(&color) + &" of the ".to_string() + bucket + &" to ".to_string() + &destination.to_string();  context.log(&log);
+            context.event(&events::fundsswept(bucket, &destination.to_string(), swept));
+        }
+    }
+}
+
+
+// The history of the platform defaults, whose current version is the one markets created now take, see defaults in the core crate.
+fn defaultshistory(state: &ScImmutableMap) -> DefaultsHistory {
+    parsehistory(&state.get_string(&"defaultshistoryjson".to_string()).value())
+}
+
+// Take a snapshot of the current platform defaults for a market created now, stamped with their version.
+fn snapshotdefaults(context: &ScFuncContext, market: &ScMutableMap) {
+    let history: DefaultsHistory = defaultshistory(&context.state().immutable());
+    market.get_int64(&"creatorsharebps".to_string()).set_value(history.current().creatorsharebps);
+    market.get_int64(&"configceiling".to_string()).set_value(configceiling(history.current().maxconfigbytes));
+    market.get_int64(&"defaultsversion".to_string()).set_value(history.version);
+}
+
+// Add the settings a market took from the platform defaults to its effective configuration.
+fn platformsettings(configuration: &mut BTreeMap<String, EffectiveSetting>, market: &ScMutableMap) {
+    for key in PLATFORMSETTINGS.iter() {
+        configuration.insert(key.to_string(), platformsetting(market.get_int64(*key).value()));
+    }
+}
+
+// Function to set the platform defaults, to be called by the contract owner: CREATORSHAREBPS, from 0 to 10000, is the share of the fee
+// of a market credited to the creator of the market in basis points, and MAXCONFIGBYTES the ceiling of the bytes of the metadata of a market,
+// 0 for the built-in 8192, which markets can only lower, see metadata in the core crate. Every market takes a snapshot of the defaults when it is first
+// initialized, see initmarket, so markets already created keep the defaults they were created with. Every call bumps the version
+// of the defaults, which every market is stamped with, and appends it to the history of getdefaultshistory.
+// It returns the defaults as DEFAULTS and their version as DEFAULTSVERSION.
+fn setdefaults(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "setdefaults") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(authorisedfor(context, "setdefaults"), "Not authorised to set the defaults - only contract creator is allowed to do this.");
+    let defaults: PlatformDefaults = PlatformDefaults { creatorsharebps: paramint64(context, "CREATORSHAREBPS"), maxconfigbytes: paramint64(context, "MAXCONFIGBYTES") };
+    let validated: Result<(), String> = validatedefaults(&defaults);
+    context.require(validated.is_ok(), validated.as_ref().err().map_or("", |error| error.as_str()));
+    let mut history: DefaultsHistory = defaultshistory(&context.state().immutable());
+    let utility = context.utility();
+    let hash = |bytes: &[u8]| utility.hash_blake2b(bytes).to_bytes().to_vec();
+    let entry: DefaultsVersion = appenddefaults(&mut history, &defaults, context.timestamp(), &hash);
+    context.state().get_string(&"defaultshistoryjson".to_string()).set_value(&serde_json::to_string(&history).expect("failed to make json of defaults history"));
+    let defaultsjson: String = serde_json::to_string(&defaults).expect("failed to make json of defaults");
+    appendadminlog(context, &("setdefaults|".to_string() + &entry.version.to_string() + &"|".to_string() + &defaultsjson + &"|".to_string() + &entry.head));
+    let log:String = "platform defaults of new markets, version ".to_string() + &entry.version.to_string() + &": ".to_string() + &defaultsjson + &", chain head ".to_string() + &entry.head;  context.log(&log);
+    context.results().get_string(&"DEFAULTS".to_string()).set_value(&defaultsjson);
+    context.results().get_int64(&"DEFAULTSVERSION".to_string()).set_value(entry.version);
+}
+
+// View returning the platform defaults, which the next market initialized takes a snapshot of, as json in DEFAULTS, and their DEFAULTSVERSION.
+fn getdefaults(context: &ScViewContext) {
+    let history: DefaultsHistory = defaultshistory(&context.state());
+    context.results().get_string(&"DEFAULTS".to_string()).set_value(&serde_json::to_string(&history.current()).expect("failed to make json of defaults"));
+    context.results().get_int64(&"DEFAULTSVERSION".to_string()).set_value(history.version);
+}
+
+// View returning the retained versions of the platform defaults as json list in ENTRIES, each with its version, time, defaults
+// and the heads of the chain before and after it, the current DEFAULTSVERSION, the chain HEAD, and as FOLDEDHEAD the head before
+// the first retained version, into which the older versions are folded.
+fn getdefaultshistory(context: &ScViewContext) {
+    let history: DefaultsHistory = defaultshistory(&context.state());
+    context.results().get_string(&"ENTRIES".to_string()).set_value(&serde_json::to_string(&history.entries).expect("failed to make json of defaults history"));
+    context.results().get_int64(&"DEFAULTSVERSION".to_string()).set_value(history.version);
+    context.results().get_string(&"HEAD".to_string()).set_value(&history.head);
+    context.results().get_string(&"FOLDEDHEAD".to_string()).set_value(&history.foldedhead);
+}
+
+// Function for the creator of markets to claim the shares of the fees of its markets credited so far, see payfee, from the creatorfees bucket.
+// It returns the transferred IOTA as CLAIMED, what could not be transferred, e.g. while transfers are blocked, stays claimable.
+fn claimcreatorfees(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "claimcreatorfees") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let caller: ScAddress = context.caller().address();
+    let creator: String = AddressCodec::CURRENT.encode(&context.utility(), &caller);
+    context.require(!addressblocked(context, &creator), &(ERR_ADDRESS_BLOCKED.to_string() + &": the creator fees of ".to_string() + &creator + &" are frozen".to_string()));
+    let claimable = context.state().get_map(&"creatorfees".to_string()).get_int64(&creator);
+    context.require(claimable.value() > 0, &("no creator fees to claim for ".to_string() + &creator));
+    let claimed: i64 = guardedtransfertoken(context, &caller, &ScColor::IOTA, claimable.value(), "creatorfee", BUCKET_CREATORFEES);
+    claimable.set_value(claimable.value() - claimed);
+    let claimedtotal = context.state().get_map(&"creatorclaimed".to_string()).get_int64(&creator);
+    claimedtotal.set_value(claimedtotal.value() + claimed);
+    let log:String = "claimed creator fees of ".to_string() + &claimed.to_string() + &" IOTA for ".to_string() + &creator + &", still claimable: ".to_string() + &claimable.value().to_string() + &" IOTA".to_string();  context.log(&log);
+    context.results().get_int64(&"CLAIMED".to_string()).set_value(claimed);
+}
+
+// View returning the shares of fees claimable by the creator ADDRESS as CLAIMABLE and what it claimed so far as CLAIMED, both in IOTA.
+fn getcreatorfees(context: &ScViewContext) {
+    let creator: String = context.params().get_string(&"ADDRESS".to_string()).value();
+    context.require(creator != "", "ADDRESS is required");
+    context.results().get_int64(&"CLAIMABLE".to_string()).set_value(context.state().get_map(&"creatorfees".to_string()).get_int64(&creator).value());
+    context.results().get_int64(&"CLAIMED".to_string()).set_value(context.state().get_map(&"creatorclaimed".to_string()).get_int64(&creator).value());
+}
+
+// Function for the treasurer to withdraw from the fees bucket, which holds the treasury's shares of the fees and the bonds and deposits
+// forfeited to the contract: AMOUNT IOTA, or the whole bucket without AMOUNT, are transferred to the caller. The shares of the creators
+// of markets are held in the creatorfees bucket, which withdrawfees never touches. It returns the transferred IOTA as WITHDRAWN.
+fn withdrawfees(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "withdrawfees") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    // only the contract owner or a treasurer should be able to do this
+    context.require(authorisedfor(context, "withdrawfees"), "Not authorised to withdraw fees - only contract creator or a treasurer is allowed to do this.");
+    let fees: i64 = context.state().get_int64(&bucketkey(BUCKET_FEES)).value();
+    let mut amount: i64 = paramint64(context, "AMOUNT");
+    if context.params().get_string(&"AMOUNT".to_string()).value() == "" {
+        amount = fees;
+    }
+    context.require(amount > 0 && amount <= fees, &("AMOUNT must be positive and at most the fees bucket of ".to_string() + &fees.to_string() + &" IOTA".to_string()));
+    let caller: ScAddress = context.caller().address();
+    let withdrawn: i64 = guardedtransfertoken(context, &caller, &ScColor::IOTA, amount, "fees", BUCKET_FEES);
+    appendadminlog(context, &("withdrawfees|".to_string() + &withdrawn.to_string()));
+    let log:String = "withdrew ".to_string() + &withdrawn.to_string() + &" IOTA of the fees to ".to_string() + &caller.to_string();  context.log(&log);
+    context.results().get_int64(&"WITHDRAWN".to_string()).set_value(withdrawn);
+}
+
+
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct OutcomeScenario {
+    // total amount of bets placed on the value
+    totalbetamountforwinningvalue: i64,
+    // realized odds if the value wins, in basis points
+    realizedoddsbps: i64,
+    operatortake: i64,
+    // IOTA left over by rounding down the win amounts
+    dust: i64,
+}
+
+// would-be settlement summary for every value bets were placed on, computed by settle, or for a book by booksettlement with its locked payouts
+fn outcomescenarios(containerofbets: &ContainerOfBets, feepercent: i64, bookpayouts: Option<&HashMap<String, i64>>) -> BTreeMap<String, OutcomeScenario> {
+    let mut scenarios: BTreeMap<String, OutcomeScenario> = BTreeMap::new();
+    for bet in containerofbets.map.values() {
+        if scenarios.contains_key(&bet.betisforvalue) {
+            continue;
+        }
+        let settlement: SettlementResult = match bookpayouts {
+            Some(payouts) => booksettlement(containerofbets, payouts, &bet.betisforvalue),
+            None => settlewithfee(containerofbets, &bet.betisforvalue, feepercent),
+        };
+        scenarios.insert(bet.betisforvalue.clone(), OutcomeScenario {
+            totalbetamountforwinningvalue: settlement.totalbetamountforwinningvalue as i64,
+            realizedoddsbps: settlement.realizedoddsbps,
+            operatortake: settlement.operatortake,
+            dust: settlement.totalbetamount as i64 - settlement.operatortake - settlement.winamounts.values().sum::<i64>(),
+        });
+    }
+    scenarios
+}
+
+// Once betting has closed, the bets cannot change anymore: the first call after the end time for bets, a late bet or closemarket,
+// stores the scenarios of all outcomes and emits the event market.bettingclosed.
+fn snapshotscenarios(context: &ScFuncContext, containerofbets: &ContainerOfBets) {
+    let scenariosjson = marketstate(context).get_string(&"outcomescenariosjson".to_string());
+    if scenariosjson.value() != "" {
+        return;
+    }
+    let payouts: Option<HashMap<String, i64>> = if marketstate(context).get_string(&"book".to_string()).value() == "true" {
+        Some(bookpayouts(&marketstate(context).immutable(), containerofbets))
+    } else {
+        None
+    };
+    let scenarios: BTreeMap<String, OutcomeScenario> = outcomescenarios(containerofbets, marketstate(context).get_int64(&"feepercent".to_string()).value(), payouts.as_ref());
+    scenariosjson.set_value(&serde_json::to_string(&scenarios).expect("failed to make json of outcome scenarios"));
+    let merkleroot: String = merklebetsroot(&context.utility(), containerofbets);
+    marketstate(context).get_string(&"merkleroot".to_string()).set_value(&merkleroot);
+    context.event(&events::marketbettingclosed(&marketstate(context).get_string(&"marketid".to_string()).value(), scenarios.len() as i64, &merkleroot));
+}
+
+// Settling must come to the figures of the scenario of the winning value stored when betting closed. A difference means that
+// the bets or the settlement changed after betting closed, and fails the transaction. Without scenarios stored by an earlier call,
+// there is nothing to compare with.
+fn requirescenario(context: &ScFuncContext, betvaluewinning: &str, settlement: &SettlementResult) {
+    let scenariosjson: String = marketstate(context).get_string(&"outcomescenariosjson".to_string()).value();
+    if scenariosjson == "" {
+        return;
+    }
+    let scenarios: BTreeMap<String, OutcomeScenario> = serde_json::from_str(&scenariosjson).expect("failed to get outcome scenarios");
+    if let Some(scenario) = scenarios.get(betvaluewinning) {
+        let dust: i64 = settlement.totalbetamount as i64 - settlement.operatortake - settlement.winamounts.values().sum::<i64>();
+        context.require(scenario.totalbetamountforwinningvalue == settlement.totalbetamountforwinningvalue as i64
+            && scenario.realizedoddsbps == settlement.realizedoddsbps
+            && scenario.operatortake == settlement.operatortake
+            && scenario.dust == dust,
+            "the settlement differs from the scenario stored when betting closed");
+    }
+}
+
+// View returning the scenarios stored when betting closed: SCENARIOS is a json map from every value bets were placed on
+// to the total bet on it, the realized odds in basis points, the operator take and the dust, "" while betting is open.
+fn getoutcomescenarios(context: &ScViewContext) {
+    context.results().get_string(&"SCENARIOS".to_string()).set_value(&viewmarketstate(context).get_string(&"outcomescenariosjson".to_string()).value());
+}
+
+// the odds of the book of a market, empty before setodds quoted the first
+fn bookodds(market: &ScImmutableMap) -> BTreeMap<String, i64> {
+    let bookjson: String = market.get_string(&"bookjson".to_string()).value();
+    if bookjson == "" {
+        return BTreeMap::new();
+    }
+    serde_json::from_str(&bookjson).expect("failed to get odds of the book")
+}
+
+// what a book can pay out: the bankroll and the stakes of all bets
+fn bookfunds(market: &ScImmutableMap) -> i64 {
+    market.get_int64(&"bankroll".to_string()).value() + market.get_int64(&"totalbetamount".to_string()).value()
+}
+
+// the payouts locked by the bets of every address of a book
+fn bookpayouts(market: &ScImmutableMap, containerofbets: &ContainerOfBets) -> HashMap<String, i64> {
+    containerofbets.map.keys()
+        .map(|betteraddress| (betteraddress.to_string(), market.get_map(&"bookpayouts".to_string()).get_int64(betteraddress).value()))
+        .collect()
+}
+
+// Function for the contract owner or a market admin to quote the odds of a market with BOOK, passed as ODDS like "yes:19000,no:21000"
+// in basis points of decimal odds for every outcome, and to fund its bankroll with the IOTA sent with the call. The implied probabilities
+// of the odds have to sum to within OVERROUNDMINBPS and OVERROUNDMAXBPS of the market, which is returned as OVERROUNDBPS. The old and the
+// new odds are written to the admin log. The new odds apply to the bets accepted from now on, bets already placed keep their odds.
+fn setodds(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "setodds") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "setodds"), "Not authorised to set the odds - only contract creator or a marketadmin is allowed to do this.");
+    requiremarket(context);
+    let market: ScMutableMap = marketstate(context);
+    context.require(market.get_string(&"book".to_string()).value() == "true", "the market has no BOOK, its odds follow from the pool");
+    context.require(market.get_string(&"marketclosed".to_string()).value() == "false", "the prediction market was already closed");
+    let oddsparam: String = context.params().get_string(&"ODDS".to_string()).value();
+    let incoming: i64 = incomingtokens(context);
+    context.require(oddsparam != "" || incoming > 0, "ODDS or IOTA for the bankroll are required");
+
+    let mut log:String;
+    if oddsparam != "" {
+        let book: Result<BTreeMap<String, i64>, String> = parsebook(&oddsparam, &parseoutcomes(&market.get_string(&"outcomes".to_string()).value()));
+        context.require(book.is_ok(), book.as_ref().err().map_or("", |error| error.as_str()));
+        let book: BTreeMap<String, i64> = book.unwrap();
+        let overround: Result<i64, String> = validatebook(&book, market.get_int64(&"overroundminbps".to_string()).value(), market.get_int64(&"overroundmaxbps".to_string()).value());
+        context.require(overround.is_ok(), overround.as_ref().err().map_or("", |error| error.as_str()));
+        let previousjson: String = market.get_string(&"bookjson".to_string()).value();
+        let bookjson: String = serde_json::to_string(&book).expect("failed to make json of the odds of the book");
+        market.get_string(&"bookjson".to_string()).set_value(&bookjson);
+        appendadminlog(context, &("setodds|".to_string() + &previousjson + &"|".to_string() + &bookjson));
+        context.results().get_int64(&"OVERROUNDBPS".to_string()).set_value(overround.unwrap());
+        log = "the odds of the book are ".to_string() + &bookjson + &", they were ".to_string() + &previousjson;  context.log(&log);
+    }
+    if incoming > 0 {
+        movebucket(context, BUCKET_STRAYS, BUCKET_POOLS, incoming);
+        creditmarketpool(context, incoming);
+        let bankroll = market.get_int64(&"bankroll".to_string());
+        bankroll.set_value(bankroll.value() + incoming);
+        log = "the bankroll of the book is funded with ".to_string() + &incoming.to_string() + &" IOTA to ".to_string() + &bankroll.value().to_string() + &" IOTA".to_string();  context.log(&log);
+    }
+    context.results().get_int64(&"BANKROLL".to_string()).set_value(market.get_int64(&"bankroll".to_string()).value());
+}
+
+// View of the book of a market with BOOK: the current ODDS per outcome as json, their OVERROUNDBPS, the BANKROLL and as LIABILITY
+// for every outcome the payout locked by the bets on it and what the book could still pay on top of that, e.g.
+// {"no":{"payout":760,"remaining":6740},"yes":{"payout":3550,"remaining":3950}}.
+fn getbook(context: &ScViewContext) {
+    let market: ScImmutableMap = viewmarketstate(context);
+    let book: BTreeMap<String, i64> = bookodds(&market);
+    let payouts: BTreeMap<String, i64> = book.keys()
+        .map(|betvalue| (betvalue.to_string(), market.get_map(&"bookliability".to_string()).get_int64(betvalue).value()))
+        .collect();
+    let liabilities: BTreeMap<String, Liability> = bookliabilities(&book, &payouts, bookfunds(&market));
+    context.results().get_string(&"ODDS".to_string()).set_value(&serde_json::to_string(&book).expect("failed to make json of the odds of the book"));
+    context.results().get_int64(&"OVERROUNDBPS".to_string()).set_value(overroundbps(&book));
+    context.results().get_int64(&"BANKROLL".to_string()).set_value(market.get_int64(&"bankroll".to_string()).value());
+    context.results().get_string(&"LIABILITY".to_string()).set_value(&serde_json::to_string(&liabilities).expect("failed to make json of the liabilities of the book"));
+}
+
+
+// the parameters of a relayed bet, the memo in base58 as passed
+fn betintent<T: ScBaseContext>(context: &T) -> BetIntent {
+    BetIntent {
+        betvalue: context.params().get_string(&"BETVALUE".to_string()).value(),
+        amount: paramint64(context, "AMOUNT"),
+        notafter: paramint64(context, "NOTAFTER"),
+        ackterms: context.params().get_string(&"ACKTERMS".to_string()).value(),
+        clientid: context.params().get_string(&"CLIENTID".to_string()).value(),
+        campaign: context.params().get_string(&"CAMPAIGN".to_string()).value(),
+        memocipher: context.params().get_string(&"MEMOCIPHER".to_string()).value(),
+    }
+}
+
+// Relay a bet signed by another account, e.g. one without IOTA for the fees or without access to the chain.
+// The caller sends AMOUNT IOTA, or tokens of the market's TOKENCOLOR, for a bet on BETVALUE of the account of the ed25519 public key PUBKEY, which signed the bytes of
+// canonicalbetfor with its next NONCE as SIGNATURE. The bet is placed like by bet, including its NOTAFTER, ACKTERMS, CLIENTID, CAMPAIGN
+// and MEMOCIPHER, and paid out to the signing account. Refunds go to the caller. A wrong nonce or signature rejects the bet.
+fn betfor(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "betfor") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let caller: ScAddress = context.caller().address();
+    // the whole call is returned if it carries other tokens than the market is denominated in
+    if returnforeigntokens(context, &caller) {
+        return;
+    }
+    let incoming: i64 = incomingtokens(context);
+    let pubkey: Vec<u8> = context.params().get_bytes(&"PUBKEY".to_string()).value();
+    let signature: Vec<u8> = context.params().get_bytes(&"SIGNATURE".to_string()).value();
+    let intent: BetIntent = betintent(context);
+    let nonce: i64 = paramint64(context, "NONCE");
+    context.require(pubkey.len() == 32, "PUBKEY must be an ed25519 public key of 32 bytes");
+
+    let better: ScAddress = context.utility().ed25519_address_from_pubkey(&pubkey);
+    let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &better);
+    let expectednonce = context.state().get_map(&"nonces".to_string()).get_int64(&betteraddress);
+    if nonce != expectednonce.value() {
+        rejectbet(context, &caller, incoming, ERR_BAD_NONCE, &("bet was not accepted, the next nonce of the betting account is ".to_string() + &expectednonce.value().to_string()));
+        return;
+    }
+    let message: Vec<u8> = canonicalbetfor(&marketstate(context).get_string(&"marketid".to_string()).value(), &intent, nonce);
+    if intent.amount != incoming || !context.utility().ed25519_valid_signature(&message, &pubkey, &signature) {
+        rejectbet(context, &caller, incoming, ERR_BAD_SIGNATURE, "bet was not accepted, the signature does not match the bet and the IOTA sent");
+        return;
+    }
+    // the nonce is used up by the verified signature, whether or not the bet is accepted
+    expectednonce.set_value(nonce + 1);
+
+    let log:String = "bet is relayed for ".to_string() + &betteraddress + &" by ".to_string() + &caller.to_string();  context.log(&log);
+    placebet(context, &better, &caller, None);
+}
+
+// View of the next NONCE expected by betfor for the betting account ADDRESS, and the bytes it has to sign as MESSAGE for the
+// bet given by BETVALUE, AMOUNT, NOTAFTER, ACKTERMS, CLIENTID, CAMPAIGN and MEMOCIPHER.
+fn getnonce(context: &ScViewContext) {
+    let decoded: Result<ScAddress, String> = AddressCodec::CURRENT.decode(&context.utility(), &context.params().get_string(&"ADDRESS".to_string()).value());
+    context.require(decoded.is_ok(), decoded.as_ref().err().map_or("", |error| error.as_str()));
+    let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &decoded.unwrap());
+    let nonce: i64 = context.state().get_map(&"nonces".to_string()).get_int64(&betteraddress).value();
+    let message: Vec<u8> = canonicalbetfor(&viewmarketstate(context).get_string(&"marketid".to_string()).value(), &betintent(context), nonce);
+    context.results().get_int64(&"NONCE".to_string()).set_value(nonce);
+    context.results().get_bytes(&"MESSAGE".to_string()).set_value(&message);
+}
+
+// View to run before settling the market: returns the WARNINGS about suspicious combinations of settings found by initmarket,
+// and the EXTENSIONBUDGET in seconds the end time for bets can still be extended by together with the LATESTBETEND it can reach.
+fn precheck(context: &ScViewContext) {
+    context.results().get_string(&"WARNINGS".to_string()).set_value(&viewmarketstate(context).get_string(&"configwarningsjson".to_string()).value());
+    setextensionresults(context, &extensionbudget(&viewmarketstate(context)));
+}
+
+// Older builds relied on the value of every bet stored a second time in a map per betting account, state[address]["betvalue"],
+// which markets migrated by hand may have in one place but not the other. The bets in the json are authoritative.
+// Repair the bet of the betting account ADDRESS from both legacy locations, which only the contract owner can do before the market is closed:
+// a bet in the json is kept and its value written to the map, while a value only found in the map becomes a bet of AMOUNT IOTA,
+// which the contract owner takes e.g. from the records of the hand migration. The bet has to be backed by IOTA: by the part of the pool
+// of the market that backs no bet, and for the rest by IOTA sent with the call, of which the remainder is refunded.
+fn reconcilelegacy(context: &ScFuncContext) {
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "reconcilelegacy"), "Not authorised to reconcile legacy bets - only contract creator or a marketadmin is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "reconcilelegacy") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    context.require(marketstate(context).get_string(&"marketclosed".to_string()).value() != "true", "bets of a closed market cannot be reconciled");
+
+    let decoded: Result<ScAddress, String> = AddressCodec::CURRENT.decode(&context.utility(), &context.params().get_string(&"ADDRESS".to_string()).value());
+    context.require(decoded.is_ok(), decoded.as_ref().err().map_or("", |error| error.as_str()));
+    let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &decoded.unwrap());
+
+    let containerofbets: ContainerOfBets = readcontainerofbets(context);
+    let mapvalue = marketstate(context).get_map(&betteraddress).get_string(&"betvalue".to_string());
+    let legacyvalue: String = mapvalue.value();
+
+    let source: &str;
+    let betvalue: String;
+    let betamount: i64;
+    match containerofbets.map.get(&betteraddress) {
+        Some(bet) => {
+            source = "container";
+            betvalue = bet.betisforvalue.to_string();
+            betamount = bet.betamount as i64;
+        }
+        None => {
+            context.require(legacyvalue != "", "no bet of the betting account is found in either location");
+            let amount: i64 = paramint64(context, "AMOUNT");
+            context.require(amount > 0 && amount <= i32::MAX as i64, "AMOUNT of the bet must be given, only its value survived");
+            let missing: i64 = missingbacking(marketpool(context), bettotals(&containerofbets).totalbetamount as i64, amount);
+            let incoming: i64 = context.incoming().balance(&ScColor::IOTA);
+            context.require(incoming >= missing, &("the pool only backs ".to_string() + &(amount - missing).to_string() + &" IOTA of the bet, the missing IOTA must be sent with the call: ".to_string() + &missing.to_string()));
+            movebucket(context, BUCKET_STRAYS, BUCKET_POOLS, missing);
+            creditmarketpool(context, missing);
+            refund(context, &context.caller().address(), incoming - missing, REFUND_UNACCEPTED);
+            source = "map";
+            betvalue = legacyvalue.to_string();
+            betamount = amount;
+            let bet = Bet {
+                betamount: amount as i32,
+                betisforvalue: betvalue.to_string(),
+                placedat: context.timestamp(),
+                attachedamount: amount,
+                addressencoding: AddressCodec::CURRENT.flag().to_string(),
+                requestid: context.request_id().to_string(),
+                clientid: "".to_string(),
+                campaign: "".to_string(),
+                history: vec![BetEntry { placedat: context.timestamp(), betamount: amount, campaign: "".to_string() }],
+            };
+            migratestorage(context);
+            writebet(context, &betteraddress, &bet);
+            addtotals(context, &betvalue, amount);
+        }
+    }
+    if legacyvalue != betvalue {
+        mapvalue.set_value(&betvalue);
+    }
+
+    let log:String = "bet of ".to_string() + &betteraddress + &" reconciled from the ".to_string() + source + &": ".to_string() + &betamount.to_string() + &" IOTA on ".to_string() + &betvalue;  context.log(&log);
+    appendadminlog(context, &("reconcilelegacy|".to_string() + &betteraddress + &"|".to_string() + source + &"|".to_string() + &betvalue + &"|".to_string() + &betamount.to_string()));
+    context.results().get_string(&"SOURCE".to_string()).set_value(source);
+}
+
+// A betting account whose bet differs between the json of the bets and the legacy map.
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct LegacyInconsistency {
+    address: String,
+    containervalue: String,
+    legacyvalue: String,
+}
+
+// View of the betting accounts with inconsistent legacy data as INCONSISTENT. Every account with a bet in the json is checked,
+// and since the map cannot be iterated, accounts with a value only in the map are found by passing candidates as ADDRESSES,
+// separated by commas, e.g. from the requests of the original bets.
+fn getlegacyinconsistencies(context: &ScViewContext) {
+    let containerofbets: ContainerOfBets = viewcontainerofbets(context);
+    let mut addresses: Vec<String> = containerofbets.map.keys().cloned().collect();
+    for candidate in context.params().get_string(&"ADDRESSES".to_string()).value().split(',').map(|candidate| candidate.trim()).filter(|candidate| *candidate != "") {
+        let decoded: Result<ScAddress, String> = AddressCodec::CURRENT.decode(&context.utility(), candidate);
+        context.require(decoded.is_ok(), decoded.as_ref().err().map_or("", |error| error.as_str()));
+        let betteraddress: String = AddressCodec::CURRENT.encode(&context.utility(), &decoded.unwrap());
+        if !addresses.contains(&betteraddress) {
+            addresses.push(betteraddress);
+        }
+    }
+    addresses.sort();
+
+    let mut inconsistencies: Vec<LegacyInconsistency> = Vec::new();
+    for betteraddress in addresses {
+        let containervalue: String = containerofbets.map.get(&betteraddress).map_or("".to_string(), |bet| bet.betisforvalue.to_string());
+        let legacyvalue: String = viewmarketstate(context).get_map(&betteraddress).get_string(&"betvalue".to_string()).value();
+        if containervalue != legacyvalue {
+            inconsistencies.push(LegacyInconsistency { address: betteraddress, containervalue, legacyvalue });
+        }
+    }
+    context.results().get_string(&"INCONSISTENT".to_string()).set_value(&serde_json::to_string(&inconsistencies).expect("failed to make json of inconsistencies"));
+}
+
+// Function to register the URL of a mirror of the market data, which wallets can discover by getmirrors, callable by anyone.
+// The caller sends a deposit of MIRRORDEPOSIT IOTA against spam, which is kept in the deposits bucket until the contract owner
+// removes the mirror. IOTA sent in excess of the deposit are refunded. Returns the MIRRORID, the slot of the mirror.
+fn registermirror(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "registermirror") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let url: String = context.params().get_string(&"URL".to_string()).value();
+    let incoming: i64 = context.incoming().balance(&ScColor::IOTA);
+    let validated: Result<(), String> = validatemirror(&url, incoming);
+    context.require(validated.is_ok(), validated.as_ref().err().map_or("", |error| error.as_str()));
+
+    let mirrors = context.state().get_string_array(&"mirrors".to_string());
+    let slot: Result<i32, String> = mirrorslot(&mirrorslots(&mirrors));
+    context.require(slot.is_ok(), slot.as_ref().err().map_or("", |error| error.as_str()));
+    let mirrorid: i32 = slot.unwrap();
+
+    let caller: ScAddress = context.caller().address();
+    let registrant: String = AddressCodec::CURRENT.encode(&context.utility(), &caller);
+    let mirror = Mirror {
+        url: url.clone(),
+        registrant: registrant.clone(),
+        deposit: MIRRORDEPOSIT,
+        registeredat: context.timestamp(),
+        addressencoding: AddressCodec::CURRENT.flag().to_string(),
+    };
+    mirrors.get_string(mirrorid).set_value(&serde_json::to_string(&mirror).expect("failed to make json of mirror"));
+    movebucket(context, BUCKET_STRAYS, BUCKET_DEPOSITS, MIRRORDEPOSIT);
+    refund(context, &caller, incoming - MIRRORDEPOSIT, REFUND_UNACCEPTED);
+
+    let log:String = "mirror ".to_string() + &mirrorid.to_string() + &" registered by ".to_string() + &registrant + &": ".to_string() + &url;  context.log(&log);
+    context.results().get_int64(&"MIRRORID".to_string()).set_value(mirrorid as i64);
+    context.event(&events::mirrorregistered(mirrorid as i64, &url, &registrant));
+}
+
+// Function for the contract owner to remove the mirror MIRRORID. The deposit is returned to the registrant,
+// unless SLASH is "true" for a spam entry, which moves the deposit to the fees.
+fn removemirror(context: &ScFuncContext) {
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "removemirror"), "Not authorised to remove mirrors - only contract creator or a marketadmin is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "removemirror") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let mirrorid: i64 = paramint64(context, "MIRRORID");
+    let mirrors = context.state().get_string_array(&"mirrors".to_string());
+    let registered: Result<Mirror, String> = registeredmirror(&mirrorslots(&mirrors), mirrorid);
+    context.require(registered.is_ok(), registered.as_ref().err().map_or("", |error| error.as_str()));
+    let mirror: Mirror = registered.unwrap();
+    let slash: bool = context.params().get_string(&"SLASH".to_string()).value() == "true";
+
+    mirrors.get_string(mirrorid as i32).set_value("");
+    let registrant: Result<ScAddress, String> = AddressCodec::from_flag(&mirror.addressencoding).and_then(|codec| codec.decode(&context.utility(), &mirror.registrant));
+    match (mirrordeposit(slash, registrant.is_ok()), registrant) {
+        (MirrorDeposit::Return, Ok(registrant)) => {
+            guardedtransfer(context, &registrant, mirror.deposit, "mirrordeposit", BUCKET_DEPOSITS);
+        }
+        (_deposit, registrant) => {
+            if let Err(error) = registrant {
+                // the deposit cannot be returned to an undecodable address, it is slashed instead of being stuck
+                let log:String = "deposit of mirror ".to_string() + &mirrorid.to_string() + &" is slashed, ".to_string() + &error;  context.log(&log);
+            }
+            movebucket(context, BUCKET_DEPOSITS, BUCKET_FEES, mirror.deposit);
+        }
+    }
+
+    let log:String = "mirror ".to_string() + &mirrorid.to_string() + &" removed: ".to_string() + &mirror.url;  context.log(&log);
+    appendadminlog(context, &("removemirror|".to_string() + &mirrorid.to_string() + &"|".to_string() + &mirror.url + &"|".to_string() + &slash.to_string()));
+    context.event(&events::mirrorremoved(mirrorid, slash));
+}
+
+// the stored slots of the mirrors array, "" for an empty one
+fn mirrorslots(mirrors: &ScMutableStringArray) -> Vec<String> {
+    (0..mirrors.length()).map(|i| mirrors.get_string(i).value()).collect()
+}
+
+// View of the registered mirrors as MIRRORS, a json object from MIRRORID to the mirror.
+fn getmirrors(context: &ScViewContext) {
+    let mirrors = context.state().get_string_array(&"mirrors".to_string());
+    let mut registeredmirrors: BTreeMap<i64, Mirror> = BTreeMap::new();
+    for i in 0..mirrors.length() {
+        let mirrorjson: String = mirrors.get_string(i).value();
+        if mirrorjson != "" {
+            registeredmirrors.insert(i as i64, serde_json::from_str(&mirrorjson).expect("failed to get mirror"));
+        }
+    }
+    context.results().get_string(&"MIRRORS".to_string()).set_value(&serde_json::to_string(&registeredmirrors).expect("failed to make json of mirrors"));
+}
+
+// A refund issued by the contract, in the refundindex array across all markets of the contract in the order of issuing.
+// Direct refunds are indexed with the IOTA actually transferred, refunds paid out later as entitlements when they are recorded.
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct RefundRecord {
+    marketid: String,
+    address: String,
+    amount: i64,
+    reason: String,
+    timestamp: i64,
+}
+
+fn recordrefund(context: &ScFuncContext, market: &ScMutableMap, address: &str, amount: i64, reason: &str) {
+    if amount == 0 {
+        return;
+    }
+    let refundrecord = RefundRecord {
+        marketid: market.get_string(&"marketid".to_string()).value(),
+        address: address.to_string(),
+        amount,
+        reason: reason.to_string(),
+        timestamp: context.timestamp(),
+    };
+    let refundindex = context.state().get_string_array(&"refundindex".to_string());
+    refundindex.get_string(refundindex.length()).set_value(&serde_json::to_string(&refundrecord).expect("failed to make json of refund"));
+    // the index keeps the latest MAXREFUNDINDEX refunds, which prunes one refund for every further one
+    let head: i64 = context.state().get_int64(&"refundindexhead".to_string()).value();
+    prunerefundsuntil(context, keptfrom(head, refundindex.length() as i64, MAXREFUNDINDEX));
+}
+
+// Prune the refunds of the index before the index until: their entries are cleared, and their number and amount are added to the
+// totals of pruned refunds, so that the export still adds up. The index starts at refundindexhead from then on.
+fn prunerefundsuntil(context: &ScFuncContext, until: i64) {
+    let refundindex = context.state().get_string_array(&"refundindex".to_string());
+    let head = context.state().get_int64(&"refundindexhead".to_string());
+    let prunedcount = context.state().get_int64(&"refundsprunedcount".to_string());
+    let prunedamount = context.state().get_int64(&"refundsprunedamount".to_string());
+    for i in head.value()..until {
+        let entry = refundindex.get_string(i as i32);
+        let refundrecord: RefundRecord = serde_json::from_str(&entry.value()).expect("failed to get refund");
+        prunedcount.set_value(prunedcount.value() + 1);
+        prunedamount.set_value(prunedamount.value() + refundrecord.amount);
+        entry.set_value("");
+    }
+    if until > head.value() {
+        head.set_value(until);
+    }
+}
+
+// Function to prune the refunds before the index CURSOR from the refund index, to be called by the contract owner or a treasurer
+// once they are exported, e.g. at month end. At most MAXREFUNDSCAN refunds are pruned by a call, PRUNEDUNTIL is the index the index
+// starts at after the call.
+fn prunerefunds(context: &ScFuncContext) {
+    // only the contract owner or a treasurer should be able to do this
+    context.require(authorisedfor(context, "prunerefunds"), "Not authorised to prune refunds - only contract creator or a treasurer is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "prunerefunds") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let cursor: i64 = paramint64(context, "CURSOR");
+    let length: i64 = context.state().get_string_array(&"refundindex".to_string()).length() as i64;
+    context.require(cursor >= 0 && cursor <= length, "CURSOR must be an index of the refund index");
+    let head: i64 = context.state().get_int64(&"refundindexhead".to_string()).value();
+    prunerefundsuntil(context, pruneuntil(head, cursor, MAXREFUNDSCAN));
+    let prunedhead: i64 = context.state().get_int64(&"refundindexhead".to_string()).value();
+    context.results().get_int64(&"PRUNEDUNTIL".to_string()).set_value(prunedhead);
+    let log:String = "refunds are pruned until ".to_string() + &prunedhead.to_string();  context.log(&log);
+    appendadminlog(context, &("prunerefunds|".to_string() + &prunedhead.to_string()));
+}
+
+// View of the refunds for accounting as REFUNDS, a json array in the order of issuing, starting at the index CURSOR or at the first
+// refund not pruned yet, FIRSTCURSOR. At most LIMIT refunds (default 100) are returned, optionally only those with the reason code
+// REASON and within the timestamps FROM and UNTIL, both inclusive, of at most MAXREFUNDSCAN scanned refunds.
+// NEXTCURSOR is the index to continue with, or -1 once all refunds were scanned. PRUNEDCOUNT and PRUNEDAMOUNT add up the pruned refunds.
+fn exportrefunds(context: &ScViewContext) {
+    let cursor: i64 = paramint64(context, "CURSOR");
+    let mut limit: i64 = paramint64(context, "LIMIT");
+    if limit <= 0 {
+        limit = DEFAULTREFUNDPAGE;
+    }
+    let reason: String = context.params().get_string(&"REASON".to_string()).value();
+    let from: i64 = paramint64(context, "FROM");
+    let until: i64 = paramint64(context, "UNTIL");
+    context.require(cursor >= 0, "CURSOR must not be negative");
+
+    let refundindex = context.state().get_string_array(&"refundindex".to_string());
+    let head: i64 = context.state().get_int64(&"refundindexhead".to_string()).value();
+    let mut refunds: Vec<RefundRecord> = Vec::new();
+    let nextcursor: i64 = scanpage(cursor.max(head), refundindex.length() as i64, limit, MAXREFUNDSCAN, |i| {
+        let refundrecord: RefundRecord = serde_json::from_str(&refundindex.get_string(i as i32).value()).expect("failed to get refund");
+        let matches: bool = (reason == "" || refundrecord.reason == reason) && refundrecord.timestamp >= from && (until == 0 || refundrecord.timestamp <= until);
+        if matches {
+            refunds.push(refundrecord);
+        }
+        matches
+    });
+    context.results().get_string(&"REFUNDS".to_string()).set_value(&serde_json::to_string(&refunds).expect("failed to make json of refunds"));
+    context.results().get_int64(&"NEXTCURSOR".to_string()).set_value(nextcursor);
+    context.results().get_int64(&"FIRSTCURSOR".to_string()).set_value(head);
+    context.results().get_int64(&"PRUNEDCOUNT".to_string()).set_value(context.state().get_int64(&"refundsprunedcount".to_string()).value());
+    context.results().get_int64(&"PRUNEDAMOUNT".to_string()).set_value(context.state().get_int64(&"refundsprunedamount".to_string()).value());
+}
+
+// View for monitoring whether the contract is internally healthy, cheap enough to be called every few minutes: it only reads
+// counters and flags and never the bets. CHECKS is a json object with the result of every check, STATUS "ok" if all passed
+// and "failed" otherwise, and LASTRECONCILED the timestamp of the last reconcile. The checks are those of healthchecks of the core crate,
+// and the number of payouts flagged for manual intervention is returned in PAYOUTSMANUAL.
+fn healthcheck(context: &ScViewContext) {
+    let state = context.state();
+    let market = viewmarketstate(context);
+    let receiptjson: String = market.get_string(&"receiptjson".to_string()).value();
+    let counters = HealthCounters {
+        balance: context.balances().balance(&ScColor::IOTA),
+        bucketstotal: BUCKETS.iter().map(|bucket| state.get_int64(&bucketkey(bucket)).value()).sum(),
+        transferscount: state.get_int64(&"transferscount".to_string()).value(),
+        transfersblocked: state.get_string(&"transfersblocked".to_string()).value() == "true",
+        storageformat: market.get_string(&"storageformat".to_string()).value(),
+        chunks: market.get_int64(&"containerofbetsjson_chunks".to_string()).value(),
+        unchunked: market.get_string(&"containerofbetsjson".to_string()).value(),
+        marketclosed: market.get_string(&"marketclosed".to_string()).value() == "true",
+        pendingoutcome: market.get_string(&"pendingoutcome".to_string()).value() != "",
+        betenddatetime: market.get_int64(&"betenddatetime".to_string()).value(),
+        entitlements: market.get_string_array(&"entitlements".to_string()).length() as i64,
+        payoutcursor: market.get_int64(&"payoutcursor".to_string()).value(),
+        closedat: if receiptjson == "" { 0 } else { serde_json::from_str::<SettlementReceipt>(&receiptjson).expect("failed to get receipt").closedat },
+        payoutsmanual: market.get_int64(&"payoutsmanual".to_string()).value(),
+    };
+    let checks: BTreeMap<String, bool> = healthchecks(&counters, context.timestamp());
+    let payoutsmanual: i64 = counters.payoutsmanual;
+
+    let healthy: bool = checks.values().all(|passed| *passed);
+    context.results().get_string(&"CHECKS".to_string()).set_value(&serde_json::to_string(&checks).expect("failed to make json of checks"));
+    context.results().get_string(&"STATUS".to_string()).set_value(if healthy { "ok" } else { "failed" });
+    context.results().get_int64(&"LASTRECONCILED".to_string()).set_value(state.get_int64(&"lastreconciled".to_string()).value());
+    context.results().get_int64(&"PAYOUTSMANUAL".to_string()).set_value(payoutsmanual);
+}
+
+// Whether the caller holds the role, which the contract owner always does.
+fn hasrole(context: &ScFuncContext, role: &str) -> bool {
+    if context.caller() == context.contract_creator() {
+        return true;
+    }
+    let caller: String = context.caller().to_string();
+    let members = context.state().get_map(&"roles".to_string()).get_string_array(role);
+    (0..members.length()).any(|i| members.get_string(i).value() == caller)
+}
+
+// Whether the caller may call the privileged function, holding the roles FUNCTIONROLES lists for it or being the contract owner.
+fn authorisedfor(context: &ScFuncContext, function: &str) -> bool {
+    let held: Vec<&str> = ROLES.iter().copied().filter(|role| hasrole(context, role)).collect();
+    authorised(function, context.caller() == context.contract_creator(), &held)
+}
+
+// Whether the caller may settle the market: the contract owner, a resolver or the ORACLE of the market, which is the only one with ORACLEONLY.
+// The dead-man switch of the chain owner applies regardless.
+fn mayresolve(context: &ScFuncContext) -> bool {
+    let oracle: String = marketstate(context).get_string(&"oracle".to_string()).value();
+    let isoracle: bool = oracle != "" && context.caller().to_string() == oracle;
+    if marketstate(context).get_string(&"oracleonly".to_string()).value() == "true" {
+        return isoracle;
+    }
+    isoracle || hasrole(context, ROLE_RESOLVER)
+}
+
+// View of the ORACLE of the market, "" without one, and ORACLEONLY, whether only the oracle may settle it.
+fn getoracle(context: &ScViewContext) {
+    context.results().get_string(&"ORACLE".to_string()).set_value(&viewmarketstate(context).get_string(&"oracle".to_string()).value());
+    context.results().get_string(&"ORACLEONLY".to_string()).set_value(&(viewmarketstate(context).get_string(&"oracleonly".to_string()).value() == "true").to_string());
+}
+
+// the view every feed is read by, with the key of the feed as KEY, reporting its value as VALUE
+const FEEDVIEW: &str = "getvalue";
+
+// Function to resolve a market with FEEDS from the median of the values its feeds report, to be called by whoever may close the market
+// after the end time for bets. Every feed is read by calling the view getvalue of its contract with its KEY, a feed without VALUE is missing.
+// With less than FEEDQUORUM values the market stays unresolved and nothing is changed, with a value deviating more than FEEDDEVIATIONBPS
+// from the median the market is put into the dispute state, in which resolvefromoracle refuses it and a resolver closes it by closemarket.
+// Otherwise the market is settled on the outcome of the median among its BOUNDARIES. The report of the feeds is returned as FEEDREPORT,
+// the outcome as WINNINGVALUE, and the receipt of the settlement records every feed with its value.
+fn resolvefromoracle(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "resolvefromoracle") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    // only the contract owner, a resolver or the oracle should be able to do this
+    context.require(mayresolve(context), "Not authorised to resolve the market from its feeds - only contract creator or a resolver is allowed to do this.");
+    context.require(marketstate(context).get_string(&"marketclosed".to_string()).value() == "false", "the prediction market was already closed");
+    let feeds: Vec<OracleFeed> = serde_json::from_str(&marketstate(context).get_string(&"feedsjson".to_string()).value()).unwrap_or_default();
+    context.require(!feeds.is_empty(), "the market has no FEEDS to be resolved from");
+    context.require(marketstate(context).get_string(&"feeddisputed".to_string()).value() != "true", "the feeds of the market diverged, a resolver has to close it by closemarket");
+    context.require(marketstate(context).get_string(&"groupid".to_string()).value() == "", "the outcome of a member of a group is declared by closegroup");
+    let betenddatetime: i64 = marketstate(context).get_int64(&"betenddatetime".to_string()).value();
+    context.require(betenddatetime == 0 || context.timestamp() > betenddatetime, "the market can only be resolved from its feeds after the end time for bets has passed");
+
+    let mut values: Vec<Option<i64>> = Vec::new();
+    for feed in feeds.iter() {
+        let params: ScMutableMap = ScMutableMap::new();
+        params.get_string(&"KEY".to_string()).set_value(&feed.key);
+        let results: ScImmutableMap = context.call(ScHname::from_bytes(&hnamebytes(&feed.hname)), context.utility().hname(FEEDVIEW), Some(params), None);
+        let value = results.get_int64(&"VALUE".to_string());
+        values.push(if value.exists() { Some(value.value()) } else { None });
+    }
+    let outcomes: Vec<String> = parseoutcomes(&marketstate(context).get_string(&"outcomes".to_string()).value());
+    let boundaries: Vec<i64> = parseboundaries(&marketstate(context).get_string(&"boundaries".to_string()).value(), &outcomes).unwrap_or_default();
+    let report: FeedReport = resolvefeeds(&feeds, &values, marketstate(context).get_int64(&"feedquorum".to_string()).value(),
+        marketstate(context).get_int64(&"feeddeviationbps".to_string()).value(), &boundaries, &outcomes);
+    let reportjson: String = serde_json::to_string(&report).expect("failed to make json of feed report");
+    context.results().get_string(&"FEEDREPORT".to_string()).set_value(&reportjson);
+    let log:String;
+    if report.status == FEEDS_NOQUORUM {
+        log = ERR_NO_QUORUM.to_string() + &": ".to_string() + &report.responses.to_string() + &" of ".to_string() + &feeds.len().to_string()
+            + &" feeds reported a value, ".to_string() + &report.quorum.to_string() + &" are required, the market stays unresolved".to_string();  context.log(&log);
+        context.results().get_string(&"ERROR".to_string()).set_value(ERR_NO_QUORUM);
+        return;
+    }
+    marketstate(context).get_string(&"feedreportjson".to_string()).set_value(&reportjson);
+    if report.status == FEEDS_DIVERGED {
+        log = "the feeds deviate up to ".to_string() + &report.deviationbps.to_string() + &" basis points from their median ".to_string() + &report.median.unwrap_or(0).to_string()
+            + &", more than ".to_string() + &report.maxdeviationbps.to_string() + &", the market is disputed".to_string();  context.log(&log);
+        marketstate(context).get_string(&"feeddisputed".to_string()).set_value(&"true".to_string());
+        recordtransition(context, &marketstate(context), "disputed");
+        appendadminlog(context, &("resolvefromoracle|diverged|".to_string() + &report.deviationbps.to_string()));
+        return;
+    }
+    log = "the median of the feeds is ".to_string() + &report.median.unwrap_or(0).to_string() + &", the outcome \"".to_string() + &report.outcome + &"\"".to_string();  context.log(&log);
+    context.results().get_string(&"WINNINGVALUE".to_string()).set_value(&report.outcome);
+    settlemarket(context, "resolvefromoracle", &report.outcome, "", None);
+}
+
+// View of the FEEDS of the market as json list of their hname and key, its BOUNDARIES, FEEDQUORUM and FEEDDEVIATIONBPS,
+// whether its feeds diverged as FEEDDISPUTED, and the report of the last reading of the feeds that changed the market as FEEDREPORT.
+fn getfeeds(context: &ScViewContext) {
+    let market: ScImmutableMap = viewmarketstate(context);
+    let feeds: Vec<OracleFeed> = serde_json::from_str(&market.get_string(&"feedsjson".to_string()).value()).unwrap_or_default();
+    context.results().get_string(&"FEEDS".to_string()).set_value(&serde_json::to_string(&feeds).expect("failed to make json of feeds"));
+    context.results().get_string(&"BOUNDARIES".to_string()).set_value(&market.get_string(&"boundaries".to_string()).value());
+    context.results().get_int64(&"FEEDQUORUM".to_string()).set_value(market.get_int64(&"feedquorum".to_string()).value());
+    context.results().get_int64(&"FEEDDEVIATIONBPS".to_string()).set_value(market.get_int64(&"feeddeviationbps".to_string()).value());
+    context.results().get_string(&"FEEDDISPUTED".to_string()).set_value(&(market.get_string(&"feeddisputed".to_string()).value() == "true").to_string());
+    context.results().get_string(&"FEEDREPORT".to_string()).set_value(&market.get_string(&"feedreportjson".to_string()).value());
+}
+
+// role which may propose and cancel an action of the kind, as it may call the function of the action directly
+// Read the parameters ROLE and AGENTID of addrole and removerole, returning the role and the agent id in its canonical encoding.
+fn roleparams(context: &ScFuncContext) -> (String, String) {
+    let role: String = context.params().get_string(&"ROLE".to_string()).value();
+    context.require(ROLES.contains(&role.as_str()), "ROLE must be treasurer, resolver or marketadmin");
+    let agentid: Vec<u8> = context.utility().base58_decode(&context.params().get_string(&"AGENTID".to_string()).value());
+    context.require(agentid.len() == ADDRESS_LENGTH + 4, "AGENTID must be a base58 encoded agent id");
+    (role, ScAgentID::from_bytes(&agentid).to_string())
+}
+
+// Function for the contract owner to assign the role ROLE, "treasurer", "resolver" or "marketadmin", to the agent AGENTID.
+fn addrole(context: &ScFuncContext) {
+    // only contract owner should be able to do this
+    let creator = context.contract_creator();
+    let caller = context.caller();
+    context.require(creator == caller, "Not authorised to assign roles - only contract creator is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "addrole") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let (role, agentid) = roleparams(context);
+    let members = context.state().get_map(&"roles".to_string()).get_string_array(&role);
+    context.require(!(0..members.length()).any(|i| members.get_string(i).value() == agentid), "the agent already holds the role");
+    context.require(members.length() < MAXROLEMEMBERS, &("no more than ".to_string() + &MAXROLEMEMBERS.to_string() + &" agents can hold a role".to_string()));
+    members.get_string(members.length()).set_value(&agentid);
+
+    let log:String = "role ".to_string() + &role + &" assigned to ".to_string() + &agentid;  context.log(&log);
+    appendadminlog(context, &("addrole|".to_string() + &role + &"|".to_string() + &agentid));
+}
+
+// Function for the contract owner to revoke the role ROLE from the agent AGENTID.
+fn removerole(context: &ScFuncContext) {
+    // only contract owner should be able to do this
+    let creator = context.contract_creator();
+    let caller = context.caller();
+    context.require(creator == caller, "Not authorised to revoke roles - only contract creator is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "removerole") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let (role, agentid) = roleparams(context);
+    let members = context.state().get_map(&"roles".to_string()).get_string_array(&role);
+    let remaining: Vec<String> = (0..members.length()).map(|i| members.get_string(i).value()).filter(|member| *member != agentid).collect();
+    context.require(remaining.len() < members.length() as usize, "the agent does not hold the role");
+    members.clear();
+    for (i, member) in remaining.iter().enumerate() {
+        members.get_string(i as i32).set_value(member);
+    }
+
+    let log:String = "role ".to_string() + &role + &" revoked from ".to_string() + &agentid;  context.log(&log);
+    appendadminlog(context, &("removerole|".to_string() + &role + &"|".to_string() + &agentid));
+}
+
+// View of the assigned roles as ROLES, a json object from each role to the agent ids holding it besides the contract owner.
+fn getroles(context: &ScViewContext) {
+    let mut roles: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for role in ROLES.iter() {
+        let members = context.state().get_map(&"roles".to_string()).get_string_array(*role);
+        roles.insert(role.to_string(), (0..members.length()).map(|i| members.get_string(i).value()).collect());
+    }
+    context.results().get_string(&"ROLES".to_string()).set_value(&serde_json::to_string(&roles).expect("failed to make json of roles"));
+}
+
+// The dispute of an account against the declared outcome, in the disputes array, with its bond held in the escrows bucket.
+// Bonds sent by several calls of the same account add up, and only a bond of at least DISPUTEBOND makes a dispute.
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct Dispute {
+    disputer: String,
+    addressencoding: String,
+    bond: i64,
+    disputedoutcome: String,
+    requestid: String,
+    settled: bool,
+}
+
+fn getdispute(state: &ScMutableMap, index: i32) -> Dispute {
+    serde_json::from_str(&state.get_string_array(&"disputes".to_string()).get_string(index).value()).expect("failed to get dispute")
+}
+
+fn storedispute(context: &ScFuncContext, index: i32, dispute: &Dispute) {
+    marketstate(context).get_string_array(&"disputes".to_string()).get_string(index).set_value(&serde_json::to_string(dispute).expect("failed to make json of dispute"));
+}
+
+// Function to dispute the outcome declared by declareoutcome before the market is closed, callable by anyone.
+// The IOTA sent with the call are the bond, which has to add up to at least DISPUTEBOND of the market, also over several calls.
+// When the market is closed with another outcome, the bonds are returned. If the declared outcome stands, the bonds of the disputes
+// are forfeited to the winners, the contract owner or the destination of residual funds, as set by DISPUTEFORFEIT.
+// Bonds below DISPUTEBOND are returned in any case.
+fn dispute(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "dispute") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    let disputebond: i64 = marketstate(context).get_int64(&"disputebond".to_string()).value();
+    context.require(disputebond > 0, "the market does not accept disputes, it has no DISPUTEBOND");
+    context.require(marketstate(context).get_string(&"marketclosed".to_string()).value() == "false", "the prediction market is not open");
+    let pendingoutcome: String = marketstate(context).get_string(&"pendingoutcome".to_string()).value();
+    context.require(pendingoutcome != "", "no outcome was declared that can be disputed");
+    let bond: i64 = context.incoming().balance(&ScColor::IOTA);
+    context.require(bond > 0, "a bond has to be sent with the dispute");
+    let disputer: String = AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address());
+    context.require(!addressblocked(context, &disputer), ERR_ADDRESS_BLOCKED);
+
+    let disputesbyaddress = marketstate(context).get_map(&"disputesbyaddress".to_string()).get_int64(&disputer);
+    let disputes = marketstate(context).get_string_array(&"disputes".to_string());
+    let mut disputefiled: Dispute;
+    let index: i32;
+    if disputesbyaddress.exists() {
+        index = disputesbyaddress.value() as i32;
+        disputefiled = getdispute(&marketstate(context), index);
+        disputefiled.bond = disputefiled.bond + bond;
+        disputefiled.disputedoutcome = pendingoutcome.clone();
+    } else {
+        context.require(disputes.length() < MAXDISPUTERS, &("no more than ".to_string() + &MAXDISPUTERS.to_string() + &" accounts can dispute the outcome".to_string()));
+        index = disputes.length();
+        disputesbyaddress.set_value(index as i64);
+        disputefiled = Dispute {
+            disputer: disputer.clone(),
+            addressencoding: AddressCodec::CURRENT.flag().to_string(),
+            bond,
+            disputedoutcome: pendingoutcome.clone(),
+            requestid: context.request_id().to_string(),
+            settled: false,
+        };
+    }
+    storedispute(context, index, &disputefiled);
+    movebucket(context, BUCKET_STRAYS, BUCKET_ESCROWS, bond);
+
+    let log:String = "outcome \"".to_string() + &pendingoutcome + &"\" is disputed by ".to_string() + &disputer + &" with a bond of ".to_string() + &disputefiled.bond.to_string() + &" IOTA".to_string();  context.log(&log);
+    context.results().get_int64(&"BOND".to_string()).set_value(disputefiled.bond);
+    context.results().get_string(&"EFFECTIVE".to_string()).set_value(&(disputefiled.bond >= disputebond).to_string());
+    context.event(&events::disputefiled(&disputer, disputefiled.bond, &pendingoutcome));
+}
+
+// Settle the bonds of all disputes when the market is closed with the winning value, or voided without one.
+// Bonds of disputes whose disputed outcome was overturned, and all bonds below DISPUTEBOND, are returned.
+// The other bonds are forfeited: with POOL, they are shared by the winners in proportion to their win amounts as additional
+// entitlements, and the rounding residue stays in the pools; with OWNER, they are added to the fees; with CHARITY, they are sent
+// to the destination of residual funds. Without winners or destination, forfeited bonds are added to the fees.
+fn settledisputes(context: &ScFuncContext, betvaluewinning: Option<&str>, winamounts: &HashMap<String, i64>) {
+    let disputebond: i64 = marketstate(context).get_int64(&"disputebond".to_string()).value();
+    let disputes = marketstate(context).get_string_array(&"disputes".to_string());
+    let mut forfeited: i64 = 0;
+    for index in 0..disputes.length() {
+        let mut disputesettled: Dispute = getdispute(&marketstate(context), index);
+        if disputesettled.settled {
+            continue;
+        }
+        let overturned: bool = overturned(betvaluewinning, &disputesettled.disputedoutcome);
+        if bondreturned(betvaluewinning, &disputesettled.disputedoutcome, disputesettled.bond, disputebond) {
+            match AddressCodec::from_flag(&disputesettled.addressencoding).and_then(|codec| codec.decode(&context.utility(), &disputesettled.disputer)) {
+                Ok(disputer) => {
+                    guardedtransfer(context, &disputer, disputesettled.bond, "disputebond", BUCKET_ESCROWS);
+                }
+                Err(error) => {
+                    let log:String = "bond of ".to_string() + &disputesettled.disputer + &" cannot be returned and is forfeited, ".to_string() + &error;  context.log(&log);
+                    forfeited = forfeited + disputesettled.bond;
+                }
+            }
+        } else {
+            forfeited = forfeited + disputesettled.bond;
+        }
+        disputesettled.settled = true;
+        storedispute(context, index, &disputesettled);
+        appendadminlog(context, &("dispute|".to_string() + &disputesettled.disputer + &"|".to_string() + &disputesettled.bond.to_string() + &"|".to_string() + &overturned.to_string()));
+        context.event(&events::disputesettled(&disputesettled.disputer, disputesettled.bond, overturned));
+    }
+    if forfeited == 0 {
+        return;
+    }
+
+    let policy: String = marketstate(context).get_string(&"disputeforfeit".to_string()).value();
+    let sweepdest: String = marketstate(context).get_string(&"sweepdest".to_string()).value();
+    let policy: &str = forfeitrecipient(&policy, winamounts.values().sum(), sweepdest != "");
+    let log:String = "forfeited bonds of ".to_string() + &forfeited.to_string() + &" IOTA go to ".to_string() + &policy;  context.log(&log);
+    if policy == FORFEIT_POOL {
+        movebucket(context, BUCKET_ESCROWS, BUCKET_POOLS, forfeited);
+        creditmarketpool(context, forfeited);
+        let containerofbets: ContainerOfBets = readcontainerofbets(context);
+        for (betteraddress, share) in bondshares(forfeited, winamounts) {
+            let bet: &Bet = &containerofbets.map[&betteraddress];
+            recordentitlement(context, &betteraddress, &bet.addressencoding, &bet.requestid, share, "bondshare");
+        }
+    } else if policy == FORFEIT_CHARITY {
+        let charity: ScAddress = AddressCodec::CURRENT.decode(&context.utility(), &sweepdest).unwrap();
+        guardedtransfer(context, &charity, forfeited, "disputebond", BUCKET_ESCROWS);
+    } else {
+        movebucket(context, BUCKET_ESCROWS, BUCKET_FEES, forfeited);
+    }
+}
+
+// View of all disputes of the market as DISPUTES, a json list of the disputer, its bond, the disputed outcome and whether it is settled.
+fn getdisputes(context: &ScViewContext) {
+    let disputes = viewmarketstate(context).get_string_array(&"disputes".to_string());
+    let mut filed: Vec<Dispute> = Vec::new();
+    for i in 0..disputes.length() {
+        filed.push(serde_json::from_str(&disputes.get_string(i).value()).expect("failed to get dispute"));
+    }
+    context.results().get_string(&"DISPUTES".to_string()).set_value(&serde_json::to_string(&filed).expect("failed to make json of disputes"));
+    context.results().get_int64(&"DISPUTEBOND".to_string()).set_value(viewmarketstate(context).get_int64(&"disputebond".to_string()).value());
+}
+
+// State of an open market carried across a redeployment of the contract by exportformigration and importmarket:
+// the bets, the pool they funded and the settings of the market, by their keys in the market's state.
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct MigrationState {
+    marketid: String,
+    containerofbetsjson: String,
+    pool: i64,
+    settings: BTreeMap<String, String>,
+    intsettings: BTreeMap<String, i64>,
+}
+
+// settings of an open market carried by a migration; the bets, the per-account values and the acknowledgements of the terms
+// are rebuilt from the bets, and an open market has no entitlements, disputes or receipt yet
+const MIGRATEDSETTINGS: [&str; 22] = ["alertflags", "alertrulesjson", "boundaries", "checkpointsjson", "commitforfeit", "commitreveal", "configjson", "configwarningsjson", "creator", "disputeforfeit", "feedsjson",
+    "oddsrevealed", "oracle", "oracleonly", "outcomes", "payoutmode", "refusalnotice", "shortfallpolicy", "strictmode", "termshash", "tiebreak", "marketid"];
+const MIGRATEDINTSETTINGS: [&str; 33] = ["activityhigh", "activitymedium", "activitywindow", "announcedelay", "betenddatetime", "betgranularity", "betsequence", "configbytes",
+    "creatorsharebps", "disputebond", "extensions", "extensionwindow", "feeddeviationbps", "feedquorum", "feepercent", "heartbeatwindow", "idempotencymaxkeys", "idempotencyminage", "maxcommitescrow", "maxcommits", "maxconfigbytes", "maxextensions", "maxsharebps", "maxshareexemptpool", "maxtotalextension", "minbet", "oddsrevealpool", "opensat", "piggybackpayouts", "resolutiongrace", "revealwindow", "totalextension", "votewindow"];
+// the destination of residual funds and the timelock of admin actions are exported as well, but only imported by a treasurer,
+// who alone may change them by setsweepdest; an import by another marketadmin leaves them unset
+const MIGRATEDTREASURYSETTINGS: [&str; 1] = ["sweepdest"];
+const MIGRATEDTREASURYINTSETTINGS: [&str; 1] = ["timelock"];
+
+// The hash of a migration is taken over the canonical encoding of its state, not over the json carried by the events,
+// so that it does not depend on how the json is serialized.
+fn migrationhash(utility: &ScUtility, migration: &MigrationState) -> String {
+    utility.hash_blake2b(&canonicalmigration(&migration.marketid, &parsecontainerofbets(&migration.containerofbetsjson), migration.pool,
+        &migration.settings, &migration.intsettings)).to_string()
+}
+
+// Function to move an open market to a redeployed contract, to be called by the contract creator or an account that is
+// both marketadmin and treasurer. The market is frozen for good: its state is emitted as migration.chunk events, which carry
+// the json of the migration state split into chunks of 1024 bytes in base58, its blake2b hash is recorded and returned as HASH,
+// and the IOTA of its pool are transferred to the caller, who sends them with the last call of importmarket on the new contract.
+fn exportformigration(context: &ScFuncContext) {
+    // only the contract owner, or a marketadmin who is also a treasurer, should be able to do this
+    context.require(authorisedfor(context, "exportformigration"), "Not authorised to export the market - only contract creator or a marketadmin who is also a treasurer is allowed to do this.");
+    openledger(context);
+    let _recorder = match idempotent(context, "exportformigration") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    requiremarket(context);
+    let market = marketstate(context);
+    context.require(market.get_string(&"marketclosed".to_string()).value() != "true", "a closed market cannot be migrated");
+    context.require(market.get_string(&"pendingoutcome".to_string()).value() == "", "a market with a declared outcome cannot be migrated");
+    context.require(market.get_int64(&"unrevealedcommits".to_string()).value() == 0, "a market with unrevealed commitments cannot be migrated");
+    context.require(market.get_string_array(&"disputes".to_string()).length() == 0, "a disputed market cannot be migrated");
+    context.require(market.get_string(&"feeddisputed".to_string()).value() != "true", "a market whose feeds diverged cannot be migrated");
+    context.require(market.get_string(&"tokencolor".to_string()).value() == "", "a market denominated in a colored token cannot be migrated");
+    context.require(market.get_string(&"book".to_string()).value() != "true", "a market with BOOK cannot be migrated, its bets are locked at their odds");
+
+    let mut settings: BTreeMap<String, String> = BTreeMap::new();
+    for key in MIGRATEDSETTINGS.iter().chain(MIGRATEDTREASURYSETTINGS.iter()) {
+        settings.insert(key.to_string(), market.get_string(*key).value());
+    }
+    let mut intsettings: BTreeMap<String, i64> = BTreeMap::new();
+    for key in MIGRATEDINTSETTINGS.iter().chain(MIGRATEDTREASURYINTSETTINGS.iter()) {
+        intsettings.insert(key.to_string(), market.get_int64(*key).value());
+    }
+    let migration = MigrationState {
+        marketid: market.get_string(&"marketid".to_string()).value(),
+        containerofbetsjson: serde_json::to_string(&readcontainerofbets(context)).expect("failed to make json of container of bets"),
+        pool: marketpool(context),
+        settings,
+        intsettings,
+    };
+    let payload: String = serde_json::to_string(&migration).expect("failed to make json of migration state");
+    let hash: String = migrationhash(&context.utility(), &migration);
+
+    // no bets and no closing from now on
+    market.get_string(&"migrationhash".to_string()).set_value(&hash);
+    market.get_string(&"marketclosed".to_string()).set_value(&"true".to_string());
+    recordtransition(context, &market, "migrated");
+
+    let chunks: Vec<&[u8]> = migrationchunks(payload.as_bytes());
+    for (index, chunk) in chunks.iter().enumerate() {
+        context.event(&events::migrationchunk(&hash, index as i64, chunks.len() as i64, &context.utility().base58_encode(chunk)));
+    }
+    let transferred: i64 = guardedtransfer(context, &context.caller().address(), migration.pool, "migration", BUCKET_POOLS);
+    creditmarketpool(context, -transferred);
+    appendadminlog(context, &("exportformigration|".to_string() + &migration.marketid + &"|".to_string() + &hash + &"|".to_string() + &transferred.to_string()));
+
+    let log:String = "market exported for migration in ".to_string() + &chunks.len().to_string() + &" chunks, hash: ".to_string() + &hash + &", pool transferred (IOTA): ".to_string() + &transferred.to_string();  context.log(&log);
+    context.results().get_string(&"HASH".to_string()).set_value(&hash);
+    context.results().get_int64(&"CHUNKS".to_string()).set_value(chunks.len() as i64);
+    context.results().get_int64(&"POOL".to_string()).set_value(transferred);
+}
+
+// Function to import a market exported by exportformigration of the previous contract, to be called by the contract owner or a marketadmin.
+// The chunks are passed one per call as CHUNK with their INDEX, the number of chunks COUNT and the HASH of the export, in any order
+// and over as many calls as needed. The call completing the chunks verifies the hash and that the bets add up to the exported pool,
+// requires the IOTA of the pool to be sent with it and opens the market, under MARKETID or else under the id of the exported market,
+// with its bets in the current storage format. Only the settings a migration carries are imported.
+fn importmarket(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "importmarket") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "importmarket"), "Not authorised to import a market - only contract creator or a marketadmin is allowed to do this.");
+    let market = marketstate(context);
+    context.require(!market.get_string(&"marketid".to_string()).exists() && !marketarchived(context, &marketparam(context)), "MARKETID is already used by another market");
+
+    let hash: String = context.params().get_string(&"HASH".to_string()).value();
+    let index: i64 = paramint64(context, "INDEX");
+    let count: i64 = paramint64(context, "COUNT");
+    let importhash = market.get_string(&"importhash".to_string());
+    context.require(hash != "", "HASH of the export is required");
+    context.require(importhash.value() == "" || importhash.value() == hash, "another export is being imported into this market");
+    context.require(count > 0 && index >= 0 && index < count, "INDEX must be below COUNT");
+    importhash.set_value(&hash);
+    let importchunks = market.get_map(&"importchunks".to_string());
+    importchunks.get_string(&index.to_string()).set_value(&context.params().get_string(&"CHUNK".to_string()).value());
+    let received: Option<Vec<u8>> = joinmigrationchunks(count, |i| {
+        let chunk: String = importchunks.get_string(&i.to_string()).value();
+        if chunk == "" { None } else { Some(context.utility().base58_decode(&chunk)) }
+    });
+    let bytes: Vec<u8> = match received {
+        Some(bytes) => bytes,
+        None => {
+            // only the call completing the import takes IOTA
+            refundincoming(context, &context.caller().address(), REFUND_UNACCEPTED);
+            context.results().get_string(&"STATUS".to_string()).set_value(&"pending".to_string());
+            return;
+        }
+    };
+    let payload: String = String::from_utf8(bytes).expect("failed to decode migration state");
+    let migration: MigrationState = serde_json::from_str(&payload).expect("failed to get migration state");
+    context.require(migrationhash(&context.utility(), &migration) == hash, "the imported chunks do not match HASH");
+    // HASH is given by the caller, so it only proves the chunks complete, not that they come from the previous contract:
+    // every imported bet has to be backed by the pool sent with the import
+    let containerofbets: ContainerOfBets = parsecontainerofbets(&migration.containerofbetsjson);
+    // the pool moves with the market, from the caller of exportformigration
+    let incoming: i64 = context.incoming().balance(&ScColor::IOTA);
+    let excess: Result<i64, String> = importrefund(bettotals(&containerofbets).totalbetamount as i64, migration.pool, incoming);
+    context.require(excess.is_ok(), excess.as_ref().err().map_or("", |error| error.as_str()));
+    let excess: i64 = excess.unwrap();
+    movebucket(context, BUCKET_STRAYS, BUCKET_POOLS, migration.pool);
+    refund(context, &context.caller().address(), excess, REFUND_UNACCEPTED);
+
+    let treasurer: bool = hasrole(context, ROLE_TREASURER);
+    for (key, value) in migration.settings.iter() {
+        if MIGRATEDSETTINGS.contains(&key.as_str()) || (treasurer && MIGRATEDTREASURYSETTINGS.contains(&key.as_str())) {
+            market.get_string(key).set_value(value);
+        }
+    }
+    for (key, value) in migration.intsettings.iter() {
+        if MIGRATEDINTSETTINGS.contains(&key.as_str()) || (treasurer && MIGRATEDTREASURYINTSETTINGS.contains(&key.as_str())) {
+            market.get_int64(key).set_value(*value);
+        }
+    }
+    let mut marketid: String = marketparam(context);
+    if marketid == "" {
+        marketid = migration.marketid.clone();
+    } else {
+        let marketids = context.state().get_string_array(&"marketids".to_string());
+        marketids.get_string(marketids.length()).set_value(&marketid);
+    }
+    market.get_string(&"marketid".to_string()).set_value(&marketid);
+    creditmarketpool(context, migration.pool);
+    // a market exported before creators were recorded is credited to the importer, with the share of the platform defaults
+    if market.get_string(&"creator".to_string()).value() == "" {
+        market.get_string(&"creator".to_string()).set_value(&AddressCodec::CURRENT.encode(&context.utility(), &context.caller().address()));
+        snapshotdefaults(context, &market);
+    }
+
+    let termshash: String = market.get_string(&"termshash".to_string()).value();
+    for (betteraddress, bet) in containerofbets.map.iter() {
+        market.get_map(betteraddress).get_string(&"betvalue".to_string()).set_value(&bet.betisforvalue);
+        if termshash != "" {
+            market.get_map(&"termsacks".to_string()).get_string(seedowner(betteraddress)).set_value(&termsack(&termshash, bet.placedat));
+        }
+    }
+    migratestorage(context);
+    for betteraddress in sortedbetteraddresses(&containerofbets) {
+        writebet(context, &betteraddress, &containerofbets.map[&betteraddress]);
+    }
+    settotals(context, &bettotals(&containerofbets));
+    market.get_string(&"pendingoutcome".to_string()).set_value("");
+    market.get_string(&"receiptjson".to_string()).set_value("");
+    market.get_string(&"outcomescenariosjson".to_string()).set_value("");
+    market.get_string(&"betsfrozen".to_string()).set_value(&"false".to_string());
+    market.get_string(&"marketclosed".to_string()).set_value(&"false".to_string());
+    market.get_string(&"initialized".to_string()).set_value(&"true".to_string());
+    for i in 0..count {
+        importchunks.get_string(&i.to_string()).set_value("");
+    }
+    recordtransition(context, &market, "imported");
+    appendadminlog(context, &("importmarket|".to_string() + &marketid + &"|".to_string() + &hash + &"|".to_string() + &migration.pool.to_string()));
+
+    let log:String = "market imported: ".to_string() + &marketid + &", bets: ".to_string() + &containerofbets.map.len().to_string() + &", pool (IOTA): ".to_string() + &migration.pool.to_string();  context.log(&log);
+    context.results().get_string(&"STATUS".to_string()).set_value(&"imported".to_string());
+    context.results().get_string(&"MARKETID".to_string()).set_value(&marketid);
+}
+
+fn tombstonestate(context: &ScFuncContext, marketid: &str) -> ScMutableMap {
+    context.state().get_map(&("tombstone|".to_string() + marketid))
+}
+
+// Whether a market with the MARKETID was archived, which then cannot be used for another market.
+fn marketarchived(context: &ScFuncContext, marketid: &str) -> bool {
+    marketid != "" && tombstonestate(context, marketid).get_string(&"digest".to_string()).exists()
+}
+
+// Function to archive a market with MARKETID, to be called by the contract owner or a marketadmin, once it is settled or voided,
+// every entitlement is paid and its residual funds are swept. Its state is emitted as archive.chunk events, which carry the json of
+// an ArchiveExport split into chunks of 1024 bytes in base58, and is then deleted. A tombstone keeps the summary of the market and
+// the digest of the export, see tombstonedigest in the core crate, which is returned as DIGEST, so that anybody holding the events
+// can verify them against the tombstone by verifytombstone. The template and the current market of an active schedule cannot be archived.
+fn archivemarket(context: &ScFuncContext) {
+    openledger(context);
+    let _recorder = match idempotent(context, "archivemarket") {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    // only the contract owner or a marketadmin should be able to do this
+    context.require(authorisedfor(context, "archivemarket"), "Not authorised to archive a market - only contract creator or a marketadmin is allowed to do this.");
+    let marketid: String = marketparam(context);
+    context.require(marketid != "", "only a market with MARKETID can be archived");
+    requiremarket(context);
+    let market: ScMutableMap = marketstate(context);
+    context.require(market.get_string(&"marketclosed".to_string()).value() == "true" && market.get_string(&"migrationhash".to_string()).value() == "",
+        "only a settled or voided market can be archived");
+    context.require(market.get_int64(&"unrevealedcommits".to_string()).value() == 0, "a market with unrevealed commitments cannot be archived");
+    let scheduleids = context.state().get_string_array(&"scheduleids".to_string());
+    for i in 0..scheduleids.length() {
+        let schedule: ScMutableMap = schedulestate(context, &scheduleids.get_string(i).value());
+        context.require(schedule.get_string(&"templateid".to_string()).value() != marketid && schedule.get_string(&"current".to_string()).value() != marketid,
+            "the template or the current market of an active schedule cannot be archived");
+    }
+    let entitlements = market.get_string_array(&"entitlements".to_string());
+    let mut payouts: Vec<ArchivedPayout> = Vec::new();
+    for i in 0..entitlements.length() {
+        let entitlement: Entitlement = serde_json::from_str(&entitlements.get_string(i).value()).expect("failed to get entitlement");
+        context.require(entitlement.paid, "a market with unpaid entitlements cannot be archived");
+        payouts.push(ArchivedPayout { address: entitlement.address, amount: entitlement.amount, paidamount: entitlement.paidamount, reason: entitlement.reason, paid: entitlement.paid });
+    }
+    context.require(marketpool(context) == 0, "the residual funds of the market have to be swept before it is archived");
+
+    let strings = |key: &str| -> Vec<String> {
+        let array = market.get_string_array(key);
+        (0..array.length()).map(|i| array.get_string(i).value()).collect()
+    };
+    let containerofbets: ContainerOfBets = readcontainerofbets(context);
+    let export = ArchiveExport {
+        marketid: marketid.clone(),
+        bets: exportedbets(&containerofbets, None),
+        payouts,
+        receiptjson: market.get_string(&"receiptjson".to_string()).value(),
+        adminlog: strings("adminlog"),
+        transitions: strings("transitions"),
+        configjson: market.get_string(&"configjson".to_string()).value(),
+    };
+    let utility = context.utility();
+    let hash = |bytes: &[u8]| utility.hash_blake2b(bytes).to_bytes().to_vec();
+    let digest: String = utility.base58_encode(&tombstonedigest(&export, &hash).digest);
+    let payload: String = serde_json::to_string(&export).expect("failed to make json of archive export");
+    let chunks: Vec<&[u8]> = migrationchunks(payload.as_bytes());
+    for (index, chunk) in chunks.iter().enumerate() {
+        context.event(&events::archivechunk(&marketid, &digest, index as i64, chunks.len() as i64, &utility.base58_encode(chunk)));
+    }
+
+    let tombstone: ScMutableMap = tombstonestate(context, &marketid);
+    tombstone.get_string(&"digest".to_string()).set_value(&digest);
+    tombstone.get_string(&"recipe".to_string()).set_value(TOMBSTONERECIPE);
+    tombstone.get_int64(&"archivedat".to_string()).set_value(context.timestamp());
+    tombstone.get_string(&"winningvalue".to_string()).set_value(&market.get_string(&"winningvalue".to_string()).value());
+    tombstone.get_string(&"voided".to_string()).set_value(&(market.get_string(&"marketvoided".to_string()).value() == "true").to_string());
+    tombstone.get_int64(&"totalbetamount".to_string()).set_value(bettotals(&containerofbets).totalbetamount as i64);
+    tombstone.get_int64(&"bets".to_string()).set_value(export.bets.len() as i64);
+    tombstone.get_int64(&"payouts".to_string()).set_value(export.payouts.len() as i64);
+    tombstone.get_int64(&"chunks".to_string()).set_value(chunks.len() as i64);
+    releaseexternalref(context, &market.get_string(&"externalref".to_string()).value());
+    appendadminlog(context, &("archivemarket|".to_string() + &marketid + &"|".to_string() + &digest));
+    // the state of the market is gone from here on, its admin log included
+    market.clear();
+
+    let log:String = "market archived: ".to_string() + &marketid + &", bets: ".to_string() + &export.bets.len().to_string() + &", digest: ".to_string() + &digest;  context.log(&log);
+    context.results().get_string(&"DIGEST".to_string()).set_value(&digest);
+    context.results().get_int64(&"CHUNKS".to_string()).set_value(chunks.len() as i64);
+}
+
+// View of the tombstone of the archived market MARKETID: its DIGEST and the RECIPE it was derived by, see tombstonedigest in the
+// core crate, the time it was archived as ARCHIVEDAT, and its summary: WINNINGVALUE, VOIDED, TOTALBETAMOUNT, the number of BETS and
+// PAYOUTS, and the number of archive.chunk events CHUNKS its export was emitted in. Nothing is recomputed, the state is gone.
+fn verifytombstone(context: &ScViewContext) {
+    let tombstone = context.state().get_map(&("tombstone|".to_string() + &marketparam(context)));
+    context.require(tombstone.get_string(&"digest".to_string()).exists(), "MARKETID is not an archived market");
+    for key in ["digest", "recipe", "winningvalue", "voided"].iter() {
+        context.results().get_string(&key.to_uppercase()).set_value(&tombstone.get_string(*key).value());
+    }
+    for key in ["archivedat", "totalbetamount", "bets", "payouts", "chunks"].iter() {
+        context.results().get_int64(&key.to_uppercase()).set_value(tombstone.get_int64(*key).value());
     }
-    
 }
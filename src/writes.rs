@@ -0,0 +1,17 @@
+// Buffered writes to the state of the contract
+//
+// The writes are collected in StateWrites of the core crate, see there. A real run applies them to the state at the end.
+
+use prediction_market_core::writes::*;
+use wasmlib::*;
+
+// write everything to the state
+pub fn applywrites(writes: &StateWrites, state: &ScMutableMap) {
+    writes.apply(|key, value| match value {
+        StateValue::Int64(value) => state.get_int64(key).set_value(*value),
+        StateValue::Text(value) => state.get_string(key).set_value(value),
+    }, |array, entry| {
+        let entries = state.get_string_array(array);
+        entries.get_string(entries.length()).set_value(entry);
+    });
+}